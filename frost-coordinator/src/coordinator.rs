@@ -1,9 +1,10 @@
 use std::any::Any;
 use std::collections::BTreeMap;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use frost_signer::config::{Config, Error as ConfigError};
 use frost_signer::{
+    metrics::observe_round,
     net::{Error as HttpNetError, Message, NetListen},
     signing_round::{
         DkgBegin, DkgPublicShare, MessageTypes, NonceRequest, NonceResponse, Signable,
@@ -21,6 +22,8 @@ use wsts::{
     v1, Point, Scalar,
 };
 
+use crate::metrics;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("{0}")]
@@ -138,6 +141,13 @@ where
     }
 
     pub fn run_distributed_key_generation(&mut self) -> Result<Point, Error> {
+        let started = Instant::now();
+        let result = self.distributed_key_generation();
+        observe_round(&metrics::DKG_ROUND_DURATION, started, &result);
+        result
+    }
+
+    fn distributed_key_generation(&mut self) -> Result<Point, Error> {
         self.current_dkg_id = self.current_dkg_id.wrapping_add(1);
         info!("Starting DKG round #{}", self.current_dkg_id);
         self.start_public_shares()?;
@@ -306,8 +316,15 @@ where
         Ok(())
     }
 
-    #[allow(non_snake_case)]
     pub fn sign_message(&mut self, msg: &[u8]) -> Result<(Signature, SchnorrProof), Error> {
+        let started = Instant::now();
+        let result = self.signing_round(msg);
+        observe_round(&metrics::SIGNING_ROUND_DURATION, started, &result);
+        result
+    }
+
+    #[allow(non_snake_case)]
+    fn signing_round(&mut self, msg: &[u8]) -> Result<(Signature, SchnorrProof), Error> {
         debug!("Attempting to Sign Message");
         if self.aggregate_public_key == Point::default() {
             return Err(Error::NoAggregatePublicKey);
@@ -480,6 +497,18 @@ where
     pub fn public_key(&self) -> &PublicKey {
         &self.public_key
     }
+
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    pub fn total_signers(&self) -> u32 {
+        self.total_signers
+    }
+
+    pub fn total_keys(&self) -> u32 {
+        self.total_keys
+    }
 }
 
 #[cfg(test)]
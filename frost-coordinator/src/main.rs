@@ -1,8 +1,8 @@
 use clap::Parser;
 
-use frost_coordinator::{coordinator::Command, create_coordinator_from_path};
-use frost_signer::logging;
-use tracing::{error, warn};
+use frost_coordinator::{coordinator::Command, create_coordinator};
+use frost_signer::{config::Config, logging, metrics};
+use tracing::{error, info, warn};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -19,7 +19,20 @@ fn main() {
     logging::initiate_tracing_subscriber();
 
     let cli = Cli::parse();
-    match create_coordinator_from_path(cli.config) {
+    let config = match Config::from_path(&cli.config) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to read config file {}: {}", cli.config, e);
+            return;
+        }
+    };
+    if let Some(address) = config.metrics_address {
+        match metrics::spawn_server(address) {
+            Ok(_) => info!("Serving metrics on {}", address),
+            Err(e) => warn!("Failed to serve metrics on {}: {}", address, e),
+        }
+    }
+    match create_coordinator(&config) {
         Ok(mut coordinator) => {
             let result = coordinator.run(&cli.command);
             if let Err(e) = result {
@@ -0,0 +1,22 @@
+use lazy_static::lazy_static;
+use prometheus::{exponential_buckets, register_histogram_vec, HistogramVec};
+
+lazy_static! {
+    /// Duration of DKG rounds, labelled with their outcome
+    pub static ref DKG_ROUND_DURATION: HistogramVec = register_histogram_vec!(
+        "frost_dkg_round_duration_seconds",
+        "Time taken to run a distributed key generation round",
+        &["outcome"],
+        exponential_buckets(0.1, 2.0, 12).unwrap()
+    )
+    .unwrap();
+
+    /// Duration of signing rounds, labelled with their outcome
+    pub static ref SIGNING_ROUND_DURATION: HistogramVec = register_histogram_vec!(
+        "frost_signing_round_duration_seconds",
+        "Time taken to collect nonces and signature shares for a message",
+        &["outcome"],
+        exponential_buckets(0.1, 2.0, 12).unwrap()
+    )
+    .unwrap();
+}
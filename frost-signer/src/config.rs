@@ -6,6 +6,7 @@ use p256k1::{
 };
 use serde::Deserialize;
 use std::fs;
+use std::net::{AddrParseError, SocketAddr};
 use toml;
 
 use crate::util::parse_public_key;
@@ -22,6 +23,12 @@ pub enum Error {
     InvalidPrivateKey(ScalarError),
     #[error("Invalid Key ID. Must specify Key IDs greater than 0.")]
     InvalidKeyID,
+    #[error("Failed to parse metrics_address: {0}")]
+    InvalidMetricsAddress(AddrParseError),
+    #[error("Failed to unlock network_private_key_keystore: {0}")]
+    Keystore(#[from] keystore::Error),
+    #[error("Only one of network_private_key and network_private_key_keystore may be specified.")]
+    AmbiguousPrivateKey,
 }
 
 #[derive(Parser)]
@@ -54,9 +61,14 @@ struct RawSigners {
 struct RawConfig {
     pub http_relay_url: String,
     pub keys_threshold: u32,
+    #[serde(default)]
     pub network_private_key: String,
+    /// Keystore holding the network_private_key, unlocked with the password in KEYSTORE_PASSWORD
+    pub network_private_key_keystore: Option<String>,
     signers: Vec<RawSigners>,
     coordinator_public_key: String,
+    /// Address the Prometheus metrics are served on, e.g. '127.0.0.1:9100'. Disabled if unset
+    metrics_address: Option<String>,
 }
 
 pub type SignerKeyIds = HashMap<u32, Vec<u32>>;
@@ -98,10 +110,25 @@ impl RawConfig {
         parse_public_key(&self.coordinator_public_key).map_err(Error::InvalidPublicKey)
     }
 
+    pub fn metrics_address(&self) -> Result<Option<SocketAddr>, Error> {
+        self.metrics_address
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(Error::InvalidMetricsAddress)
+    }
+
     pub fn network_private_key(&self) -> Result<Scalar, Error> {
-        let network_private_key = Scalar::try_from(self.network_private_key.as_str())
-            .map_err(Error::InvalidPrivateKey)?;
-        Ok(network_private_key)
+        let network_private_key = match &self.network_private_key_keystore {
+            Some(_) if !self.network_private_key.is_empty() => {
+                return Err(Error::AmbiguousPrivateKey)
+            }
+            Some(path) => {
+                Scalar::try_from(keystore::unlock(path, keystore::KeyKind::Network)?.as_str())
+            }
+            None => Scalar::try_from(self.network_private_key.as_str()),
+        };
+        network_private_key.map_err(Error::InvalidPrivateKey)
     }
 }
 
@@ -121,6 +148,7 @@ pub struct Config {
     pub coordinator_public_key: ecdsa::PublicKey,
     pub total_signers: u32,
     pub total_keys: u32,
+    pub metrics_address: Option<SocketAddr>,
 }
 
 impl Config {
@@ -141,6 +169,7 @@ impl Config {
             total_keys: public_keys.key_ids.len().try_into().unwrap(),
             public_keys,
             signer_key_ids,
+            metrics_address: None,
         }
     }
 
@@ -153,14 +182,16 @@ impl Config {
 impl TryFrom<&RawConfig> for Config {
     type Error = Error;
     fn try_from(raw_config: &RawConfig) -> Result<Self, Error> {
-        Ok(Config::new(
+        let mut config = Config::new(
             raw_config.keys_threshold,
             raw_config.coordinator_public_key()?,
             raw_config.public_keys()?,
             raw_config.signer_key_ids(),
             raw_config.network_private_key()?,
             raw_config.http_relay_url.clone(),
-        ))
+        );
+        config.metrics_address = raw_config.metrics_address()?;
+        Ok(config)
     }
 }
 
@@ -261,4 +292,43 @@ mod test {
         assert_eq!(public_keys.signers.len(), 2);
         assert_eq!(public_keys.key_ids.len(), 4);
     }
+
+    #[test]
+    fn network_private_key_keystore_test() {
+        let mut config = RawConfig {
+            network_private_key: "9aSCCR6eirt1NAHwJtSz4HMwBHTyMo62SyPMvVDt5DQn".to_string(),
+            network_private_key_keystore: Some("signer.json".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.network_private_key(),
+            Err(Error::AmbiguousPrivateKey)
+        ));
+
+        config.network_private_key = String::new();
+        config.network_private_key_keystore = Some("missing-keystore.json".to_string());
+        assert!(matches!(
+            config.network_private_key(),
+            Err(Error::Keystore(_))
+        ));
+    }
+
+    #[test]
+    fn metrics_address_test() {
+        let mut config = RawConfig::default();
+        // Metrics are disabled by default
+        assert_eq!(config.metrics_address().unwrap(), None);
+
+        config.metrics_address = Some("localhost".to_string());
+        assert!(matches!(
+            config.metrics_address(),
+            Err(Error::InvalidMetricsAddress(_))
+        ));
+
+        config.metrics_address = Some("127.0.0.1:9100".to_string());
+        assert_eq!(
+            config.metrics_address().unwrap(),
+            Some("127.0.0.1:9100".parse().unwrap())
+        );
+    }
 }
@@ -0,0 +1,50 @@
+use std::{
+    collections::HashMap,
+    io::{Error as IoError, Write},
+    net::TcpListener,
+    time::Duration,
+};
+
+use tracing::{debug, warn};
+use yarpc::http::{IoStream, Message, Request, Response};
+
+/// How long to wait for a client to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serve connections one at a time until the listener fails, answering each request with
+/// `handler`. `name` labels the logs of the server.
+pub fn run(
+    listener: TcpListener,
+    name: &str,
+    content_type: &str,
+    handler: impl Fn(&Request) -> (u16, &'static str, Vec<u8>),
+) {
+    for stream in listener.incoming() {
+        let result = stream.and_then(|mut stream| {
+            stream.set_read_timeout(Some(READ_TIMEOUT))?;
+            serve(&mut stream, name, content_type, &handler)
+        });
+        if let Err(e) = result {
+            warn!("Failed to serve {} connection: {}", name, e);
+        }
+    }
+}
+
+/// Read a request from the stream and write back the status code, reason phrase and content
+/// returned by `handler`
+pub fn serve(
+    io: &mut impl IoStream,
+    name: &str,
+    content_type: &str,
+    handler: impl Fn(&Request) -> (u16, &'static str, Vec<u8>),
+) -> Result<(), IoError> {
+    let request = Request::read(io.istream())?;
+    debug!("{} request: {} {}", name, request.method, request.url);
+    let (code, phrase, content) = handler(&request);
+
+    let headers = HashMap::from([("content-type".to_string(), content_type.to_string())]);
+    let response = Response::new(code, phrase.to_string(), headers, content);
+    let ostream = io.ostream();
+    response.write(ostream)?;
+    ostream.flush()
+}
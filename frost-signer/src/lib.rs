@@ -1,5 +1,7 @@
 pub mod config;
+pub mod http_server;
 pub mod logging;
+pub mod metrics;
 pub mod net;
 pub mod signer;
 pub mod signing_round;
@@ -3,6 +3,7 @@ use tracing::{error, info, warn};
 
 use frost_signer::config::{Cli, Config};
 use frost_signer::logging;
+use frost_signer::metrics;
 use frost_signer::signer::Signer;
 
 fn main() {
@@ -19,6 +20,13 @@ fn main() {
                 signer.signer_id
             ); // sign-on message
 
+            if let Some(address) = signer.config.metrics_address {
+                match metrics::spawn_server(address) {
+                    Ok(_) => info!("Serving metrics on {}", address),
+                    Err(e) => warn!("Failed to serve metrics on {}: {}", address, e),
+                }
+            }
+
             //Start listening for p2p messages
             if let Err(e) = signer.start_p2p_sync() {
                 warn!("An error occurred in the P2P Network: {}", e);
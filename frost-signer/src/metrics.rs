@@ -0,0 +1,93 @@
+use std::{
+    io::Error as IoError,
+    net::{SocketAddr, TcpListener},
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use lazy_static::lazy_static;
+use prometheus::{register_histogram, Encoder, Histogram, HistogramVec, TextEncoder, TEXT_FORMAT};
+use yarpc::http::{IoStream, Method, Request};
+
+use crate::http_server;
+
+lazy_static! {
+    /// Time taken by a single poll of the relay, successful or not
+    pub static ref RELAY_POLL_DURATION: Histogram = register_histogram!(
+        "frost_relay_poll_duration_seconds",
+        "Time taken to poll the relay for the next message"
+    )
+    .unwrap();
+}
+
+/// Record the duration of a round started at `started`, labelled with its outcome
+pub fn observe_round<T, E>(histogram: &HistogramVec, started: Instant, result: &Result<T, E>) {
+    let outcome = if result.is_ok() { "success" } else { "failure" };
+    histogram
+        .with_label_values(&[outcome])
+        .observe(started.elapsed().as_secs_f64());
+}
+
+/// Serve the metrics registered by any crate of the process on a background thread
+pub fn spawn_server(address: SocketAddr) -> Result<JoinHandle<()>, IoError> {
+    let listener = TcpListener::bind(address)?;
+    Ok(thread::spawn(move || run(listener)))
+}
+
+/// Serve the metrics on `listener` until it fails
+pub fn run(listener: TcpListener) {
+    http_server::run(listener, "metrics", TEXT_FORMAT, respond)
+}
+
+/// Answer a single request read from the stream
+pub fn serve(io: &mut impl IoStream) -> Result<(), IoError> {
+    http_server::serve(io, "metrics", TEXT_FORMAT, respond)
+}
+
+/// Answer `GET /metrics` in the Prometheus text format
+fn respond(request: &Request) -> (u16, &'static str, Vec<u8>) {
+    let path = request
+        .url
+        .split_once('?')
+        .map_or(request.url.as_str(), |(path, _)| path);
+    match (request.method, path) {
+        (Method::GET, "/metrics") => {
+            let mut content = Vec::new();
+            match TextEncoder::new().encode(&prometheus::gather(), &mut content) {
+                Ok(()) => (200, "OK", content),
+                Err(e) => (500, "Internal Server Error", e.to_string().into_bytes()),
+            }
+        }
+        _ => (404, "Not Found", b"Not Found".to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::from_utf8;
+
+    use yarpc::http::{MemIoStreamEx, Message, Response};
+
+    use super::*;
+
+    fn get(url: &str) -> Response {
+        let mut output = Vec::new();
+        let message = format!("GET {url} HTTP/1.1\r\n\r\n");
+        let mut stream = message.as_bytes().mem_io_stream(&mut output);
+        serve(&mut stream).unwrap();
+        Response::read(&mut output.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn metrics_should_be_served_in_text_format() {
+        RELAY_POLL_DURATION.observe(0.25);
+
+        let response = get("/metrics");
+        assert_eq!(response.code, 200);
+        let content = from_utf8(&response.content).unwrap();
+        assert!(content.contains("# TYPE frost_relay_poll_duration_seconds histogram"));
+        assert!(content.contains("frost_relay_poll_duration_seconds_count"));
+
+        assert_eq!(get("/").code, 404);
+    }
+}
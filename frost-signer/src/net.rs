@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::{fmt::Debug, time::Duration};
 use tracing::{debug, warn};
 
-use crate::signing_round;
+use crate::{metrics, signing_round};
 // Message is the format over the wire
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Message {
@@ -57,6 +57,7 @@ impl NetListen for HttpNetListen {
     fn poll(&mut self, id: u32) {
         let url = url_with_id(&self.net.http_relay_url, id);
         debug!("poll {}", url);
+        let _timer = metrics::RELAY_POLL_DURATION.start_timer();
         match ureq::get(&url).call() {
             Ok(response) => {
                 self.net.connected = true;
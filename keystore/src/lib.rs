@@ -0,0 +1,475 @@
+use aes_gcm::{
+    aead::{Aead, Payload},
+    Aes256Gcm, KeyInit, Nonce,
+};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use wsts::{Point, Scalar};
+pub use zeroize::Zeroizing;
+
+/// Version of the keystore file format
+pub const KEYSTORE_VERSION: u32 = 1;
+
+/// Environment variable holding the password which unlocks keystores
+pub const PASSWORD_ENV: &str = "KEYSTORE_PASSWORD";
+
+/// Environment variable naming a file which holds the password which unlocks keystores
+pub const PASSWORD_FILE_ENV: &str = "KEYSTORE_PASSWORD_FILE";
+
+const SALT_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+const KEY_SIZE: usize = 32;
+
+/// Upper bounds of the scrypt parameters read from keystore files, so a crafted file cannot make
+/// the key derivation exhaust memory or run forever
+const MAX_LOG_N: u8 = 20;
+const MAX_R: u32 = 32;
+const MAX_P: u32 = 16;
+/// Upper bound of the memory used by the key derivation, which is 128 * r * 2^log_n bytes
+const MAX_MEMORY: u64 = 1 << 30;
+
+/// Errors associated with reading, writing and unlocking keystores
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("IO Error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("Json Error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Hex Error: {0}")]
+    HexError(#[from] hex::FromHexError),
+    #[error("Unsupported keystore version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Invalid scrypt parameters: {0}")]
+    InvalidParams(String),
+    #[error("Failed to encrypt the key")]
+    Encryption,
+    #[error("Wrong password or corrupted keystore")]
+    Decryption,
+    #[error("Invalid {0} key: {1}")]
+    InvalidKey(KeyKind, String),
+    #[error("Expected a {0} key but the keystore holds a {1} key")]
+    WrongKind(KeyKind, KeyKind),
+    #[error("No password to unlock {0}. Set KEYSTORE_PASSWORD or KEYSTORE_PASSWORD_FILE")]
+    MissingPassword(String),
+    #[error("The passwords do not match")]
+    PasswordMismatch,
+}
+
+/// The kinds of private keys held in keystores, each in the format its config expects
+#[derive(Serialize, Deserialize, clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyKind {
+    /// A Stacks private key in hex with the compression suffix, e.g. `stacks_private_key`
+    Stacks,
+    /// A frost network key in base58, e.g. `network_private_key`
+    Network,
+    /// A plain secp256k1 secret key in hex, e.g. the signer API `secret_key`
+    Secp256k1,
+}
+
+impl fmt::Display for KeyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyKind::Stacks => write!(f, "stacks"),
+            KeyKind::Network => write!(f, "network"),
+            KeyKind::Secp256k1 => write!(f, "secp256k1"),
+        }
+    }
+}
+
+impl KeyKind {
+    /// Generate a random private key
+    pub fn generate(&self) -> String {
+        let scalar = Scalar::random(&mut OsRng);
+        match self {
+            KeyKind::Stacks => format!("{}01", hex::encode(scalar.to_bytes())),
+            KeyKind::Network => scalar.to_string(),
+            KeyKind::Secp256k1 => hex::encode(scalar.to_bytes()),
+        }
+    }
+
+    /// Parse a private key of this kind
+    fn parse(&self, key: &str) -> Result<Scalar, Error> {
+        let invalid = |reason: &str| Error::InvalidKey(*self, reason.to_string());
+        let bytes = match self {
+            KeyKind::Network => {
+                return Scalar::try_from(key).map_err(|e| invalid(&format!("{:?}", e)))
+            }
+            KeyKind::Stacks => match hex::decode(key)? {
+                mut bytes if bytes.len() == 33 && bytes[32] == 1 => {
+                    bytes.pop();
+                    bytes
+                }
+                bytes => bytes,
+            },
+            KeyKind::Secp256k1 => hex::decode(key)?,
+        };
+        let bytes: [u8; 32] = bytes.try_into().map_err(|_| invalid("expected 32 bytes"))?;
+        Ok(Scalar::from(bytes))
+    }
+
+    /// Check that `key` is a private key of this kind
+    pub fn validate(&self, key: &str) -> Result<(), Error> {
+        self.parse(key).map(|_| ())
+    }
+
+    /// The public key of a private key of this kind, in the format configs list public keys in
+    pub fn public_key(&self, key: &str) -> Result<String, Error> {
+        let point = Point::from(&self.parse(key)?);
+        Ok(match self {
+            KeyKind::Network => point.to_string(),
+            KeyKind::Stacks | KeyKind::Secp256k1 => hex::encode(point.compress().as_bytes()),
+        })
+    }
+}
+
+/// Cost parameters of the scrypt key derivation
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScryptParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl Default for ScryptParams {
+    fn default() -> Self {
+        Self {
+            log_n: scrypt::Params::RECOMMENDED_LOG_N,
+            r: scrypt::Params::RECOMMENDED_R,
+            p: scrypt::Params::RECOMMENDED_P,
+        }
+    }
+}
+
+impl ScryptParams {
+    fn derive_key(&self, password: &str, salt: &[u8]) -> Result<Zeroizing<[u8; KEY_SIZE]>, Error> {
+        if self.log_n > MAX_LOG_N
+            || self.r > MAX_R
+            || self.p > MAX_P
+            || (128 * u64::from(self.r)) << self.log_n > MAX_MEMORY
+        {
+            return Err(Error::InvalidParams(format!(
+                "log_n {}, r {} and p {} exceed the supported cost",
+                self.log_n, self.r, self.p
+            )));
+        }
+        let params = scrypt::Params::new(self.log_n, self.r, self.p, KEY_SIZE)
+            .map_err(|e| Error::InvalidParams(e.to_string()))?;
+        let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+        scrypt::scrypt(password.as_bytes(), salt, &params, &mut *key)
+            .map_err(|e| Error::InvalidParams(e.to_string()))?;
+        Ok(key)
+    }
+}
+
+/// A private key encrypted with AES-256-GCM under a key derived from a password with scrypt
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Keystore {
+    pub version: u32,
+    pub kind: KeyKind,
+    pub scrypt: ScryptParams,
+    /// Salt of the key derivation in hex
+    pub salt: String,
+    /// AES-GCM nonce in hex
+    pub nonce: String,
+    /// The encrypted key followed by the AES-GCM tag in hex
+    pub ciphertext: String,
+}
+
+/// The header fields authenticated along with the encrypted key, so they cannot be swapped
+fn associated_data(version: u32, kind: KeyKind) -> Vec<u8> {
+    format!("{}:{}", version, kind).into_bytes()
+}
+
+impl Keystore {
+    /// Encrypt `key` with `password` using the recommended scrypt parameters
+    pub fn encrypt(kind: KeyKind, key: &str, password: &str) -> Result<Self, Error> {
+        Self::encrypt_with(kind, key, password, ScryptParams::default())
+    }
+
+    /// Encrypt `key` with `password` using the given scrypt parameters
+    pub fn encrypt_with(
+        kind: KeyKind,
+        key: &str,
+        password: &str,
+        scrypt: ScryptParams,
+    ) -> Result<Self, Error> {
+        kind.validate(key)?;
+        let mut salt = [0u8; SALT_SIZE];
+        let mut nonce = [0u8; NONCE_SIZE];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+        let derived_key = scrypt.derive_key(password, &salt)?;
+        let cipher = Aes256Gcm::new((&*derived_key).into());
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: key.as_bytes(),
+                    aad: &associated_data(KEYSTORE_VERSION, kind),
+                },
+            )
+            .map_err(|_| Error::Encryption)?;
+        Ok(Self {
+            version: KEYSTORE_VERSION,
+            kind,
+            scrypt,
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    /// Decrypt the key with `password`. The key is wiped from memory once dropped.
+    pub fn decrypt(&self, password: &str) -> Result<Zeroizing<String>, Error> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(Error::UnsupportedVersion(self.version));
+        }
+        let nonce = hex::decode(&self.nonce)?;
+        if nonce.len() != NONCE_SIZE {
+            return Err(Error::Decryption);
+        }
+        let derived_key = self
+            .scrypt
+            .derive_key(password, &hex::decode(&self.salt)?)?;
+        let cipher = Aes256Gcm::new((&*derived_key).into());
+        let key = Zeroizing::new(
+            cipher
+                .decrypt(
+                    Nonce::from_slice(&nonce),
+                    Payload {
+                        msg: &hex::decode(&self.ciphertext)?,
+                        aad: &associated_data(self.version, self.kind),
+                    },
+                )
+                .map_err(|_| Error::Decryption)?,
+        );
+        let key = std::str::from_utf8(&key).map_err(|_| Error::Decryption)?;
+        Ok(Zeroizing::new(key.to_string()))
+    }
+
+    /// Re-encrypt the key with `new_password`, under a fresh salt and nonce
+    pub fn rotate(&self, password: &str, new_password: &str) -> Result<Self, Error> {
+        Self::encrypt_with(
+            self.kind,
+            &self.decrypt(password)?,
+            new_password,
+            self.scrypt,
+        )
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Write the keystore to a new file, readable by its owner only
+    pub fn create(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Replace the keystore at `path`, so it is never left half written
+    pub fn replace(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        // A leftover from an interrupted replacement
+        let _ = fs::remove_file(&tmp_path);
+        self.create(&tmp_path)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+/// The password in the file named by `{var}_FILE`, or else in `var`, if either is set
+pub fn password_from_env(var: &str) -> Result<Option<Zeroizing<String>>, Error> {
+    password_from_vars(var, |name| std::env::var(name).ok())
+}
+
+/// The password in the file named by `{var}_FILE`, or else in `var`, looking the variables up
+/// with `lookup`
+pub fn password_from_vars(
+    var: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Option<Zeroizing<String>>, Error> {
+    if let Some(path) = lookup(&format!("{}_FILE", var)) {
+        let password = Zeroizing::new(fs::read_to_string(path)?);
+        return Ok(Some(Zeroizing::new(
+            password.trim_end_matches(['\r', '\n']).to_string(),
+        )));
+    }
+    Ok(lookup(var).map(Zeroizing::new))
+}
+
+/// Decrypt the keystore at `path` without prompting, with the password in
+/// `KEYSTORE_PASSWORD_FILE` or `KEYSTORE_PASSWORD`, as services do on startup
+pub fn unlock(path: impl AsRef<Path>, kind: KeyKind) -> Result<Zeroizing<String>, Error> {
+    let path = path.as_ref();
+    let password = password_from_env(PASSWORD_ENV)?
+        .ok_or_else(|| Error::MissingPassword(path.display().to_string()))?;
+    unlock_with(path, kind, &password)
+}
+
+/// Decrypt the keystore at `path` with `password`, checking that it holds a key of the given kind
+pub fn unlock_with(
+    path: impl AsRef<Path>,
+    kind: KeyKind,
+    password: &str,
+) -> Result<Zeroizing<String>, Error> {
+    let keystore = Keystore::from_path(path)?;
+    if keystore.kind != kind {
+        return Err(Error::WrongKind(kind, keystore.kind));
+    }
+    keystore.decrypt(password)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    // Cheap parameters to keep the tests fast
+    const TEST_PARAMS: ScryptParams = ScryptParams {
+        log_n: 4,
+        r: 8,
+        p: 1,
+    };
+
+    #[test]
+    fn encrypt_decrypt_test() {
+        for kind in [KeyKind::Stacks, KeyKind::Network, KeyKind::Secp256k1] {
+            let key = kind.generate();
+            kind.validate(&key).unwrap();
+            let keystore = Keystore::encrypt_with(kind, &key, "password", TEST_PARAMS).unwrap();
+            assert_eq!(*keystore.decrypt("password").unwrap(), key);
+            assert!(matches!(
+                keystore.decrypt("wrong password"),
+                Err(Error::Decryption)
+            ));
+        }
+    }
+
+    #[test]
+    fn invalid_key_test() {
+        assert!(
+            Keystore::encrypt_with(KeyKind::Stacks, "garbage", "password", TEST_PARAMS).is_err()
+        );
+        assert!(matches!(
+            KeyKind::Secp256k1.validate("abcd"),
+            Err(Error::InvalidKey(KeyKind::Secp256k1, _))
+        ));
+        assert!(KeyKind::Network
+            .validate("9aSCCR6eirt1NAHwJtSz4HMwBHTyMo62SyPMvVDt5DQn")
+            .is_ok());
+    }
+
+    #[test]
+    fn swapped_header_test() {
+        let key = KeyKind::Secp256k1.generate();
+        let mut keystore =
+            Keystore::encrypt_with(KeyKind::Secp256k1, &key, "password", TEST_PARAMS).unwrap();
+        // Secp256k1 and Stacks keys share a hex encoding, so only the tag tells them apart
+        keystore.kind = KeyKind::Stacks;
+        assert!(matches!(
+            keystore.decrypt("password"),
+            Err(Error::Decryption)
+        ));
+    }
+
+    #[test]
+    fn costly_params_test() {
+        let key = KeyKind::Network.generate();
+        let mut keystore =
+            Keystore::encrypt_with(KeyKind::Network, &key, "password", TEST_PARAMS).unwrap();
+        for scrypt in [
+            ScryptParams {
+                log_n: 40,
+                ..TEST_PARAMS
+            },
+            ScryptParams {
+                r: u32::MAX,
+                ..TEST_PARAMS
+            },
+            ScryptParams {
+                p: u32::MAX,
+                ..TEST_PARAMS
+            },
+            ScryptParams {
+                log_n: 20,
+                r: 32,
+                p: 1,
+            },
+        ] {
+            keystore.scrypt = scrypt;
+            assert!(matches!(
+                keystore.decrypt("password"),
+                Err(Error::InvalidParams(_))
+            ));
+        }
+        // The recommended parameters are within bounds
+        assert!(ScryptParams::default().log_n <= MAX_LOG_N);
+    }
+
+    /// Looks variables up in `vars`
+    fn lookup<'a>(vars: &'a [(&str, String)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.clone())
+        }
+    }
+
+    #[test]
+    fn password_from_vars_test() {
+        let dir = TempDir::new("").unwrap();
+        let path = dir.path().join("password");
+        fs::write(&path, "from file\n").unwrap();
+        let vars = [
+            ("PASSWORD", "from var".to_string()),
+            ("PASSWORD_FILE", path.display().to_string()),
+        ];
+
+        // A password file takes precedence, without its trailing newline
+        let password = password_from_vars("PASSWORD", lookup(&vars))
+            .unwrap()
+            .unwrap();
+        assert_eq!(password.as_str(), "from file");
+        let password = password_from_vars("PASSWORD", lookup(&vars[..1]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(password.as_str(), "from var");
+        assert!(password_from_vars("PASSWORD", lookup(&[]))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn rotate_test() {
+        let dir = TempDir::new("").unwrap();
+        let path = dir.path().join("network.json");
+        let key = KeyKind::Network.generate();
+        let keystore = Keystore::encrypt_with(KeyKind::Network, &key, "old", TEST_PARAMS).unwrap();
+        keystore.create(&path).unwrap();
+        // Keystores are never overwritten by accident
+        assert!(keystore.create(&path).is_err());
+
+        let rotated = Keystore::from_path(&path)
+            .unwrap()
+            .rotate("old", "new")
+            .unwrap();
+        assert_ne!(rotated.salt, keystore.salt);
+        rotated.replace(&path).unwrap();
+        let keystore = Keystore::from_path(&path).unwrap();
+        assert!(keystore.decrypt("old").is_err());
+        assert_eq!(*keystore.decrypt("new").unwrap(), key);
+    }
+}
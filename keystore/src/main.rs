@@ -0,0 +1,104 @@
+use clap::{Parser, Subcommand};
+use keystore::{password_from_env, Error, KeyKind, Keystore, Zeroizing, PASSWORD_ENV};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+/// Environment variable holding the new password of created, imported and rotated keystores
+const NEW_PASSWORD_ENV: &str = "KEYSTORE_NEW_PASSWORD";
+
+///Command line interface for encrypted private key stores
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Subcommand action to take
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a private key into a new keystore and print its public key
+    Create {
+        /// Kind of private key to generate
+        #[arg(short, long, value_enum)]
+        kind: KeyKind,
+        /// Path of the keystore file to create
+        path: PathBuf,
+    },
+    /// Encrypt a private key read from stdin into a new keystore and print its public key
+    Import {
+        /// Kind of the private key
+        #[arg(short, long, value_enum)]
+        kind: KeyKind,
+        /// Path of the keystore file to create
+        path: PathBuf,
+    },
+    /// Print the private key held in a keystore
+    Export {
+        /// Path of the keystore file
+        path: PathBuf,
+    },
+    /// Re-encrypt a keystore with a new password
+    Rotate {
+        /// Path of the keystore file
+        path: PathBuf,
+    },
+}
+
+/// The password in the environment, or else prompted for on the terminal
+fn password() -> Result<Zeroizing<String>, Error> {
+    match password_from_env(PASSWORD_ENV)? {
+        Some(password) => Ok(password),
+        None => Ok(Zeroizing::new(rpassword::prompt_password("Password: ")?)),
+    }
+}
+
+/// The new password in the environment, or else prompted for twice on the terminal
+fn new_password() -> Result<Zeroizing<String>, Error> {
+    if let Some(password) = password_from_env(NEW_PASSWORD_ENV)? {
+        return Ok(password);
+    }
+    let password = Zeroizing::new(rpassword::prompt_password("New password: ")?);
+    let repeated = Zeroizing::new(rpassword::prompt_password("Repeat the new password: ")?);
+    if password != repeated {
+        return Err(Error::PasswordMismatch);
+    }
+    Ok(password)
+}
+
+fn create(kind: KeyKind, key: &str, path: &Path) -> Result<(), Error> {
+    Keystore::encrypt(kind, key, &new_password()?)?.create(path)?;
+    println!("{}", kind.public_key(key)?);
+    Ok(())
+}
+
+fn run(command: Command) -> Result<(), Error> {
+    match command {
+        Command::Create { kind, path } => create(kind, &kind.generate(), &path),
+        Command::Import { kind, path } => {
+            let mut key = Zeroizing::new(String::new());
+            std::io::stdin().lock().read_line(&mut key)?;
+            let key = key.trim();
+            kind.validate(key)?;
+            create(kind, key, &path)
+        }
+        Command::Export { path } => {
+            println!(
+                "{}",
+                Keystore::from_path(&path)?.decrypt(&password()?)?.as_str()
+            );
+            Ok(())
+        }
+        Command::Rotate { path } => Keystore::from_path(&path)?
+            .rotate(&password()?, &new_password()?)?
+            .replace(&path),
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli.command) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,444 @@
+use std::{
+    io::Error as IoError,
+    net::TcpListener,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    time::Duration,
+};
+
+use blockstack_lib::{burnchains::Txid, types::chainstate::BurnchainHeaderHash};
+use frost_signer::http_server;
+use rusqlite::Error as RusqliteError;
+use serde_json::{json, Value};
+use yarpc::http::{IoStream, Method, QueryEx, Request};
+
+use crate::coordinator::{Command, Error as CoordinatorError};
+use crate::peg_queue::{Entry, Error as PegQueueError, SqlitePegQueueError, Status};
+
+/// How long to wait for the coordinator to serve a request. The coordinator serves requests
+/// between the steps of its polling cycle, and a DKG round may take a while.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Requests served by the admin API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminRequest {
+    /// List the queued ops with the given status
+    ListOps(Status),
+    /// Show the op with the given txid, once for each burn block it was seen in
+    GetOp(Txid),
+    /// Hand an op which did not go through out again
+    RequeueOp(Txid, BurnchainHeaderHash),
+    /// Never process the op
+    SkipOp(Txid, BurnchainHeaderHash),
+    /// Run a DKG round and switch the peg wallet to the new aggregate key
+    RunDkg,
+    /// Hand the peg wallet off to the signer set configured in the given signer config file
+    HandOff(String),
+    /// Report the aggregate key, peg wallet address and its utxos
+    GetWallet,
+    /// Reconcile the peg wallet balance with the sBTC supply
+    GetSolvency,
+    /// Re-read the config file, reporting the changed fields and those which need a restart
+    ReloadConfig,
+}
+
+/// Errors returned by the admin API
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum AdminError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    Unavailable(String),
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl AdminError {
+    fn code(&self) -> (u16, &'static str) {
+        match self {
+            AdminError::BadRequest(_) => (400, "Bad Request"),
+            AdminError::Unauthorized(_) => (401, "Unauthorized"),
+            AdminError::NotFound(_) => (404, "Not Found"),
+            AdminError::Conflict(_) => (409, "Conflict"),
+            AdminError::Unavailable(_) => (503, "Service Unavailable"),
+            AdminError::Failed(_) => (500, "Internal Server Error"),
+        }
+    }
+}
+
+impl From<CoordinatorError> for AdminError {
+    fn from(err: CoordinatorError) -> Self {
+        match &err {
+            CoordinatorError::UnknownOp(_)
+            | CoordinatorError::PegQueueError(PegQueueError::SqlitePegQueueError(
+                SqlitePegQueueError::RusqliteError(RusqliteError::QueryReturnedNoRows),
+            )) => AdminError::NotFound(err.to_string()),
+            CoordinatorError::PegQueueError(PegQueueError::SqlitePegQueueError(
+                SqlitePegQueueError::InvalidStatusTransition(..)
+                | SqlitePegQueueError::AlreadyBroadcast(_),
+            ))
+            | CoordinatorError::WalletNotEmpty(_)
+            | CoordinatorError::HandoffInProgress(_) => AdminError::Conflict(err.to_string()),
+            _ => AdminError::Failed(err.to_string()),
+        }
+    }
+}
+
+/// The result of an admin request, sent back by the coordinator
+pub type AdminResponse = Result<Value, AdminError>;
+
+/// JSON view of a queued op
+pub fn entry_to_json(entry: &Entry) -> Value {
+    json!({
+        "txid": entry.op.txid().to_hex(),
+        "burn_header_hash": entry.op.burn_header_hash().to_hex(),
+        "block_height": entry.block_height,
+        "status": entry.status.as_str(),
+        "stacks_txid": entry.stacks_txid.map(|txid| txid.to_hex()),
+        "bitcoin_txid": entry.bitcoin_txid.map(|txid| txid.to_string()),
+        "attempts": entry.attempts,
+        "created_at": entry.created_at,
+        "updated_at": entry.updated_at,
+        "reason": entry.reason,
+        "op": serde_json::to_value(&entry.op).unwrap_or(Value::Null),
+    })
+}
+
+/// HTTP server exposing the admin API. Requests are passed to the running coordinator as
+/// `Command::Admin`, so the coordinator stays the only owner of its queue and wallets.
+///
+/// | Method | Path                                      | Request                      |
+/// |--------|-------------------------------------------|------------------------------|
+/// | GET    | `/ops?status=<status>`                    | `AdminRequest::ListOps`      |
+/// | GET    | `/ops/<txid>`                             | `AdminRequest::GetOp`        |
+/// | POST   | `/ops/<txid>/<burn_header_hash>/requeue`  | `AdminRequest::RequeueOp`    |
+/// | POST   | `/ops/<txid>/<burn_header_hash>/skip`     | `AdminRequest::SkipOp`       |
+/// | POST   | `/dkg`                                    | `AdminRequest::RunDkg`       |
+/// | POST   | `/handoff?signer_config=<path>`           | `AdminRequest::HandOff`      |
+/// | GET    | `/wallet`                                 | `AdminRequest::GetWallet`    |
+/// | GET    | `/solvency`                               | `AdminRequest::GetSolvency`  |
+/// | POST   | `/config/reload`                          | `AdminRequest::ReloadConfig` |
+///
+/// If a token is set, every request must carry it in an `Authorization: Bearer <token>` header.
+pub struct AdminServer {
+    commands: Sender<Command>,
+    token: Option<String>,
+}
+
+impl AdminServer {
+    pub fn new(commands: Sender<Command>) -> Self {
+        Self {
+            commands,
+            token: None,
+        }
+    }
+
+    /// Require clients to authenticate with the given bearer token
+    pub fn set_token(&mut self, token: String) {
+        self.token = Some(token);
+    }
+
+    /// Serve the admin API on `listener` until it fails
+    pub fn run(&self, listener: TcpListener) {
+        http_server::run(listener, "admin API", "application/json", |request| {
+            self.respond(request)
+        })
+    }
+
+    /// Answer a single request read from the stream
+    pub fn serve(&self, io: &mut impl IoStream) -> Result<(), IoError> {
+        http_server::serve(io, "admin API", "application/json", |request| {
+            self.respond(request)
+        })
+    }
+
+    fn respond(&self, request: &Request) -> (u16, &'static str, Vec<u8>) {
+        let result = self
+            .authenticate(request)
+            .and_then(|_| parse_request(request))
+            .and_then(|request| self.call(request));
+
+        let (code, phrase, content) = match result {
+            Ok(value) => (200, "OK", value),
+            Err(e) => {
+                let (code, phrase) = e.code();
+                (code, phrase, json!({ "error": e.to_string() }))
+            }
+        };
+        (code, phrase, content.to_string().into_bytes())
+    }
+
+    fn authenticate(&self, request: &Request) -> Result<(), AdminError> {
+        let Some(token) = &self.token else {
+            return Ok(());
+        };
+        let provided = request
+            .headers
+            .get("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| AdminError::Unauthorized("Missing bearer token".to_string()))?;
+        // Compare in constant time so the token cannot be guessed byte by byte
+        let matches = provided.len() == token.len()
+            && provided
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0;
+        if !matches {
+            return Err(AdminError::Unauthorized("Invalid bearer token".to_string()));
+        }
+        Ok(())
+    }
+
+    fn call(&self, request: AdminRequest) -> AdminResponse {
+        let (reply, response) = mpsc::channel();
+        self.commands
+            .send(Command::Admin(request, reply))
+            .map_err(|_| AdminError::Unavailable("The coordinator is stopped".to_string()))?;
+        match response.recv_timeout(REQUEST_TIMEOUT) {
+            Ok(response) => response,
+            Err(RecvTimeoutError::Timeout) => Err(AdminError::Unavailable(
+                "The coordinator did not respond in time. The request may still be carried out"
+                    .to_string(),
+            )),
+            Err(RecvTimeoutError::Disconnected) => Err(AdminError::Unavailable(
+                "The coordinator stopped before responding".to_string(),
+            )),
+        }
+    }
+}
+
+fn parse_request(request: &Request) -> Result<AdminRequest, AdminError> {
+    let path = request
+        .url
+        .split_once('?')
+        .map_or(request.url.as_str(), |(path, _)| path);
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match (request.method, segments.as_slice()) {
+        (Method::GET, ["ops"]) => {
+            let query = request.url.url_query();
+            let status = query.get("status").ok_or_else(|| {
+                AdminError::BadRequest("Missing status query parameter".to_string())
+            })?;
+            Ok(AdminRequest::ListOps(status.parse().map_err(
+                |e: SqlitePegQueueError| AdminError::BadRequest(e.to_string()),
+            )?))
+        }
+        (Method::GET, ["ops", txid]) => Ok(AdminRequest::GetOp(parse_txid(txid)?)),
+        (Method::POST, ["ops", txid, burn_header_hash, "requeue"]) => Ok(AdminRequest::RequeueOp(
+            parse_txid(txid)?,
+            parse_burn_header_hash(burn_header_hash)?,
+        )),
+        (Method::POST, ["ops", txid, burn_header_hash, "skip"]) => Ok(AdminRequest::SkipOp(
+            parse_txid(txid)?,
+            parse_burn_header_hash(burn_header_hash)?,
+        )),
+        (Method::POST, ["dkg"]) => Ok(AdminRequest::RunDkg),
+        (Method::POST, ["handoff"]) => {
+            let query = request.url.url_query();
+            let signer_config = query.get("signer_config").ok_or_else(|| {
+                AdminError::BadRequest("Missing signer_config query parameter".to_string())
+            })?;
+            Ok(AdminRequest::HandOff(signer_config.to_string()))
+        }
+        (Method::GET, ["wallet"]) => Ok(AdminRequest::GetWallet),
+        (Method::GET, ["solvency"]) => Ok(AdminRequest::GetSolvency),
+        (Method::POST, ["config", "reload"]) => Ok(AdminRequest::ReloadConfig),
+        _ => Err(AdminError::NotFound(format!(
+            "No such endpoint: {} {}",
+            request.method, path
+        ))),
+    }
+}
+
+fn parse_txid(txid: &str) -> Result<Txid, AdminError> {
+    Txid::from_hex(txid.trim_start_matches("0x"))
+        .map_err(|e| AdminError::BadRequest(format!("Invalid txid {}: {}", txid, e)))
+}
+
+fn parse_burn_header_hash(burn_header_hash: &str) -> Result<BurnchainHeaderHash, AdminError> {
+    BurnchainHeaderHash::from_hex(burn_header_hash.trim_start_matches("0x")).map_err(|e| {
+        AdminError::BadRequest(format!(
+            "Invalid burn header hash {}: {}",
+            burn_header_hash, e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::from_utf8;
+    use std::thread;
+
+    use yarpc::http::{MemIoStreamEx, Message, Response};
+
+    use super::*;
+
+    fn request(method: Method, url: &str) -> Request {
+        Request::new(method, url.to_string(), Default::default(), vec![])
+    }
+
+    #[test]
+    fn requests_should_be_routed() {
+        let txid = "0x".to_string() + &"01".repeat(32);
+        let burn_header_hash = "02".repeat(32);
+
+        assert_eq!(
+            parse_request(&request(Method::GET, "/ops?status=failed")),
+            Ok(AdminRequest::ListOps(Status::Failed))
+        );
+        assert_eq!(
+            parse_request(&request(Method::GET, &format!("/ops/{txid}"))),
+            Ok(AdminRequest::GetOp(Txid([1; 32])))
+        );
+        assert_eq!(
+            parse_request(&request(
+                Method::POST,
+                &format!("/ops/{txid}/{burn_header_hash}/requeue")
+            )),
+            Ok(AdminRequest::RequeueOp(
+                Txid([1; 32]),
+                BurnchainHeaderHash([2; 32])
+            ))
+        );
+        assert_eq!(
+            parse_request(&request(
+                Method::POST,
+                &format!("/ops/{txid}/{burn_header_hash}/skip")
+            )),
+            Ok(AdminRequest::SkipOp(
+                Txid([1; 32]),
+                BurnchainHeaderHash([2; 32])
+            ))
+        );
+        assert_eq!(
+            parse_request(&request(Method::POST, "/dkg")),
+            Ok(AdminRequest::RunDkg)
+        );
+        assert_eq!(
+            parse_request(&request(
+                Method::POST,
+                "/handoff?signer_config=conf/signer.toml"
+            )),
+            Ok(AdminRequest::HandOff("conf/signer.toml".to_string()))
+        );
+        assert_eq!(
+            parse_request(&request(Method::GET, "/wallet")),
+            Ok(AdminRequest::GetWallet)
+        );
+        assert_eq!(
+            parse_request(&request(Method::GET, "/solvency")),
+            Ok(AdminRequest::GetSolvency)
+        );
+        assert_eq!(
+            parse_request(&request(Method::POST, "/config/reload")),
+            Ok(AdminRequest::ReloadConfig)
+        );
+    }
+
+    #[test]
+    fn invalid_requests_should_be_refused() {
+        assert!(matches!(
+            parse_request(&request(Method::GET, "/ops")),
+            Err(AdminError::BadRequest(_))
+        ));
+        assert!(matches!(
+            parse_request(&request(Method::POST, "/handoff")),
+            Err(AdminError::BadRequest(_))
+        ));
+        assert!(matches!(
+            parse_request(&request(Method::GET, "/ops?status=unknown")),
+            Err(AdminError::BadRequest(_))
+        ));
+        assert!(matches!(
+            parse_request(&request(Method::GET, "/ops/not-a-txid")),
+            Err(AdminError::BadRequest(_))
+        ));
+        assert!(matches!(
+            parse_request(&request(Method::GET, "/dkg")),
+            Err(AdminError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn requests_should_be_served_by_the_coordinator() {
+        let (commands, receiver) = mpsc::channel();
+        let coordinator = thread::spawn(move || {
+            for command in receiver.iter().take(2) {
+                let Command::Admin(request, reply) = command else {
+                    panic!("Expected an admin request");
+                };
+                let response = match request {
+                    AdminRequest::GetWallet => Ok(json!({ "address": "tb1p" })),
+                    _ => Err(AdminError::Conflict(
+                        "Invalid status transition".to_string(),
+                    )),
+                };
+                reply.send(response).unwrap();
+            }
+        });
+        let server = AdminServer::new(commands);
+
+        let mut output = Vec::new();
+        let mut stream = "GET /wallet HTTP/1.1\r\n\r\n"
+            .as_bytes()
+            .mem_io_stream(&mut output);
+        server.serve(&mut stream).unwrap();
+        let response = Response::read(&mut output.as_slice()).unwrap();
+        assert_eq!(response.code, 200);
+        assert_eq!(
+            from_utf8(&response.content).unwrap(),
+            r#"{"address":"tb1p"}"#
+        );
+
+        let mut output = Vec::new();
+        let url = format!("/ops/{}/{}/requeue", "01".repeat(32), "02".repeat(32));
+        let message = format!("POST {url} HTTP/1.1\r\n\r\n");
+        let mut stream = message.as_bytes().mem_io_stream(&mut output);
+        server.serve(&mut stream).unwrap();
+        let response = Response::read(&mut output.as_slice()).unwrap();
+        assert_eq!(response.code, 409);
+
+        coordinator.join().unwrap();
+    }
+
+    #[test]
+    fn requests_should_require_the_token() {
+        let (commands, receiver) = mpsc::channel();
+        let coordinator = thread::spawn(move || {
+            let Ok(Command::Admin(request, reply)) = receiver.recv() else {
+                panic!("Expected an admin request");
+            };
+            assert_eq!(request, AdminRequest::GetWallet);
+            reply.send(Ok(json!({ "address": "tb1p" }))).unwrap();
+        });
+        let mut server = AdminServer::new(commands);
+        server.set_token("secret".to_string());
+
+        for message in [
+            "POST /dkg HTTP/1.1\r\n\r\n",
+            "POST /dkg HTTP/1.1\r\nAuthorization: Bearer guess\r\n\r\n",
+        ] {
+            let mut output = Vec::new();
+            let mut stream = message.as_bytes().mem_io_stream(&mut output);
+            server.serve(&mut stream).unwrap();
+            let response = Response::read(&mut output.as_slice()).unwrap();
+            assert_eq!(response.code, 401);
+        }
+
+        let mut output = Vec::new();
+        let mut stream = "GET /wallet HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n"
+            .as_bytes()
+            .mem_io_stream(&mut output);
+        server.serve(&mut stream).unwrap();
+        let response = Response::read(&mut output.as_slice()).unwrap();
+        assert_eq!(response.code, 200);
+
+        coordinator.join().unwrap();
+    }
+}
@@ -1,12 +1,18 @@
 use std::{borrow::Cow, str::FromStr};
 
 use bdk::descriptor::calc_checksum;
-use bitcoin::{consensus::Encodable, hashes::sha256d::Hash, util::amount::Amount, Txid};
+use bitcoin::{
+    consensus::{encode::deserialize, Encodable},
+    hashes::sha256d::Hash,
+    util::amount::Amount,
+    BlockHash, Txid,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tracing::{debug, info, warn};
 use url::Url;
 
+#[cfg_attr(test, mockall::automock)]
 pub trait BitcoinNode {
     /// Broadcast the BTC transaction to the bitcoin node
     fn broadcast_transaction(&self, tx: &BitcoinTransaction) -> Result<Txid, Error>;
@@ -14,10 +20,33 @@ pub trait BitcoinNode {
     fn load_wallet(&self, address: &bitcoin::Address) -> Result<(), Error>;
     /// Get all utxos from the given address
     fn list_unspent(&self, address: &bitcoin::Address) -> Result<Vec<UTXO>, Error>;
+    /// Estimate the fee rate in sat/vB for a transaction to confirm within `conf_target` blocks
+    fn estimate_fee_rate(&self, conf_target: u16) -> Result<u64, Error>;
+    /// Get the height of the current chain tip
+    fn block_height(&self) -> Result<u64, Error>;
+    /// Get a transaction relevant to the loaded wallet along with its confirmation status
+    fn get_transaction(&self, txid: &Txid) -> Result<WalletTransaction, Error>;
+    /// Whether the transaction is currently in the node's mempool
+    fn is_in_mempool(&self, txid: &Txid) -> Result<bool, Error>;
+    /// Get any transaction included in the given block
+    fn get_raw_transaction(
+        &self,
+        txid: &Txid,
+        block_hash: &BlockHash,
+    ) -> Result<BitcoinTransaction, Error>;
 }
 
 pub type BitcoinTransaction = bitcoin::Transaction;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletTransaction {
+    pub transaction: BitcoinTransaction,
+    /// Number of confirmations. Zero if the transaction is in the mempool, negative if it conflicts with a confirmed transaction.
+    pub confirmations: i64,
+    /// Wallet transactions spending the same inputs
+    pub wallet_conflicts: Vec<Txid>,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("IO Error: {0}")]
@@ -34,6 +63,29 @@ pub enum Error {
     DescriptorError(#[from] bdk::descriptor::error::Error),
     #[error("URL Parse error: {0}")]
     UrlParseError(#[from] url::ParseError),
+    #[error("Unable to estimate fee rate: {0}")]
+    FeeEstimationError(String),
+    #[error("Invalid raw transaction: {0}")]
+    InvalidRawTransaction(String),
+    #[error("Transaction {0} is unknown to the wallet")]
+    UnknownTransaction(Txid),
+}
+
+impl Error {
+    /// Whether the error is caused by the bitcoin node being unreachable or failing a call for
+    /// now, so the call may be retried
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::IOError(_) | Error::RPCError(_) | Error::FeeEstimationError(_) => true,
+            Error::InvalidResponseJSON(_)
+            | Error::InvalidUTXO(_)
+            | Error::InvalidTxHash
+            | Error::DescriptorError(_)
+            | Error::UrlParseError(_)
+            | Error::InvalidRawTransaction(_)
+            | Error::UnknownTransaction(_) => false,
+        }
+    }
 }
 
 #[allow(non_snake_case)]
@@ -124,6 +176,62 @@ impl BitcoinNode for LocalhostBitcoinNode {
 
         result
     }
+
+    fn estimate_fee_rate(&self, conf_target: u16) -> Result<u64, Error> {
+        debug!("Estimating fee rate...");
+        let response = self.call("estimatesmartfee", (conf_target,))?;
+        Self::raw_to_fee_rate(&response)
+    }
+
+    fn block_height(&self) -> Result<u64, Error> {
+        debug!("Retrieving block height...");
+        self.call("getblockcount", ())?
+            .as_u64()
+            .ok_or(Error::InvalidResponseJSON(
+                "Could not parse block count".to_string(),
+            ))
+    }
+
+    fn get_transaction(&self, txid: &Txid) -> Result<WalletTransaction, Error> {
+        debug!("Retrieving transaction {}...", txid);
+        let response = match self.call_wallet("gettransaction", (txid.to_string(),)) {
+            Ok(response) => response,
+            Err(Error::RPCError(message))
+                if message.contains("Invalid or non-wallet transaction id") =>
+            {
+                return Err(Error::UnknownTransaction(*txid))
+            }
+            Err(e) => return Err(e),
+        };
+        Self::raw_to_wallet_transaction(&response)
+    }
+
+    fn is_in_mempool(&self, txid: &Txid) -> Result<bool, Error> {
+        debug!("Checking mempool for transaction {}...", txid);
+        match self.call("getmempoolentry", (txid.to_string(),)) {
+            Ok(_) => Ok(true),
+            Err(Error::RPCError(message)) if message.contains("Transaction not in mempool") => {
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn get_raw_transaction(
+        &self,
+        txid: &Txid,
+        block_hash: &BlockHash,
+    ) -> Result<BitcoinTransaction, Error> {
+        debug!("Retrieving raw transaction {}...", txid);
+        let response = self.call(
+            "getrawtransaction",
+            (txid.to_string(), false, block_hash.to_string()),
+        )?;
+        let raw_tx = response.as_str().ok_or(Error::InvalidResponseJSON(
+            "Could not parse hex".to_string(),
+        ))?;
+        Self::hex_to_transaction(raw_tx)
+    }
 }
 
 impl LocalhostBitcoinNode {
@@ -247,6 +355,67 @@ impl LocalhostBitcoinNode {
         Ok(())
     }
 
+    /// Convert an `estimatesmartfee` response from BTC/kvB to sat/vB, rounding up
+    fn raw_to_fee_rate(raw: &Value) -> Result<u64, Error> {
+        let fee_rate = raw["feerate"].as_f64().ok_or_else(|| {
+            Error::FeeEstimationError(
+                raw["errors"]
+                    .as_array()
+                    .and_then(|errors| errors.first())
+                    .and_then(|error| error.as_str())
+                    .unwrap_or("No feerate in estimatesmartfee response")
+                    .to_string(),
+            )
+        })?;
+        let sats_per_kvbyte = Amount::from_btc(fee_rate)
+            .map_err(|_| {
+                Error::InvalidResponseJSON(format!(
+                    "Could not parse the float {} as a bitcoin amount",
+                    fee_rate
+                ))
+            })?
+            .to_sat();
+        Ok((sats_per_kvbyte + 999) / 1000)
+    }
+
+    fn raw_to_wallet_transaction(raw: &Value) -> Result<WalletTransaction, Error> {
+        let raw_tx = raw["hex"].as_str().ok_or(Error::InvalidResponseJSON(
+            "Could not parse hex".to_string(),
+        ))?;
+        let transaction = Self::hex_to_transaction(raw_tx)?;
+        let confirmations = raw["confirmations"]
+            .as_i64()
+            .ok_or(Error::InvalidResponseJSON(
+                "Could not parse confirmations".to_string(),
+            ))?;
+        let wallet_conflicts = raw["walletconflicts"]
+            .as_array()
+            .map(|conflicts| {
+                conflicts
+                    .iter()
+                    .map(|txid| {
+                        txid.as_str()
+                            .and_then(|txid| Txid::from_str(txid).ok())
+                            .ok_or(Error::InvalidTxHash)
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
+        Ok(WalletTransaction {
+            transaction,
+            confirmations,
+            wallet_conflicts,
+        })
+    }
+
+    fn hex_to_transaction(raw_tx: &str) -> Result<BitcoinTransaction, Error> {
+        let tx_bytes =
+            hex::decode(raw_tx).map_err(|e| Error::InvalidRawTransaction(e.to_string()))?;
+        deserialize::<BitcoinTransaction>(&tx_bytes)
+            .map_err(|e| Error::InvalidRawTransaction(e.to_string()))
+    }
+
     fn raw_to_utxo(raw: &Value) -> Result<UTXO, Error> {
         Ok(UTXO {
             txid: raw["txid"]
@@ -335,6 +504,75 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn should_map_json_to_fee_rate() {
+        // 0.00001 BTC/kvB is 1 sat/vB
+        let value = json!({"feerate": 0.00001, "blocks": 6});
+        assert_eq!(LocalhostBitcoinNode::raw_to_fee_rate(&value).unwrap(), 1);
+
+        // Partial sats per vbyte are rounded up
+        let value = json!({"feerate": 0.00012345, "blocks": 2});
+        assert_eq!(LocalhostBitcoinNode::raw_to_fee_rate(&value).unwrap(), 13);
+
+        let value = json!({"errors": ["Insufficient data or no feerate found"], "blocks": 0});
+        assert!(matches!(
+            LocalhostBitcoinNode::raw_to_fee_rate(&value),
+            Err(Error::FeeEstimationError(_))
+        ));
+    }
+
+    #[test]
+    fn should_map_json_to_wallet_transaction() {
+        let transaction = BitcoinTransaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![bitcoin::TxOut {
+                value: 1337,
+                script_pubkey: bitcoin::Script::new(),
+            }],
+        };
+        let conflict = "19b7fb5fd6dc25b76aeedb812b7fdc7bf8fac343913706c8b39d23ef7375860c";
+        let value = json!({
+            "confirmations": -1,
+            "txid": transaction.txid().to_string(),
+            "walletconflicts": [conflict],
+            "hex": bitcoin::consensus::encode::serialize_hex(&transaction),
+        });
+
+        let res = LocalhostBitcoinNode::raw_to_wallet_transaction(&value).unwrap();
+        assert_eq!(
+            res,
+            WalletTransaction {
+                transaction,
+                confirmations: -1,
+                wallet_conflicts: vec![Txid::from_str(conflict).unwrap()],
+            }
+        );
+    }
+
+    #[test]
+    fn should_map_hex_to_transaction() {
+        let transaction = BitcoinTransaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![bitcoin::TxOut {
+                value: 1337,
+                script_pubkey: bitcoin::Script::new(),
+            }],
+        };
+        let raw_tx = bitcoin::consensus::encode::serialize_hex(&transaction);
+        assert_eq!(
+            LocalhostBitcoinNode::hex_to_transaction(&raw_tx).unwrap(),
+            transaction
+        );
+        assert!(matches!(
+            LocalhostBitcoinNode::hex_to_transaction("zz"),
+            Err(Error::InvalidRawTransaction(_))
+        ));
+    }
+
     #[test]
     fn should_map_json_to_utxo() {
         let value = json!({
@@ -1,13 +1,23 @@
 use std::iter::repeat;
 
 use crate::bitcoin_node::UTXO;
+use crate::coin_selector::{CoinSelector, LargestFirst, SelectionTarget, TAPROOT_INPUT_VBYTES};
 use crate::peg_wallet::{BitcoinWallet as BitcoinWalletTrait, Error as PegWalletError};
 use crate::stacks_node::PegOutRequestOp;
 use bitcoin::blockdata::opcodes;
+use bitcoin::consensus::encode::serialize;
 use bitcoin::TxOut;
 use bitcoin::{
-    blockdata::script, hashes::hex::FromHex, schnorr::TweakedPublicKey, Address, Network, OutPoint,
-    Script, Transaction, TxIn, XOnlyPublicKey,
+    blockdata::script, hashes::hex::FromHex, schnorr::TweakedPublicKey,
+    util::address::WitnessVersion, Address, Network, OutPoint, Script, Transaction, TxIn, Witness,
+    XOnlyPublicKey,
+};
+use blockstack_lib::{
+    address::{
+        AddressHashMode, C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
+        C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+    },
+    chainstate::stacks::address::{PoxAddress, PoxAddressType20, PoxAddressType32},
 };
 use tracing::{debug, warn};
 
@@ -23,31 +33,82 @@ pub enum Error {
     MissingFulfillmentUTXO,
     #[error("Fulfillment UTXO amount does not equal the fulfillment fee.")]
     MismatchedFulfillmentFee,
+    #[error("No peg-out request ops to fulfill.")]
+    NoPegOutRequestOps,
+    #[error("Fee rate {0} sat/vB does not increase the fee of the transaction.")]
+    FeeRateTooLow(u64),
+    #[error("Unable to bump the fee of the transaction due to insufficient change.")]
+    InsufficientFundsForFeeBump,
+    #[error("No utxos to sweep.")]
+    NoUTXOs,
+}
+
+/// Bounds and timing for the miner fees paid by peg-out fulfillment transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitcoinFeePolicy {
+    /// Number of blocks the fee estimate should target for confirmation
+    pub conf_target: u16,
+    /// Lowest fee rate in sat/vB the coordinator will pay
+    pub min_fee_rate: u64,
+    /// Highest fee rate in sat/vB the coordinator will pay
+    pub max_fee_rate: u64,
+    /// Number of blocks a fulfillment may remain unconfirmed before its fee is bumped
+    pub bump_after_blocks: u64,
+}
+
+impl BitcoinFeePolicy {
+    /// Clamp the given fee rate to the configured floor and ceiling
+    pub fn clamp(&self, fee_rate: u64) -> u64 {
+        fee_rate.clamp(self.min_fee_rate, self.max_fee_rate)
+    }
+
+    /// Fee rate for a replacement transaction given the current and estimated rates.
+    /// Returns None if the ceiling prevents any increase.
+    pub fn bumped_fee_rate(&self, current_fee_rate: u64, estimated_fee_rate: u64) -> Option<u64> {
+        // Replacements must increase the rate by a meaningful step to be relayed
+        let min_increase = current_fee_rate + (current_fee_rate / 4).max(1);
+        let fee_rate = self.clamp(estimated_fee_rate.max(min_increase));
+        (fee_rate > current_fee_rate).then_some(fee_rate)
+    }
 }
 
 pub struct BitcoinWallet {
     address: Address,
     public_key: XOnlyPublicKey,
+    coin_selector: Box<dyn CoinSelector>,
 }
 
 impl BitcoinWallet {
     pub fn new(public_key: XOnlyPublicKey, network: Network) -> Self {
-        let tweaked_public_key = TweakedPublicKey::dangerous_assume_tweaked(public_key);
-        let address = Address::p2tr_tweaked(tweaked_public_key, network);
         Self {
-            address,
+            address: taproot_address(public_key, network),
             public_key,
+            coin_selector: Box::new(LargestFirst),
         }
     }
+
+    /// Sets the strategy used to choose the utxos funding peg-out fulfillments
+    pub fn set_coin_selector(&mut self, coin_selector: Box<dyn CoinSelector>) {
+        self.coin_selector = coin_selector;
+    }
+
+    fn script_pubkey(&self) -> Script {
+        let public_key_tweaked = TweakedPublicKey::dangerous_assume_tweaked(self.public_key);
+        Script::new_v1_p2tr_tweaked(public_key_tweaked)
+    }
 }
 
 impl BitcoinWalletTrait for BitcoinWallet {
     type Error = Error;
-    fn fulfill_peg_out(
+    fn fulfill_peg_outs(
         &self,
-        op: &PegOutRequestOp,
+        ops: &[PegOutRequestOp],
         available_utxos: Vec<UTXO>,
+        fee_rate: u64,
     ) -> Result<(Transaction, Vec<TxOut>), PegWalletError> {
+        if ops.is_empty() {
+            return Err(PegWalletError::from(Error::NoPegOutRequestOps));
+        }
         // Create an empty transaction
         let mut tx = Transaction {
             version: 2,
@@ -55,75 +116,224 @@ impl BitcoinWalletTrait for BitcoinWallet {
             input: vec![],
             output: vec![],
         };
-        // Consume UTXOs until we have enough to cover the total spend (fulfillment fee and peg out amount)
+        let total_amount: u64 = ops.iter().map(|op| op.amount).sum();
+        // Do not want to use Script::new_v1_p2tr because it will tweak our key when we don't want it to
+        let script_pubkey = self.script_pubkey();
+
+        tx.output.push(withdrawal_data_output());
+
+        // One withdrawal output per recipient
+        for op in ops {
+            let withdrawal_output = bitcoin::TxOut {
+                value: op.amount,
+                script_pubkey: recipient_script_pubkey(&op.recipient),
+            };
+            tx.output.push(withdrawal_output);
+        }
+
+        // Every fulfillment utxo must be spent, so take them first
+        let (fulfillment_utxos, funding_utxos): (Vec<UTXO>, Vec<UTXO>) =
+            available_utxos.into_iter().partition(|utxo| {
+                utxo.vout == 2 && ops.iter().any(|op| utxo.txid == op.txid.to_string())
+            });
         let mut total_consumed = 0;
         let mut prevouts = vec![];
-        let mut found_fulfillment_utxo = false;
-        for utxo in available_utxos.into_iter() {
-            if utxo.txid == op.txid.to_string() && utxo.vout == 2 {
-                // This is the fulfillment utxo.
-                if utxo.amount != op.fulfillment_fee {
-                    // Something is wrong. The fulfillment fee should match the fulfillment utxo amount.
-                    // Malformed Peg Request Op
-                    return Err(PegWalletError::from(Error::MismatchedFulfillmentFee));
-                }
-                tx.input.push(utxo_to_input(&utxo)?);
-                prevouts.push(utxo_to_output(&utxo)?);
-                found_fulfillment_utxo = true;
-            } else if total_consumed < op.amount {
-                total_consumed += utxo.amount;
-                tx.input.push(utxo_to_input(&utxo)?);
-                prevouts.push(utxo_to_output(&utxo)?);
-            } else if found_fulfillment_utxo {
-                // We have consumed enough to cover the total spend
-                // i.e. have found the fulfillment utxo and covered the peg out amount
-                break;
+        for op in ops {
+            let utxo = fulfillment_utxos
+                .iter()
+                .find(|utxo| utxo.txid == op.txid.to_string())
+                .ok_or_else(|| {
+                    // If we did not find a fulfillment utxo, something went wrong
+                    warn!("Failed to find fulfillment utxo.");
+                    PegWalletError::from(Error::MissingFulfillmentUTXO)
+                })?;
+            if utxo.amount != op.fulfillment_fee {
+                // Something is wrong. The fulfillment fee should match the fulfillment utxo amount.
+                // Malformed Peg Request Op
+                return Err(PegWalletError::from(Error::MismatchedFulfillmentFee));
             }
+            total_consumed += utxo.amount;
+            tx.input.push(utxo_to_input(utxo)?);
+            prevouts.push(utxo_to_output(utxo)?);
         }
-        // Sanity check all the things!
-        // If we did not find the fulfillment utxo, something went wrong
-        if !found_fulfillment_utxo {
-            warn!("Failed to find fulfillment utxo.");
-            return Err(PegWalletError::from(Error::MissingFulfillmentUTXO));
+
+        // Select utxos to cover the rest of the total spend (peg out amounts and miner fee)
+        let change_output = bitcoin::TxOut {
+            value: 0,
+            script_pubkey: script_pubkey.clone(),
+        };
+        let dust = script_pubkey.dust_value().to_sat();
+        let target = SelectionTarget {
+            amount: (total_amount + fee_rate * fulfillment_vsize(&tx))
+                .saturating_sub(total_consumed),
+            input_fee: fee_rate * TAPROOT_INPUT_VBYTES,
+            cost_of_change: fee_rate * serialize(&change_output).len() as u64 + dust,
+        };
+        let selected_utxos = self
+            .coin_selector
+            .select(funding_utxos, &target)
+            .ok_or_else(|| {
+                warn!(
+                    "Available utxos cannot cover the intended spend: {} (selection target {})",
+                    total_amount, target.amount
+                );
+                PegWalletError::from(Error::InsufficientFunds)
+            })?;
+        for utxo in selected_utxos {
+            total_consumed += utxo.amount;
+            tx.input.push(utxo_to_input(&utxo)?);
+            prevouts.push(utxo_to_output(&utxo)?);
         }
-        // Check that we have sufficient funds and didn't just run out of available utxos.
-        if total_consumed < op.amount {
+
+        // Sanity check that the selection covers the spend
+        let fee = fee_rate * fulfillment_vsize(&tx);
+        if total_consumed < total_amount + fee {
             warn!(
-                "Consumed total {} is less than intended spend: {}",
-                total_consumed, op.amount
+                "Consumed total {} is less than intended spend: {} (fee {})",
+                total_consumed, total_amount, fee
             );
             return Err(PegWalletError::from(Error::InsufficientFunds));
         }
 
-        // Get the transaction change amount
-        let change_amount = total_consumed - op.amount;
+        // Get the transaction change amount once the change output is paid for
+        tx.output.push(change_output);
+        let fee = fee_rate * fulfillment_vsize(&tx);
+        let change_amount = (total_consumed - total_amount).saturating_sub(fee);
         debug!(
-            "change_amount: {:?}, total_consumed: {:?}, op.amount: {:?}",
-            change_amount, total_consumed, op.amount
+            "change_amount: {:?}, total_consumed: {:?}, total_amount: {:?}, fee: {:?}",
+            change_amount, total_consumed, total_amount, fee
         );
-        // Do not want to use Script::new_v1_p2tr because it will tweak our key when we don't want it to
-        let public_key_tweaked = TweakedPublicKey::dangerous_assume_tweaked(self.public_key);
-        let script_pubkey = Script::new_v1_p2tr_tweaked(public_key_tweaked);
+        if change_amount >= dust {
+            if let Some(change_output) = tx.output.last_mut() {
+                change_output.value = change_amount;
+            }
+        } else {
+            // The excess is less than the cost of the change output, leave it to the miner
+            debug!("Not enough change to clear dust limit. Not adding change address.");
+            tx.output.pop();
+        }
 
-        tx.output.push(withdrawal_data_output());
+        Ok((tx, prevouts))
+    }
 
-        let withdrawal_output = bitcoin::TxOut {
-            value: op.amount,
-            script_pubkey: script_pubkey.clone(),
-        };
-        tx.output.push(withdrawal_output);
+    fn bump_fee(
+        &self,
+        tx: &Transaction,
+        prevouts: &[TxOut],
+        available_utxos: Vec<UTXO>,
+        fee_rate: u64,
+    ) -> Result<(Transaction, Vec<TxOut>), PegWalletError> {
+        let mut replacement = tx.clone();
+        let mut prevouts = prevouts.to_vec();
+        // The replacement must be re-signed, so drop the existing signatures
+        for input in replacement.input.iter_mut() {
+            input.witness = Default::default();
+        }
+        let mut total_in: u64 = prevouts.iter().map(|prevout| prevout.value).sum();
+        let total_out: u64 = tx.output.iter().map(|output| output.value).sum();
+        let current_fee = total_in.saturating_sub(total_out);
+        if fee_rate * fulfillment_vsize(&replacement) <= current_fee {
+            return Err(PegWalletError::from(Error::FeeRateTooLow(fee_rate)));
+        }
 
-        if change_amount >= script_pubkey.dust_value().to_sat() {
-            let change_output = bitcoin::TxOut {
-                value: change_amount,
-                script_pubkey,
+        // Take the additional fee from our change output, adding one if the transaction has none
+        let script_pubkey = self.script_pubkey();
+        let dust = script_pubkey.dust_value().to_sat();
+        let change_index = match replacement
+            .output
+            .iter()
+            .rposition(|output| output.script_pubkey == script_pubkey)
+        {
+            Some(index) => index,
+            None => {
+                replacement.output.push(bitcoin::TxOut {
+                    value: 0,
+                    script_pubkey,
+                });
+                replacement.output.len() - 1
+            }
+        };
+        let spend: u64 = replacement
+            .output
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != change_index)
+            .map(|(_, output)| output.value)
+            .sum();
+
+        // Replacements may only add confirmed inputs. Spend the largest utxos first when the
+        // change cannot cover the fee increase.
+        let mut utxos: Vec<UTXO> = available_utxos
+            .into_iter()
+            .filter(|utxo| {
+                utxo.confirmations > 0
+                    && !replacement.input.iter().any(|input| {
+                        input.previous_output.txid.to_string() == utxo.txid
+                            && input.previous_output.vout == utxo.vout
+                    })
+            })
+            .collect();
+        utxos.sort_by_key(|utxo| utxo.amount);
+        loop {
+            let fee = fee_rate * fulfillment_vsize(&replacement);
+            if total_in >= spend + fee + dust {
+                replacement.output[change_index].value = total_in - spend - fee;
+                return Ok((replacement, prevouts));
+            }
+            let Some(utxo) = utxos.pop() else {
+                warn!(
+                    "Wallet cannot cover fee increase to {} sat/vB of transaction {}",
+                    fee_rate,
+                    tx.txid()
+                );
+                return Err(PegWalletError::from(Error::InsufficientFundsForFeeBump));
             };
-            tx.output.push(change_output);
-        } else {
-            // Instead of leaving that change to the BTC miner, we could / should bump the sortition fee
-            debug!("Not enough change to clear dust limit. Not adding change address.");
+            debug!(
+                "Adding utxo {}:{} to cover the fee increase",
+                utxo.txid, utxo.vout
+            );
+            total_in += utxo.amount;
+            replacement.input.push(utxo_to_input(&utxo)?);
+            prevouts.push(utxo_to_output(&utxo)?);
         }
+    }
 
+    fn sweep(
+        &self,
+        utxos: Vec<UTXO>,
+        fee_rate: u64,
+    ) -> Result<(Transaction, Vec<TxOut>), PegWalletError> {
+        if utxos.is_empty() {
+            return Err(PegWalletError::from(Error::NoUTXOs));
+        }
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![],
+            output: vec![],
+        };
+        let mut prevouts = vec![];
+        let mut total_consumed = 0;
+        for utxo in &utxos {
+            total_consumed += utxo.amount;
+            tx.input.push(utxo_to_input(utxo)?);
+            prevouts.push(utxo_to_output(utxo)?);
+        }
+        let script_pubkey = self.script_pubkey();
+        let dust = script_pubkey.dust_value().to_sat();
+        tx.output.push(bitcoin::TxOut {
+            value: 0,
+            script_pubkey,
+        });
+        let fee = fee_rate * fulfillment_vsize(&tx);
+        if total_consumed < fee + dust {
+            debug!(
+                "Sweeping {} sats does not cover the fee {} and dust limit {}",
+                total_consumed, fee, dust
+            );
+            return Err(PegWalletError::from(Error::InsufficientFunds));
+        }
+        tx.output[0].value = total_consumed - fee;
         Ok((tx, prevouts))
     }
 
@@ -134,6 +344,16 @@ impl BitcoinWalletTrait for BitcoinWallet {
     fn x_only_pub_key(&self) -> &XOnlyPublicKey {
         &self.public_key
     }
+
+    fn set_x_only_pub_key(&mut self, public_key: XOnlyPublicKey) {
+        self.address = taproot_address(public_key, self.address.network);
+        self.public_key = public_key;
+    }
+}
+
+fn taproot_address(public_key: XOnlyPublicKey, network: Network) -> Address {
+    let tweaked_public_key = TweakedPublicKey::dangerous_assume_tweaked(public_key);
+    Address::p2tr_tweaked(tweaked_public_key, network)
 }
 
 fn withdrawal_data_output() -> TxOut {
@@ -154,6 +374,55 @@ fn withdrawal_data_output() -> TxOut {
     }
 }
 
+/// Virtual size of the transaction once every input carries a taproot key path signature
+pub fn fulfillment_vsize(tx: &Transaction) -> u64 {
+    let mut signed = tx.clone();
+    for input in signed.input.iter_mut() {
+        input.witness = Witness::from_vec(vec![vec![0; 64]]);
+    }
+    (signed.weight() as u64 + 3) / 4
+}
+
+// Helper function to build the script paying to a peg-out recipient
+fn recipient_script_pubkey(recipient: &PoxAddress) -> Script {
+    match recipient {
+        PoxAddress::Standard(address, hash_mode) => {
+            let is_p2pkh = match hash_mode {
+                Some(hash_mode) => *hash_mode == AddressHashMode::SerializeP2PKH,
+                None => {
+                    address.version == C32_ADDRESS_VERSION_MAINNET_SINGLESIG
+                        || address.version == C32_ADDRESS_VERSION_TESTNET_SINGLESIG
+                }
+            };
+            let hash = &address.bytes.0;
+            if is_p2pkh {
+                script::Builder::new()
+                    .push_opcode(opcodes::all::OP_DUP)
+                    .push_opcode(opcodes::all::OP_HASH160)
+                    .push_slice(hash)
+                    .push_opcode(opcodes::all::OP_EQUALVERIFY)
+                    .push_opcode(opcodes::all::OP_CHECKSIG)
+                    .into_script()
+            } else {
+                script::Builder::new()
+                    .push_opcode(opcodes::all::OP_HASH160)
+                    .push_slice(hash)
+                    .push_opcode(opcodes::all::OP_EQUAL)
+                    .into_script()
+            }
+        }
+        PoxAddress::Addr20(_, PoxAddressType20::P2WPKH, hash) => {
+            Script::new_witness_program(WitnessVersion::V0, hash)
+        }
+        PoxAddress::Addr32(_, PoxAddressType32::P2WSH, hash) => {
+            Script::new_witness_program(WitnessVersion::V0, hash)
+        }
+        PoxAddress::Addr32(_, PoxAddressType32::P2TR, key) => {
+            Script::new_witness_program(WitnessVersion::V1, key)
+        }
+    }
+}
+
 // Helper function to convert a utxo to an unsigned input
 fn utxo_to_input(utxo: &UTXO) -> Result<TxIn, Error> {
     let input = TxIn {
@@ -183,11 +452,14 @@ fn utxo_to_output(utxo: &UTXO) -> Result<TxOut, Error> {
 
 #[cfg(test)]
 mod tests {
-    use super::{BitcoinWallet, Error};
+    use super::{fulfillment_vsize, BitcoinFeePolicy, BitcoinWallet, Error};
     use crate::bitcoin_node::UTXO;
+    use crate::coin_selector::{BranchAndBound, LargestFirst};
     use crate::peg_wallet::{BitcoinWallet as BitcoinWalletTrait, Error as PegWalletError};
     use crate::util::test::{build_peg_out_request_op, PRIVATE_KEY_HEX};
     use bitcoin::XOnlyPublicKey;
+    use blockstack_lib::burnchains::Txid;
+    use blockstack_lib::chainstate::stacks::address::{PoxAddress, PoxAddressType32};
     use hex::encode;
     use rand::Rng;
     use std::str::FromStr;
@@ -241,7 +513,7 @@ mod tests {
         let fulfillment_utxo = build_utxo(op.txid.to_string(), 2, 1);
         txouts.push(fulfillment_utxo);
 
-        let result = wallet.fulfill_peg_out(&op, txouts);
+        let result = wallet.fulfill_peg_out(&op, txouts, 0);
         assert!(result.is_err());
         assert_eq!(
             result.err().unwrap(),
@@ -251,10 +523,11 @@ mod tests {
 
     #[test]
     fn fulfill_peg_out_change() {
-        let wallet = bitcoin_wallet();
-        let amount = 200000;
+        let mut wallet = bitcoin_wallet();
+        wallet.set_coin_selector(Box::new(LargestFirst));
+        let amount = 190000;
 
-        // (1+2+3+4+5)*10000 = 210000 > 200000. We have change of 10000
+        // (6+5+4+3+2)*10000 + 1 = 200001 > 190000. We have change of 10001
         let mut txouts = build_utxos(6); // (1+2+3+4+5+6)*10000 = 210000
 
         let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1);
@@ -262,12 +535,12 @@ mod tests {
         let fulfillment_utxo = build_utxo(op.txid.to_string(), 2, 1);
         txouts.push(fulfillment_utxo);
 
-        let (btc_tx, _) = wallet.fulfill_peg_out(&op, txouts).unwrap();
-        assert_eq!(btc_tx.input.len(), 7);
+        let (btc_tx, _) = wallet.fulfill_peg_out(&op, txouts, 0).unwrap();
+        assert_eq!(btc_tx.input.len(), 6);
         assert_eq!(btc_tx.output.len(), 3); // We have change!
         assert_eq!(btc_tx.output[0].value, 0);
         assert_eq!(btc_tx.output[1].value, amount);
-        assert_eq!(btc_tx.output[2].value, 10000);
+        assert_eq!(btc_tx.output[2].value, 10001);
     }
 
     #[test]
@@ -275,7 +548,7 @@ mod tests {
         let wallet = bitcoin_wallet();
         let amount = 9999;
 
-        // 1*10000 = 10000 > 9999. We only have change of 1...not enough to cover dust
+        // 1*10000 + 1 = 10001 > 9999. We only have change of 2...not enough to cover dust
         let mut txouts = build_utxos(1); // 1*10000 = 10000
 
         let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1);
@@ -283,9 +556,34 @@ mod tests {
         let fulfillment_utxo = build_utxo(op.txid.to_string(), 2, 1);
         txouts.push(fulfillment_utxo);
 
-        let (btc_tx, _) = wallet.fulfill_peg_out(&op, txouts).unwrap();
+        let (btc_tx, _) = wallet.fulfill_peg_out(&op, txouts, 0).unwrap();
         assert_eq!(btc_tx.input.len(), 2);
         assert_eq!(btc_tx.output.len(), 2); // No change!
+        assert_eq!(btc_tx.output[1].value, amount);
+    }
+
+    #[test]
+    fn fulfill_peg_out_pays_recipient() {
+        let wallet = bitcoin_wallet();
+        let amount = 9999;
+
+        let mut txouts = build_utxos(1);
+        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1);
+        txouts.push(build_utxo(op.txid.to_string(), 2, 1));
+
+        // The withdrawal goes to the recipient of the request, here a taproot output
+        let PoxAddress::Addr32(_, PoxAddressType32::P2TR, key) = op.recipient else {
+            panic!("Expected a taproot recipient");
+        };
+        let mut recipient_script_pubkey = vec![0x51, 0x20]; // OP_1 OP_PUSHBYTES_32
+        recipient_script_pubkey.extend_from_slice(&key);
+
+        let (btc_tx, _) = wallet.fulfill_peg_out(&op, txouts, 0).unwrap();
+        assert_eq!(btc_tx.output[1].value, amount);
+        assert_eq!(
+            btc_tx.output[1].script_pubkey.as_bytes(),
+            recipient_script_pubkey.as_slice()
+        );
     }
 
     #[test]
@@ -303,7 +601,7 @@ mod tests {
         txouts.push(fulfillment_utxo_invalid_vout);
         txouts.push(fulfillment_utxo_invalid_txid);
 
-        let result = wallet.fulfill_peg_out(&op, txouts);
+        let result = wallet.fulfill_peg_out(&op, txouts, 0);
 
         assert!(result.is_err());
         assert_eq!(
@@ -324,7 +622,7 @@ mod tests {
         let fulfillment_utxo_invalid_amount = build_utxo(op.txid.to_string(), 2, 1);
         txouts.push(fulfillment_utxo_invalid_amount);
 
-        let result = wallet.fulfill_peg_out(&op, txouts);
+        let result = wallet.fulfill_peg_out(&op, txouts, 0);
 
         assert!(result.is_err());
         assert_eq!(
@@ -332,4 +630,282 @@ mod tests {
             PegWalletError::BitcoinWalletError(Error::MismatchedFulfillmentFee)
         );
     }
+
+    #[test]
+    fn fulfill_peg_outs_batch() {
+        let mut wallet = bitcoin_wallet();
+        wallet.set_coin_selector(Box::new(LargestFirst));
+        let first_amount = 100000;
+        let second_amount = 40000;
+
+        // (6+5+4)*10000 + 1 + 2 = 150003 > 140000. We have change of 10003
+        let mut txouts = build_utxos(6);
+
+        let first_op = build_peg_out_request_op(PRIVATE_KEY_HEX, first_amount, 1, 1);
+        let mut second_op = build_peg_out_request_op(PRIVATE_KEY_HEX, second_amount, 1, 2);
+        second_op.txid = Txid([1; 32]);
+        // Build fulfillment utxos that match the generated ops
+        txouts.push(build_utxo(first_op.txid.to_string(), 2, 1));
+        txouts.push(build_utxo(second_op.txid.to_string(), 2, 2));
+
+        let (btc_tx, prevouts) = wallet
+            .fulfill_peg_outs(&[first_op, second_op], txouts, 0)
+            .unwrap();
+        // Three funding utxos and both fulfillment utxos
+        assert_eq!(btc_tx.input.len(), 5);
+        assert_eq!(prevouts.len(), 5);
+        assert_eq!(btc_tx.output.len(), 4);
+        assert_eq!(btc_tx.output[0].value, 0);
+        assert_eq!(btc_tx.output[1].value, first_amount);
+        assert!(btc_tx.output[1].script_pubkey.is_v1_p2tr());
+        assert_eq!(btc_tx.output[2].value, second_amount);
+        assert!(btc_tx.output[2].script_pubkey.is_v1_p2tr());
+        assert_ne!(
+            btc_tx.output[1].script_pubkey,
+            btc_tx.output[2].script_pubkey
+        );
+        assert_eq!(btc_tx.output[3].value, 10003);
+    }
+
+    #[test]
+    fn fulfill_peg_outs_missing_fulfillment_utxo() {
+        let wallet = bitcoin_wallet();
+
+        let mut txouts = build_utxos(6);
+
+        let first_op = build_peg_out_request_op(PRIVATE_KEY_HEX, 10000, 1, 1);
+        let mut second_op = build_peg_out_request_op(PRIVATE_KEY_HEX, 10000, 1, 1);
+        second_op.txid = Txid([1; 32]);
+        // Only the first op has a fulfillment utxo
+        txouts.push(build_utxo(first_op.txid.to_string(), 2, 1));
+
+        let result = wallet.fulfill_peg_outs(&[first_op, second_op], txouts, 0);
+        assert_eq!(
+            result.err().unwrap(),
+            PegWalletError::BitcoinWalletError(Error::MissingFulfillmentUTXO)
+        );
+    }
+
+    #[test]
+    fn fulfill_peg_outs_no_ops() {
+        let wallet = bitcoin_wallet();
+
+        let result = wallet.fulfill_peg_outs(&[], build_utxos(1), 0);
+        assert_eq!(
+            result.err().unwrap(),
+            PegWalletError::BitcoinWalletError(Error::NoPegOutRequestOps)
+        );
+    }
+
+    #[test]
+    fn fulfill_peg_out_deducts_fee() {
+        let mut wallet = bitcoin_wallet();
+        let fee_rate = 10;
+        wallet.set_coin_selector(Box::new(LargestFirst));
+        let amount = 190000;
+
+        let mut txouts = build_utxos(6);
+        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1);
+        txouts.push(build_utxo(op.txid.to_string(), 2, 1));
+
+        let (btc_tx, prevouts) = wallet.fulfill_peg_out(&op, txouts, fee_rate).unwrap();
+        let total_in: u64 = prevouts.iter().map(|prevout| prevout.value).sum();
+        let total_out: u64 = btc_tx.output.iter().map(|output| output.value).sum();
+        assert_eq!(total_in - total_out, 10 * fulfillment_vsize(&btc_tx));
+        assert_eq!(btc_tx.output.len(), 3);
+        assert_eq!(
+            btc_tx.output[2].value,
+            10001 - 10 * fulfillment_vsize(&btc_tx)
+        );
+    }
+
+    #[test]
+    fn fulfill_peg_out_fee_consumes_more_utxos() {
+        let wallet = bitcoin_wallet();
+        let fee_rate = 10;
+        // The fulfillment utxo and one funding utxo cover the amount but not the fee
+        let amount = 10000;
+
+        let mut txouts = vec![
+            build_utxo(generate_txid(), 0, 10000),
+            build_utxo(generate_txid(), 1, 10000),
+        ];
+        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1);
+        txouts.push(build_utxo(op.txid.to_string(), 2, 1));
+
+        let (btc_tx, _) = wallet.fulfill_peg_out(&op, txouts, fee_rate).unwrap();
+        assert_eq!(btc_tx.input.len(), 3);
+    }
+
+    #[test]
+    fn bump_fee_reduces_change() {
+        let mut wallet = bitcoin_wallet();
+        let fee_rate = 2;
+        wallet.set_coin_selector(Box::new(LargestFirst));
+        let amount = 190000;
+
+        let mut txouts = build_utxos(6);
+        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1);
+        txouts.push(build_utxo(op.txid.to_string(), 2, 1));
+
+        let (btc_tx, prevouts) = wallet.fulfill_peg_out(&op, txouts, fee_rate).unwrap();
+        let (replacement, replacement_prevouts) =
+            wallet.bump_fee(&btc_tx, &prevouts, vec![], 5).unwrap();
+        let vsize = fulfillment_vsize(&replacement);
+        assert_eq!(replacement.input, btc_tx.input);
+        assert_eq!(replacement_prevouts, prevouts);
+        assert_eq!(replacement.output[1], btc_tx.output[1]);
+        assert_eq!(
+            btc_tx.output[2].value - replacement.output[2].value,
+            3 * vsize
+        );
+
+        // A lower fee rate is not a replacement
+        assert_eq!(
+            wallet
+                .bump_fee(&btc_tx, &prevouts, vec![], 1)
+                .err()
+                .unwrap(),
+            PegWalletError::BitcoinWalletError(Error::FeeRateTooLow(1))
+        );
+        // The change cannot cover an absurd fee rate
+        assert_eq!(
+            wallet
+                .bump_fee(&btc_tx, &prevouts, vec![], 1000)
+                .err()
+                .unwrap(),
+            PegWalletError::BitcoinWalletError(Error::InsufficientFundsForFeeBump)
+        );
+    }
+
+    #[test]
+    fn bump_fee_adds_input_and_change() {
+        let wallet = bitcoin_wallet();
+        let amount = 9999;
+
+        // Leaves no change, as in fulfill_peg_out_no_change
+        let mut txouts = build_utxos(1);
+        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1);
+        txouts.push(build_utxo(op.txid.to_string(), 2, 1));
+        let (btc_tx, prevouts) = wallet.fulfill_peg_out(&op, txouts, 0).unwrap();
+        assert_eq!(btc_tx.output.len(), 2);
+
+        // Unconfirmed utxos cannot be added to a replacement
+        let unconfirmed = build_utxo(generate_txid(), 0, 50000);
+        assert_eq!(
+            wallet
+                .bump_fee(&btc_tx, &prevouts, vec![unconfirmed.clone()], 5)
+                .err()
+                .unwrap(),
+            PegWalletError::BitcoinWalletError(Error::InsufficientFundsForFeeBump)
+        );
+
+        let confirmed = UTXO {
+            confirmations: 1,
+            ..build_utxo(generate_txid(), 0, 20000)
+        };
+        let (replacement, replacement_prevouts) = wallet
+            .bump_fee(&btc_tx, &prevouts, vec![unconfirmed, confirmed.clone()], 5)
+            .unwrap();
+        assert_eq!(replacement.input.len(), btc_tx.input.len() + 1);
+        assert_eq!(replacement_prevouts.len(), prevouts.len() + 1);
+        assert_eq!(
+            replacement
+                .input
+                .last()
+                .unwrap()
+                .previous_output
+                .txid
+                .to_string(),
+            confirmed.txid
+        );
+        assert_eq!(replacement.output[..2], btc_tx.output[..]);
+        assert_eq!(replacement.output.len(), 3);
+        assert_eq!(
+            replacement.output[2].script_pubkey,
+            wallet.address().script_pubkey()
+        );
+
+        // The replacement pays exactly the new fee rate
+        let total_in: u64 = replacement_prevouts
+            .iter()
+            .map(|prevout| prevout.value)
+            .sum();
+        let total_out: u64 = replacement.output.iter().map(|output| output.value).sum();
+        assert_eq!(total_in - total_out, 5 * fulfillment_vsize(&replacement));
+    }
+
+    #[test]
+    fn fee_policy_bumped_fee_rate() {
+        let policy = BitcoinFeePolicy {
+            conf_target: 6,
+            min_fee_rate: 1,
+            max_fee_rate: 100,
+            bump_after_blocks: 6,
+        };
+        assert_eq!(policy.clamp(0), 1);
+        assert_eq!(policy.clamp(1000), 100);
+        // The estimate is used when it is a large enough increase
+        assert_eq!(policy.bumped_fee_rate(10, 20), Some(20));
+        // Otherwise the rate is increased by at least a quarter
+        assert_eq!(policy.bumped_fee_rate(20, 10), Some(25));
+        assert_eq!(policy.bumped_fee_rate(1, 1), Some(2));
+        // No increase is possible at the ceiling
+        assert_eq!(policy.bumped_fee_rate(100, 200), None);
+    }
+
+    #[test]
+    fn fulfill_peg_out_changeless() {
+        let mut wallet = bitcoin_wallet();
+        wallet.set_coin_selector(Box::new(BranchAndBound));
+        let amount = 49999;
+
+        // 5*10000 + 1 = 50001 covers the amount without leaving change worth an output
+        let mut txouts = build_utxos(6);
+        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1);
+        txouts.push(build_utxo(op.txid.to_string(), 2, 1));
+
+        let (btc_tx, _) = wallet.fulfill_peg_out(&op, txouts, 0).unwrap();
+        assert_eq!(btc_tx.output.len(), 2);
+        assert_eq!(btc_tx.output[1].value, amount);
+    }
+
+    #[test]
+    fn sweep_should_pay_all_utxos_to_the_wallet() {
+        let wallet = bitcoin_wallet();
+        let fee_rate = 10;
+        let utxos = build_utxos(3);
+        let total: u64 = utxos.iter().map(|utxo| utxo.amount).sum();
+
+        let (tx, prevouts) = wallet.sweep(utxos, fee_rate).unwrap();
+        assert_eq!(tx.input.len(), 3);
+        assert_eq!(prevouts.len(), 3);
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(tx.output[0].script_pubkey, wallet.address().script_pubkey());
+        assert_eq!(tx.output[0].value, total - 10 * fulfillment_vsize(&tx));
+
+        assert_eq!(
+            wallet.sweep(vec![], fee_rate).err().unwrap(),
+            PegWalletError::BitcoinWalletError(Error::NoUTXOs)
+        );
+        let fee_rate = 1_000_000;
+        assert_eq!(
+            wallet.sweep(build_utxos(1), fee_rate).err().unwrap(),
+            PegWalletError::BitcoinWalletError(Error::InsufficientFunds)
+        );
+    }
+
+    #[test]
+    fn set_x_only_pub_key_should_switch_address() {
+        let mut wallet = bitcoin_wallet();
+        let public_key = XOnlyPublicKey::from_str(
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let expected = BitcoinWallet::new(public_key, bitcoin::Network::Testnet);
+
+        wallet.set_x_only_pub_key(public_key);
+        assert_eq!(wallet.x_only_pub_key(), &public_key);
+        assert_eq!(wallet.address(), expected.address());
+    }
 }
@@ -9,6 +9,11 @@ pub struct Cli {
     #[arg(short, long)]
     pub config: String,
 
+    /// Environment whose override file, e.g. coordinator.testnet.toml for coordinator.toml,
+    /// is layered on top of the config file
+    #[arg(short, long, env = "STACKS_COORDINATOR_ENVIRONMENT")]
+    pub environment: Option<String>,
+
     /// Optional starting block height to use.
     /// Will override any listed value within the config file
     /// Must be greater than 0.
@@ -33,4 +38,6 @@ pub enum Command {
     Dkg,
     // Run distributed key generation round then sign a message
     DkgSign,
+    // Compare the peg wallet balance with the sBTC supply and print the report
+    Reconcile,
 }
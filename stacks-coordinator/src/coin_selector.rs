@@ -0,0 +1,281 @@
+use crate::bitcoin_node::UTXO;
+
+/// Virtual size of a taproot key path input, rounded up
+pub const TAPROOT_INPUT_VBYTES: u64 = 58;
+
+/// The number of branches branch-and-bound explores before giving up on a changeless solution
+const BRANCH_AND_BOUND_MAX_TRIES: usize = 100_000;
+
+/// What the funding utxos selected for a transaction must cover
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectionTarget {
+    /// Amount the effective values of the selected utxos must add up to.
+    /// Includes the fee of the transaction without funding inputs and without change.
+    pub amount: u64,
+    /// Fee paid for spending each selected utxo
+    pub input_fee: u64,
+    /// Fee of a change output plus the smallest change worth creating
+    pub cost_of_change: u64,
+}
+
+impl SelectionTarget {
+    /// Value a utxo contributes once the fee for spending it is paid
+    fn effective_value(&self, utxo: &UTXO) -> u64 {
+        utxo.amount.saturating_sub(self.input_fee)
+    }
+}
+
+/// Chooses which utxos fund a transaction
+pub trait CoinSelector: Send {
+    /// Selects utxos whose effective values cover the target, or None if the utxos are insufficient
+    fn select(&self, utxos: Vec<UTXO>, target: &SelectionTarget) -> Option<Vec<UTXO>>;
+}
+
+/// The coin selection strategies available to the peg wallet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoinSelection {
+    BranchAndBound,
+    LargestFirst,
+    OldestFirst,
+    Consolidate { max_inputs: usize },
+}
+
+impl CoinSelection {
+    pub fn selector(&self) -> Box<dyn CoinSelector> {
+        match self {
+            Self::BranchAndBound => Box::new(BranchAndBound),
+            Self::LargestFirst => Box::new(LargestFirst),
+            Self::OldestFirst => Box::new(OldestFirst),
+            Self::Consolidate { max_inputs } => Box::new(Consolidate {
+                max_inputs: *max_inputs,
+            }),
+        }
+    }
+}
+
+/// Searches for a set of utxos matching the target closely enough to not need change,
+/// falling back to largest-first when there is none
+pub struct BranchAndBound;
+
+impl CoinSelector for BranchAndBound {
+    fn select(&self, utxos: Vec<UTXO>, target: &SelectionTarget) -> Option<Vec<UTXO>> {
+        let mut pool: Vec<(u64, UTXO)> = utxos
+            .into_iter()
+            .map(|utxo| (target.effective_value(&utxo), utxo))
+            .filter(|(value, _)| *value > 0)
+            .collect();
+        // Exploring the largest values first reaches the target, and prunes, sooner
+        pool.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+        let upper_bound = target.amount + target.cost_of_change;
+        let mut remaining: u64 = pool.iter().map(|(value, _)| value).sum();
+        if remaining < target.amount {
+            return None;
+        }
+
+        // Depth first search where each utxo is either included or, after backtracking, excluded
+        let mut current_value = 0;
+        let mut selection: Vec<usize> = vec![];
+        let mut best: Option<(u64, Vec<usize>)> = None;
+        let mut index = 0;
+        for _ in 0..BRANCH_AND_BOUND_MAX_TRIES {
+            let backtrack =
+                if current_value + remaining < target.amount || current_value > upper_bound {
+                    true
+                } else if current_value >= target.amount {
+                    // Including more utxos only adds to the excess, so record the solution and move on
+                    let excess = current_value - target.amount;
+                    if best
+                        .as_ref()
+                        .map_or(true, |(best_excess, _)| excess < *best_excess)
+                    {
+                        best = Some((excess, selection.clone()));
+                    }
+                    if excess == 0 {
+                        break;
+                    }
+                    true
+                } else {
+                    index == pool.len()
+                };
+
+            if backtrack {
+                // Exclude the most recently included utxo; everything after it is undecided again
+                let Some(last) = selection.pop() else {
+                    break;
+                };
+                remaining += pool[last + 1..index]
+                    .iter()
+                    .map(|(value, _)| value)
+                    .sum::<u64>();
+                current_value -= pool[last].0;
+                index = last + 1;
+            } else {
+                current_value += pool[index].0;
+                remaining -= pool[index].0;
+                selection.push(index);
+                index += 1;
+            }
+        }
+
+        match best {
+            Some((_, selection)) => {
+                let mut pool: Vec<Option<UTXO>> =
+                    pool.into_iter().map(|(_, utxo)| Some(utxo)).collect();
+                Some(
+                    selection
+                        .into_iter()
+                        .filter_map(|index| pool[index].take())
+                        .collect(),
+                )
+            }
+            None => LargestFirst.select(pool.into_iter().map(|(_, utxo)| utxo).collect(), target),
+        }
+    }
+}
+
+/// Spends the largest utxos first, minimizing the number of inputs
+pub struct LargestFirst;
+
+impl CoinSelector for LargestFirst {
+    fn select(&self, mut utxos: Vec<UTXO>, target: &SelectionTarget) -> Option<Vec<UTXO>> {
+        utxos.sort_by(|a, b| b.amount.cmp(&a.amount));
+        accumulate(utxos, target)
+    }
+}
+
+/// Spends the utxos with the most confirmations first
+pub struct OldestFirst;
+
+impl CoinSelector for OldestFirst {
+    fn select(&self, mut utxos: Vec<UTXO>, target: &SelectionTarget) -> Option<Vec<UTXO>> {
+        utxos.sort_by(|a, b| b.confirmations.cmp(&a.confirmations));
+        accumulate(utxos, target)
+    }
+}
+
+/// Covers the target with the largest utxos, then sweeps in the smallest utxos
+/// whose value exceeds the fee of spending them, up to `max_inputs` in total
+pub struct Consolidate {
+    pub max_inputs: usize,
+}
+
+impl CoinSelector for Consolidate {
+    fn select(&self, mut utxos: Vec<UTXO>, target: &SelectionTarget) -> Option<Vec<UTXO>> {
+        utxos.sort_by(|a, b| b.amount.cmp(&a.amount));
+        let mut selected = accumulate(utxos.iter().cloned(), target)?;
+        let sweep = utxos
+            .into_iter()
+            .skip(selected.len())
+            .rev()
+            .filter(|utxo| target.effective_value(utxo) > 0)
+            .take(self.max_inputs.saturating_sub(selected.len()));
+        selected.extend(sweep);
+        Some(selected)
+    }
+}
+
+/// Takes utxos in order until their effective values cover the target
+fn accumulate(
+    utxos: impl IntoIterator<Item = UTXO>,
+    target: &SelectionTarget,
+) -> Option<Vec<UTXO>> {
+    let mut total = 0;
+    let mut selected = vec![];
+    for utxo in utxos {
+        if total >= target.amount {
+            break;
+        }
+        let value = target.effective_value(&utxo);
+        if value == 0 {
+            continue;
+        }
+        total += value;
+        selected.push(utxo);
+    }
+    (total >= target.amount).then_some(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utxo(vout: u32, amount: u64, confirmations: u64) -> UTXO {
+        UTXO {
+            txid: format!("{:064x}", vout),
+            vout,
+            amount,
+            confirmations,
+            ..Default::default()
+        }
+    }
+
+    fn amounts(utxos: &[UTXO]) -> Vec<u64> {
+        utxos.iter().map(|utxo| utxo.amount).collect()
+    }
+
+    fn target(amount: u64) -> SelectionTarget {
+        SelectionTarget {
+            amount,
+            input_fee: 100,
+            cost_of_change: 500,
+        }
+    }
+
+    fn pool() -> Vec<UTXO> {
+        vec![
+            utxo(0, 1_000, 10),
+            utxo(1, 5_100, 1),
+            utxo(2, 20_100, 30),
+            utxo(3, 3_100, 5),
+            utxo(4, 50, 100),
+        ]
+    }
+
+    #[test]
+    fn branch_and_bound_should_find_changeless_selection() {
+        // 5000 + 3000 matches exactly, no change needed
+        let selected = BranchAndBound.select(pool(), &target(8_000)).unwrap();
+        assert_eq!(amounts(&selected), vec![5_100, 3_100]);
+    }
+
+    #[test]
+    fn branch_and_bound_should_fall_back_to_largest_first() {
+        // Nothing lands within 500 of 19_000 without the 20_000 utxo overshooting
+        let selected = BranchAndBound.select(pool(), &target(19_000)).unwrap();
+        assert_eq!(amounts(&selected), vec![20_100]);
+    }
+
+    #[test]
+    fn largest_first_should_minimize_inputs() {
+        let selected = LargestFirst.select(pool(), &target(21_000)).unwrap();
+        assert_eq!(amounts(&selected), vec![20_100, 5_100]);
+    }
+
+    #[test]
+    fn oldest_first_should_prefer_confirmations() {
+        let selected = OldestFirst.select(pool(), &target(20_500)).unwrap();
+        // The dust utxo is skipped as it costs more to spend than it is worth
+        assert_eq!(amounts(&selected), vec![20_100, 1_000]);
+    }
+
+    #[test]
+    fn consolidate_should_sweep_small_utxos() {
+        let selected = Consolidate { max_inputs: 3 }
+            .select(pool(), &target(1_000))
+            .unwrap();
+        assert_eq!(amounts(&selected), vec![20_100, 1_000, 3_100]);
+    }
+
+    #[test]
+    fn selection_should_fail_when_funds_are_insufficient() {
+        for selection in [
+            CoinSelection::BranchAndBound,
+            CoinSelection::LargestFirst,
+            CoinSelection::OldestFirst,
+            CoinSelection::Consolidate { max_inputs: 10 },
+        ] {
+            assert_eq!(selection.selector().select(pool(), &target(30_000)), None);
+        }
+    }
+}
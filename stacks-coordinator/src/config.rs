@@ -1,17 +1,103 @@
 use blockstack_lib::{
     address::AddressHashMode,
     burnchains::Address,
-    chainstate::stacks::TransactionVersion,
+    chainstate::stacks::{MultisigHashMode, TransactionVersion},
     types::chainstate::{StacksAddress, StacksPrivateKey, StacksPublicKey},
     vm::ContractName,
 };
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use url::Url;
 
-use crate::util::address_version;
+use keystore::KeyKind;
+
+use crate::bitcoin_wallet::BitcoinFeePolicy;
+use crate::coin_selector::CoinSelection;
+use crate::stacks_wallet::StacksFeePolicy;
+use crate::util::{address_version, multisig_address_version};
 
 /// Default polling interval in seconds
 const DEFAULT_POLLING_INTERVAL: u64 = 5;
 
+/// Default number of seconds a pending op may go without progress before it is requeued
+const DEFAULT_PENDING_LEASE_TIMEOUT: u64 = 600;
+
+/// Default number of burn block confirmations before an op is processed
+const DEFAULT_MIN_CONFIRMATIONS: u64 = 1;
+
+/// Default lowest fee rate in sat/vB paid by peg-out fulfillments
+const DEFAULT_BITCOIN_FEE_RATE_MIN: u64 = 1;
+
+/// Default highest fee rate in sat/vB paid by peg-out fulfillments
+const DEFAULT_BITCOIN_FEE_RATE_MAX: u64 = 500;
+
+/// Default number of blocks targeted by the fee estimate
+const DEFAULT_BITCOIN_FEE_CONF_TARGET: u16 = 6;
+
+/// Default number of blocks a fulfillment may remain unconfirmed before its fee is bumped
+const DEFAULT_BITCOIN_FEE_BUMP_BLOCKS: u64 = 6;
+
+/// Default lowest fee in microSTX paid by Stacks transactions when estimating fees
+const DEFAULT_STACKS_FEE_MIN: u64 = 1;
+
+/// Default highest fee in microSTX paid by Stacks transactions
+const DEFAULT_STACKS_FEE_MAX: u64 = 1_000_000;
+
+/// Default number of seconds a Stacks transaction may remain in the mempool before its fee is bumped
+const DEFAULT_STACKS_FEE_BUMP_TIMEOUT: u64 = 1800;
+
+/// Default maximum number of inputs spent by a fulfillment when consolidating utxos
+const DEFAULT_CONSOLIDATION_MAX_INPUTS: usize = 50;
+
+/// Default number of Bitcoin confirmations before a peg-out fulfillment is considered final
+const DEFAULT_FULFILLMENT_CONFIRMATIONS: u64 = 6;
+
+/// Default number of seconds between solvency reconciliations
+const DEFAULT_RECONCILIATION_INTERVAL: u64 = 3600;
+
+/// Default discrepancy in sats tolerated by the solvency reconciliation, covering fulfillment fees
+const DEFAULT_SOLVENCY_TOLERANCE: u64 = 10_000;
+
+/// Default number of Bitcoin blocks deposits to the previous peg wallet are accepted for after a handoff
+const DEFAULT_HANDOFF_GRACE_PERIOD: u64 = 144;
+
+/// Prefix of the environment variables overriding config fields, e.g. STACKS_COORDINATOR_POLLING_INTERVAL
+pub const ENV_PREFIX: &str = "STACKS_COORDINATOR_";
+
+/// Integer and boolean fields, whose `STACKS_COORDINATOR_*` variables are parsed as such. The
+/// variables of every other field are taken as plain strings.
+const ENV_TYPED_FIELDS: [&str; 22] = [
+    "frost_dkg_round_id",
+    "start_block_height",
+    "transaction_fee",
+    "stacks_signatures_required",
+    "stacks_fee_estimation",
+    "stacks_fee_min",
+    "stacks_fee_max",
+    "stacks_fee_bump_timeout",
+    "polling_interval",
+    "pending_lease_timeout",
+    "min_confirmations",
+    "batch_peg_outs",
+    "batch_peg_ins",
+    "bitcoin_fee_rate_min",
+    "bitcoin_fee_rate_max",
+    "bitcoin_fee_conf_target",
+    "bitcoin_fee_bump_blocks",
+    "consolidation_max_inputs",
+    "fulfillment_confirmations",
+    "reconciliation_interval",
+    "solvency_tolerance",
+    "handoff_grace_period",
+];
+
+/// Fields holding secrets, which may instead be read from the file named by `<field>_file`, or
+/// unlocked from the keystore named by `<field>_keystore`
+const SECRET_FIELDS: [(&str, KeyKind); 2] = [
+    ("stacks_private_key", KeyKind::Stacks),
+    ("network_private_key", KeyKind::Network),
+];
+
 /// Errors associated with reading the Config file
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -25,6 +111,24 @@ pub enum Error {
     InvalidContract(String),
     #[error("Failed to parse stacks_private_key: {0}")]
     InvalidPrivateKey(String),
+    #[error("{} config errors: {}", .0.len(), .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    InvalidFields(Vec<Error>),
+}
+
+impl Error {
+    /// Report every error found while validating a config at once
+    fn aggregate(mut errors: Vec<Error>) -> Self {
+        if errors.len() == 1 {
+            errors.remove(0)
+        } else {
+            Error::InvalidFields(errors)
+        }
+    }
+}
+
+/// Keep the value of a validated field, collecting its error otherwise
+fn check<T>(errors: &mut Vec<Error>, result: Result<T, Error>) -> Option<T> {
+    result.map_err(|e| errors.push(e)).ok()
 }
 
 #[derive(serde::Deserialize)]
@@ -34,26 +138,167 @@ pub enum Network {
     Testnet,
 }
 
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StacksMultisigHashMode {
+    P2sh,
+    P2wsh,
+}
+
+impl StacksMultisigHashMode {
+    pub fn multisig_hash_mode(&self) -> MultisigHashMode {
+        match self {
+            StacksMultisigHashMode::P2sh => MultisigHashMode::P2SH,
+            StacksMultisigHashMode::P2wsh => MultisigHashMode::P2WSH,
+        }
+    }
+
+    pub fn address_hash_mode(&self) -> AddressHashMode {
+        match self {
+            StacksMultisigHashMode::P2sh => AddressHashMode::SerializeP2SH,
+            StacksMultisigHashMode::P2wsh => AddressHashMode::SerializeP2WSH,
+        }
+    }
+}
+
+/// A signer of a multisig Stacks fee wallet, holding one of private_key, private_key_keystore or
+/// signer_url
+#[derive(serde::Deserialize, Default)]
+pub struct RawStacksSigner {
+    pub private_key: Option<String>,
+    /// Keystore of the private key, unlocked with the password in KEYSTORE_PASSWORD_FILE or KEYSTORE_PASSWORD
+    pub private_key_keystore: Option<String>,
+    /// Url of an external signer holding the private key
+    pub signer_url: Option<String>,
+    /// Public key of the signer. Must be specified if private_key_keystore or signer_url is used
+    pub public_key: Option<String>,
+}
+
+impl RawStacksSigner {
+    fn parse(&self) -> Result<StacksSignerConfig, String> {
+        let public_key = self
+            .public_key
+            .as_deref()
+            .map(StacksPublicKey::from_hex)
+            .transpose()
+            .map_err(|e| format!("invalid public_key: {}", e))?;
+        match (
+            &self.private_key,
+            &self.private_key_keystore,
+            &self.signer_url,
+            public_key,
+        ) {
+            (Some(private_key), None, None, _) => StacksPrivateKey::from_hex(private_key)
+                .map(StacksSignerConfig::PrivateKey)
+                .map_err(|e| format!("invalid private_key: {}", e)),
+            (None, Some(path), None, Some(public_key)) => Ok(StacksSignerConfig::Keystore {
+                path: PathBuf::from(path),
+                public_key,
+            }),
+            (None, None, Some(url), Some(public_key)) => Url::parse(url)
+                .map(|url| StacksSignerConfig::Remote { url, public_key })
+                .map_err(|e| format!("invalid signer_url: {}", e)),
+            (None, Some(_), None, None) | (None, None, Some(_), None) => {
+                Err("must specify public_key with private_key_keystore or signer_url".to_string())
+            }
+            _ => Err(
+                "must specify one of private_key, private_key_keystore and signer_url".to_string(),
+            ),
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CoinSelectionStrategy {
+    BranchAndBound,
+    LargestFirst,
+    OldestFirst,
+    Consolidate,
+}
+
 #[derive(serde::Deserialize, Default)]
 pub struct RawConfig {
     pub sbtc_contract: String,
+    /// Private key of the Stacks fee wallet. Must be specified if stacks_signer_url is not used
+    #[serde(default)]
     pub stacks_private_key: String,
+    /// Url of an external signer holding the private key of the Stacks fee wallet
+    pub stacks_signer_url: Option<String>,
+    /// Public key of the Stacks fee wallet. Must be specified if stacks_signer_url is used
+    pub stacks_public_key: Option<String>,
+    /// Signers of a multisig Stacks fee wallet, in the order of their public keys in its address.
+    /// Replaces stacks_private_key and stacks_signer_url
+    pub stacks_signers: Option<Vec<RawStacksSigner>>,
+    /// Number of stacks_signers whose signatures authorize a Stacks transaction. Must be specified if stacks_signers is used
+    pub stacks_signatures_required: Option<u16>,
+    /// Hash mode of the multisig Stacks fee wallet's address ('p2sh' or 'p2wsh'). Default: 'p2sh'
+    pub stacks_multisig_hash_mode: Option<StacksMultisigHashMode>,
     pub stacks_node_rpc_url: String,
+    /// Url of the Stacks API indexing the node's transactions, queried for their status. Default: stacks_node_rpc_url
+    pub stacks_api_url: Option<String>,
     pub bitcoin_node_rpc_url: String,
     pub frost_dkg_round_id: u64,
     pub signer_config_path: Option<String>,
+    /// Burn block height the peg queue starts polling at. Ignored once the queue processed a block
     pub start_block_height: Option<u64>,
     pub data_directory: Option<String>,
     /// The network version we are using ('mainnet' or 'testnet'). Default: 'mainnet'
     pub network: Option<Network>,
-    /// The transaction fee in Satoshis used to broadcast transactions to the stacks node
+    /// The transaction fee in Satoshis used to broadcast transactions to the stacks node.
+    /// When fees are estimated, it is paid if estimation fails and is the least paid otherwise.
     pub transaction_fee: u64,
+    /// Estimate the fee of each Stacks transaction with the stacks node. Default: true
+    pub stacks_fee_estimation: Option<bool>,
+    /// Lowest fee in microSTX paid by Stacks transactions when estimating fees. Default: 1
+    pub stacks_fee_min: Option<u64>,
+    /// Highest fee in microSTX paid by Stacks transactions. Default: 1000000
+    pub stacks_fee_max: Option<u64>,
+    /// Seconds a Stacks transaction may remain in the mempool before it is replaced with a higher fee. Default: 1800
+    pub stacks_fee_bump_timeout: Option<u64>,
     /// Frost specific config options. Must be specified if signer_config_path is not used
     pub http_relay_url: Option<String>,
     pub frost_state_file: Option<String>,
     pub network_private_key: Option<String>,
     /// Controls how many seconds to wait between polls
     pub polling_interval: Option<u64>,
+    /// Seconds a pending op may go without progress before it is requeued on startup
+    pub pending_lease_timeout: Option<u64>,
+    /// Number of burn block confirmations, including the op's own block, before an op is processed. Default: 1
+    pub min_confirmations: Option<u64>,
+    /// Fulfill all peg out requests processed in a polling cycle with a single Bitcoin transaction. Default: false
+    pub batch_peg_outs: Option<bool>,
+    /// Mint the peg ins of a burn block with consecutive nonces, broadcasting them concurrently. Default: false
+    pub batch_peg_ins: Option<bool>,
+    /// Lowest fee rate in sat/vB paid by peg-out fulfillments. Default: 1
+    pub bitcoin_fee_rate_min: Option<u64>,
+    /// Highest fee rate in sat/vB paid by peg-out fulfillments. Default: 500
+    pub bitcoin_fee_rate_max: Option<u64>,
+    /// Number of blocks the estimated fee rate should confirm within. Default: 6
+    pub bitcoin_fee_conf_target: Option<u16>,
+    /// Number of blocks a fulfillment may remain unconfirmed before it is replaced with a higher fee. Default: 6
+    pub bitcoin_fee_bump_blocks: Option<u64>,
+    /// How utxos funding peg-out fulfillments are chosen ('branch-and-bound', 'largest-first', 'oldest-first' or 'consolidate'). Default: 'largest-first'
+    pub coin_selection: Option<CoinSelectionStrategy>,
+    /// Maximum number of inputs spent by a fulfillment when coin_selection is 'consolidate'. Default: 50
+    pub consolidation_max_inputs: Option<usize>,
+    /// Number of Bitcoin confirmations before a peg-out fulfillment is acknowledged. Default: 6
+    pub fulfillment_confirmations: Option<u64>,
+    /// Address the admin API listens on, e.g. '127.0.0.1:9000'. The API is disabled if unset
+    pub admin_api_address: Option<String>,
+    /// Bearer token the admin API requires of its clients. Must be specified if admin_api_address is not a loopback address
+    pub admin_api_token: Option<String>,
+    /// Address the Prometheus metrics are served on, e.g. '127.0.0.1:9100'. Disabled if unset
+    pub metrics_address: Option<String>,
+    /// Seconds between reconciliations of the peg wallet balance with the sBTC supply. 0 disables them. Default: 3600
+    pub reconciliation_interval: Option<u64>,
+    /// Discrepancy in sats between the peg wallet balance and the sBTC supply tolerated by reconciliations. Default: 10000
+    pub solvency_tolerance: Option<u64>,
+    /// Bitcoin blocks during which the previous peg wallet is still honoured after a wallet handoff. Default: 144
+    pub handoff_grace_period: Option<u64>,
+    /// Name of the stacking pool contract, deployed alongside the sBTC contract. If set, the peg wallet
+    /// is handed off to the pool's signers at every PoX reward cycle. Requires http_relay_url and network_private_key
+    pub stacking_pool_contract: Option<String>,
 }
 
 impl RawConfig {
@@ -62,6 +307,37 @@ impl RawConfig {
         Ok(config)
     }
 
+    /// Read the config file at `path`, layering the override file of `environment`, if any, and
+    /// the `STACKS_COORDINATOR_*` environment variables on top. Keystores are unlocked with the
+    /// password in `KEYSTORE_PASSWORD_FILE` or `KEYSTORE_PASSWORD`.
+    pub fn load(path: impl AsRef<Path>, environment: Option<&str>) -> Result<Self, Error> {
+        Self::from_sources(path.as_ref(), environment, std::env::vars())
+    }
+
+    fn from_sources(
+        path: &Path,
+        environment: Option<&str>,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, Error> {
+        let vars: Vec<_> = vars.into_iter().collect();
+        let mut errors = Vec::new();
+        let mut table = read_table(path)?;
+        if let Some(environment) = environment {
+            let layer = read_table(&override_path(path, environment))?;
+            merge(&mut table, layer, &mut errors);
+        }
+        merge(&mut table, env_table(vars.iter().cloned()), &mut errors);
+        read_secret_files(&mut table, &mut errors, |name| {
+            vars.iter()
+                .find(|(var, _)| var == name)
+                .map(|(_, value)| value.clone())
+        });
+        if !errors.is_empty() {
+            return Err(Error::aggregate(errors));
+        }
+        Ok(toml::Value::Table(table).try_into()?)
+    }
+
     pub fn parse_contract(&self) -> Result<(ContractName, StacksAddress), Error> {
         let mut split = self.sbtc_contract.split('.');
         let contract_address = split
@@ -79,23 +355,171 @@ impl RawConfig {
         Ok((contract_name, contract_address))
     }
 
-    pub fn parse_stacks_private_key(&self) -> Result<(StacksPrivateKey, StacksAddress), Error> {
-        let sender_key = StacksPrivateKey::from_hex(&self.stacks_private_key)
-            .map_err(|e| Error::InvalidPrivateKey(e.to_string()))?;
-
-        let pk = StacksPublicKey::from_private(&sender_key);
+    pub fn parse_stacks_signer(&self) -> Result<(StacksSignerConfig, StacksAddress), Error> {
+        let signer = match (
+            self.stacks_private_key.is_empty(),
+            &self.stacks_signer_url,
+            &self.stacks_signers,
+        ) {
+            (_, None, None) => StacksSignerConfig::PrivateKey(
+                StacksPrivateKey::from_hex(&self.stacks_private_key)
+                    .map_err(|e| Error::InvalidPrivateKey(e.to_string()))?,
+            ),
+            (true, Some(url), None) => {
+                let url = Url::parse(url).map_err(|e| {
+                    Error::InvalidConfig(format!("Invalid stacks_signer_url: {}", e))
+                })?;
+                let public_key = self.stacks_public_key.as_deref().ok_or_else(|| {
+                    Error::InvalidConfig(
+                        "Must specify stacks_public_key when stacks_signer_url is specified."
+                            .to_string(),
+                    )
+                })?;
+                let public_key = StacksPublicKey::from_hex(public_key).map_err(|e| {
+                    Error::InvalidConfig(format!("Invalid stacks_public_key: {}", e))
+                })?;
+                StacksSignerConfig::Remote { url, public_key }
+            }
+            (true, None, Some(signers)) => self.parse_stacks_multisig(signers)?,
+            _ => {
+                return Err(Error::InvalidConfig(
+                    "Must specify only one of stacks_private_key, stacks_signer_url and stacks_signers."
+                        .to_string(),
+                ))
+            }
+        };
 
+        let version = self.parse_version().0;
+        let (version, hash_mode, signatures_required) = match &signer {
+            StacksSignerConfig::Multisig {
+                hash_mode,
+                signatures_required,
+                ..
+            } => (
+                multisig_address_version(&version),
+                hash_mode.address_hash_mode(),
+                usize::from(*signatures_required),
+            ),
+            _ => (
+                address_version(&version),
+                AddressHashMode::SerializeP2PKH,
+                1,
+            ),
+        };
         let address = StacksAddress::from_public_keys(
-            address_version(&self.parse_version().0),
-            &AddressHashMode::SerializeP2PKH,
-            1,
-            &vec![pk],
+            version,
+            &hash_mode,
+            signatures_required,
+            &signer.public_keys(),
         )
         .ok_or(Error::InvalidPrivateKey(
-            "Failed to generate stacks address from private key.".to_string(),
+            "Failed to generate stacks address from public key.".to_string(),
         ))?;
 
-        Ok((sender_key, address))
+        Ok((signer, address))
+    }
+
+    fn parse_stacks_multisig(
+        &self,
+        signers: &[RawStacksSigner],
+    ) -> Result<StacksSignerConfig, Error> {
+        let signatures_required = self.stacks_signatures_required.ok_or_else(|| {
+            Error::InvalidConfig(
+                "Must specify stacks_signatures_required when stacks_signers is specified."
+                    .to_string(),
+            )
+        })?;
+        if signatures_required == 0 || usize::from(signatures_required) > signers.len() {
+            return Err(Error::InvalidConfig(format!(
+                "stacks_signatures_required must be between 1 and the number of stacks_signers ({}).",
+                signers.len()
+            )));
+        }
+        let signers = signers
+            .iter()
+            .enumerate()
+            .map(|(i, signer)| {
+                signer.parse().map_err(|e| {
+                    Error::InvalidConfig(format!("Invalid stacks_signers[{}]: {}", i, e))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(StacksSignerConfig::Multisig {
+            hash_mode: self
+                .stacks_multisig_hash_mode
+                .unwrap_or(StacksMultisigHashMode::P2sh),
+            signatures_required,
+            signers,
+        })
+    }
+
+    pub fn parse_bitcoin_fee_policy(&self) -> Result<BitcoinFeePolicy, Error> {
+        let policy = BitcoinFeePolicy {
+            conf_target: self
+                .bitcoin_fee_conf_target
+                .unwrap_or(DEFAULT_BITCOIN_FEE_CONF_TARGET),
+            min_fee_rate: self
+                .bitcoin_fee_rate_min
+                .unwrap_or(DEFAULT_BITCOIN_FEE_RATE_MIN),
+            max_fee_rate: self
+                .bitcoin_fee_rate_max
+                .unwrap_or(DEFAULT_BITCOIN_FEE_RATE_MAX),
+            bump_after_blocks: self
+                .bitcoin_fee_bump_blocks
+                .unwrap_or(DEFAULT_BITCOIN_FEE_BUMP_BLOCKS),
+        };
+        if policy.conf_target == 0 {
+            return Err(Error::InvalidConfig(
+                "bitcoin_fee_conf_target must be greater than 0.".to_string(),
+            ));
+        }
+        if policy.min_fee_rate > policy.max_fee_rate {
+            return Err(Error::InvalidConfig(
+                "bitcoin_fee_rate_min must not exceed bitcoin_fee_rate_max.".to_string(),
+            ));
+        }
+        Ok(policy)
+    }
+
+    pub fn parse_stacks_fee_policy(&self) -> Result<StacksFeePolicy, Error> {
+        let policy = StacksFeePolicy {
+            min_fee: self.stacks_fee_min.unwrap_or(DEFAULT_STACKS_FEE_MIN),
+            max_fee: self.stacks_fee_max.unwrap_or(DEFAULT_STACKS_FEE_MAX),
+            bump_timeout: self
+                .stacks_fee_bump_timeout
+                .unwrap_or(DEFAULT_STACKS_FEE_BUMP_TIMEOUT),
+        };
+        if policy.min_fee > policy.max_fee {
+            return Err(Error::InvalidConfig(
+                "stacks_fee_min must not exceed stacks_fee_max.".to_string(),
+            ));
+        }
+        Ok(policy)
+    }
+
+    pub fn parse_coin_selection(&self) -> Result<CoinSelection, Error> {
+        Ok(
+            match self
+                .coin_selection
+                .as_ref()
+                .unwrap_or(&CoinSelectionStrategy::LargestFirst)
+            {
+                CoinSelectionStrategy::BranchAndBound => CoinSelection::BranchAndBound,
+                CoinSelectionStrategy::LargestFirst => CoinSelection::LargestFirst,
+                CoinSelectionStrategy::OldestFirst => CoinSelection::OldestFirst,
+                CoinSelectionStrategy::Consolidate => {
+                    let max_inputs = self
+                        .consolidation_max_inputs
+                        .unwrap_or(DEFAULT_CONSOLIDATION_MAX_INPUTS);
+                    if max_inputs == 0 {
+                        return Err(Error::InvalidConfig(
+                            "consolidation_max_inputs must be greater than 0.".to_string(),
+                        ));
+                    }
+                    CoinSelection::Consolidate { max_inputs }
+                }
+            },
+        )
     }
 
     pub fn parse_version(&self) -> (TransactionVersion, bitcoin::Network) {
@@ -107,12 +531,202 @@ impl RawConfig {
     }
 }
 
+/// The override file of an environment, e.g. `coordinator.testnet.toml` for `coordinator.toml`
+pub fn override_path(path: &Path, environment: &str) -> PathBuf {
+    path.with_extension(format!("{}.toml", environment))
+}
+
+fn read_table(path: &Path) -> Result<toml::Table, Error> {
+    toml::from_str(&std::fs::read_to_string(path)?)
+        .map_err(|e| Error::InvalidConfig(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+/// The fields set by `STACKS_COORDINATOR_*` variables. Values of integer and boolean fields are
+/// parsed as such, every other value is taken as a plain string.
+fn env_table(vars: impl IntoIterator<Item = (String, String)>) -> toml::Table {
+    vars.into_iter()
+        .filter_map(|(name, value)| {
+            let field = name.strip_prefix(ENV_PREFIX)?.to_lowercase();
+            let value = if ENV_TYPED_FIELDS.contains(&field.as_str()) {
+                value
+                    .parse()
+                    .map(toml::Value::Integer)
+                    .or_else(|_| value.parse().map(toml::Value::Boolean))
+                    // Left as a string, so the field's type error is reported
+                    .unwrap_or(toml::Value::String(value))
+            } else {
+                toml::Value::String(value)
+            };
+            Some((field, value))
+        })
+        .collect()
+}
+
+/// Layer the fields of `layer` over `table`. A secret set in `layer`, directly or through its
+/// `_file` or `_keystore`, replaces all its forms in the lower layers.
+fn merge(table: &mut toml::Table, layer: toml::Table, errors: &mut Vec<Error>) {
+    for (secret, _) in SECRET_FIELDS {
+        let forms = [
+            secret.to_string(),
+            format!("{}_file", secret),
+            format!("{}_keystore", secret),
+        ];
+        match forms
+            .iter()
+            .filter(|form| layer.contains_key(*form))
+            .count()
+        {
+            0 => continue,
+            1 => {}
+            _ => errors.push(Error::InvalidConfig(format!(
+                "Only one of {} may be specified.",
+                forms.join(", ")
+            ))),
+        }
+        for form in &forms {
+            table.remove(form);
+        }
+    }
+    table.extend(layer);
+}
+
+/// Replace each `<secret>_file` with the contents of the file it names, and each
+/// `<secret>_keystore` with the key unlocked from the keystore it names, with the password in
+/// the variables looked up with `lookup`
+fn read_secret_files(
+    table: &mut toml::Table,
+    errors: &mut Vec<Error>,
+    lookup: impl Fn(&str) -> Option<String>,
+) {
+    for (secret, kind) in SECRET_FIELDS {
+        for (form, is_keystore) in [
+            (format!("{}_file", secret), false),
+            (format!("{}_keystore", secret), true),
+        ] {
+            let Some(path) = table.remove(&form) else {
+                continue;
+            };
+            let Some(path) = path.as_str() else {
+                errors.push(Error::InvalidConfig(format!("{} must be a path.", form)));
+                continue;
+            };
+            let value = if is_keystore {
+                keystore::password_from_vars(keystore::PASSWORD_ENV, &lookup)
+                    .and_then(|password| {
+                        password.ok_or_else(|| keystore::Error::MissingPassword(path.to_string()))
+                    })
+                    .and_then(|password| keystore::unlock_with(path, kind, &password))
+                    .map(|key| key.to_string())
+                    .map_err(|e| e.to_string())
+            } else {
+                std::fs::read_to_string(path)
+                    .map(|value| value.trim_end().to_string())
+                    .map_err(|e| e.to_string())
+            };
+            match value {
+                Ok(value) => {
+                    table.insert(secret.to_string(), toml::Value::String(value));
+                }
+                Err(e) => errors.push(Error::InvalidConfig(format!(
+                    "Failed to read {} {:?}: {}",
+                    form, path, e
+                ))),
+            }
+        }
+    }
+}
+
+/// How the transactions of the Stacks fee wallet are signed
+#[derive(Clone)]
+pub enum StacksSignerConfig {
+    /// Sign with a private key held by the coordinator
+    PrivateKey(StacksPrivateKey),
+    /// Sign with a private key kept encrypted in a keystore
+    Keystore {
+        path: PathBuf,
+        public_key: StacksPublicKey,
+    },
+    /// Sign with an external signer holding the private key of the public key
+    Remote {
+        url: Url,
+        public_key: StacksPublicKey,
+    },
+    /// Collect the signatures of several signers, in order
+    Multisig {
+        hash_mode: StacksMultisigHashMode,
+        signatures_required: u16,
+        signers: Vec<StacksSignerConfig>,
+    },
+}
+
+impl StacksSignerConfig {
+    /// The public keys authorizing the transactions of the Stacks fee wallet
+    pub fn public_keys(&self) -> Vec<StacksPublicKey> {
+        match self {
+            StacksSignerConfig::PrivateKey(private_key) => {
+                vec![StacksPublicKey::from_private(private_key)]
+            }
+            StacksSignerConfig::Keystore { public_key, .. }
+            | StacksSignerConfig::Remote { public_key, .. } => vec![*public_key],
+            StacksSignerConfig::Multisig { signers, .. } => signers
+                .iter()
+                .flat_map(StacksSignerConfig::public_keys)
+                .collect(),
+        }
+    }
+}
+
+impl PartialEq for StacksSignerConfig {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (StacksSignerConfig::PrivateKey(a), StacksSignerConfig::PrivateKey(b)) => {
+                a.to_hex() == b.to_hex()
+            }
+            (
+                StacksSignerConfig::Remote { url, public_key },
+                StacksSignerConfig::Remote {
+                    url: other_url,
+                    public_key: other_public_key,
+                },
+            ) => url == other_url && public_key == other_public_key,
+            (
+                StacksSignerConfig::Keystore { path, public_key },
+                StacksSignerConfig::Keystore {
+                    path: other_path,
+                    public_key: other_public_key,
+                },
+            ) => path == other_path && public_key == other_public_key,
+            (
+                StacksSignerConfig::Multisig {
+                    hash_mode,
+                    signatures_required,
+                    signers,
+                },
+                StacksSignerConfig::Multisig {
+                    hash_mode: other_hash_mode,
+                    signatures_required: other_signatures_required,
+                    signers: other_signers,
+                },
+            ) => {
+                hash_mode == other_hash_mode
+                    && signatures_required == other_signatures_required
+                    && signers == other_signers
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Config {
     pub contract_name: ContractName,
     pub contract_address: StacksAddress,
-    pub stacks_private_key: StacksPrivateKey,
+    /// How the transactions of the Stacks fee wallet are signed
+    pub stacks_signer: StacksSignerConfig,
     pub stacks_address: StacksAddress,
     pub stacks_node_rpc_url: Url,
+    /// Url of the Stacks API queried for the status of transactions
+    pub stacks_api_url: Option<Url>,
     pub bitcoin_node_rpc_url: Url,
     pub frost_dkg_round_id: u64,
     pub signer_config_path: Option<String>,
@@ -122,49 +736,193 @@ pub struct Config {
     pub stacks_version: TransactionVersion,
     /// The transaction fee in Satoshis used to broadcast transactions to the stacks node
     pub transaction_fee: u64,
+    /// Estimate the fee of each Stacks transaction with the stacks node
+    pub stacks_fee_estimation: bool,
+    /// Fee bounds and bump timing for Stacks transactions
+    pub stacks_fee_policy: StacksFeePolicy,
     /// Frost specific config options. Must be specified if signer_config_path is not used
     pub http_relay_url: Option<String>,
     pub network_private_key: Option<String>,
     /// Controls how many seconds to wait between polls
     pub polling_interval: u64,
+    /// Seconds a pending op may go without progress before it is requeued on startup
+    pub pending_lease_timeout: u64,
+    /// Number of burn block confirmations, including the op's own block, before an op is processed
+    pub min_confirmations: u64,
+    /// Fulfill all peg out requests processed in a polling cycle with a single Bitcoin transaction
+    pub batch_peg_outs: bool,
+    /// Mint the peg ins of a burn block with consecutive nonces, broadcasting them concurrently
+    pub batch_peg_ins: bool,
+    /// Fee rate bounds and bump timing for peg-out fulfillments
+    pub bitcoin_fee_policy: BitcoinFeePolicy,
+    /// How utxos funding peg-out fulfillments are chosen
+    pub coin_selection: CoinSelection,
+    /// Number of Bitcoin confirmations before a peg-out fulfillment is acknowledged
+    pub fulfillment_confirmations: u64,
+    /// Address the admin API listens on, if enabled
+    pub admin_api_address: Option<SocketAddr>,
+    /// Bearer token the admin API requires of its clients, if any
+    pub admin_api_token: Option<String>,
+    /// Address the Prometheus metrics are served on, if enabled
+    pub metrics_address: Option<SocketAddr>,
+    /// Seconds between reconciliations of the peg wallet balance with the sBTC supply, if enabled
+    pub reconciliation_interval: Option<u64>,
+    /// Discrepancy in sats between the peg wallet balance and the sBTC supply tolerated by reconciliations
+    pub solvency_tolerance: u64,
+    /// Bitcoin blocks during which the previous peg wallet is still honoured after a wallet handoff
+    pub handoff_grace_period: u64,
+    /// Stacking pool contract whose signers take over the peg wallet every reward cycle, if enabled
+    pub stacking_pool_contract: Option<ContractName>,
 }
 
 impl TryFrom<RawConfig> for Config {
     type Error = Error;
     fn try_from(config: RawConfig) -> Result<Self, Error> {
+        // Validate every field before failing, so all errors are reported at once
+        let mut errors = Vec::new();
         if config.signer_config_path.is_none() {
             if config.http_relay_url.is_none() {
-                return Err(Error::InvalidConfig(
+                errors.push(Error::InvalidConfig(
                     "Must specify http_relay_url when no signer_config_path specified.".to_string(),
                 ));
             }
             if config.frost_state_file.is_none() {
-                return Err(Error::InvalidConfig(
+                errors.push(Error::InvalidConfig(
                     "Must specify frost_state_file when no signer_config_path specified."
                         .to_string(),
                 ));
             }
             if config.network_private_key.is_none() {
-                return Err(Error::InvalidConfig(
+                errors.push(Error::InvalidConfig(
                     "Must specify network_private_key when no signer config_path specified."
                         .to_string(),
                 ));
             }
         }
-        let (contract_name, contract_address) = config.parse_contract()?;
+        if config.stacking_pool_contract.is_some()
+            && (config.http_relay_url.is_none() || config.network_private_key.is_none())
+        {
+            errors.push(Error::InvalidConfig(
+                "Must specify http_relay_url and network_private_key when stacking_pool_contract is specified."
+                    .to_string(),
+            ));
+        }
+        if config.fulfillment_confirmations == Some(0) {
+            errors.push(Error::InvalidConfig(
+                "fulfillment_confirmations must be greater than 0.".to_string(),
+            ));
+        }
+        if config.min_confirmations == Some(0) {
+            errors.push(Error::InvalidConfig(
+                "min_confirmations must be greater than 0.".to_string(),
+            ));
+        }
+        let contract = check(&mut errors, config.parse_contract());
         let (stacks_version, bitcoin_network) = config.parse_version();
-        let (stacks_private_key, stacks_address) = config.parse_stacks_private_key()?;
+        let stacks_signer = check(&mut errors, config.parse_stacks_signer());
+        let bitcoin_fee_policy = check(&mut errors, config.parse_bitcoin_fee_policy());
+        let coin_selection = check(&mut errors, config.parse_coin_selection());
+        let stacks_fee_policy = check(&mut errors, config.parse_stacks_fee_policy());
+        let stacks_node_rpc_url = check(
+            &mut errors,
+            Url::parse(&config.stacks_node_rpc_url)
+                .map_err(|e| Error::InvalidConfig(format!("Invalid stacks_node_rpc_url: {}", e))),
+        );
+        let stacks_api_url = check(
+            &mut errors,
+            config
+                .stacks_api_url
+                .as_deref()
+                .map(Url::parse)
+                .transpose()
+                .map_err(|e| Error::InvalidConfig(format!("Invalid stacks_api_url: {}", e))),
+        );
+        let bitcoin_node_rpc_url = check(
+            &mut errors,
+            Url::parse(&config.bitcoin_node_rpc_url)
+                .map_err(|e| Error::InvalidConfig(format!("Invalid bitcoin_node_rpc_url: {}", e))),
+        );
+        let admin_api_token = config
+            .admin_api_token
+            .clone()
+            .filter(|token| !token.is_empty());
+        let admin_api_address = check(
+            &mut errors,
+            config
+                .admin_api_address
+                .as_deref()
+                .map(str::parse::<SocketAddr>)
+                .transpose()
+                .map_err(|e| Error::InvalidConfig(format!("Invalid admin_api_address: {}", e)))
+                .and_then(|address| match address {
+                    // The admin API steers the peg wallet, so only local clients may use it unauthenticated
+                    Some(address) if !address.ip().is_loopback() && admin_api_token.is_none() => {
+                        Err(Error::InvalidConfig(
+                            "Must specify admin_api_token when admin_api_address is not a loopback address."
+                                .to_string(),
+                        ))
+                    }
+                    address => Ok(address),
+                }),
+        );
+        let metrics_address = check(
+            &mut errors,
+            config
+                .metrics_address
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .map_err(|e| Error::InvalidConfig(format!("Invalid metrics_address: {}", e))),
+        );
+        let stacking_pool_contract = check(
+            &mut errors,
+            config
+                .stacking_pool_contract
+                .map(ContractName::try_from)
+                .transpose()
+                .map_err(|e| {
+                    Error::InvalidContract(format!("Bad stacking pool contract name: {}.", e))
+                }),
+        );
+        let (
+            Some((contract_name, contract_address)),
+            Some((stacks_signer, stacks_address)),
+            Some(bitcoin_fee_policy),
+            Some(coin_selection),
+            Some(stacks_fee_policy),
+            Some(stacks_node_rpc_url),
+            Some(stacks_api_url),
+            Some(bitcoin_node_rpc_url),
+            Some(admin_api_address),
+            Some(metrics_address),
+            Some(stacking_pool_contract),
+            true,
+        ) = (
+            contract,
+            stacks_signer,
+            bitcoin_fee_policy,
+            coin_selection,
+            stacks_fee_policy,
+            stacks_node_rpc_url,
+            stacks_api_url,
+            bitcoin_node_rpc_url,
+            admin_api_address,
+            metrics_address,
+            stacking_pool_contract,
+            errors.is_empty(),
+        )
+        else {
+            return Err(Error::aggregate(errors));
+        };
 
         Ok(Self {
             contract_name,
             contract_address,
-            stacks_private_key,
+            stacks_signer,
             stacks_address,
-            stacks_node_rpc_url: Url::parse(&config.stacks_node_rpc_url)
-                .map_err(|e| Error::InvalidConfig(format!("Invalid stacks_node_rpc_url: {}", e)))?,
-            bitcoin_node_rpc_url: Url::parse(&config.bitcoin_node_rpc_url).map_err(|e| {
-                Error::InvalidConfig(format!("Invalid bitcoin_node_rpc_url: {}", e))
-            })?,
+            stacks_node_rpc_url,
+            stacks_api_url,
+            bitcoin_node_rpc_url,
             frost_dkg_round_id: config.frost_dkg_round_id,
             signer_config_path: config.signer_config_path,
             start_block_height: config.start_block_height,
@@ -172,19 +930,135 @@ impl TryFrom<RawConfig> for Config {
             bitcoin_network,
             stacks_version,
             transaction_fee: config.transaction_fee,
+            stacks_fee_estimation: config.stacks_fee_estimation.unwrap_or(true),
+            stacks_fee_policy,
             http_relay_url: config.http_relay_url,
             network_private_key: config.network_private_key,
             polling_interval: config.polling_interval.unwrap_or(DEFAULT_POLLING_INTERVAL),
+            pending_lease_timeout: config
+                .pending_lease_timeout
+                .unwrap_or(DEFAULT_PENDING_LEASE_TIMEOUT),
+            min_confirmations: config
+                .min_confirmations
+                .unwrap_or(DEFAULT_MIN_CONFIRMATIONS),
+            batch_peg_outs: config.batch_peg_outs.unwrap_or(false),
+            batch_peg_ins: config.batch_peg_ins.unwrap_or(false),
+            bitcoin_fee_policy,
+            coin_selection,
+            fulfillment_confirmations: config
+                .fulfillment_confirmations
+                .unwrap_or(DEFAULT_FULFILLMENT_CONFIRMATIONS),
+            admin_api_address,
+            admin_api_token,
+            metrics_address,
+            reconciliation_interval: Some(
+                config
+                    .reconciliation_interval
+                    .unwrap_or(DEFAULT_RECONCILIATION_INTERVAL),
+            )
+            .filter(|interval| *interval > 0),
+            solvency_tolerance: config
+                .solvency_tolerance
+                .unwrap_or(DEFAULT_SOLVENCY_TOLERANCE),
+            handoff_grace_period: config
+                .handoff_grace_period
+                .unwrap_or(DEFAULT_HANDOFF_GRACE_PERIOD),
+            stacking_pool_contract,
         })
     }
 }
 
+/// The fields which differ between the running config and a reloaded one
+#[derive(Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ConfigReload {
+    /// Fields which took effect without a restart
+    pub applied: Vec<&'static str>,
+    /// Fields which only take effect after a restart
+    pub restart_required: Vec<&'static str>,
+}
+
+impl ConfigReload {
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.restart_required.is_empty()
+    }
+}
+
 impl Config {
     pub fn from_path(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
         let raw_config: RawConfig = toml::from_str(&std::fs::read_to_string(path)?)?;
         let config = Config::try_from(raw_config)?;
         Ok(config)
     }
+
+    /// Load the config from layered sources, see [`RawConfig::load`]
+    pub fn load(path: impl AsRef<Path>, environment: Option<&str>) -> Result<Self, Error> {
+        Config::try_from(RawConfig::load(path, environment)?)
+    }
+
+    /// Take the fields of `reloaded` which can change while the coordinator runs, and report
+    /// the fields which changed. The others keep their running value until a restart, as they
+    /// are baked into the nodes, wallets and frost coordinator.
+    pub fn reload(&mut self, reloaded: Config) -> ConfigReload {
+        let mut reload = ConfigReload::default();
+        macro_rules! applied {
+            ($($field:ident),*) => {
+                $(if self.$field != reloaded.$field {
+                    self.$field = reloaded.$field;
+                    reload.applied.push(stringify!($field));
+                })*
+            };
+        }
+        macro_rules! restart_required {
+            ($($field:ident),*) => {
+                $(if self.$field != reloaded.$field {
+                    reload.restart_required.push(stringify!($field));
+                })*
+            };
+        }
+        applied!(
+            transaction_fee,
+            stacks_fee_policy,
+            polling_interval,
+            pending_lease_timeout,
+            min_confirmations,
+            batch_peg_outs,
+            batch_peg_ins,
+            bitcoin_fee_policy,
+            coin_selection,
+            fulfillment_confirmations,
+            reconciliation_interval,
+            solvency_tolerance,
+            handoff_grace_period
+        );
+        // http_relay_url is left out on purpose. The relay connection is owned by the frost
+        // coordinators of the current and previous signer sets, along with their DKG state, and
+        // swapping it between the rounds of a signing would drop their messages.
+        restart_required!(
+            contract_name,
+            contract_address,
+            stacks_address,
+            stacks_node_rpc_url,
+            stacks_api_url,
+            bitcoin_node_rpc_url,
+            frost_dkg_round_id,
+            signer_config_path,
+            start_block_height,
+            data_directory,
+            bitcoin_network,
+            stacks_version,
+            stacks_fee_estimation,
+            http_relay_url,
+            network_private_key,
+            admin_api_address,
+            admin_api_token,
+            metrics_address,
+            stacking_pool_contract
+        );
+        if self.stacks_signer != reloaded.stacks_signer {
+            reload.restart_required.push("stacks_signer");
+        }
+        reload
+    }
 }
 
 #[cfg(test)]
@@ -257,7 +1131,12 @@ transaction_fee = 2000
         assert_eq!(config.stacks_version, TransactionVersion::Mainnet);
         assert_eq!(config.contract_name.to_string(), sbtc_contract_name);
         assert_eq!(config.contract_address.to_string(), sbtc_contract_address);
-        assert_eq!(config.stacks_private_key.to_hex(), stacks_private_key);
+        assert!(
+            config.stacks_signer
+                == StacksSignerConfig::PrivateKey(
+                    StacksPrivateKey::from_hex(stacks_private_key).unwrap()
+                )
+        );
 
         // Test config with no signer_config_file
         let config = write_new_config(
@@ -273,7 +1152,12 @@ transaction_fee = 2000
         assert_eq!(config.stacks_version, TransactionVersion::Mainnet);
         assert_eq!(config.contract_name.to_string(), sbtc_contract_name);
         assert_eq!(config.contract_address.to_string(), sbtc_contract_address);
-        assert_eq!(config.stacks_private_key.to_hex(), stacks_private_key);
+        assert!(
+            config.stacks_signer
+                == StacksSignerConfig::PrivateKey(
+                    StacksPrivateKey::from_hex(stacks_private_key).unwrap()
+                )
+        );
 
         // Test config with no signer_config_file or http_relay_url
         let config = write_new_config(
@@ -375,29 +1259,403 @@ transaction_fee = 2000
         assert!(matches!(config, Err(Error::InvalidPrivateKey(_))));
     }
 
+    fn valid_raw_config() -> RawConfig {
+        RawConfig {
+            sbtc_contract: "SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE.sbtc-alpha".to_string(),
+            stacks_private_key: PRIVATE_KEY_HEX.to_string(),
+            stacks_node_rpc_url: "http://localhost:20443".to_string(),
+            bitcoin_node_rpc_url: "http://localhost:18443".to_string(),
+            signer_config_path: Some(String::new()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn zero_min_confirmations_test() {
+        let config = RawConfig {
+            min_confirmations: Some(0),
+            ..valid_raw_config()
+        };
+        assert!(matches!(
+            Config::try_from(config),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn remote_admin_api_requires_token_test() {
+        let config = RawConfig {
+            admin_api_address: Some("0.0.0.0:9000".to_string()),
+            ..valid_raw_config()
+        };
+        assert!(matches!(
+            Config::try_from(config),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        let config = Config::try_from(RawConfig {
+            admin_api_address: Some("0.0.0.0:9000".to_string()),
+            admin_api_token: Some("secret".to_string()),
+            ..valid_raw_config()
+        })
+        .unwrap();
+        assert_eq!(config.admin_api_token, Some("secret".to_string()));
+
+        let config = Config::try_from(RawConfig {
+            admin_api_address: Some("127.0.0.1:9000".to_string()),
+            ..valid_raw_config()
+        })
+        .unwrap();
+        assert_eq!(config.admin_api_token, None);
+    }
+
+    #[test]
+    fn reload_test() {
+        let mut config = Config::try_from(valid_raw_config()).unwrap();
+        assert!(config.reload(config.clone()).is_empty());
+
+        let reloaded = Config::try_from(RawConfig {
+            polling_interval: Some(60),
+            transaction_fee: 500,
+            http_relay_url: Some("http://relay:9776".to_string()),
+            ..valid_raw_config()
+        })
+        .unwrap();
+        let reload = config.reload(reloaded);
+        assert_eq!(reload.applied, vec!["transaction_fee", "polling_interval"]);
+        assert_eq!(reload.restart_required, vec!["http_relay_url"]);
+        assert_eq!(config.polling_interval, 60);
+        assert_eq!(config.transaction_fee, 500);
+        assert_eq!(config.http_relay_url, None);
+    }
+
+    #[test]
+    fn stacking_pool_contract_requires_network_test() {
+        let config = RawConfig {
+            stacking_pool_contract: Some("sbtc-stacking-pool".to_string()),
+            ..valid_raw_config()
+        };
+        assert!(matches!(
+            Config::try_from(config),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_fields_test() {
+        let config = RawConfig {
+            sbtc_contract: "garbage".to_string(),
+            min_confirmations: Some(0),
+            metrics_address: Some("localhost".to_string()),
+            ..valid_raw_config()
+        };
+        match Config::try_from(config) {
+            Err(Error::InvalidFields(errors)) => {
+                assert_eq!(errors.len(), 3);
+                assert!(matches!(errors[0], Error::InvalidConfig(_)));
+                assert!(matches!(errors[1], Error::InvalidContract(_)));
+                assert!(matches!(errors[2], Error::InvalidConfig(_)));
+            }
+            _ => panic!("expected every invalid field to be reported"),
+        }
+    }
+
+    #[test]
+    fn layered_sources_test() {
+        let dir = TempDir::new("").unwrap();
+        let path = dir.path().join("coordinator.toml");
+        std::fs::write(
+            &path,
+            format!(
+                r#"
+sbtc_contract = "SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE.sbtc-alpha"
+stacks_private_key = "{PRIVATE_KEY_HEX}"
+stacks_node_rpc_url = "http://localhost:20443"
+bitcoin_node_rpc_url = "http://localhost:18443"
+frost_dkg_round_id = 0
+transaction_fee = 2000
+polling_interval = 10
+min_confirmations = 3
+"#
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            override_path(&path, "testnet"),
+            "network = \"testnet\"\npolling_interval = 20\n",
+        )
+        .unwrap();
+        let key_path = dir.path().join("network_private_key");
+        std::fs::write(&key_path, "network key\n").unwrap();
+        let env = vec![
+            ("STACKS_COORDINATOR_POLLING_INTERVAL", "30".to_string()),
+            ("STACKS_COORDINATOR_BATCH_PEG_OUTS", "true".to_string()),
+            (
+                "STACKS_COORDINATOR_HTTP_RELAY_URL",
+                "http://relay:9776".to_string(),
+            ),
+            (
+                "STACKS_COORDINATOR_NETWORK_PRIVATE_KEY_FILE",
+                key_path.display().to_string(),
+            ),
+            ("RUST_LOG", "debug".to_string()),
+        ];
+        let vars = || {
+            vars.iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+        };
+
+        let config = RawConfig::from_sources(&path, Some("testnet"), vars()).unwrap();
+        assert!(matches!(config.network, Some(Network::Testnet)));
+        assert_eq!(config.min_confirmations, Some(3));
+        assert_eq!(config.polling_interval, Some(30));
+        assert_eq!(config.batch_peg_outs, Some(true));
+        assert_eq!(config.http_relay_url.as_deref(), Some("http://relay:9776"));
+        assert_eq!(config.network_private_key.as_deref(), Some("network key"));
+
+        // Only integer and boolean fields are parsed as such
+        let typed = vars().chain([
+            (
+                "STACKS_COORDINATOR_ADMIN_API_TOKEN".to_string(),
+                "123456".to_string(),
+            ),
+            (
+                "STACKS_COORDINATOR_FROST_STATE_FILE".to_string(),
+                "inf".to_string(),
+            ),
+        ]);
+        let config = RawConfig::from_sources(&path, None, typed).unwrap();
+        assert_eq!(config.admin_api_token.as_deref(), Some("123456"));
+        assert_eq!(config.frost_state_file.as_deref(), Some("inf"));
+        let mistyped = vars().chain([(
+            "STACKS_COORDINATOR_MIN_CONFIRMATIONS".to_string(),
+            "nan".to_string(),
+        )]);
+        assert!(RawConfig::from_sources(&path, None, mistyped).is_err());
+
+        // The override file of an unknown environment must exist
+        assert!(RawConfig::from_sources(&path, Some("mainnet"), vars()).is_err());
+
+        // Keystores are unlocked with the password in the environment
+        let keystore_path = dir.path().join("stacks_private_key.json");
+        let scrypt = keystore::ScryptParams {
+            log_n: 4,
+            r: 8,
+            p: 1,
+        };
+        keystore::Keystore::encrypt_with(KeyKind::Stacks, PRIVATE_KEY_HEX, "password", scrypt)
+            .unwrap()
+            .create(&keystore_path)
+            .unwrap();
+        let with_keystore = vars().chain([(
+            "STACKS_COORDINATOR_STACKS_PRIVATE_KEY_KEYSTORE".to_string(),
+            keystore_path.display().to_string(),
+        )]);
+        assert!(matches!(
+            RawConfig::from_sources(&path, None, with_keystore.clone()),
+            Err(Error::InvalidConfig(_))
+        ));
+        let with_password =
+            with_keystore.chain([(keystore::PASSWORD_ENV.to_string(), "password".to_string())]);
+        let config = RawConfig::from_sources(&path, None, with_password).unwrap();
+        assert_eq!(config.stacks_private_key, PRIVATE_KEY_HEX);
+
+        // A secret may only be given once per source
+        let conflicting = vars().chain([(
+            "STACKS_COORDINATOR_NETWORK_PRIVATE_KEY".to_string(),
+            "other key".to_string(),
+        )]);
+        assert!(matches!(
+            RawConfig::from_sources(&path, None, conflicting),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn parse_stacks_fee_policy_test() {
+        let mut config = RawConfig::default();
+        let policy = config.parse_stacks_fee_policy().unwrap();
+        assert_eq!(policy.min_fee, DEFAULT_STACKS_FEE_MIN);
+        assert_eq!(policy.max_fee, DEFAULT_STACKS_FEE_MAX);
+        assert_eq!(policy.bump_timeout, DEFAULT_STACKS_FEE_BUMP_TIMEOUT);
+
+        config.stacks_fee_min = Some(2_000);
+        config.stacks_fee_max = Some(1_000);
+        assert!(matches!(
+            config.parse_stacks_fee_policy(),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn parse_bitcoin_fee_policy_test() {
+        let mut config = RawConfig::default();
+        let policy = config.parse_bitcoin_fee_policy().unwrap();
+        assert_eq!(policy.min_fee_rate, DEFAULT_BITCOIN_FEE_RATE_MIN);
+        assert_eq!(policy.max_fee_rate, DEFAULT_BITCOIN_FEE_RATE_MAX);
+
+        // The floor must not exceed the ceiling
+        config.bitcoin_fee_rate_min = Some(20);
+        config.bitcoin_fee_rate_max = Some(10);
+        assert!(matches!(
+            config.parse_bitcoin_fee_policy(),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        config.bitcoin_fee_rate_max = Some(20);
+        config.bitcoin_fee_conf_target = Some(0);
+        assert!(matches!(
+            config.parse_bitcoin_fee_policy(),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn parse_coin_selection_test() {
+        let mut config = RawConfig::default();
+        assert_eq!(
+            config.parse_coin_selection().unwrap(),
+            CoinSelection::LargestFirst
+        );
+
+        config.coin_selection = Some(CoinSelectionStrategy::Consolidate);
+        assert_eq!(
+            config.parse_coin_selection().unwrap(),
+            CoinSelection::Consolidate {
+                max_inputs: DEFAULT_CONSOLIDATION_MAX_INPUTS
+            }
+        );
+
+        config.consolidation_max_inputs = Some(0);
+        assert!(matches!(
+            config.parse_coin_selection(),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
     #[test]
-    fn parse_stacks_private_key_test() {
+    fn parse_stacks_signer_test() {
         let mut config = RawConfig::default();
         // An empty private key should fail
         assert!(matches!(
-            config.parse_stacks_private_key(),
+            config.parse_stacks_signer(),
             Err(Error::InvalidPrivateKey(_))
         ));
 
         // An invalid key shoudl fail
         config.stacks_private_key = "This is an invalid private key...".to_string();
         assert!(matches!(
-            config.parse_stacks_private_key(),
+            config.parse_stacks_signer(),
             Err(Error::InvalidPrivateKey(_))
         ));
 
         // A valid key should succeed
         config.stacks_private_key =
             "d655b2523bcd65e34889725c73064feb17ceb796831c0e111ba1a552b0f31b3901".to_string();
-        assert_eq!(
-            config.parse_stacks_private_key().unwrap().0.to_hex(),
-            config.stacks_private_key
+        let (signer, private_key_address) = config.parse_stacks_signer().unwrap();
+        assert!(matches!(
+            signer,
+            StacksSignerConfig::PrivateKey(key) if key.to_hex() == config.stacks_private_key
+        ));
+
+        // A private key and an external signer are mutually exclusive
+        config.stacks_signer_url = Some("http://localhost:9500".to_string());
+        assert!(matches!(
+            config.parse_stacks_signer(),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        // An external signer needs the public key it signs for
+        config.stacks_private_key = String::new();
+        assert!(matches!(
+            config.parse_stacks_signer(),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        // The address of an external signer is that of its public key
+        let public_key = StacksPublicKey::from_private(
+            &StacksPrivateKey::from_hex(
+                "d655b2523bcd65e34889725c73064feb17ceb796831c0e111ba1a552b0f31b3901",
+            )
+            .unwrap(),
         );
+        config.stacks_public_key = Some(public_key.to_hex());
+        let (signer, address) = config.parse_stacks_signer().unwrap();
+        assert!(signer.public_keys() == vec![public_key]);
+        assert_eq!(address, private_key_address);
+    }
+
+    #[test]
+    fn parse_stacks_multisig_test() {
+        let keys = [
+            StacksPrivateKey::new(),
+            StacksPrivateKey::new(),
+            StacksPrivateKey::new(),
+        ];
+        let public_keys: Vec<_> = keys.iter().map(StacksPublicKey::from_private).collect();
+        let mut config = RawConfig {
+            stacks_private_key: String::new(),
+            stacks_signers: Some(vec![
+                RawStacksSigner {
+                    private_key: Some(keys[0].to_hex()),
+                    ..Default::default()
+                },
+                RawStacksSigner {
+                    private_key_keystore: Some("signer.json".to_string()),
+                    public_key: Some(public_keys[1].to_hex()),
+                    ..Default::default()
+                },
+                RawStacksSigner {
+                    signer_url: Some("http://localhost:9500".to_string()),
+                    public_key: Some(public_keys[2].to_hex()),
+                    ..Default::default()
+                },
+            ]),
+            network: Some(Network::Testnet),
+            ..valid_raw_config()
+        };
+        // The number of signatures required must be given, and at most the number of signers
+        assert!(matches!(
+            config.parse_stacks_signer(),
+            Err(Error::InvalidConfig(_))
+        ));
+        config.stacks_signatures_required = Some(4);
+        assert!(matches!(
+            config.parse_stacks_signer(),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        // The address is the multisig address of the signers' public keys
+        config.stacks_signatures_required = Some(2);
+        let (signer, address) = config.parse_stacks_signer().unwrap();
+        assert_eq!(signer.public_keys(), public_keys);
+        let expected_address = StacksAddress::from_public_keys(
+            multisig_address_version(&TransactionVersion::Testnet),
+            &AddressHashMode::SerializeP2SH,
+            2,
+            &public_keys,
+        )
+        .unwrap();
+        assert_eq!(address, expected_address);
+
+        config.stacks_multisig_hash_mode = Some(StacksMultisigHashMode::P2wsh);
+        let (_, address) = config.parse_stacks_signer().unwrap();
+        assert_ne!(address, expected_address);
+
+        // Keystores and external signers need the public key they sign for
+        config.stacks_signers.as_mut().unwrap()[2].public_key = None;
+        assert!(matches!(
+            config.parse_stacks_signer(),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        // A multisig wallet has no single private key
+        config.stacks_signers.as_mut().unwrap()[2].public_key = Some(public_keys[2].to_hex());
+        config.stacks_private_key = PRIVATE_KEY_HEX.to_string();
+        assert!(matches!(
+            config.parse_stacks_signer(),
+            Err(Error::InvalidConfig(_))
+        ));
     }
 
     #[test]
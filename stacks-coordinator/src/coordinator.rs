@@ -1,12 +1,19 @@
+use backoff::{backoff::Backoff, ExponentialBackoffBuilder};
 use bitcoin::{
     psbt::Prevouts,
     util::{
         base58,
         sighash::{Error as SighashError, SighashCache},
     },
-    SchnorrSighashType, XOnlyPublicKey,
+    BlockHash, OutPoint, SchnorrSighashType, TxOut, XOnlyPublicKey,
+};
+use blockstack_lib::{
+    burnchains::Txid,
+    chainstate::stacks::StacksTransaction,
+    types::chainstate::{BurnchainHeaderHash, StacksAddress},
+    util::secp256k1::Secp256k1PublicKey,
+    vm::{types::PrincipalData, ContractName},
 };
-use blockstack_lib::{types::chainstate::StacksAddress, util::secp256k1::Secp256k1PublicKey};
 use frost_coordinator::{
     coordinator::Error as FrostCoordinatorError, create_coordinator, create_coordinator_from_path,
 };
@@ -14,22 +21,39 @@ use frost_signer::{
     config::Config as SignerConfig,
     net::{Error as HttpNetError, HttpNetListen},
     signing_round::DkgPublicShare,
+    util::parse_public_key,
 };
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap},
     fs::File,
     path::{Path, PathBuf},
-    sync::mpsc::RecvError,
-    thread::sleep,
-    time::Duration,
+    str::FromStr,
+    sync::mpsc::{Receiver, RecvError, RecvTimeoutError, Sender, TryRecvError},
+    time::{Duration, Instant},
 };
-use tracing::{debug, info, warn};
-use wsts::{bip340::SchnorrProof, common::Signature, field::Element, Point, Scalar};
+use tracing::{debug, error, info, warn};
+use wsts::{bip340::SchnorrProof, common::Signature, ecdsa, field::Element, Point, Scalar};
 
-use crate::bitcoin_wallet::BitcoinWallet;
-use crate::stacks_node::{self, Error as StacksNodeError};
-use crate::stacks_wallet::StacksWallet;
-use crate::{config::Config, stacks_node::client::BroadcastError};
+use crate::admin::{entry_to_json, AdminError, AdminRequest, AdminResponse};
+use crate::bitcoin_wallet::{
+    fulfillment_vsize, BitcoinFeePolicy, BitcoinWallet, Error as BitcoinWalletError,
+};
+use crate::metrics::{
+    BROADCAST_RETRIES, FEE_BUMPS, PEG_WALLET_BALANCE, QUEUE_DEPTH, SOLVENCY_DISCREPANCY,
+};
+use crate::reconciliation::SolvencyReport;
+use crate::stacks_node::{self, Error as StacksNodeError, SignerSet, TransactionStatus};
+use crate::stacks_tx_signer::{
+    Error as StacksTxSignerError, KeystoreSigner, MultisigSigner, PrivateKeySigner, RemoteSigner,
+    StacksTxSigner,
+};
+use crate::stacks_wallet::{Error as StacksWalletError, StacksFeePolicy, StacksWallet};
+use crate::{
+    config::{Config, ConfigReload, StacksSignerConfig},
+    stacks_node::client::BroadcastError,
+};
 use crate::{
     peg_wallet::{
         BitcoinWallet as BitcoinWalletTrait, Error as PegWalletError, PegWallet,
@@ -41,11 +65,17 @@ use crate::{
 // Traits in scope
 use crate::bitcoin_node::{
     BitcoinNode, BitcoinTransaction, Error as BitcoinNodeError, LocalhostBitcoinNode,
+    WalletTransaction, UTXO,
+};
+use crate::nonce_manager::{
+    Error as NonceManagerError, NonceManager, OutstandingTransaction, SqliteNonceManager,
 };
 use crate::peg_queue::{
-    Error as PegQueueError, PegQueue, SbtcOp, SqlitePegQueue, SqlitePegQueueError,
+    Error as PegQueueError, Fulfillment, PegQueue, SbtcOp, SqlitePegQueue, SqlitePegQueueError,
+    Status,
 };
 use crate::stacks_node::{client::NodeClient, StacksNode};
+use crate::util::now;
 
 type FrostCoordinator = frost_coordinator::coordinator::Coordinator<HttpNetListen>;
 
@@ -58,6 +88,12 @@ const MAX_NONCE_RETRIES: u64 = 10;
 // The max number of retries for invalid fee's we should attempt before erroring out
 const MAX_FEE_RETRIES: u64 = 2;
 
+// The delay before retrying a polling cycle which failed transiently, doubling with each failure
+const RETRY_INITIAL_INTERVAL: Duration = Duration::from_secs(1);
+
+// The longest delay between retries of a failing polling cycle
+const RETRY_MAX_INTERVAL: Duration = Duration::from_secs(300);
+
 /// Kinds of common errors used by stacks coordinator
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -82,6 +118,10 @@ pub enum Error {
     StacksNodeError(#[from] StacksNodeError),
     #[error("Bitcoin Node Error: {0}")]
     BitcoinNodeError(#[from] BitcoinNodeError),
+    #[error("Nonce Manager Error: {0}")]
+    NonceManagerError(#[from] NonceManagerError),
+    #[error("Stacks Tx Signer Error: {0}")]
+    StacksTxSignerError(#[from] StacksTxSignerError),
     #[error("{0}")]
     ConfigError(String),
     #[error("Invalid bitcoin wallet public key: {0}")]
@@ -94,15 +134,82 @@ pub enum Error {
     MaxFeeRetriesExceeded,
     #[error("Max nonce retries exceeded.")]
     MaxNonceRetriesExceeded,
+    #[error("Stacks broadcast thread panicked")]
+    BroadcastThreadPanicked,
     #[error("Point error: {0}")]
     PointError(String),
+    #[error("Missing previous output {0} of fulfillment transaction")]
+    MissingPrevout(OutPoint),
+    #[error("No op with txid {0} in the queue")]
+    UnknownOp(Txid),
+    #[error("Peg wallet {0} still holds utxos. Hand it off to a new signer set instead.")]
+    WalletNotEmpty(String),
+    #[error("Handoff from peg wallet {0} is still in progress")]
+    HandoffInProgress(String),
+}
+
+impl Error {
+    /// Whether the error is expected to clear up by itself, such as an unreachable node, so the
+    /// failed operation may be retried. Config and consistency errors are fatal.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::HttpNetError(_) => true,
+            Error::PegQueueError(e) => e.is_transient(),
+            Error::StacksNodeError(e) => e.is_transient(),
+            Error::BitcoinNodeError(e) => e.is_transient(),
+            Error::FrostCoordinatorError(e) => matches!(
+                e,
+                FrostCoordinatorError::Timeout | FrostCoordinatorError::NetworkError(_)
+            ),
+            Error::StacksTxSignerError(e)
+            | Error::PegWalletError(PegWalletError::StacksWalletError(
+                StacksWalletError::StacksTxSignerError(e),
+            )) => e.is_transient(),
+            Error::MaxFeeRetriesExceeded | Error::MaxNonceRetriesExceeded => true,
+            Error::PegWalletError(_)
+            | Error::SqlitePegQueueError(_)
+            | Error::UnexpectedSenderDisconnect(_)
+            | Error::NonceManagerError(_)
+            | Error::ConfigError(_)
+            | Error::InvalidPublicKey(_)
+            | Error::SigningError(_)
+            | Error::NoCoordinator
+            | Error::BroadcastThreadPanicked
+            | Error::PointError(_)
+            | Error::MissingPrevout(_)
+            | Error::UnknownOp(_)
+            | Error::WalletNotEmpty(_)
+            | Error::HandoffInProgress(_) => false,
+        }
+    }
+}
+
+/// The peg wallet of the signer set which handed off to the current one. It is kept until the end
+/// of the grace period so late deposits and queued peg outs can still be processed.
+pub struct PreviousWallet {
+    /// Coordinates the previous signer set, which signs spends of the previous address
+    pub frost_coordinator: FrostCoordinator,
+    pub address: bitcoin::Address,
+    /// Last Bitcoin block height at which the previous address is honoured
+    pub grace_period_end: u64,
+}
+
+/// Hand-off of the peg wallet to the signers of the stacking pool at every PoX reward cycle
+pub struct SignerRotation {
+    pub stacking_pool: ContractName,
+    /// Network key and relay of the frost coordinators of every signer set
+    pub network_private_key: Scalar,
+    pub http_relay_url: String,
+    /// Reward cycle of the current signer set, once known
+    pub reward_cycle: Option<u64>,
 }
 
 pub trait Coordinator: Sized {
     type PegQueue: PegQueue;
     type FeeWallet: PegWallet;
-    type StacksNode: StacksNode;
+    type StacksNode: StacksNode + Sync;
     type BitcoinNode: BitcoinNode;
+    type NonceManager: NonceManager;
 
     // Required methods
     fn peg_queue(&self) -> &Self::PegQueue;
@@ -110,184 +217,1730 @@ pub trait Coordinator: Sized {
     fn fee_wallet(&self) -> &Self::FeeWallet;
     fn frost_coordinator(&self) -> &FrostCoordinator;
     fn frost_coordinator_mut(&mut self) -> &mut FrostCoordinator;
+    fn previous_wallet(&self) -> Option<&PreviousWallet>;
+    fn previous_wallet_mut(&mut self) -> &mut Option<PreviousWallet>;
+    fn signer_rotation(&self) -> Option<&SignerRotation>;
+    fn signer_rotation_mut(&mut self) -> Option<&mut SignerRotation>;
     fn stacks_node(&self) -> &Self::StacksNode;
     fn stacks_node_mut(&mut self) -> &mut Self::StacksNode;
     fn bitcoin_node(&self) -> &Self::BitcoinNode;
+    fn nonce_manager(&self) -> &Self::NonceManager;
+    /// Whether peg out requests polled in the same cycle are fulfilled by a single Bitcoin transaction
+    fn batch_peg_outs(&self) -> bool;
+    /// Whether the peg ins of a burn block are minted with consecutive nonces and broadcast concurrently
+    fn batch_peg_ins(&self) -> bool;
+    /// Fee rate bounds and bump timing for peg out fulfillments
+    fn bitcoin_fee_policy(&self) -> &BitcoinFeePolicy;
+    /// Fee bounds and bump timing for Stacks transactions
+    fn stacks_fee_policy(&self) -> &StacksFeePolicy;
+    /// Number of Bitcoin confirmations before a peg out fulfillment is acknowledged
+    fn fulfillment_confirmations(&self) -> u64;
+    /// Time after which a pending op which has not progressed is handed out again
+    fn pending_lease_timeout(&self) -> Duration;
+    /// Directory persisting the coordinator's state, if any
+    fn data_directory(&self) -> Option<&Path>;
+    /// Time between solvency reconciliations, if they are scheduled
+    fn reconciliation_interval(&self) -> Option<Duration>;
+    /// Discrepancy in sats between the peg wallet balance and the sBTC supply which is tolerated
+    fn solvency_tolerance(&self) -> u64;
+    /// Bitcoin blocks during which the previous peg wallet is honoured after a handoff
+    fn handoff_grace_period(&self) -> u64;
+    /// Time between polling cycles
+    fn polling_interval(&self) -> Duration;
+    /// Re-read the config file, applying the fields which can change while running
+    fn reload_config(&mut self) -> Result<ConfigReload>;
 
     // Provided methods
-    fn run(mut self, polling_interval: u64) -> Result<()> {
+    /// Process sBTC ops every polling interval until stopped through `commands`.
+    /// Commands take effect between the steps of a polling cycle, so an op is never abandoned
+    /// halfway through a step. The queue state is persisted as each op progresses.
+    ///
+    /// A cycle failing with a transient error is retried with exponential backoff. Any other
+    /// error stops the coordinator.
+    fn run(mut self, commands: Receiver<Command>) -> Result<()> {
+        let mut retry_backoff = ExponentialBackoffBuilder::new()
+            .with_initial_interval(RETRY_INITIAL_INTERVAL)
+            .with_max_interval(RETRY_MAX_INTERVAL)
+            .with_max_elapsed_time(None)
+            .build();
+        let mut state = RunState::Running;
+        let mut last_reconciliation: Option<Instant> = None;
         loop {
-            info!("Polling for withdrawal and deposit requests to process...");
-            self.peg_queue().poll(self.stacks_node())?;
-            self.process_queue()?;
+            let mut retry_delay = None;
+            for step in Step::ALL {
+                state = receive_commands(state, &commands, |request| {
+                    self.serve_admin_request(request).map_err(AdminError::from)
+                })?;
+                let run_step = match state {
+                    RunState::Running => true,
+                    RunState::Draining => !step.takes_new_ops(),
+                    RunState::Paused | RunState::Stopped => false,
+                };
+                if !run_step {
+                    continue;
+                }
+                if let Err(e) = self.run_step(step) {
+                    if !e.is_transient() {
+                        error!("Fatal error during {:?}: {}", step, e);
+                        return Err(e);
+                    }
+                    let delay = retry_backoff.next_backoff().unwrap_or(RETRY_MAX_INTERVAL);
+                    warn!(
+                        "Transient error during {:?}: {}. Retrying in {:?}...",
+                        step, e, delay
+                    );
+                    retry_delay = Some(delay);
+                    break;
+                }
+            }
+            if retry_delay.is_none() {
+                retry_backoff.reset();
+                if let Some(interval) = self.reconciliation_interval() {
+                    if last_reconciliation.map_or(true, |last| last.elapsed() >= interval) {
+                        last_reconciliation = Some(Instant::now());
+                        // A failed reconciliation is retried at the next interval
+                        if let Err(e) = self.reconcile() {
+                            warn!(
+                                "Unable to reconcile the peg wallet with the sBTC supply: {}",
+                                e
+                            );
+                        }
+                    }
+                }
+                if state == RunState::Draining && !self.has_ops_in_flight()? {
+                    info!("All in-flight ops are resolved");
+                    state = RunState::Stopped;
+                }
+            }
+            if state == RunState::Stopped {
+                break;
+            }
 
-            sleep(Duration::from_secs(polling_interval));
+            // Wait for the next polling cycle, waking up early on commands
+            state = wait_for_command(
+                state,
+                &commands,
+                retry_delay.unwrap_or(self.polling_interval()),
+                |request| self.serve_admin_request(request).map_err(AdminError::from),
+            )?;
         }
+        info!("Coordinator stopped");
+        Ok(())
     }
 
     fn process_queue(&mut self) -> Result<()> {
-        loop {
-            match self.peg_queue().sbtc_op()? {
-                Some(SbtcOp::PegIn(op)) => {
-                    debug!("Processing peg in request: {:?}", op);
-                    self.peg_in(op)?;
+        let mut peg_in_batches: BTreeMap<u64, Vec<stacks_node::PegInOp>> = BTreeMap::new();
+        let mut peg_out_batch = vec![];
+        // sBTC requested by each requester this cycle, which is not yet burned
+        let mut requested = HashMap::new();
+        while let Some(op) = self.peg_queue().sbtc_op()? {
+            let (txid, burn_header_hash) = (*op.txid(), *op.burn_header_hash());
+            let result = match op {
+                SbtcOp::PegIn(op) => match self.check_peg_in_deposit(&op) {
+                    Ok(Some(reason)) => {
+                        warn!("Rejecting peg in request {}: {}", txid, reason);
+                        self.peg_queue()
+                            .reject(&txid, &burn_header_hash, &reason)
+                            .map_err(Error::from)
+                    }
+                    Ok(None) if self.batch_peg_ins() => {
+                        debug!("Adding peg in request to batch: {:?}", op);
+                        peg_in_batches.entry(op.block_height).or_default().push(op);
+                        Ok(())
+                    }
+                    Ok(None) => {
+                        debug!("Processing peg in request: {:?}", op);
+                        self.peg_in(op)
+                    }
+                    Err(e) => Err(e),
+                },
+                SbtcOp::PegOutRequest(op) => match self.check_sbtc_balance(&op, &mut requested) {
+                    Ok(Some(reason)) => {
+                        warn!("Rejecting peg out request {}: {}", txid, reason);
+                        self.peg_queue()
+                            .reject(&txid, &burn_header_hash, &reason)
+                            .map_err(Error::from)
+                    }
+                    Ok(None) if self.batch_peg_outs() => {
+                        debug!("Adding peg out request to batch: {:?}", op);
+                        peg_out_batch.push(op);
+                        Ok(())
+                    }
+                    Ok(None) => {
+                        debug!("Processing peg out request: {:?}", op);
+                        self.peg_out(op)
+                    }
+                    Err(e) => Err(e),
+                },
+            };
+            self.handle_op_result(&txid, &burn_header_hash, result)?;
+        }
+
+        for (block_height, ops) in peg_in_batches {
+            debug!(
+                "Processing batch of {} peg in requests from burn block {}",
+                ops.len(),
+                block_height
+            );
+            for (op, result) in self.peg_in_batch(ops)? {
+                if let Err(e) = self.handle_op_result(&op.txid, &op.burn_header_hash, result) {
+                    // The op stays pending and is retried once its lease expires
+                    error!("Failed to process peg in request {}: {}", op.txid, e);
                 }
-                Some(SbtcOp::PegOutRequest(op)) => {
-                    debug!("Processing peg out request: {:?}", op);
-                    self.peg_out(op)?;
+            }
+        }
+
+        if peg_out_batch.is_empty() {
+            return Ok(());
+        }
+        debug!(
+            "Processing batch of {} peg out requests",
+            peg_out_batch.len()
+        );
+        match self.peg_out_batch(&peg_out_batch) {
+            Ok(results) => {
+                for (op, result) in results {
+                    if let Err(e) = self.handle_op_result(&op.txid, &op.burn_header_hash, result) {
+                        // The op stays pending and is retried once its lease expires
+                        error!("Failed to process peg out request {}: {}", op.txid, e);
+                    }
+                }
+                Ok(())
+            }
+            Err(e @ Error::PegWalletError(_)) if !e.is_transient() => {
+                // Nothing was broadcast. Fall back to fulfilling the requests one by one so
+                // a single malformed request does not hold back the rest of the batch.
+                warn!(
+                    "Failed to build peg out batch: {}. Processing requests individually...",
+                    e
+                );
+                for op in peg_out_batch {
+                    let (txid, burn_header_hash) = (op.txid, op.burn_header_hash);
+                    let result = self.peg_out(op);
+                    self.handle_op_result(&txid, &burn_header_hash, result)?;
                 }
-                None => return Ok(()),
+                Ok(())
             }
+            Err(e) => Err(e),
         }
     }
 }
 
 // Private helper functions
 trait CoordinatorHelpers: Coordinator {
-    fn peg_in(&mut self, op: stacks_node::PegInOp) -> Result<()> {
-        // Build a transaction from the peg in op and broadcast it to the node with reattempts
-        self.try_broadcast_transaction(&op)
+    fn run_step(&mut self, step: Step) -> Result<()> {
+        match step {
+            Step::Poll => {
+                // Ops left pending by a failed cycle are handed out again
+                let requeued = self
+                    .peg_queue()
+                    .requeue_expired(self.pending_lease_timeout())?;
+                if requeued > 0 {
+                    info!("Requeued {} pending ops whose lease expired", requeued);
+                }
+                info!("Polling for withdrawal and deposit requests to process...");
+                self.peg_queue().poll(self.stacks_node())?;
+                self.update_metrics()
+            }
+            Step::ReconcileNonces => self.reconcile_nonces(),
+            Step::ProcessQueue => self.process_queue(),
+            Step::ProcessStacksBroadcasts => self.process_stacks_broadcasts(),
+            Step::TrackFulfillments => self.track_fulfillments(),
+            Step::RotateSigners => self.rotate_signers(),
+            Step::SweepPreviousWallet => self.sweep_previous_wallet(),
+        }
     }
 
-    fn peg_out(&mut self, op: stacks_node::PegOutRequestOp) -> Result<()> {
-        // First build both the sBTC and BTC transactions before attempting to broadcast either of them
-        // This ensures that if either of the transactions fail to build, neither of them will be broadcast
+    /// Refresh the queue depth and peg wallet balance gauges. The balance is left as is
+    /// if the bitcoin node cannot be reached, as it does not affect processing.
+    fn update_metrics(&self) -> Result<()> {
+        for status in [
+            Status::New,
+            Status::Pending,
+            Status::StacksBroadcast,
+            Status::BitcoinBroadcast,
+            Status::Flagged,
+        ] {
+            let depth = self.peg_queue().entries(status)?.len();
+            QUEUE_DEPTH
+                .with_label_values(&[status.as_str()])
+                .set(depth as i64);
+        }
+        match self.wallet_utxos() {
+            Ok(utxos) => {
+                PEG_WALLET_BALANCE.set(utxos.iter().map(|utxo| utxo.amount).sum::<u64>() as i64)
+            }
+            Err(e) => warn!("Unable to retrieve peg wallet balance: {}", e),
+        }
+        Ok(())
+    }
 
-        // Build and sign a fulfilled bitcoin transaction
-        let fulfill_tx = self.fulfill_peg_out(&op)?;
+    /// The utxos of the peg wallet, including those left on the previous address by a handoff
+    fn wallet_utxos(&self) -> Result<Vec<UTXO>> {
+        let mut utxos = self
+            .bitcoin_node()
+            .list_unspent(self.fee_wallet().bitcoin().address())?;
+        if let Some(previous) = self.previous_wallet() {
+            utxos.extend(self.bitcoin_node().list_unspent(&previous.address)?);
+        }
+        Ok(utxos)
+    }
 
-        // Build a transaction from the peg out request op and broadcast it to the node with reattempts
-        self.try_broadcast_transaction(&op)?;
+    /// Serve a request of the admin API
+    fn serve_admin_request(&mut self, request: AdminRequest) -> Result<Value> {
+        match request {
+            AdminRequest::ListOps(status) => Ok(self
+                .peg_queue()
+                .entries(status)?
+                .iter()
+                .map(entry_to_json)
+                .collect()),
+            AdminRequest::GetOp(txid) => self.op_report(&txid),
+            AdminRequest::RequeueOp(txid, burn_header_hash) => {
+                self.peg_queue().requeue(&txid, &burn_header_hash)?;
+                self.op_report(&txid)
+            }
+            AdminRequest::SkipOp(txid, burn_header_hash) => {
+                info!("Skipping op {} at an operator's request", txid);
+                self.peg_queue().fail(&txid, &burn_header_hash)?;
+                self.op_report(&txid)
+            }
+            AdminRequest::RunDkg => {
+                self.run_dkg()?;
+                self.wallet_report()
+            }
+            AdminRequest::HandOff(signer_config_path) => {
+                let frost_coordinator =
+                    create_coordinator_from_path(&signer_config_path).map_err(|e| {
+                        Error::ConfigError(format!(
+                            "Invalid signer config {:?}: {}",
+                            signer_config_path, e
+                        ))
+                    })?;
+                self.hand_off_wallet(frost_coordinator)?;
+                self.wallet_report()
+            }
+            AdminRequest::GetWallet => self.wallet_report(),
+            AdminRequest::ReloadConfig => {
+                Ok(serde_json::to_value(self.reload_config()?).unwrap_or(Value::Null))
+            }
+            AdminRequest::GetSolvency => {
+                Ok(serde_json::to_value(self.reconcile()?).unwrap_or(Value::Null))
+            }
+        }
+    }
+
+    /// The queue entries of the op, with its Stacks and Bitcoin txids
+    fn op_report(&self, txid: &Txid) -> Result<Value> {
+        let entries = self.peg_queue().entries_by_txid(txid)?;
+        if entries.is_empty() {
+            return Err(Error::UnknownOp(*txid));
+        }
+        Ok(entries.iter().map(entry_to_json).collect())
+    }
+
+    /// The aggregate public key, peg wallet address and its utxos, and the previous peg wallet
+    /// if a handoff is in progress
+    fn wallet_report(&self) -> Result<Value> {
+        let wallet = self.fee_wallet().bitcoin();
+        let utxos = self.bitcoin_node().list_unspent(wallet.address())?;
+        let previous_wallet = match self.previous_wallet() {
+            Some(previous) => {
+                let utxos = self.bitcoin_node().list_unspent(&previous.address)?;
+                json!({
+                    "address": previous.address.to_string(),
+                    "grace_period_end": previous.grace_period_end,
+                    "balance": utxos.iter().map(|utxo| utxo.amount).sum::<u64>(),
+                    "utxos": utxos,
+                })
+            }
+            None => Value::Null,
+        };
+        Ok(json!({
+            "aggregate_public_key": wallet.x_only_pub_key().to_string(),
+            "address": wallet.address().to_string(),
+            "balance": utxos.iter().map(|utxo| utxo.amount).sum::<u64>(),
+            "utxos": utxos,
+            "previous_wallet": previous_wallet,
+        }))
+    }
+
+    /// Compare the peg wallet balance with the sBTC supply, accounting for in-flight ops
+    fn solvency_report(&self) -> Result<SolvencyReport> {
+        let wallet_address = self.fee_wallet().bitcoin().address();
+        let utxos = self.wallet_utxos()?;
+        let sbtc_supply = self
+            .stacks_node()
+            .sbtc_total_supply(self.fee_wallet().stacks().address())?;
+        let mut entries = vec![];
+        for status in [Status::New, Status::Pending, Status::StacksBroadcast] {
+            entries.extend(self.peg_queue().entries(status)?);
+        }
+        Ok(SolvencyReport::new(
+            wallet_address.to_string(),
+            &utxos,
+            sbtc_supply,
+            &entries,
+        ))
+    }
+
+    /// Produce a solvency report, logging an error if the discrepancy is not explained by in-flight ops
+    fn reconcile(&self) -> Result<SolvencyReport> {
+        let report = self.solvency_report()?;
+        SOLVENCY_DISCREPANCY.set(report.discrepancy as i64);
+        if report.is_balanced(self.solvency_tolerance()) {
+            info!(
+                "Peg wallet {} holds {} sats for a sBTC supply of {} ({} sats of peg ins pending, {} of peg outs, {} of unconfirmed deposits)",
+                report.wallet_address,
+                report.wallet_balance,
+                report.sbtc_supply,
+                report.pending_peg_ins,
+                report.pending_peg_outs,
+                report.unconfirmed_deposits
+            );
+        } else {
+            error!(
+                "Peg wallet {} holds {} sats for a sBTC supply of {}: discrepancy of {} sats ({} sats of peg ins pending, {} of peg outs, {} of unconfirmed deposits)",
+                report.wallet_address,
+                report.wallet_balance,
+                report.sbtc_supply,
+                report.discrepancy,
+                report.pending_peg_ins,
+                report.pending_peg_outs,
+                report.unconfirmed_deposits
+            );
+        }
+        Ok(report)
+    }
+
+    /// Run a DKG round and switch the peg wallet to the resulting aggregate key, publishing it
+    /// to the sBTC contract. The signers replace their key, so the peg wallet must be empty.
+    fn run_dkg(&mut self) -> Result<XOnlyPublicKey> {
+        let wallet_address = self.fee_wallet().bitcoin().address();
+        if !self.bitcoin_node().list_unspent(wallet_address)?.is_empty() {
+            return Err(Error::WalletNotEmpty(wallet_address.to_string()));
+        }
+        info!("Running DKG round...");
+        let point = self
+            .frost_coordinator_mut()
+            .run_distributed_key_generation()?;
+        let xonly_pubkey = XOnlyPublicKey::from_slice(&point.x().to_bytes())
+            .map_err(|e| Error::InvalidPublicKey(e.to_string()))?;
+        self.switch_wallet(xonly_pubkey)?;
+        Ok(xonly_pubkey)
+    }
+
+    /// Hand the peg wallet off to the signer set of `frost_coordinator`. A DKG round is run with
+    /// the new signers and their aggregate key is published to the sBTC contract. The utxos of
+    /// the previous address are swept to the new one, signed by the previous signer set, which is
+    /// kept until the end of the grace period.
+    fn hand_off_wallet(
+        &mut self,
+        mut frost_coordinator: FrostCoordinator,
+    ) -> Result<XOnlyPublicKey> {
+        if let Some(previous) = self.previous_wallet() {
+            return Err(Error::HandoffInProgress(previous.address.to_string()));
+        }
+        info!("Running DKG round with the new signer set...");
+        let point = frost_coordinator.run_distributed_key_generation()?;
+        let xonly_pubkey = XOnlyPublicKey::from_slice(&point.x().to_bytes())
+            .map_err(|e| Error::InvalidPublicKey(e.to_string()))?;
+        let grace_period_end = self.bitcoin_node().block_height()? + self.handoff_grace_period();
+
+        let frost_coordinator = std::mem::replace(self.frost_coordinator_mut(), frost_coordinator);
+        let address = self.fee_wallet().bitcoin().address().clone();
+        info!(
+            "Handing off peg wallet {} to a new signer set. Deposits to it are accepted until block {}",
+            address, grace_period_end
+        );
+        *self.previous_wallet_mut() = Some(PreviousWallet {
+            frost_coordinator,
+            address,
+            grace_period_end,
+        });
+        self.save_handoff_state()?;
+        self.switch_wallet(xonly_pubkey)?;
+
+        // The sweep is retried by every polling cycle until the grace period ends
+        if let Err(e) = self.sweep_previous_wallet() {
+            warn!("Unable to sweep the previous peg wallet: {}", e);
+        }
+        Ok(xonly_pubkey)
+    }
+
+    /// Hand the peg wallet off to the signers of the stacking pool once the PoX reward cycle rolls
+    /// over. Unless a reward cycle was saved by a previous run, the cycle seen first is taken to be
+    /// the one of the current signer set. The pool never
+    /// reuses a public key, so the signers of consecutive cycles are distinct.
+    fn rotate_signers(&mut self) -> Result<()> {
+        let Some(rotation) = self.signer_rotation() else {
+            return Ok(());
+        };
+        let stacking_pool = rotation.stacking_pool.clone();
+        let reward_cycle = rotation.reward_cycle;
+        let network_private_key = rotation.network_private_key;
+        let http_relay_url = rotation.http_relay_url.clone();
+        let sender = *self.fee_wallet().stacks().address();
+        let cycle = self
+            .stacks_node()
+            .current_reward_cycle(&sender, &stacking_pool)?;
+        match reward_cycle {
+            Some(reward_cycle) if reward_cycle >= cycle => return Ok(()),
+            Some(_) => {}
+            None => {
+                info!("Signing with the signer set of reward cycle {}", cycle);
+                self.set_reward_cycle(cycle)?;
+                return Ok(());
+            }
+        }
+        if let Some(previous) = self.previous_wallet() {
+            debug!(
+                "Reward cycle {} started. Waiting for the handoff from {} to end before rotating signers",
+                cycle, previous.address
+            );
+            return Ok(());
+        }
 
-        // Broadcast the BTC transaction to the Bitcoin node
-        self.bitcoin_node().broadcast_transaction(&fulfill_tx)?;
+        let Some(signer_set) = self
+            .stacks_node()
+            .current_signer_set(&sender, &stacking_pool, cycle)?
+            .filter(|signer_set| !signer_set.public_keys.signers.is_empty())
+        else {
+            warn!(
+                "No signers in the stacking pool of reward cycle {}. Keeping the current signer set",
+                cycle
+            );
+            self.set_reward_cycle(cycle)?;
+            return Ok(());
+        };
+        // The pool read may belong to the next cycle if it just started
+        if self
+            .stacks_node()
+            .current_reward_cycle(&sender, &stacking_pool)?
+            != cycle
+        {
+            return Ok(());
+        }
         info!(
-            "Broadcasted fulfilled BTC transaction: {}",
-            fulfill_tx.txid()
+            "Reward cycle {} started. Handing the peg wallet off to its {} signers",
+            cycle,
+            signer_set.public_keys.signers.len()
         );
+
+        // The coordinator keeps the key registered in the sBTC contract
+        let coordinator_public_key = self
+            .stacks_node()
+            .coordinator_public_key(&sender)?
+            .ok_or(Error::NoCoordinator)?;
+        let frost_coordinator = create_coordinator(&SignerConfig::new(
+            signer_set.keys_threshold,
+            coordinator_public_key,
+            signer_set.public_keys,
+            signer_set.signer_key_ids,
+            network_private_key,
+            http_relay_url,
+        ))
+        .map_err(|e| Error::ConfigError(e.to_string()))?;
+        self.hand_off_wallet(frost_coordinator)?;
+        self.set_reward_cycle(cycle)?;
         Ok(())
     }
 
-    fn fulfill_peg_out(&mut self, op: &stacks_node::PegOutRequestOp) -> Result<BitcoinTransaction> {
-        // Retreive the utxos
-        let utxos = self
-            .bitcoin_node()
-            .list_unspent(self.fee_wallet().bitcoin().address())?;
+    /// Persist the previous peg wallet and the reward cycle of the current signer set to the data
+    /// directory, so a restart during the grace period can still sign its sweep and the
+    /// fulfillments of queued peg outs, and a restart after a rollover still rotates the signers
+    fn save_handoff_state(&self) -> Result<()> {
+        let Some(data_directory) = self.data_directory() else {
+            return Ok(());
+        };
+        let previous_wallet = self
+            .previous_wallet()
+            .map(SavedPreviousWallet::try_from)
+            .transpose()?;
+        let reward_cycle = self
+            .signer_rotation()
+            .and_then(|rotation| rotation.reward_cycle);
+        write_handoff_state(
+            data_directory,
+            &HandoffState {
+                reward_cycle,
+                previous_wallet,
+            },
+        )
+    }
 
-        // Build unsigned fulfilled peg out transaction
-        let (mut tx, prevouts) = self.fee_wallet().bitcoin().fulfill_peg_out(op, utxos)?;
-        let sighash_tx = tx.clone();
-        let mut sighash_cache = SighashCache::new(&sighash_tx);
-        // Sign the transaction
-        for index in 0..tx.input.len() {
-            let taproot_sighash = sighash_cache
-                .taproot_key_spend_signature_hash(
-                    index,
-                    &Prevouts::All(&prevouts),
-                    SchnorrSighashType::Default,
-                )
-                .map_err(Error::SigningError)?;
-            let (_frost_sig, schnorr_proof) = self
-                .frost_coordinator_mut()
-                .sign_message(&taproot_sighash.as_hash())?;
+    fn set_reward_cycle(&mut self, cycle: u64) -> Result<()> {
+        if let Some(rotation) = self.signer_rotation_mut() {
+            rotation.reward_cycle = Some(cycle);
+        }
+        self.save_handoff_state()
+    }
 
-            debug!(
-                "Fulfill Tx {:?} SchnorrProof ({},{})",
-                &tx, schnorr_proof.r, schnorr_proof.s
+    /// Switch the peg wallet to the aggregate key of the current signer set, publishing it to the
+    /// sBTC contract
+    fn switch_wallet(&mut self, xonly_pubkey: XOnlyPublicKey) -> Result<()> {
+        if let Some(data_directory) = self.data_directory() {
+            write_dkg_public_shares(
+                data_directory,
+                self.frost_coordinator().get_dkg_public_shares(),
+            )?;
+        }
+
+        let address = *self.fee_wallet().stacks().address();
+        let nonce = self.nonce_manager().next_nonce(&address)?;
+        let tx = self
+            .fee_wallet()
+            .stacks()
+            .build_set_bitcoin_wallet_public_key_transaction(&xonly_pubkey, nonce)?;
+        self.stacks_node().broadcast_transaction(&tx)?;
+        self.nonce_manager().record(&address, nonce, Some(&tx))?;
+
+        self.fee_wallet_mut()
+            .bitcoin_mut()
+            .set_x_only_pub_key(xonly_pubkey);
+        self.bitcoin_node()
+            .load_wallet(self.fee_wallet().bitcoin().address())?;
+        info!(
+            "Switched peg wallet to aggregate public key {} at {}",
+            xonly_pubkey,
+            self.fee_wallet().bitcoin().address()
+        );
+        Ok(())
+    }
+
+    /// Sweep the utxos of the previous peg wallet to the current one. Until the grace period ends,
+    /// the fulfillment utxos of queued peg outs are left in place so the peg outs can be fulfilled.
+    /// The previous wallet is dropped once the grace period is over.
+    fn sweep_previous_wallet(&mut self) -> Result<()> {
+        let Some(previous) = self.previous_wallet() else {
+            return Ok(());
+        };
+        let (address, grace_period_end) = (previous.address.clone(), previous.grace_period_end);
+        let in_grace_period = self.bitcoin_node().block_height()? <= grace_period_end;
+
+        let mut utxos = self.bitcoin_node().list_unspent(&address)?;
+        if in_grace_period {
+            let mut queued_peg_outs = vec![];
+            for status in [Status::New, Status::Pending, Status::StacksBroadcast] {
+                for entry in self.peg_queue().entries(status)? {
+                    if let SbtcOp::PegOutRequest(op) = entry.op {
+                        queued_peg_outs.push(op.txid.to_string());
+                    }
+                }
+            }
+            utxos.retain(|utxo| !(utxo.vout == 2 && queued_peg_outs.contains(&utxo.txid)));
+        }
+        // A handoff interrupted before the switch leaves the previous address in use
+        if !utxos.is_empty() && address != *self.fee_wallet().bitcoin().address() {
+            let fee_rate = self.estimate_fee_rate();
+            let amount: u64 = utxos.iter().map(|utxo| utxo.amount).sum();
+            match self.fee_wallet().bitcoin().sweep(utxos, fee_rate) {
+                Ok((tx, prevouts)) => {
+                    let tx = self.sign_transaction(tx, &prevouts)?;
+                    let txid = self.bitcoin_node().broadcast_transaction(&tx)?;
+                    info!(
+                        "Swept {} sats from previous peg wallet {} in {}",
+                        amount, address, txid
+                    );
+                }
+                Err(PegWalletError::BitcoinWalletError(BitcoinWalletError::InsufficientFunds)) => {
+                    debug!(
+                        "Previous peg wallet {} holds {} sats, too few to sweep",
+                        address, amount
+                    );
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        if !in_grace_period {
+            info!(
+                "Grace period of previous peg wallet {} ended at block {}",
+                address, grace_period_end
             );
+            *self.previous_wallet_mut() = None;
+            self.save_handoff_state()?;
+        }
+        Ok(())
+    }
 
-            let finalized = schnorr_proof.to_bytes();
-            let finalized_b58 = base58::encode_slice(&finalized);
-            debug!("CALC SIG ({}) {}", finalized.len(), finalized_b58);
+    /// Whether any op awaits its Stacks transaction or Bitcoin fulfillment
+    fn has_ops_in_flight(&self) -> Result<bool> {
+        Ok(!self.peg_queue().stacks_broadcasts()?.is_empty()
+            || !self.peg_queue().fulfillments()?.is_empty())
+    }
 
-            tx.input[index].witness.push(finalized);
+    /// Takes ops which failed to build out of the queue and propagates any other error
+    fn handle_op_result(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+        result: Result<()>,
+    ) -> Result<()> {
+        match result {
+            // Wallet errors occur while building transactions, before anything is broadcast.
+            // Retrying the op would fail the same way, so take it out of the queue. Signers which
+            // cannot be reached may sign once they are back, so those ops are retried.
+            Err(e @ Error::PegWalletError(_)) if !e.is_transient() => {
+                warn!("Failed to process op {}: {}", txid, e);
+                self.peg_queue().fail(txid, burn_header_hash)?;
+                Ok(())
+            }
+            // Any other error leaves the op pending so it is requeued once its lease expires
+            result => result,
         }
-        //Return the signed transaction
-        Ok(tx)
     }
 
-    /// Broadcast a transaction to the stacks node, retrying if the nonce is rejected or the fee set too low until a retry limit is reached
-    fn try_broadcast_transaction<T: BuildStacksTransaction>(&mut self, op: &T) -> Result<()> {
-        // Retrieve the nonce from the stacks node using the sBTC wallet address
+    /// Returns why the peg in request must be rejected if its Bitcoin transaction does not pay
+    /// the requested amount to the current peg wallet
+    fn check_peg_in_deposit(&self, op: &stacks_node::PegInOp) -> Result<Option<String>> {
+        let txid = bitcoin::Txid::from_str(&op.txid.to_string())
+            .map_err(|_| BitcoinNodeError::InvalidTxHash)?;
+        let block_hash = BlockHash::from_str(&op.burn_header_hash.to_string())
+            .map_err(|_| BitcoinNodeError::InvalidTxHash)?;
+        let tx = self
+            .bitcoin_node()
+            .get_raw_transaction(&txid, &block_hash)?;
+
+        let script_pubkey = self.fee_wallet().bitcoin().address().script_pubkey();
+        // Deposits to the previous peg wallet are honoured until its grace period ends
+        let previous_script_pubkey = self
+            .previous_wallet()
+            .filter(|previous| op.block_height <= previous.grace_period_end)
+            .map(|previous| previous.address.script_pubkey());
+        let deposits: Vec<u64> = tx
+            .output
+            .iter()
+            .filter(|output| {
+                output.script_pubkey == script_pubkey
+                    || Some(&output.script_pubkey) == previous_script_pubkey.as_ref()
+            })
+            .map(|output| output.value)
+            .collect();
+        if deposits.is_empty() {
+            return Ok(Some(format!(
+                "deposit does not pay the peg wallet {}",
+                self.fee_wallet().bitcoin().address()
+            )));
+        }
+        if !deposits.contains(&op.amount) {
+            return Ok(Some(format!(
+                "requested {} sBTC but deposited {:?} sats",
+                op.amount, deposits
+            )));
+        }
+        Ok(None)
+    }
+
+    /// Returns why the peg out request must be rejected if its requester does not hold enough sBTC
+    /// to cover it on top of the requests already accepted this cycle
+    fn check_sbtc_balance(
+        &self,
+        op: &stacks_node::PegOutRequestOp,
+        requested: &mut HashMap<PrincipalData, u128>,
+    ) -> Result<Option<String>> {
+        let requester = self.fee_wallet().stacks().peg_out_requester(op)?;
+        let balance = self
+            .stacks_node()
+            .sbtc_balance(self.fee_wallet().stacks().address(), &requester)?;
+        let already_requested = requested.get(&requester).copied().unwrap_or_default();
+        let total_requested = already_requested + u128::from(op.amount);
+        if total_requested > balance {
+            return Ok(Some(format!(
+                "{} requested {} sBTC but holds {} ({} already requested)",
+                requester, op.amount, balance, already_requested
+            )));
+        }
+        requested.insert(requester, total_requested);
+        Ok(None)
+    }
+
+    fn peg_in(&mut self, op: stacks_node::PegInOp) -> Result<()> {
+        // Build a transaction from the peg in op and broadcast it to the node with reattempts
+        let stacks_txid = self.try_broadcast_transaction(&op)?;
+        self.peg_queue()
+            .stacks_broadcast(&op.txid, &op.burn_header_hash, &stacks_txid)?;
+        Ok(())
+    }
+
+    /// Mint the sBTC of the given peg ins with consecutive nonces, broadcasting the transactions
+    /// concurrently. Returns the outcome of each op rather than failing the whole batch.
+    fn peg_in_batch(
+        &mut self,
+        ops: Vec<stacks_node::PegInOp>,
+    ) -> Result<Vec<(stacks_node::PegInOp, Result<()>)>> {
+        let address = *self.fee_wallet().stacks().address();
+        let nonces = self.nonce_manager().next_nonces(&address, ops.len())?;
+
+        // A nonce is only reserved for a transaction which was built, to avoid leaving a gap
+        let mut next = 0;
+        let mut results = vec![];
+        let mut txs = vec![];
+        for op in ops {
+            let reserved = self
+                .fee_wallet()
+                .stacks()
+                .build_transaction(&op, nonces[next])
+                .map_err(Error::from)
+                .and_then(|tx| {
+                    self.nonce_manager()
+                        .record(&address, nonces[next], Some(&tx))?;
+                    Ok(tx)
+                });
+            match reserved {
+                Ok(tx) => {
+                    txs.push((op, nonces[next], tx));
+                    next += 1;
+                }
+                Err(e) => results.push((op, Err(e))),
+            }
+        }
+
+        let stacks_node = self.stacks_node();
+        let broadcasts: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = txs
+                .iter()
+                .map(|(_, _, tx)| scope.spawn(move || stacks_node.broadcast_transaction(tx)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().map_err(|_| Error::BroadcastThreadPanicked))
+                .collect()
+        });
+
+        for ((op, nonce, tx), broadcast) in txs.into_iter().zip(broadcasts) {
+            let result = match broadcast {
+                Ok(broadcast) => self.settle_peg_in_broadcast(&op, nonce, tx, broadcast),
+                // The transaction may have reached the node, so it keeps its nonce
+                Err(e) => Err(e),
+            };
+            results.push((op, result));
+        }
+        Ok(results)
+    }
+
+    /// Records the outcome of broadcasting a batched peg in transaction. A transaction rejected for
+    /// its fee is rebuilt with the fee the node expects and broadcast again right away. The nonce
+    /// of a transaction which did not make it to the node is released for the next one.
+    fn settle_peg_in_broadcast(
+        &mut self,
+        op: &stacks_node::PegInOp,
+        nonce: u64,
+        mut tx: StacksTransaction,
+        mut broadcast: std::result::Result<(), StacksNodeError>,
+    ) -> Result<()> {
         let address = *self.fee_wallet().stacks().address();
-        let mut nonce = self.stacks_node_mut().next_nonce(&address)?;
-        let mut nonce_retries = 0;
         let mut fee_retries = 0;
         loop {
-            // Build a transaction using the peg in op and calculated nonce
-            let tx = self.fee_wallet().stacks().build_transaction(op, nonce)?;
-
-            // Broadcast the resulting sBTC transaction to the stacks node
-            match self.stacks_node().broadcast_transaction(&tx) {
+            match broadcast {
+                Ok(()) => {
+                    let stacks_txid = tx.txid();
+                    info!("Broadcasted sBTC transaction: {}", stacks_txid);
+                    self.nonce_manager().record(&address, nonce, Some(&tx))?;
+                    self.peg_queue().stacks_broadcast(
+                        &op.txid,
+                        &op.burn_header_hash,
+                        &stacks_txid,
+                    )?;
+                    return Ok(());
+                }
                 Err(StacksNodeError::BroadcastError(BroadcastError::ConflictingNonceInMempool)) => {
-                    warn!("Transaction rejected by stacks node due to conflicting nonce in mempool. Stacks node may be falling behind!");
-                    nonce_retries += 1;
-                    if nonce_retries > MAX_NONCE_RETRIES {
-                        return Err(Error::MaxNonceRetriesExceeded);
-                    }
-                    warn!("Incrementing nonce and retrying...");
-                    nonce = self.stacks_node_mut().next_nonce(&address)?;
+                    // Another transaction holds the nonce. Skip it until the next reconciliation.
+                    self.nonce_manager().record(&address, nonce, None)?;
+                    return Err(StacksNodeError::BroadcastError(
+                        BroadcastError::ConflictingNonceInMempool,
+                    )
+                    .into());
                 }
                 Err(StacksNodeError::BroadcastError(BroadcastError::FeeTooLow(
                     expected,
                     actual,
-                ))) => {
+                ))) if fee_retries < MAX_FEE_RETRIES => {
                     fee_retries += 1;
-                    if fee_retries > MAX_FEE_RETRIES {
-                        return Err(Error::MaxFeeRetriesExceeded);
-                    }
+                    BROADCAST_RETRIES.with_label_values(&["fee"]).inc();
                     warn!(
                         "Transaction rejected by stacks node due to provided fee being too low: {}",
                         actual
                     );
                     warn!("Incrementing fee to {} and retrying...", expected);
                     self.fee_wallet_mut().stacks_mut().set_fee(expected);
+                    tx = match self.fee_wallet().stacks().build_transaction(op, nonce) {
+                        Ok(tx) => tx,
+                        Err(e) => {
+                            self.nonce_manager().release(&address, nonce)?;
+                            return Err(e.into());
+                        }
+                    };
+                    broadcast = self.stacks_node().broadcast_transaction(&tx);
                 }
-                Err(e) => return Err(e.into()),
-                Ok(_) => {
-                    info!("Broadcasted sBTC transaction: {}", tx.txid());
-                    return Ok(());
+                Err(e) => {
+                    self.nonce_manager().release(&address, nonce)?;
+                    return Err(e.into());
                 }
             }
         }
     }
-}
-
-impl<T: Coordinator> CoordinatorHelpers for T {}
-
-pub enum Command {
-    Stop,
-    Timeout,
-}
 
-pub struct StacksCoordinator {
-    frost_coordinator: FrostCoordinator,
-    local_peg_queue: SqlitePegQueue,
-    local_stacks_node: NodeClient,
-    local_bitcoin_node: LocalhostBitcoinNode,
-    pub local_fee_wallet: WrapPegWallet,
-}
+    fn peg_out(&mut self, op: stacks_node::PegOutRequestOp) -> Result<()> {
+        // Make sure the request can be fulfilled before burning the requester's sBTC.
+        // The BTC is only released once the burn succeeded on Stacks.
+        self.build_fulfillment(std::slice::from_ref(&op))?;
 
-impl StacksCoordinator {
-    pub fn run_dkg_round(&mut self) -> Result<XOnlyPublicKey> {
-        let p = self.frost_coordinator.run_distributed_key_generation()?;
-        XOnlyPublicKey::from_slice(&p.x().to_bytes())
-            .map_err(|e| Error::InvalidPublicKey(e.to_string()))
+        // Build a transaction from the peg out request op and broadcast it to the node with reattempts
+        let stacks_txid = self.try_broadcast_transaction(&op)?;
+        self.peg_queue()
+            .stacks_broadcast(&op.txid, &op.burn_header_hash, &stacks_txid)?;
+        Ok(())
     }
 
-    pub fn sign_message(&mut self, message: &str) -> Result<(Signature, SchnorrProof)> {
-        Ok(self.frost_coordinator.sign_message(message.as_bytes())?)
+    /// Burn the sBTC of all the given peg out requests, to be fulfilled by a single Bitcoin transaction.
+    /// Fails if the batch cannot be built. Once broadcasting started, returns the outcome of each op
+    /// rather than abandoning the rest of the batch.
+    fn peg_out_batch(
+        &mut self,
+        ops: &[stacks_node::PegOutRequestOp],
+    ) -> Result<Vec<(stacks_node::PegOutRequestOp, Result<()>)>> {
+        // Check every burn and build the BTC transaction before broadcasting any of them. The burn
+        // transactions are only built and signed when they are broadcast, with the correct nonce.
+        for op in ops {
+            self.fee_wallet().stacks().peg_out_requester(op)?;
+        }
+        self.build_fulfillment(ops)?;
+
+        let mut results = vec![];
+        for op in ops {
+            // Each burn is recorded as soon as it is broadcast
+            let result = self.try_broadcast_transaction(op).and_then(|stacks_txid| {
+                Ok(self.peg_queue().stacks_broadcast(
+                    &op.txid,
+                    &op.burn_header_hash,
+                    &stacks_txid,
+                )?)
+            });
+            results.push((op.clone(), result));
+        }
+        Ok(results)
     }
-}
 
-fn create_frost_coordinator_from_path(
-    signer_config_path: &str,
-    config: &Config,
-    stacks_node: &mut NodeClient,
-    stacks_wallet: &StacksWallet,
-) -> Result<FrostCoordinator> {
-    debug!("Creating frost coordinator from signer config path...");
-    let coordinator = create_coordinator_from_path(signer_config_path).map_err(|e| {
+    /// Resolve ops whose Stacks transaction was broadcast. Peg ins are acknowledged once minted,
+    /// peg outs are fulfilled once burned, ops whose transaction aborted are rejected and dropped
+    /// transactions are broadcast again.
+    fn process_stacks_broadcasts(&mut self) -> Result<()> {
+        let mut burned = vec![];
+        for (op, stacks_txid) in self.peg_queue().stacks_broadcasts()? {
+            let (txid, burn_header_hash) = (*op.txid(), *op.burn_header_hash());
+            let (stacks_txid, status) = match self.stacks_transaction_status(&stacks_txid) {
+                Ok(status) => status,
+                Err(e) => {
+                    warn!(
+                        "Unable to retrieve status of Stacks transaction {}: {}",
+                        stacks_txid, e
+                    );
+                    continue;
+                }
+            };
+            match (status, op) {
+                (TransactionStatus::Pending, _) => {
+                    debug!("Stacks transaction {} is pending", stacks_txid);
+                }
+                (TransactionStatus::Success, SbtcOp::PegIn(_)) => {
+                    info!("Peg in {} minted by {}", txid, stacks_txid);
+                    self.peg_queue().acknowledge(&txid, &burn_header_hash)?;
+                }
+                (TransactionStatus::Success, SbtcOp::PegOutRequest(op)) => {
+                    info!("Peg out request {} burned by {}", txid, stacks_txid);
+                    burned.push(op);
+                }
+                (TransactionStatus::Aborted(reason), _) => {
+                    warn!(
+                        "Stacks transaction {} for op {} aborted: {}",
+                        stacks_txid, txid, reason
+                    );
+                    self.peg_queue().reject(&txid, &burn_header_hash, &reason)?;
+                }
+                (TransactionStatus::Dropped(reason), _)
+                    if self.nonce_manager().is_outstanding(&stacks_txid)? =>
+                {
+                    debug!(
+                        "Stacks transaction {} for op {} was dropped: {}. Waiting for it to be re-submitted",
+                        stacks_txid, txid, reason
+                    );
+                }
+                (TransactionStatus::Dropped(reason), op) => {
+                    warn!(
+                        "Stacks transaction {} for op {} was dropped: {}. Broadcasting again...",
+                        stacks_txid, txid, reason
+                    );
+                    let stacks_txid = match &op {
+                        SbtcOp::PegIn(op) => self.try_broadcast_transaction(op)?,
+                        SbtcOp::PegOutRequest(op) => self.try_broadcast_transaction(op)?,
+                    };
+                    self.peg_queue()
+                        .stacks_broadcast(&txid, &burn_header_hash, &stacks_txid)?;
+                }
+            }
+        }
+
+        if burned.is_empty() {
+            return Ok(());
+        }
+        if self.batch_peg_outs() {
+            self.release_burned_peg_outs(&burned)
+        } else {
+            for op in burned {
+                self.release_burned_peg_outs(std::slice::from_ref(&op))?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Returns the status of an op's Stacks transaction along with the txid it applies to. When the
+    /// transaction was dropped after replacing a stuck one, the replaced transaction may have been
+    /// mined instead, in which case its status is returned.
+    fn stacks_transaction_status(&self, stacks_txid: &Txid) -> Result<(Txid, TransactionStatus)> {
+        let status = self.stacks_node().get_transaction_status(stacks_txid)?;
+        if !matches!(status, TransactionStatus::Dropped(_)) {
+            return Ok((*stacks_txid, status));
+        }
+        for replaced_txid in self.nonce_manager().replaced_txids(stacks_txid)? {
+            match self.stacks_node().get_transaction_status(&replaced_txid)? {
+                status @ (TransactionStatus::Success | TransactionStatus::Aborted(_)) => {
+                    debug!(
+                        "Stacks transaction {} was mined in place of its replacement {}",
+                        replaced_txid, stacks_txid
+                    );
+                    return Ok((replaced_txid, status));
+                }
+                _ => {}
+            }
+        }
+        Ok((*stacks_txid, status))
+    }
+
+    /// Broadcast a Bitcoin transaction fulfilling the given peg out requests, whose sBTC was burned
+    fn release_burned_peg_outs(&mut self, ops: &[stacks_node::PegOutRequestOp]) -> Result<()> {
+        let fulfill_tx = match self.fulfill_peg_outs(ops) {
+            Ok(fulfill_tx) => fulfill_tx,
+            Err(Error::PegWalletError(e)) => {
+                // The sBTC is gone, so the requests must not be failed. Keep retrying until the
+                // wallet can cover them.
+                error!(
+                    "Unable to fulfill {} burned peg out requests: {}. Retrying on the next poll",
+                    ops.len(),
+                    e
+                );
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        self.broadcast_fulfillment(ops, &fulfill_tx)
+    }
+
+    /// Record the fulfillment of the burned peg out requests, then broadcast it. Once recorded the
+    /// requests are never fulfilled again, and a fulfillment which did not reach the bitcoin node
+    /// is broadcast again when it is tracked.
+    fn broadcast_fulfillment(
+        &mut self,
+        ops: &[stacks_node::PegOutRequestOp],
+        fulfill_tx: &BitcoinTransaction,
+    ) -> Result<()> {
+        let broadcast_height = self.bitcoin_node().block_height()?;
+        let fulfilled: Vec<_> = ops
+            .iter()
+            .map(|op| (op.txid, op.burn_header_hash))
+            .collect();
+        self.peg_queue()
+            .bitcoin_broadcast(&fulfilled, fulfill_tx, broadcast_height)?;
+        match self.bitcoin_node().broadcast_transaction(fulfill_tx) {
+            Ok(bitcoin_txid) => info!(
+                "Broadcasted fulfilled BTC transaction for {} peg out requests: {}",
+                ops.len(),
+                bitcoin_txid
+            ),
+            Err(e) => warn!(
+                "Unable to broadcast fulfillment {} of {} peg out requests: {}. Retrying when tracked",
+                fulfill_tx.txid(),
+                ops.len(),
+                e
+            ),
+        }
+        Ok(())
+    }
+
+    fn fulfill_peg_out(&mut self, op: &stacks_node::PegOutRequestOp) -> Result<BitcoinTransaction> {
+        self.fulfill_peg_outs(std::slice::from_ref(op))
+    }
+
+    fn fulfill_peg_outs(
+        &mut self,
+        ops: &[stacks_node::PegOutRequestOp],
+    ) -> Result<BitcoinTransaction> {
+        let (tx, prevouts) = self.build_fulfillment(ops)?;
+        self.sign_transaction(tx, &prevouts)
+    }
+
+    /// Build an unsigned transaction fulfilling the given peg out requests, returning it with its prevouts
+    fn build_fulfillment(
+        &mut self,
+        ops: &[stacks_node::PegOutRequestOp],
+    ) -> Result<(BitcoinTransaction, Vec<TxOut>)> {
+        // Retreive the utxos, including the fulfillment utxos left on the previous address
+        let utxos = self.wallet_utxos()?;
+
+        // Build unsigned fulfilled peg out transaction paying the current fee rate
+        let fee_rate = self.estimate_fee_rate();
+        Ok(self
+            .fee_wallet()
+            .bitcoin()
+            .fulfill_peg_outs(ops, utxos, fee_rate)?)
+    }
+
+    /// Sign every input of the transaction with the FROST key. Inputs spending the previous peg
+    /// wallet are signed by the previous signer set.
+    fn sign_transaction(
+        &mut self,
+        mut tx: BitcoinTransaction,
+        prevouts: &[TxOut],
+    ) -> Result<BitcoinTransaction> {
+        let sighash_tx = tx.clone();
+        let mut sighash_cache = SighashCache::new(&sighash_tx);
+        // Sign the transaction
+        for index in 0..tx.input.len() {
+            let taproot_sighash = sighash_cache
+                .taproot_key_spend_signature_hash(
+                    index,
+                    &Prevouts::All(prevouts),
+                    SchnorrSighashType::Default,
+                )
+                .map_err(Error::SigningError)?;
+            let message = taproot_sighash.as_hash();
+            let (_frost_sig, schnorr_proof) = match self.previous_wallet_mut() {
+                Some(previous)
+                    if previous.address.script_pubkey() == prevouts[index].script_pubkey =>
+                {
+                    previous.frost_coordinator.sign_message(&message)?
+                }
+                _ => self.frost_coordinator_mut().sign_message(&message)?,
+            };
+
+            debug!(
+                "Signed Tx {:?} SchnorrProof ({},{})",
+                &tx, schnorr_proof.r, schnorr_proof.s
+            );
+
+            let finalized = schnorr_proof.to_bytes();
+            let finalized_b58 = base58::encode_slice(&finalized);
+            debug!("CALC SIG ({}) {}", finalized.len(), finalized_b58);
+
+            tx.input[index].witness.push(finalized);
+        }
+        //Return the signed transaction
+        Ok(tx)
+    }
+
+    /// The estimated fee rate in sat/vB bounded by the fee policy
+    fn estimate_fee_rate(&self) -> u64 {
+        let policy = self.bitcoin_fee_policy();
+        match self.bitcoin_node().estimate_fee_rate(policy.conf_target) {
+            Ok(fee_rate) => policy.clamp(fee_rate),
+            Err(e) => {
+                warn!(
+                    "Failed to estimate fee rate: {}. Falling back to {} sat/vB",
+                    e, policy.min_fee_rate
+                );
+                policy.min_fee_rate
+            }
+        }
+    }
+
+    /// Follow every broadcast fulfillment until it has enough confirmations to acknowledge its ops,
+    /// re-broadcasting fulfillments which dropped out of the mempool and bumping the fee of stuck ones
+    fn track_fulfillments(&mut self) -> Result<()> {
+        let block_height = self.bitcoin_node().block_height()?;
+        for fulfillment in self.peg_queue().fulfillments()? {
+            match self.track_fulfillment(&fulfillment, block_height) {
+                // The node may be temporarily unavailable, check again on the next poll
+                Err(Error::BitcoinNodeError(e)) => {
+                    warn!(
+                        "Unable to check status of fulfillment {}: {}",
+                        fulfillment.bitcoin_txid, e
+                    );
+                }
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    fn track_fulfillment(&mut self, fulfillment: &Fulfillment, block_height: u64) -> Result<()> {
+        let wallet_tx = match (
+            self.bitcoin_node()
+                .get_transaction(&fulfillment.bitcoin_txid),
+            &fulfillment.transaction,
+        ) {
+            (Ok(wallet_tx), _) => wallet_tx,
+            // The fulfillment was recorded but did not reach the node, e.g. the coordinator
+            // stopped before broadcasting it
+            (Err(BitcoinNodeError::UnknownTransaction(_)), Some(tx)) => {
+                return self.rebroadcast_fulfillment(fulfillment, tx);
+            }
+            (Err(e), _) => return Err(e.into()),
+        };
+        let required_confirmations = self.fulfillment_confirmations() as i64;
+        match wallet_tx.confirmations {
+            confirmations if confirmations >= required_confirmations => {
+                info!(
+                    "Fulfillment {} reached {} confirmations",
+                    fulfillment.bitcoin_txid, confirmations
+                );
+                for (txid, burn_header_hash) in &fulfillment.ops {
+                    self.peg_queue().acknowledge(txid, burn_header_hash)?;
+                }
+            }
+            confirmations if confirmations > 0 => {
+                debug!(
+                    "Fulfillment {} has {} of {} confirmations",
+                    fulfillment.bitcoin_txid, confirmations, required_confirmations
+                );
+            }
+            0 if !self
+                .bitcoin_node()
+                .is_in_mempool(&fulfillment.bitcoin_txid)? =>
+            {
+                self.rebroadcast_fulfillment(fulfillment, &wallet_tx.transaction)?;
+            }
+            0 => {
+                let bump_after_blocks = self.bitcoin_fee_policy().bump_after_blocks;
+                if block_height >= fulfillment.broadcast_height + bump_after_blocks {
+                    self.bump_stuck_fulfillment(fulfillment, &wallet_tx.transaction, block_height)?;
+                }
+            }
+            _ => self.resolve_conflicted_fulfillment(fulfillment, &wallet_tx)?,
+        }
+        Ok(())
+    }
+
+    /// Re-broadcast a fulfillment which is neither confirmed nor in the mempool,
+    /// escalating to the operator if the node does not accept it
+    fn rebroadcast_fulfillment(
+        &mut self,
+        fulfillment: &Fulfillment,
+        tx: &BitcoinTransaction,
+    ) -> Result<()> {
+        match self.bitcoin_node().broadcast_transaction(tx) {
+            Ok(_) => {
+                warn!(
+                    "Re-broadcasted fulfillment {} which was not in the mempool",
+                    fulfillment.bitcoin_txid
+                );
+                Ok(())
+            }
+            Err(e) => {
+                error!(
+                    "Fulfillment {} is not in the mempool and was rejected on re-broadcast: {}. Flagging its ops for operator attention",
+                    fulfillment.bitcoin_txid, e
+                );
+                self.flag_fulfillment(fulfillment)
+            }
+        }
+    }
+
+    /// A fulfillment conflicts with a confirmed transaction. If that transaction is an earlier
+    /// version of the fulfillment the replacement lost the race and the earlier version is tracked
+    /// instead. Otherwise the fulfillment's inputs were spent elsewhere and the operator is alerted.
+    fn resolve_conflicted_fulfillment(
+        &mut self,
+        fulfillment: &Fulfillment,
+        wallet_tx: &WalletTransaction,
+    ) -> Result<()> {
+        let change_script = self.fee_wallet().bitcoin().address().script_pubkey();
+        let payments = |tx: &BitcoinTransaction| -> Vec<TxOut> {
+            tx.output
+                .iter()
+                .filter(|output| output.script_pubkey != change_script)
+                .cloned()
+                .collect()
+        };
+        for conflict_txid in &wallet_tx.wallet_conflicts {
+            let conflict = self.bitcoin_node().get_transaction(conflict_txid)?;
+            if conflict.confirmations > 0
+                && payments(&conflict.transaction) == payments(&wallet_tx.transaction)
+            {
+                info!(
+                    "Fulfillment {} was confirmed as {}",
+                    fulfillment.bitcoin_txid, conflict_txid
+                );
+                self.peg_queue().replace_fulfillment(
+                    &fulfillment.bitcoin_txid,
+                    conflict_txid,
+                    fulfillment.broadcast_height,
+                )?;
+                return Ok(());
+            }
+        }
+        error!(
+            "Fulfillment {} conflicts with a confirmed transaction. Flagging its ops for operator attention",
+            fulfillment.bitcoin_txid
+        );
+        self.flag_fulfillment(fulfillment)
+    }
+
+    fn flag_fulfillment(&self, fulfillment: &Fulfillment) -> Result<()> {
+        for (txid, burn_header_hash) in &fulfillment.ops {
+            self.peg_queue().flag(txid, burn_header_hash)?;
+        }
+        Ok(())
+    }
+
+    /// Replace a fulfillment which stayed unconfirmed for too long with one paying a higher fee rate
+    fn bump_stuck_fulfillment(
+        &mut self,
+        fulfillment: &Fulfillment,
+        tx: &BitcoinTransaction,
+        block_height: u64,
+    ) -> Result<()> {
+        match self.bump_fulfillment(tx) {
+            Ok(Some(replacement_txid)) => {
+                info!(
+                    "Replaced fulfillment {} with {} after {} blocks unconfirmed",
+                    fulfillment.bitcoin_txid,
+                    replacement_txid,
+                    block_height - fulfillment.broadcast_height
+                );
+                FEE_BUMPS.with_label_values(&["bitcoin"]).inc();
+                self.peg_queue().replace_fulfillment(
+                    &fulfillment.bitcoin_txid,
+                    &replacement_txid,
+                    block_height,
+                )?;
+                Ok(())
+            }
+            Ok(None) => Ok(()),
+            // A fulfillment that cannot be bumped stays in the mempool as is
+            Err(Error::PegWalletError(e)) => {
+                warn!(
+                    "Unable to bump fee of fulfillment {}: {}",
+                    fulfillment.bitcoin_txid, e
+                );
+                Ok(())
+            }
+            Err(Error::BitcoinNodeError(e)) => {
+                warn!(
+                    "Unable to broadcast replacement of fulfillment {}: {}",
+                    fulfillment.bitcoin_txid, e
+                );
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Build, sign and broadcast a replacement of the fulfillment paying a higher fee rate.
+    /// Returns None if the fee policy does not allow a higher fee rate.
+    fn bump_fulfillment(&mut self, tx: &BitcoinTransaction) -> Result<Option<bitcoin::Txid>> {
+        let prevouts = tx
+            .input
+            .iter()
+            .map(|input| {
+                let outpoint = input.previous_output;
+                self.bitcoin_node()
+                    .get_transaction(&outpoint.txid)?
+                    .transaction
+                    .output
+                    .get(outpoint.vout as usize)
+                    .cloned()
+                    .ok_or(Error::MissingPrevout(outpoint))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let total_in: u64 = prevouts.iter().map(|prevout| prevout.value).sum();
+        let total_out: u64 = tx.output.iter().map(|output| output.value).sum();
+        let current_fee_rate = total_in.saturating_sub(total_out) / fulfillment_vsize(tx);
+
+        let Some(fee_rate) = self
+            .bitcoin_fee_policy()
+            .bumped_fee_rate(current_fee_rate, self.estimate_fee_rate())
+        else {
+            warn!(
+                "Fulfillment {} already pays the maximum fee rate of {} sat/vB",
+                tx.txid(),
+                current_fee_rate
+            );
+            return Ok(None);
+        };
+        debug!(
+            "Bumping fee rate of fulfillment {} from {} to {} sat/vB",
+            tx.txid(),
+            current_fee_rate,
+            fee_rate
+        );
+        let utxos = self.wallet_utxos()?;
+        let (replacement, prevouts) = self
+            .fee_wallet()
+            .bitcoin()
+            .bump_fee(tx, &prevouts, utxos, fee_rate)?;
+        let replacement = self.sign_transaction(replacement, &prevouts)?;
+        Ok(Some(
+            self.bitcoin_node().broadcast_transaction(&replacement)?,
+        ))
+    }
+
+    /// Reconcile the locally assigned nonces with the stacks node. Outstanding transactions dropped
+    /// from the mempool are re-submitted. Those which cannot be release their nonce, so the gap
+    /// is filled by the next transaction. Those pending for longer than the bump timeout are
+    /// replaced with a higher fee.
+    fn reconcile_nonces(&mut self) -> Result<()> {
+        let address = *self.fee_wallet().stacks().address();
+        let account_nonce = self.stacks_node().account_nonce(&address)?;
+        let outstanding = self.nonce_manager().reconcile(&address, account_nonce)?;
+
+        let now = now();
+        let mut expected_nonce = account_nonce;
+        for OutstandingTransaction {
+            transaction: tx,
+            broadcast_at,
+        } in outstanding
+        {
+            let (txid, nonce) = (tx.txid(), tx.get_origin_nonce());
+            if nonce > expected_nonce {
+                warn!(
+                    "Nonces {} to {} of {} are unassigned. Filling them with the next transactions",
+                    expected_nonce,
+                    nonce - 1,
+                    address
+                );
+            }
+            expected_nonce = nonce + 1;
+
+            let reason = match self.stacks_node().get_transaction_status(&txid) {
+                Ok(TransactionStatus::Dropped(reason)) => reason,
+                Ok(TransactionStatus::Pending)
+                    if now.saturating_sub(broadcast_at)
+                        >= self.stacks_fee_policy().bump_timeout =>
+                {
+                    self.bump_stacks_transaction(&address, &tx)?;
+                    continue;
+                }
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!(
+                        "Unable to retrieve status of Stacks transaction {}: {}",
+                        txid, e
+                    );
+                    continue;
+                }
+            };
+            warn!(
+                "Stacks transaction {} with nonce {} was dropped: {}. Re-submitting...",
+                txid, nonce, reason
+            );
+            if let Err(e) = self.stacks_node().broadcast_transaction(&tx) {
+                warn!(
+                    "Failed to re-submit Stacks transaction {}: {}. Releasing nonce {}",
+                    txid, e, nonce
+                );
+                self.nonce_manager().release(&address, nonce)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace a Stacks transaction stuck in the mempool with one paying a higher fee
+    fn bump_stacks_transaction(
+        &mut self,
+        address: &StacksAddress,
+        tx: &StacksTransaction,
+    ) -> Result<()> {
+        let (txid, nonce, fee) = (tx.txid(), tx.get_origin_nonce(), tx.get_tx_fee());
+        let estimated_fee = self.fee_wallet().stacks().estimate_fee(tx);
+        let Some(bumped_fee) = self.stacks_fee_policy().bumped_fee(fee, estimated_fee) else {
+            warn!(
+                "Stacks transaction {} is stuck but already pays the maximum fee of {}",
+                txid, fee
+            );
+            return Ok(());
+        };
+        let replacement = self.fee_wallet().stacks().bump_fee(tx, bumped_fee)?;
+        let replacement_txid = replacement.txid();
+        if let Err(e) = self.stacks_node().broadcast_transaction(&replacement) {
+            warn!("Failed to replace stuck Stacks transaction {}: {}", txid, e);
+            return Ok(());
+        }
+        info!(
+            "Replaced stuck Stacks transaction {} paying {} with {} paying {}",
+            txid, fee, replacement_txid, bumped_fee
+        );
+        FEE_BUMPS.with_label_values(&["stacks"]).inc();
+        self.nonce_manager()
+            .record_replacement(&txid, &replacement_txid)?;
+        self.nonce_manager()
+            .record(address, nonce, Some(&replacement))?;
+        self.peg_queue()
+            .replace_stacks_transaction(&txid, &replacement_txid)?;
+        Ok(())
+    }
+
+    /// Broadcast a transaction to the stacks node, retrying if the nonce is rejected or the fee set too low until a retry limit is reached.
+    /// Returns the txid of the broadcasted transaction.
+    fn try_broadcast_transaction<T: BuildStacksTransaction>(&mut self, op: &T) -> Result<Txid> {
+        // Assign the nonce locally, as the stacks node does not account for transactions in its mempool
+        let address = *self.fee_wallet().stacks().address();
+        let mut nonce = self.nonce_manager().next_nonce(&address)?;
+        let mut nonce_retries = 0;
+        let mut fee_retries = 0;
+        loop {
+            // Build a transaction using the peg in op and calculated nonce
+            let tx = self.fee_wallet().stacks().build_transaction(op, nonce)?;
+
+            // Broadcast the resulting sBTC transaction to the stacks node
+            match self.stacks_node().broadcast_transaction(&tx) {
+                Err(StacksNodeError::BroadcastError(BroadcastError::ConflictingNonceInMempool)) => {
+                    warn!("Transaction rejected by stacks node due to conflicting nonce in mempool. Stacks node may be falling behind!");
+                    nonce_retries += 1;
+                    BROADCAST_RETRIES.with_label_values(&["nonce"]).inc();
+                    if nonce_retries > MAX_NONCE_RETRIES {
+                        return Err(Error::MaxNonceRetriesExceeded);
+                    }
+                    warn!("Incrementing nonce and retrying...");
+                    // Another transaction holds the nonce. Skip it until the next reconciliation.
+                    self.nonce_manager().record(&address, nonce, None)?;
+                    nonce = self.nonce_manager().next_nonce(&address)?;
+                }
+                Err(StacksNodeError::BroadcastError(BroadcastError::FeeTooLow(
+                    expected,
+                    actual,
+                ))) => {
+                    fee_retries += 1;
+                    BROADCAST_RETRIES.with_label_values(&["fee"]).inc();
+                    if fee_retries > MAX_FEE_RETRIES {
+                        return Err(Error::MaxFeeRetriesExceeded);
+                    }
+                    warn!(
+                        "Transaction rejected by stacks node due to provided fee being too low: {}",
+                        actual
+                    );
+                    warn!("Incrementing fee to {} and retrying...", expected);
+                    self.fee_wallet_mut().stacks_mut().set_fee(expected);
+                }
+                Err(e) => return Err(e.into()),
+                Ok(_) => {
+                    let txid = tx.txid();
+                    info!("Broadcasted sBTC transaction: {}", txid);
+                    self.nonce_manager().record(&address, nonce, Some(&tx))?;
+                    return Ok(txid);
+                }
+            }
+        }
+    }
+}
+
+impl<T: Coordinator> CoordinatorHelpers for T {}
+
+/// Commands controlling a running coordinator
+#[derive(Debug)]
+pub enum Command {
+    /// Stop after the current step
+    Stop,
+    /// No command arrived within the polling interval
+    Timeout,
+    /// Stop processing after the current step until resumed
+    Pause,
+    /// Resume processing after a pause
+    Resume,
+    /// Stop taking on new ops, then stop once the in-flight ones are resolved
+    Drain,
+    /// Serve a request of the admin API, sending the response back
+    Admin(AdminRequest, Sender<AdminResponse>),
+}
+
+/// State of the run loop, driven by commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState {
+    Running,
+    Paused,
+    Draining,
+    Stopped,
+}
+
+impl RunState {
+    /// The state after receiving the command
+    pub fn apply(self, command: Command) -> Self {
+        match (self, command) {
+            (Self::Stopped, _) | (_, Command::Stop) => Self::Stopped,
+            (_, Command::Drain) => Self::Draining,
+            (Self::Running, Command::Pause) => Self::Paused,
+            (Self::Paused, Command::Resume) => Self::Running,
+            (state, _) => state,
+        }
+    }
+}
+
+/// Apply the commands received so far without blocking, serving admin requests with `serve`
+fn receive_commands(
+    mut state: RunState,
+    commands: &Receiver<Command>,
+    mut serve: impl FnMut(AdminRequest) -> AdminResponse,
+) -> Result<RunState> {
+    loop {
+        match commands.try_recv() {
+            Ok(command) => state = handle_command(state, command, &mut serve),
+            Err(TryRecvError::Empty) => return Ok(state),
+            Err(TryRecvError::Disconnected) => return Err(RecvError.into()),
+        }
+    }
+}
+
+/// Wait up to `timeout` for a command other than an admin request, serving admin requests
+/// with `serve` in the meantime
+fn wait_for_command(
+    mut state: RunState,
+    commands: &Receiver<Command>,
+    timeout: Duration,
+    mut serve: impl FnMut(AdminRequest) -> AdminResponse,
+) -> Result<RunState> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let command =
+            match commands.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(command) => command,
+                Err(RecvTimeoutError::Timeout) => return Ok(state.apply(Command::Timeout)),
+                Err(RecvTimeoutError::Disconnected) => return Err(RecvError.into()),
+            };
+        let is_admin_request = matches!(command, Command::Admin(..));
+        state = handle_command(state, command, &mut serve);
+        if !is_admin_request {
+            return Ok(state);
+        }
+    }
+}
+
+fn handle_command(
+    state: RunState,
+    command: Command,
+    serve: &mut impl FnMut(AdminRequest) -> AdminResponse,
+) -> RunState {
+    match command {
+        Command::Admin(request, reply) => {
+            info!("Serving admin request {:?}", request);
+            // The requester may have given up waiting
+            let _ = reply.send(serve(request));
+            state
+        }
+        command => {
+            info!("Received command {:?}", command);
+            state.apply(command)
+        }
+    }
+}
+
+/// Steps of a polling cycle, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Poll,
+    ReconcileNonces,
+    ProcessQueue,
+    ProcessStacksBroadcasts,
+    TrackFulfillments,
+    RotateSigners,
+    SweepPreviousWallet,
+}
+
+impl Step {
+    const ALL: [Step; 7] = [
+        Step::Poll,
+        Step::ReconcileNonces,
+        Step::ProcessQueue,
+        Step::ProcessStacksBroadcasts,
+        Step::TrackFulfillments,
+        Step::RotateSigners,
+        Step::SweepPreviousWallet,
+    ];
+
+    /// Whether the step takes on new ops, which a draining coordinator skips
+    fn takes_new_ops(self) -> bool {
+        matches!(self, Step::Poll | Step::ProcessQueue)
+    }
+}
+
+pub struct StacksCoordinator {
+    frost_coordinator: FrostCoordinator,
+    previous_wallet: Option<PreviousWallet>,
+    signer_rotation: Option<SignerRotation>,
+    config: Config,
+    config_path: Option<PathBuf>,
+    config_environment: Option<String>,
+    polling_interval: Duration,
+    local_peg_queue: SqlitePegQueue,
+    local_stacks_node: NodeClient,
+    local_bitcoin_node: LocalhostBitcoinNode,
+    pub local_fee_wallet: WrapPegWallet,
+    batch_peg_outs: bool,
+    batch_peg_ins: bool,
+    bitcoin_fee_policy: BitcoinFeePolicy,
+    stacks_fee_policy: StacksFeePolicy,
+    fulfillment_confirmations: u64,
+    pending_lease_timeout: Duration,
+    data_directory: Option<PathBuf>,
+    local_nonce_manager: SqliteNonceManager,
+    reconciliation_interval: Option<Duration>,
+    solvency_tolerance: u64,
+    handoff_grace_period: u64,
+}
+
+impl StacksCoordinator {
+    pub fn run_dkg_round(&mut self) -> Result<XOnlyPublicKey> {
+        let p = self.frost_coordinator.run_distributed_key_generation()?;
+        XOnlyPublicKey::from_slice(&p.x().to_bytes())
+            .map_err(|e| Error::InvalidPublicKey(e.to_string()))
+    }
+
+    pub fn sign_message(&mut self, message: &str) -> Result<(Signature, SchnorrProof)> {
+        Ok(self.frost_coordinator.sign_message(message.as_bytes())?)
+    }
+
+    /// Reconcile the peg wallet balance with the sBTC supply once
+    pub fn reconcile_solvency(&self) -> Result<SolvencyReport> {
+        self.reconcile()
+    }
+
+    /// Sets the config file, and the environment whose override file is layered on top of it,
+    /// re-read by `reload_config`
+    pub fn set_config_source(&mut self, path: impl Into<PathBuf>, environment: Option<String>) {
+        self.config_path = Some(path.into());
+        self.config_environment = environment;
+    }
+
+    /// Push the reloadable fields of the config to the wallets and peg queue
+    fn apply_config(&mut self) {
+        let config = &self.config;
+        let stacks_wallet = &mut self.local_fee_wallet.stacks_wallet;
+        stacks_wallet.set_fee(config.transaction_fee);
+        stacks_wallet.set_fee_policy(config.stacks_fee_policy);
+        self.local_fee_wallet
+            .bitcoin_wallet
+            .set_coin_selector(config.coin_selection.selector());
+        self.local_peg_queue
+            .set_min_confirmations(config.min_confirmations);
+        self.polling_interval = Duration::from_secs(config.polling_interval);
+        self.pending_lease_timeout = Duration::from_secs(config.pending_lease_timeout);
+        self.batch_peg_outs = config.batch_peg_outs;
+        self.batch_peg_ins = config.batch_peg_ins;
+        self.bitcoin_fee_policy = config.bitcoin_fee_policy;
+        self.stacks_fee_policy = config.stacks_fee_policy;
+        self.fulfillment_confirmations = config.fulfillment_confirmations;
+        self.reconciliation_interval = config.reconciliation_interval.map(Duration::from_secs);
+        self.solvency_tolerance = config.solvency_tolerance;
+        self.handoff_grace_period = config.handoff_grace_period;
+    }
+}
+
+fn create_frost_coordinator_from_path(
+    signer_config_path: &str,
+    config: &Config,
+    stacks_node: &mut NodeClient,
+    stacks_wallet: &StacksWallet,
+) -> Result<FrostCoordinator> {
+    debug!("Creating frost coordinator from signer config path...");
+    let coordinator = create_coordinator_from_path(signer_config_path).map_err(|e| {
         Error::ConfigError(format!(
             "Invalid signer_config_path {:?}: {}",
             signer_config_path, e
@@ -332,14 +1985,7 @@ fn create_frost_coordinator_from_contract(
         .ok_or_else(|| Error::NoCoordinator)?;
     let public_keys = stacks_node.public_keys(&config.stacks_address)?;
     let signer_key_ids = stacks_node.signer_key_ids(&config.stacks_address)?;
-    let network_private_key = Scalar::try_from(
-        config
-            .network_private_key
-            .clone()
-            .unwrap_or(String::new())
-            .as_bytes(),
-    )
-    .map_err(|_| Error::ConfigError("Invalid network_private_key.".to_string()))?;
+    let network_private_key = parse_network_private_key(config)?;
     let http_relay_url = config.http_relay_url.clone().unwrap_or(String::new());
     create_coordinator(&SignerConfig::new(
         keys_threshold.try_into().unwrap(),
@@ -352,6 +1998,17 @@ fn create_frost_coordinator_from_contract(
     .map_err(|e| Error::ConfigError(e.to_string()))
 }
 
+fn parse_network_private_key(config: &Config) -> Result<Scalar> {
+    Scalar::try_from(
+        config
+            .network_private_key
+            .clone()
+            .unwrap_or(String::new())
+            .as_bytes(),
+    )
+    .map_err(|_| Error::ConfigError("Invalid network_private_key.".to_string()))
+}
+
 fn create_frost_coordinator(
     config: &Config,
     stacks_node: &mut NodeClient,
@@ -365,195 +2022,1558 @@ fn create_frost_coordinator(
     } else {
         create_frost_coordinator_from_contract(config, stacks_node)
     }
-}
+}
+
+fn read_dkg_public_shares(path: impl AsRef<Path>) -> Result<BTreeMap<u32, DkgPublicShare>> {
+    let dkg_public_shares_path = path.as_ref().join("dkg_public_shares.json");
+
+    serde_json::from_reader(File::open(&dkg_public_shares_path).map_err(|err| {
+        Error::ConfigError(format!(
+            "Unable to open DKG public shares file {}: {}",
+            dkg_public_shares_path.to_str().unwrap_or("Invalid path"),
+            err
+        ))
+    })?)
+    .map_err(|err| Error::ConfigError(format!("Unable to parse DKG public shares JSON: {}", err)))
+}
+
+fn write_dkg_public_shares(
+    path: impl AsRef<Path>,
+    dkg_public_shares: &BTreeMap<u32, DkgPublicShare>,
+) -> Result<()> {
+    let dkg_public_shares_path = path.as_ref().join("dkg_public_shares.json");
+
+    let dkg_public_shares_file = File::options()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&dkg_public_shares_path)
+        .map_err(|err| {
+            Error::ConfigError(format!(
+                "Unable to open DKG public shares file {}: {}",
+                dkg_public_shares_path.to_str().unwrap_or("Invalid path"),
+                err
+            ))
+        })?;
+
+    serde_json::to_writer_pretty(dkg_public_shares_file, dkg_public_shares).map_err(|err| {
+        Error::ConfigError(format!(
+            "Unable to write DKG public shares to file {}: {}",
+            dkg_public_shares_path.to_str().unwrap_or("Invalid path"),
+            err
+        ))
+    })?;
+
+    Ok(())
+}
+
+/// Handoff state persisted in the data directory
+#[derive(Default, Serialize, Deserialize)]
+struct HandoffState {
+    /// Reward cycle of the current signer set
+    reward_cycle: Option<u64>,
+    previous_wallet: Option<SavedPreviousWallet>,
+}
+
+/// The previous peg wallet, with everything needed to recreate its frost coordinator except the
+/// network private key, which is taken from the configuration
+#[derive(Serialize, Deserialize)]
+struct SavedPreviousWallet {
+    address: String,
+    aggregate_public_key: String,
+    coordinator_public_key: String,
+    keys_threshold: u32,
+    total_signers: u32,
+    total_keys: u32,
+    dkg_public_shares: BTreeMap<u32, DkgPublicShare>,
+    grace_period_end: u64,
+}
+
+impl TryFrom<&PreviousWallet> for SavedPreviousWallet {
+    type Error = Error;
+    fn try_from(previous: &PreviousWallet) -> Result<Self> {
+        let frost_coordinator = &previous.frost_coordinator;
+        let point = frost_coordinator.get_aggregate_public_key()?;
+        let xonly_pubkey = XOnlyPublicKey::from_slice(&point.x().to_bytes())
+            .map_err(|e| Error::InvalidPublicKey(e.to_string()))?;
+        Ok(Self {
+            address: previous.address.to_string(),
+            aggregate_public_key: xonly_pubkey.to_string(),
+            coordinator_public_key: base58::encode_slice(
+                &frost_coordinator.public_key().to_bytes(),
+            ),
+            keys_threshold: frost_coordinator.threshold(),
+            total_signers: frost_coordinator.total_signers(),
+            total_keys: frost_coordinator.total_keys(),
+            dkg_public_shares: frost_coordinator.get_dkg_public_shares().clone(),
+            grace_period_end: previous.grace_period_end,
+        })
+    }
+}
+
+impl SavedPreviousWallet {
+    /// Recreate the previous peg wallet. Its frost coordinator signs with whichever of
+    /// `network_private_keys` belongs to the saved coordinator public key.
+    fn restore(
+        self,
+        network_private_keys: &[Scalar],
+        http_relay_url: String,
+    ) -> Result<PreviousWallet> {
+        let coordinator_public_key =
+            parse_public_key(&self.coordinator_public_key).map_err(|e| {
+                Error::InvalidPublicKey(format!(
+                    "Invalid coordinator public key {}: {:?}",
+                    self.coordinator_public_key, e
+                ))
+            })?;
+        let network_private_key = *network_private_keys
+            .iter()
+            .find(|key| {
+                ecdsa::PublicKey::new(key)
+                    .map(|public_key| public_key.to_bytes() == coordinator_public_key.to_bytes())
+                    .unwrap_or(false)
+            })
+            .ok_or_else(|| {
+                Error::ConfigError(format!(
+                    "No network_private_key matches coordinator public key {} of previous peg wallet {}",
+                    self.coordinator_public_key, self.address
+                ))
+            })?;
+        let mut frost_coordinator = create_coordinator(&SignerConfig {
+            http_relay_url,
+            keys_threshold: self.keys_threshold,
+            network_private_key,
+            public_keys: Default::default(),
+            signer_key_ids: Default::default(),
+            coordinator_public_key,
+            total_signers: self.total_signers,
+            total_keys: self.total_keys,
+            metrics_address: None,
+        })
+        .map_err(|e| Error::ConfigError(e.to_string()))?;
+
+        let xonly_pubkey = XOnlyPublicKey::from_str(&self.aggregate_public_key)
+            .map_err(|e| Error::InvalidPublicKey(e.to_string()))?;
+        frost_coordinator.set_dkg_public_shares(self.dkg_public_shares);
+        frost_coordinator.set_aggregate_public_key(
+            Point::lift_x(&Element::from(xonly_pubkey.serialize()))
+                .map_err(|e| Error::PointError(format!("{:?}", e)))?,
+        );
+        let address = bitcoin::Address::from_str(&self.address).map_err(|e| {
+            Error::ConfigError(format!(
+                "Invalid previous peg wallet address {}: {}",
+                self.address, e
+            ))
+        })?;
+        Ok(PreviousWallet {
+            frost_coordinator,
+            address,
+            grace_period_end: self.grace_period_end,
+        })
+    }
+}
+
+/// The saved handoff state, or the default one if none was saved yet
+fn read_handoff_state(path: impl AsRef<Path>) -> Result<HandoffState> {
+    let handoff_path = path.as_ref().join("handoff.json");
+    if !handoff_path.exists() {
+        return Ok(HandoffState::default());
+    }
+
+    serde_json::from_reader(File::open(&handoff_path).map_err(|err| {
+        Error::ConfigError(format!(
+            "Unable to open handoff state file {}: {}",
+            handoff_path.to_str().unwrap_or("Invalid path"),
+            err
+        ))
+    })?)
+    .map_err(|err| Error::ConfigError(format!("Unable to parse handoff state JSON: {}", err)))
+}
+
+fn write_handoff_state(path: impl AsRef<Path>, handoff_state: &HandoffState) -> Result<()> {
+    let handoff_path = path.as_ref().join("handoff.json");
+
+    let handoff_file = File::options()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&handoff_path)
+        .map_err(|err| {
+            Error::ConfigError(format!(
+                "Unable to open handoff state file {}: {}",
+                handoff_path.to_str().unwrap_or("Invalid path"),
+                err
+            ))
+        })?;
+
+    serde_json::to_writer_pretty(handoff_file, handoff_state).map_err(|err| {
+        Error::ConfigError(format!(
+            "Unable to write handoff state to file {}: {}",
+            handoff_path.to_str().unwrap_or("Invalid path"),
+            err
+        ))
+    })?;
+
+    Ok(())
+}
+
+fn load_dkg_data(
+    data_directory: Option<&str>,
+    frost_coordinator: &mut FrostCoordinator,
+    stacks_node: &mut NodeClient,
+    stacks_wallet: &StacksWallet,
+    address: &StacksAddress,
+) -> Result<XOnlyPublicKey> {
+    debug!("Retrieving bitcoin wallet public key from sBTC contract...");
+    if let Some(xonly_pubkey) = stacks_node.bitcoin_wallet_public_key(address)? {
+        if let Some(data_directory) = data_directory {
+            frost_coordinator.set_dkg_public_shares(read_dkg_public_shares(data_directory)?);
+        }
+        // We have to set the frost_coordinator aggregate key
+        frost_coordinator.set_aggregate_public_key(
+            Point::lift_x(&Element::from(xonly_pubkey.serialize()))
+                .map_err(|e| Error::PointError(format!("{:?}", e)))?,
+        );
+        Ok(xonly_pubkey)
+    } else {
+        // If we don't get one stored in the contract...run the DKG round and get the resulting public key and use that
+        let point = frost_coordinator.run_distributed_key_generation()?;
+
+        if let Some(data_directory) = data_directory {
+            write_dkg_public_shares(data_directory, frost_coordinator.get_dkg_public_shares())?;
+        }
+
+        let xonly_pubkey = XOnlyPublicKey::from_slice(&point.x().to_bytes())
+            .map_err(|e| Error::InvalidPublicKey(e.to_string()))?;
+
+        // Set the bitcoin address using the sbtc contract
+        let nonce = stacks_node.next_nonce(address)?;
+        let tx =
+            stacks_wallet.build_set_bitcoin_wallet_public_key_transaction(&xonly_pubkey, nonce)?;
+        stacks_node.broadcast_transaction(&tx)?;
+        Ok(xonly_pubkey)
+    }
+}
+
+fn create_stacks_tx_signer(signer: &StacksSignerConfig) -> Result<Box<dyn StacksTxSigner>> {
+    match signer {
+        StacksSignerConfig::PrivateKey(private_key) => {
+            Ok(Box::new(PrivateKeySigner::new(*private_key)))
+        }
+        StacksSignerConfig::Keystore { path, public_key } => {
+            let signer = KeystoreSigner::unlock(path)?;
+            if signer.public_key() != *public_key {
+                return Err(
+                    StacksTxSignerError::PublicKeyMismatch(signer.public_key().to_hex()).into(),
+                );
+            }
+            Ok(Box::new(signer))
+        }
+        StacksSignerConfig::Remote { url, public_key } => {
+            Ok(Box::new(RemoteSigner::connect(url.clone(), *public_key)?))
+        }
+        StacksSignerConfig::Multisig { .. } => Err(Error::ConfigError(
+            "A multisig signer cannot sign for another multisig".to_string(),
+        )),
+    }
+}
+
+impl TryFrom<&Config> for StacksCoordinator {
+    type Error = Error;
+    fn try_from(config: &Config) -> Result<Self> {
+        info!("Initializing stacks coordinator...");
+        let mut local_stacks_node = NodeClient::new(
+            config.stacks_node_rpc_url.clone(),
+            config.contract_name.clone(),
+            config.contract_address,
+        );
+        match &config.stacks_api_url {
+            Some(api_url) => local_stacks_node.set_api_url(api_url.clone()),
+            None => warn!(
+                "No stacks_api_url configured. Querying transaction statuses from the Stacks node"
+            ),
+        }
+
+        let mut stacks_wallet = match &config.stacks_signer {
+            StacksSignerConfig::Multisig {
+                hash_mode,
+                signatures_required,
+                signers,
+            } => {
+                let signers = signers
+                    .iter()
+                    .map(create_stacks_tx_signer)
+                    .collect::<Result<Vec<_>>>()?;
+                StacksWallet::new_multisig(
+                    config.contract_name.clone(),
+                    config.contract_address,
+                    MultisigSigner::new(
+                        hash_mode.multisig_hash_mode(),
+                        *signatures_required,
+                        signers,
+                    )?,
+                    config.stacks_address,
+                    config.stacks_version,
+                    config.transaction_fee,
+                )
+            }
+            signer => StacksWallet::new(
+                config.contract_name.clone(),
+                config.contract_address,
+                create_stacks_tx_signer(signer)?,
+                config.stacks_address,
+                config.stacks_version,
+                config.transaction_fee,
+            ),
+        };
+        if config.stacks_fee_estimation {
+            stacks_wallet.set_fee_estimator(
+                Box::new(NodeClient::new(
+                    config.stacks_node_rpc_url.clone(),
+                    config.contract_name.clone(),
+                    config.contract_address,
+                )),
+                config.stacks_fee_policy,
+            );
+        }
+
+        let mut frost_coordinator =
+            create_frost_coordinator(config, &mut local_stacks_node, &stacks_wallet)?;
+
+        // Load the public key from either the frost_coordinator or the sBTC contract
+        let xonly_pubkey = load_dkg_data(
+            config.data_directory.as_deref(),
+            &mut frost_coordinator,
+            &mut local_stacks_node,
+            &stacks_wallet,
+            &config.stacks_address,
+        )?;
+        let mut bitcoin_wallet = BitcoinWallet::new(xonly_pubkey, config.bitcoin_network);
+        bitcoin_wallet.set_coin_selector(config.coin_selection.selector());
+
+        // Load the bitcoin wallet
+        let local_bitcoin_node = LocalhostBitcoinNode::new(config.bitcoin_node_rpc_url.clone());
+        local_bitcoin_node.load_wallet(bitcoin_wallet.address())?;
+
+        // If a user has not specified a start block height, begin from the current burn block height by default
+        let start_block_height = config.start_block_height;
+        let current_block_height = local_stacks_node.burn_block_height()?;
+        let local_peg_queue = if let Some(path) = &config.data_directory {
+            let db_path = PathBuf::from(path).join("peg_queue.sqlite");
+            SqlitePegQueue::new(
+                db_path,
+                start_block_height,
+                current_block_height,
+                config.min_confirmations,
+            )
+        } else {
+            SqlitePegQueue::in_memory(
+                start_block_height,
+                current_block_height,
+                config.min_confirmations,
+            )
+        }?;
+
+        let local_nonce_manager = if let Some(path) = &config.data_directory {
+            SqliteNonceManager::new(PathBuf::from(path).join("nonces.sqlite"))
+        } else {
+            SqliteNonceManager::in_memory()
+        }?;
+
+        // Signer rotation resumes from the reward cycle saved by a previous run
+        let handoff_state = match &config.data_directory {
+            Some(path) => read_handoff_state(path)?,
+            None => HandoffState::default(),
+        };
+        let signer_rotation = match &config.stacking_pool_contract {
+            Some(stacking_pool) => Some(SignerRotation {
+                stacking_pool: stacking_pool.clone(),
+                network_private_key: parse_network_private_key(config)?,
+                http_relay_url: config.http_relay_url.clone().unwrap_or_default(),
+                reward_cycle: handoff_state.reward_cycle,
+            }),
+            None => None,
+        };
+
+        // A handoff interrupted by a restart goes on with the previous peg wallet
+        let previous_wallet = match handoff_state.previous_wallet {
+            Some(saved) => {
+                let mut network_private_keys = vec![];
+                let http_relay_url = match &config.signer_config_path {
+                    Some(path) => {
+                        let signer_config = SignerConfig::from_path(path).map_err(|e| {
+                            Error::ConfigError(format!(
+                                "Invalid signer_config_path {:?}: {}",
+                                path, e
+                            ))
+                        })?;
+                        network_private_keys.push(signer_config.network_private_key);
+                        signer_config.http_relay_url
+                    }
+                    None => config.http_relay_url.clone().unwrap_or_default(),
+                };
+                if config.network_private_key.is_some() {
+                    network_private_keys.push(parse_network_private_key(config)?);
+                }
+                let previous = saved.restore(&network_private_keys, http_relay_url)?;
+                info!(
+                    "Restored previous peg wallet {}. Deposits to it are accepted until block {}",
+                    previous.address, previous.grace_period_end
+                );
+                Some(previous)
+            }
+            None => None,
+        };
+
+        // Ops left pending by a previous run which died mid-processing are handed out again
+        let pending_lease_timeout = Duration::from_secs(config.pending_lease_timeout);
+        let requeued = local_peg_queue.requeue_expired(pending_lease_timeout)?;
+        if requeued > 0 {
+            info!("Requeued {} pending ops whose lease expired", requeued);
+        }
+
+        Ok(Self {
+            local_peg_queue,
+            local_stacks_node,
+            local_bitcoin_node,
+            frost_coordinator,
+            previous_wallet,
+            signer_rotation,
+            local_fee_wallet: WrapPegWallet {
+                bitcoin_wallet,
+                stacks_wallet,
+            },
+            batch_peg_outs: config.batch_peg_outs,
+            batch_peg_ins: config.batch_peg_ins,
+            bitcoin_fee_policy: config.bitcoin_fee_policy,
+            stacks_fee_policy: config.stacks_fee_policy,
+            fulfillment_confirmations: config.fulfillment_confirmations,
+            pending_lease_timeout,
+            data_directory: config.data_directory.as_ref().map(PathBuf::from),
+            local_nonce_manager,
+            reconciliation_interval: config.reconciliation_interval.map(Duration::from_secs),
+            solvency_tolerance: config.solvency_tolerance,
+            handoff_grace_period: config.handoff_grace_period,
+            config: config.clone(),
+            config_path: None,
+            config_environment: None,
+            polling_interval: Duration::from_secs(config.polling_interval),
+        })
+    }
+}
+
+impl Coordinator for StacksCoordinator {
+    type PegQueue = SqlitePegQueue;
+    type FeeWallet = WrapPegWallet;
+    type StacksNode = NodeClient;
+    type BitcoinNode = LocalhostBitcoinNode;
+    type NonceManager = SqliteNonceManager;
+
+    fn peg_queue(&self) -> &Self::PegQueue {
+        &self.local_peg_queue
+    }
+
+    fn fee_wallet_mut(&mut self) -> &mut Self::FeeWallet {
+        &mut self.local_fee_wallet
+    }
+
+    fn fee_wallet(&self) -> &Self::FeeWallet {
+        &self.local_fee_wallet
+    }
+
+    fn frost_coordinator(&self) -> &FrostCoordinator {
+        &self.frost_coordinator
+    }
+
+    fn frost_coordinator_mut(&mut self) -> &mut FrostCoordinator {
+        &mut self.frost_coordinator
+    }
+
+    fn previous_wallet(&self) -> Option<&PreviousWallet> {
+        self.previous_wallet.as_ref()
+    }
+
+    fn previous_wallet_mut(&mut self) -> &mut Option<PreviousWallet> {
+        &mut self.previous_wallet
+    }
+
+    fn signer_rotation(&self) -> Option<&SignerRotation> {
+        self.signer_rotation.as_ref()
+    }
+
+    fn signer_rotation_mut(&mut self) -> Option<&mut SignerRotation> {
+        self.signer_rotation.as_mut()
+    }
+
+    fn stacks_node(&self) -> &Self::StacksNode {
+        &self.local_stacks_node
+    }
+
+    fn stacks_node_mut(&mut self) -> &mut Self::StacksNode {
+        &mut self.local_stacks_node
+    }
+
+    fn bitcoin_node(&self) -> &Self::BitcoinNode {
+        &self.local_bitcoin_node
+    }
+
+    fn nonce_manager(&self) -> &Self::NonceManager {
+        &self.local_nonce_manager
+    }
+
+    fn batch_peg_outs(&self) -> bool {
+        self.batch_peg_outs
+    }
+
+    fn batch_peg_ins(&self) -> bool {
+        self.batch_peg_ins
+    }
+
+    fn bitcoin_fee_policy(&self) -> &BitcoinFeePolicy {
+        &self.bitcoin_fee_policy
+    }
+
+    fn stacks_fee_policy(&self) -> &StacksFeePolicy {
+        &self.stacks_fee_policy
+    }
+
+    fn fulfillment_confirmations(&self) -> u64 {
+        self.fulfillment_confirmations
+    }
+
+    fn pending_lease_timeout(&self) -> Duration {
+        self.pending_lease_timeout
+    }
+
+    fn data_directory(&self) -> Option<&Path> {
+        self.data_directory.as_deref()
+    }
+
+    fn reconciliation_interval(&self) -> Option<Duration> {
+        self.reconciliation_interval
+    }
+
+    fn solvency_tolerance(&self) -> u64 {
+        self.solvency_tolerance
+    }
+
+    fn handoff_grace_period(&self) -> u64 {
+        self.handoff_grace_period
+    }
+
+    fn polling_interval(&self) -> Duration {
+        self.polling_interval
+    }
+
+    fn reload_config(&mut self) -> Result<ConfigReload> {
+        let path = self.config_path.clone().ok_or_else(|| {
+            Error::ConfigError("The coordinator was not started from a config file".to_string())
+        })?;
+        let mut reloaded = Config::load(&path, self.config_environment.as_deref())
+            .map_err(|e| Error::ConfigError(format!("Invalid config file {:?}: {}", path, e)))?;
+        // Given on the command line rather than in the config file
+        reloaded.signer_config_path = self.config.signer_config_path.clone();
+        reloaded.start_block_height = self.config.start_block_height;
+
+        let reload = self.config.reload(reloaded);
+        self.apply_config();
+        if reload.is_empty() {
+            info!("Reloaded config file {:?}: nothing changed", path);
+        } else {
+            info!(
+                "Reloaded config file {:?}: applied {:?}",
+                path, reload.applied
+            );
+        }
+        if !reload.restart_required.is_empty() {
+            warn!(
+                "Changes to {:?} take effect after a restart",
+                reload.restart_required
+            );
+        }
+        Ok(reload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::admin::{AdminRequest, AdminResponse};
+    use crate::bitcoin_node::{
+        BitcoinTransaction, Error as BitcoinNodeError, MockBitcoinNode, WalletTransaction, UTXO,
+    };
+    use crate::bitcoin_wallet::{BitcoinFeePolicy, BitcoinWallet};
+    use crate::config::{Config, ConfigReload, RawConfig};
+    use crate::coordinator::{
+        read_handoff_state, receive_commands, wait_for_command, Command, Coordinator,
+        CoordinatorHelpers, Error, FrostCoordinator, PreviousWallet, RunState, SignerRotation,
+        StacksCoordinator,
+    };
+    use crate::nonce_manager::{NonceManager, SqliteNonceManager};
+    use crate::peg_queue::{
+        Error as PegQueueError, PegQueue, SqlitePegQueue, SqlitePegQueueError, Status,
+    };
+    use crate::peg_wallet::{
+        BitcoinWallet as _, Error as PegWalletError, PegWallet, StacksWallet as _, WrapPegWallet,
+    };
+    use crate::stacks_node::client::BroadcastError;
+    use crate::stacks_node::{
+        Error as StacksNodeError, MockStacksNode, PegInOp, PegOutRequestOp, SignerSet,
+        TransactionStatus,
+    };
+    use crate::stacks_tx_signer::{
+        Error as StacksTxSignerError, MockRemoteSigner, PrivateKeySigner, RemoteSigner,
+    };
+    use crate::stacks_wallet::{Error as StacksWalletError, StacksFeePolicy, StacksWallet};
+    use crate::util::test::{build_peg_out_request_op, PRIVATE_KEY_HEX, PUBLIC_KEY_HEX};
+    use bitcoin::consensus::Encodable;
+    use bitcoin::hashes::Hash;
+    use bitcoin::util::schnorr::TweakedPublicKey;
+    use bitcoin::{BlockHash, TxIn, TxOut, XOnlyPublicKey};
+    use blockstack_lib::address::AddressHashMode;
+    use blockstack_lib::burnchains::Txid;
+    use blockstack_lib::chainstate::stacks::address::{PoxAddress, PoxAddressType20};
+    use blockstack_lib::chainstate::stacks::TransactionVersion;
+    use blockstack_lib::types::chainstate::{
+        BurnchainHeaderHash, StacksAddress, StacksPrivateKey, StacksPublicKey,
+    };
+    use blockstack_lib::vm::ContractName;
+    use frost_signer::config::{Config as SignerConfig, PublicKeys, SignerKeyIds};
+    use frost_signer::net::{HttpNet, HttpNetListen};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::net::{SocketAddr, TcpListener};
+    use std::path::{Path, PathBuf};
+    use std::str::FromStr;
+    use std::thread;
+    use std::time::Duration;
+    use tempdir::TempDir;
+    use url::Url;
+    use wsts::{ecdsa, field::Element, Point, Scalar};
+
+    /// Coordinator driving mocked Stacks and Bitcoin nodes
+    struct TestCoordinator {
+        peg_queue: SqlitePegQueue,
+        fee_wallet: WrapPegWallet,
+        frost_coordinator: FrostCoordinator,
+        previous_wallet: Option<PreviousWallet>,
+        signer_rotation: Option<SignerRotation>,
+        stacks_node: MockStacksNode,
+        bitcoin_node: MockBitcoinNode,
+        nonce_manager: SqliteNonceManager,
+        batch_peg_outs: bool,
+        batch_peg_ins: bool,
+        bitcoin_fee_policy: BitcoinFeePolicy,
+        stacks_fee_policy: StacksFeePolicy,
+        data_directory: Option<PathBuf>,
+    }
+
+    impl TestCoordinator {
+        fn new(stacks_node: MockStacksNode, bitcoin_node: MockBitcoinNode) -> Self {
+            let stacks_private_key = StacksPrivateKey::from_hex(PRIVATE_KEY_HEX).unwrap();
+            let stacks_address = StacksAddress::from_public_keys(
+                blockstack_lib::address::C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+                &AddressHashMode::SerializeP2PKH,
+                1,
+                &vec![StacksPublicKey::from_private(&stacks_private_key)],
+            )
+            .unwrap();
+            let stacks_wallet = StacksWallet::new(
+                ContractName::from("sbtc-alpha"),
+                StacksAddress::from_string("SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE").unwrap(),
+                Box::new(PrivateKeySigner::new(stacks_private_key)),
+                stacks_address,
+                TransactionVersion::Testnet,
+                10,
+            );
+            let bitcoin_wallet = BitcoinWallet::new(
+                XOnlyPublicKey::from_str(PUBLIC_KEY_HEX).unwrap(),
+                bitcoin::Network::Testnet,
+            );
+            Self {
+                peg_queue: SqlitePegQueue::in_memory(Some(1), 1, 1).unwrap(),
+                fee_wallet: WrapPegWallet {
+                    bitcoin_wallet,
+                    stacks_wallet,
+                },
+                frost_coordinator: test_frost_coordinator(),
+                previous_wallet: None,
+                signer_rotation: None,
+                stacks_node,
+                bitcoin_node,
+                nonce_manager: SqliteNonceManager::in_memory().unwrap(),
+                batch_peg_outs: false,
+                batch_peg_ins: false,
+                bitcoin_fee_policy: BitcoinFeePolicy {
+                    conf_target: 6,
+                    min_fee_rate: 1,
+                    max_fee_rate: 100,
+                    bump_after_blocks: 6,
+                },
+                stacks_fee_policy: StacksFeePolicy {
+                    min_fee: 10,
+                    max_fee: 1000,
+                    bump_timeout: 600,
+                },
+                data_directory: None,
+            }
+        }
+
+        /// Polls the given ops at burn block height 1, leaving them `New`
+        fn poll_ops(&self, peg_ins: Vec<PegInOp>, peg_outs: Vec<PegOutRequestOp>) {
+            let mut stacks_node = MockStacksNode::new();
+            stacks_node.expect_burn_block_height().returning(|| Ok(1));
+            stacks_node
+                .expect_burn_header_hash()
+                .returning(|_| Ok(BurnchainHeaderHash([0; 32])));
+            stacks_node
+                .expect_get_peg_in_ops()
+                .returning(move |_| Ok(peg_ins.clone()));
+            stacks_node
+                .expect_get_peg_out_request_ops()
+                .returning(move |_| Ok(peg_outs.clone()));
+            self.peg_queue.poll(&stacks_node).unwrap();
+        }
+
+        /// Polls the given ops at burn block height 1 and hands them out, leaving them `Pending`
+        fn queue_ops(&self, peg_ins: Vec<PegInOp>, peg_outs: Vec<PegOutRequestOp>) {
+            self.poll_ops(peg_ins, peg_outs);
+            while self.peg_queue.sbtc_op().unwrap().is_some() {}
+        }
+
+        fn status(&self, txid: &Txid) -> Status {
+            self.peg_queue.entries_by_txid(txid).unwrap()[0].status
+        }
+
+        /// Moves a pending peg out request through its burn to the broadcast of its fulfillment
+        fn record_fulfillment(&self, op: &PegOutRequestOp, transaction: &BitcoinTransaction) {
+            self.peg_queue
+                .stacks_broadcast(&op.txid, &op.burn_header_hash, &Txid([0xee; 32]))
+                .unwrap();
+            self.peg_queue
+                .bitcoin_broadcast(&[(op.txid, op.burn_header_hash)], transaction, 100)
+                .unwrap();
+        }
+    }
+
+    impl Coordinator for TestCoordinator {
+        type PegQueue = SqlitePegQueue;
+        type FeeWallet = WrapPegWallet;
+        type StacksNode = MockStacksNode;
+        type BitcoinNode = MockBitcoinNode;
+        type NonceManager = SqliteNonceManager;
+
+        fn peg_queue(&self) -> &Self::PegQueue {
+            &self.peg_queue
+        }
+
+        fn fee_wallet_mut(&mut self) -> &mut Self::FeeWallet {
+            &mut self.fee_wallet
+        }
+
+        fn fee_wallet(&self) -> &Self::FeeWallet {
+            &self.fee_wallet
+        }
+
+        fn frost_coordinator(&self) -> &FrostCoordinator {
+            &self.frost_coordinator
+        }
+
+        fn frost_coordinator_mut(&mut self) -> &mut FrostCoordinator {
+            &mut self.frost_coordinator
+        }
+
+        fn previous_wallet(&self) -> Option<&PreviousWallet> {
+            self.previous_wallet.as_ref()
+        }
+
+        fn previous_wallet_mut(&mut self) -> &mut Option<PreviousWallet> {
+            &mut self.previous_wallet
+        }
+
+        fn signer_rotation(&self) -> Option<&SignerRotation> {
+            self.signer_rotation.as_ref()
+        }
+
+        fn signer_rotation_mut(&mut self) -> Option<&mut SignerRotation> {
+            self.signer_rotation.as_mut()
+        }
+
+        fn stacks_node(&self) -> &Self::StacksNode {
+            &self.stacks_node
+        }
+
+        fn stacks_node_mut(&mut self) -> &mut Self::StacksNode {
+            &mut self.stacks_node
+        }
+
+        fn bitcoin_node(&self) -> &Self::BitcoinNode {
+            &self.bitcoin_node
+        }
+
+        fn nonce_manager(&self) -> &Self::NonceManager {
+            &self.nonce_manager
+        }
+
+        fn batch_peg_outs(&self) -> bool {
+            self.batch_peg_outs
+        }
+
+        fn batch_peg_ins(&self) -> bool {
+            self.batch_peg_ins
+        }
+
+        fn bitcoin_fee_policy(&self) -> &BitcoinFeePolicy {
+            &self.bitcoin_fee_policy
+        }
+
+        fn stacks_fee_policy(&self) -> &StacksFeePolicy {
+            &self.stacks_fee_policy
+        }
+
+        fn fulfillment_confirmations(&self) -> u64 {
+            1
+        }
+
+        fn pending_lease_timeout(&self) -> Duration {
+            Duration::from_secs(600)
+        }
+
+        fn data_directory(&self) -> Option<&Path> {
+            self.data_directory.as_deref()
+        }
+
+        fn reconciliation_interval(&self) -> Option<Duration> {
+            None
+        }
+
+        fn solvency_tolerance(&self) -> u64 {
+            0
+        }
+
+        fn handoff_grace_period(&self) -> u64 {
+            10
+        }
+
+        fn polling_interval(&self) -> Duration {
+            Duration::from_secs(1)
+        }
+
+        fn reload_config(&mut self) -> crate::coordinator::Result<ConfigReload> {
+            Err(Error::ConfigError("No config file".to_string()))
+        }
+    }
+
+    /// A frost coordinator which is never connected to any signer
+    fn test_frost_coordinator() -> FrostCoordinator {
+        let network_private_key = Scalar::from(1u32);
+        let config = SignerConfig::new(
+            1,
+            ecdsa::PublicKey::new(&network_private_key).unwrap(),
+            PublicKeys::default(),
+            SignerKeyIds::default(),
+            network_private_key,
+            "http://127.0.0.1:0".to_string(),
+        );
+        let net = HttpNet::new(config.http_relay_url.clone());
+        FrostCoordinator::new(1, &config, HttpNetListen::new(net, vec![])).unwrap()
+    }
+
+    /// A peg out request with a distinct txid, along with the utxo paying its fulfillment fee
+    fn peg_out_request_op(id: u8, amount: u64) -> (PegOutRequestOp, UTXO) {
+        let mut op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1000);
+        op.txid = Txid([id; 32]);
+        op.block_height = 1;
+        let utxo = UTXO {
+            txid: op.txid.to_string(),
+            vout: 2,
+            amount: op.fulfillment_fee,
+            ..Default::default()
+        };
+        (op, utxo)
+    }
+
+    /// A peg in with a distinct txid
+    fn peg_in_op(id: u8) -> PegInOp {
+        PegInOp {
+            recipient: StacksAddress::from_string("SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE")
+                .unwrap()
+                .into(),
+            peg_wallet_address: PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [0; 20]),
+            amount: 1000,
+            memo: vec![],
+            txid: Txid([id; 32]),
+            burn_header_hash: BurnchainHeaderHash([0; 32]),
+            block_height: 1,
+            vtxindex: id as u32,
+        }
+    }
+
+    fn bitcoin_txid(byte: u8) -> bitcoin::Txid {
+        bitcoin::Txid::from_str(&format!("{:02x}", byte).repeat(32)).unwrap()
+    }
+
+    /// A taproot address other than the test peg wallet's
+    fn other_address() -> bitcoin::Address {
+        bitcoin::Address::p2tr_tweaked(
+            TweakedPublicKey::dangerous_assume_tweaked(
+                XOnlyPublicKey::from_str(
+                    "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+                )
+                .unwrap(),
+            ),
+            bitcoin::Network::Testnet,
+        )
+    }
+
+    /// A Bitcoin transaction depositing `value` to the given address
+    fn deposit_transaction(address: &bitcoin::Address, value: u64) -> BitcoinTransaction {
+        BitcoinTransaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![],
+            output: vec![TxOut {
+                value,
+                script_pubkey: address.script_pubkey(),
+            }],
+        }
+    }
+
+    /// A peg in of the given deposit, identified the way the Stacks node reports burnchain ops:
+    /// with txid and burn header hash bytes in the big-endian order Bitcoin displays them in
+    fn deposit_peg_in_op(
+        deposit: &BitcoinTransaction,
+        block_hash: &BlockHash,
+        amount: u64,
+    ) -> PegInOp {
+        let mut txid = deposit.txid().into_inner();
+        txid.reverse();
+        let mut burn_header_hash = block_hash.into_inner();
+        burn_header_hash.reverse();
+        PegInOp {
+            txid: Txid(txid),
+            burn_header_hash: BurnchainHeaderHash(burn_header_hash),
+            amount,
+            ..peg_in_op(1)
+        }
+    }
+
+    /// A wallet transaction paying `value` to an empty script
+    fn wallet_transaction(
+        value: u64,
+        confirmations: i64,
+        wallet_conflicts: Vec<bitcoin::Txid>,
+    ) -> WalletTransaction {
+        WalletTransaction {
+            transaction: BitcoinTransaction {
+                version: 2,
+                lock_time: bitcoin::PackedLockTime(0),
+                input: vec![],
+                output: vec![TxOut {
+                    value,
+                    script_pubkey: Default::default(),
+                }],
+            },
+            confirmations,
+            wallet_conflicts,
+        }
+    }
+
+    /// A peg wallet utxo funding fulfillments
+    fn funding_utxo(amount: u64) -> UTXO {
+        UTXO {
+            txid: Txid([0xff; 32]).to_string(),
+            vout: 0,
+            amount,
+            ..Default::default()
+        }
+    }
+
+    /// A bitcoin node holding the given peg wallet utxos and estimating a fee rate of 1 sat/vB
+    fn bitcoin_node_with_utxos(utxos: Vec<UTXO>) -> MockBitcoinNode {
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node
+            .expect_list_unspent()
+            .returning(move |_| Ok(utxos.clone()));
+        bitcoin_node.expect_estimate_fee_rate().returning(|_| Ok(1));
+        bitcoin_node
+    }
+
+    #[test]
+    fn stacks_broadcasts_should_follow_their_transaction_status() {
+        let (minted, aborted, dropped) = (peg_in_op(1), peg_in_op(2), peg_in_op(3));
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_get_transaction_status()
+            .returning(|stacks_txid| {
+                Ok(match stacks_txid.0[0] {
+                    1 => TransactionStatus::Success,
+                    2 => TransactionStatus::Aborted("abort_by_response (err u1)".to_string()),
+                    3 => TransactionStatus::Dropped("unknown for 7 burn blocks".to_string()),
+                    _ => TransactionStatus::Pending,
+                })
+            });
+        stacks_node
+            .expect_broadcast_transaction()
+            .times(1)
+            .returning(|_| Ok(()));
+        let mut coordinator = TestCoordinator::new(stacks_node, MockBitcoinNode::new());
+        let address = *coordinator.fee_wallet().stacks().address();
+        coordinator.nonce_manager.reconcile(&address, 0).unwrap();
+        coordinator.queue_ops(
+            vec![minted.clone(), aborted.clone(), dropped.clone()],
+            vec![],
+        );
+        for op in [&minted, &aborted, &dropped] {
+            coordinator
+                .peg_queue
+                .stacks_broadcast(&op.txid, &op.burn_header_hash, &op.txid)
+                .unwrap();
+        }
+
+        coordinator.process_stacks_broadcasts().unwrap();
+
+        assert_eq!(coordinator.status(&minted.txid), Status::Confirmed);
+        assert_eq!(coordinator.status(&aborted.txid), Status::Rejected);
+        // The dropped mint is broadcast again with a nonce of its own
+        let entry = &coordinator
+            .peg_queue
+            .entries_by_txid(&dropped.txid)
+            .unwrap()[0];
+        assert_eq!(entry.status, Status::StacksBroadcast);
+        let stacks_txid = entry.stacks_txid.unwrap();
+        assert_ne!(stacks_txid, dropped.txid);
+        assert!(coordinator
+            .nonce_manager
+            .is_outstanding(&stacks_txid)
+            .unwrap());
+    }
+
+    #[test]
+    fn pending_stacks_broadcasts_should_be_left_alone() {
+        let op = peg_in_op(1);
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_get_transaction_status()
+            .returning(|_| Ok(TransactionStatus::Pending));
+        stacks_node.expect_broadcast_transaction().never();
+        let mut coordinator = TestCoordinator::new(stacks_node, MockBitcoinNode::new());
+        coordinator.queue_ops(vec![op.clone()], vec![]);
+        coordinator
+            .peg_queue
+            .stacks_broadcast(&op.txid, &op.burn_header_hash, &Txid([0xee; 32]))
+            .unwrap();
+
+        coordinator.process_stacks_broadcasts().unwrap();
+
+        assert_eq!(coordinator.status(&op.txid), Status::StacksBroadcast);
+    }
+
+    #[test]
+    fn peg_in_batch_should_retry_a_fee_too_low_right_away() {
+        let ops = vec![peg_in_op(1), peg_in_op(2)];
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_broadcast_transaction()
+            .withf(|tx| tx.get_tx_fee() < 50)
+            .times(2)
+            .returning(|_| {
+                Err(StacksNodeError::BroadcastError(BroadcastError::FeeTooLow(
+                    50, 10,
+                )))
+            });
+        stacks_node
+            .expect_broadcast_transaction()
+            .withf(|tx| tx.get_tx_fee() == 50)
+            .times(2)
+            .returning(|_| Ok(()));
+        let mut coordinator = TestCoordinator::new(stacks_node, MockBitcoinNode::new());
+        let address = *coordinator.fee_wallet().stacks().address();
+        coordinator.nonce_manager.reconcile(&address, 0).unwrap();
+        coordinator.queue_ops(ops.clone(), vec![]);
+
+        for (op, result) in coordinator.peg_in_batch(ops).unwrap() {
+            result.unwrap();
+            assert_eq!(coordinator.status(&op.txid), Status::StacksBroadcast);
+        }
+        let outstanding = coordinator.nonce_manager.reconcile(&address, 0).unwrap();
+        assert_eq!(
+            outstanding
+                .iter()
+                .map(|outstanding| (
+                    outstanding.transaction.get_origin_nonce(),
+                    outstanding.transaction.get_tx_fee()
+                ))
+                .collect::<Vec<_>>(),
+            vec![(0, 50), (1, 50)]
+        );
+    }
+
+    #[test]
+    fn peg_in_batch_should_release_the_nonce_of_a_failed_broadcast() {
+        let ops = vec![peg_in_op(1), peg_in_op(2)];
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_broadcast_transaction()
+            .withf(|tx| tx.get_origin_nonce() == 0)
+            .returning(|_| {
+                Err(StacksNodeError::BroadcastError(BroadcastError::Other(
+                    "rejected".to_string(),
+                )))
+            });
+        stacks_node
+            .expect_broadcast_transaction()
+            .withf(|tx| tx.get_origin_nonce() == 1)
+            .returning(|_| Ok(()));
+        let mut coordinator = TestCoordinator::new(stacks_node, MockBitcoinNode::new());
+        let address = *coordinator.fee_wallet().stacks().address();
+        coordinator.nonce_manager.reconcile(&address, 0).unwrap();
+        coordinator.queue_ops(ops.clone(), vec![]);
+
+        let results = coordinator.peg_in_batch(ops.clone()).unwrap();
+        assert!(results[0].1.is_err());
+        assert!(results[1].1.is_ok());
+        // The failed op waits for its lease to expire while the next transaction fills its nonce
+        assert_eq!(coordinator.status(&ops[0].txid), Status::Pending);
+        assert_eq!(coordinator.status(&ops[1].txid), Status::StacksBroadcast);
+        assert_eq!(coordinator.nonce_manager.next_nonce(&address).unwrap(), 0);
+    }
+
+    #[test]
+    fn sbtc_balance_should_cover_all_requests_of_a_cycle() {
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node.expect_sbtc_balance().returning(|_, _| Ok(1500));
+        let coordinator = TestCoordinator::new(stacks_node, MockBitcoinNode::new());
+        let mut requested = HashMap::new();
+
+        let (op, _) = peg_out_request_op(1, 1000);
+        assert_eq!(
+            coordinator.check_sbtc_balance(&op, &mut requested).unwrap(),
+            None
+        );
+        // The requester's balance does not cover a second request on top of the first
+        let (op, _) = peg_out_request_op(2, 1000);
+        let reason = coordinator
+            .check_sbtc_balance(&op, &mut requested)
+            .unwrap()
+            .unwrap();
+        assert!(reason.contains("requested 1000 sBTC but holds 1500 (1000 already requested)"));
+        // A rejected request does not count against the balance
+        let (op, _) = peg_out_request_op(3, 500);
+        assert_eq!(
+            coordinator.check_sbtc_balance(&op, &mut requested).unwrap(),
+            None
+        );
+        assert_eq!(requested.into_values().collect::<Vec<_>>(), vec![1500]);
+    }
+
+    #[test]
+    fn overdrawing_peg_out_should_be_rejected_before_signing() {
+        let (op, _) = peg_out_request_op(1, 1000);
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node.expect_sbtc_balance().returning(|_, _| Ok(999));
+        stacks_node.expect_broadcast_transaction().never();
+        // The mocked Bitcoin node fails the test if a fulfillment is attempted
+        let mut coordinator = TestCoordinator::new(stacks_node, MockBitcoinNode::new());
+        coordinator.poll_ops(vec![], vec![op.clone()]);
+
+        coordinator.process_queue().unwrap();
+
+        let entry = &coordinator.peg_queue.entries_by_txid(&op.txid).unwrap()[0];
+        assert_eq!(entry.status, Status::Rejected);
+        assert!(entry
+            .reason
+            .as_deref()
+            .unwrap()
+            .contains("requested 1000 sBTC but holds 999"));
+        assert_eq!(entry.stacks_txid, None);
+    }
 
-fn read_dkg_public_shares(path: impl AsRef<Path>) -> Result<BTreeMap<u32, DkgPublicShare>> {
-    let dkg_public_shares_path = path.as_ref().join("dkg_public_shares.json");
+    #[test]
+    fn peg_in_deposit_should_be_looked_up_in_its_burn_block() {
+        let mut coordinator = TestCoordinator::new(MockStacksNode::new(), MockBitcoinNode::new());
+        let deposit = deposit_transaction(coordinator.fee_wallet().bitcoin().address(), 1000);
+        let block_hash = BlockHash::from_inner(core::array::from_fn(|i| i as u8));
+        let (deposit_txid, returned) = (deposit.txid(), deposit.clone());
+        coordinator
+            .bitcoin_node
+            .expect_get_raw_transaction()
+            .withf(move |txid, hash| *txid == deposit_txid && *hash == block_hash)
+            .returning(move |_, _| Ok(returned.clone()));
 
-    serde_json::from_reader(File::open(&dkg_public_shares_path).map_err(|err| {
-        Error::ConfigError(format!(
-            "Unable to open DKG public shares file {}: {}",
-            dkg_public_shares_path.to_str().unwrap_or("Invalid path"),
-            err
-        ))
-    })?)
-    .map_err(|err| Error::ConfigError(format!("Unable to parse DKG public shares JSON: {}", err)))
-}
+        let op = deposit_peg_in_op(&deposit, &block_hash, 1000);
+        assert_eq!(coordinator.check_peg_in_deposit(&op).unwrap(), None);
+        let op = deposit_peg_in_op(&deposit, &block_hash, 999);
+        assert_eq!(
+            coordinator.check_peg_in_deposit(&op).unwrap(),
+            Some("requested 999 sBTC but deposited [1000] sats".to_string())
+        );
+    }
 
-fn write_dkg_public_shares(
-    path: impl AsRef<Path>,
-    dkg_public_shares: &BTreeMap<u32, DkgPublicShare>,
-) -> Result<()> {
-    let dkg_public_shares_path = path.as_ref().join("dkg_public_shares.json");
+    #[test]
+    fn deposit_to_previous_wallet_should_be_honoured_until_grace_period_end() {
+        let previous_address = other_address();
+        let deposit = deposit_transaction(&previous_address, 1000);
+        let mut bitcoin_node = MockBitcoinNode::new();
+        let returned = deposit.clone();
+        bitcoin_node
+            .expect_get_raw_transaction()
+            .returning(move |_, _| Ok(returned.clone()));
+        let mut coordinator = TestCoordinator::new(MockStacksNode::new(), bitcoin_node);
+        coordinator.previous_wallet = Some(PreviousWallet {
+            frost_coordinator: test_frost_coordinator(),
+            address: previous_address,
+            grace_period_end: 10,
+        });
 
-    let dkg_public_shares_file = File::options()
-        .create(true)
-        .write(true)
-        .open(&dkg_public_shares_path)
-        .map_err(|err| {
-            Error::ConfigError(format!(
-                "Unable to open DKG public shares file {}: {}",
-                dkg_public_shares_path.to_str().unwrap_or("Invalid path"),
-                err
-            ))
-        })?;
+        let block_hash = BlockHash::from_inner([1; 32]);
+        let mut op = deposit_peg_in_op(&deposit, &block_hash, 1000);
+        op.block_height = 10;
+        assert_eq!(coordinator.check_peg_in_deposit(&op).unwrap(), None);
+        op.block_height = 11;
+        let reason = coordinator.check_peg_in_deposit(&op).unwrap().unwrap();
+        assert!(reason.starts_with("deposit does not pay the peg wallet"));
+    }
 
-    serde_json::to_writer_pretty(dkg_public_shares_file, dkg_public_shares).map_err(|err| {
-        Error::ConfigError(format!(
-            "Unable to write DKG public shares to file {}: {}",
-            dkg_public_shares_path.to_str().unwrap_or("Invalid path"),
-            err
-        ))
-    })?;
+    #[test]
+    fn peg_in_without_deposit_should_be_rejected_before_minting() {
+        let block_hash = BlockHash::from_inner([0; 32]);
+        let deposit = deposit_transaction(&other_address(), 1000);
+        let op = deposit_peg_in_op(&deposit, &block_hash, 1000);
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node
+            .expect_get_raw_transaction()
+            .returning(move |_, _| Ok(deposit.clone()));
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node.expect_broadcast_transaction().never();
+        let mut coordinator = TestCoordinator::new(stacks_node, bitcoin_node);
+        coordinator.poll_ops(vec![op.clone()], vec![]);
 
-    Ok(())
-}
+        coordinator.process_queue().unwrap();
 
-fn load_dkg_data(
-    data_directory: Option<&str>,
-    frost_coordinator: &mut FrostCoordinator,
-    stacks_node: &mut NodeClient,
-    stacks_wallet: &StacksWallet,
-    address: &StacksAddress,
-) -> Result<XOnlyPublicKey> {
-    debug!("Retrieving bitcoin wallet public key from sBTC contract...");
-    if let Some(xonly_pubkey) = stacks_node.bitcoin_wallet_public_key(address)? {
-        if let Some(data_directory) = data_directory {
-            frost_coordinator.set_dkg_public_shares(read_dkg_public_shares(data_directory)?);
-        }
-        // We have to set the frost_coordinator aggregate key
-        frost_coordinator.set_aggregate_public_key(
-            Point::lift_x(&Element::from(xonly_pubkey.serialize()))
-                .map_err(|e| Error::PointError(format!("{:?}", e)))?,
-        );
-        Ok(xonly_pubkey)
-    } else {
-        // If we don't get one stored in the contract...run the DKG round and get the resulting public key and use that
-        let point = frost_coordinator.run_distributed_key_generation()?;
+        let entry = &coordinator.peg_queue.entries_by_txid(&op.txid).unwrap()[0];
+        assert_eq!(entry.status, Status::Rejected);
+        assert!(entry
+            .reason
+            .as_deref()
+            .unwrap()
+            .starts_with("deposit does not pay the peg wallet"));
+    }
 
-        if let Some(data_directory) = data_directory {
-            write_dkg_public_shares(data_directory, frost_coordinator.get_dkg_public_shares())?;
+    /// A previous peg wallet at `other_address` honoured until the given block
+    fn previous_wallet_until(grace_period_end: u64) -> PreviousWallet {
+        PreviousWallet {
+            frost_coordinator: test_frost_coordinator(),
+            address: other_address(),
+            grace_period_end,
         }
+    }
 
-        let xonly_pubkey = XOnlyPublicKey::from_slice(&point.x().to_bytes())
-            .map_err(|e| Error::InvalidPublicKey(e.to_string()))?;
+    #[test]
+    fn hand_off_should_wait_for_the_previous_handoff() {
+        // The mocked nodes fail the test if a DKG round or a wallet switch is attempted
+        let mut coordinator = TestCoordinator::new(MockStacksNode::new(), MockBitcoinNode::new());
+        coordinator.previous_wallet = Some(previous_wallet_until(10));
+        let address = coordinator.fee_wallet().bitcoin().address().clone();
 
-        // Set the bitcoin address using the sbtc contract
-        let nonce = stacks_node.next_nonce(address)?;
-        let tx =
-            stacks_wallet.build_set_bitcoin_wallet_public_key_transaction(&xonly_pubkey, nonce)?;
-        stacks_node.broadcast_transaction(&tx)?;
-        Ok(xonly_pubkey)
+        assert!(matches!(
+            coordinator.hand_off_wallet(test_frost_coordinator()),
+            Err(Error::HandoffInProgress(_))
+        ));
+        assert_eq!(
+            coordinator.previous_wallet.as_ref().unwrap().address,
+            other_address()
+        );
+        assert_eq!(*coordinator.fee_wallet().bitcoin().address(), address);
     }
-}
 
-impl TryFrom<&Config> for StacksCoordinator {
-    type Error = Error;
-    fn try_from(config: &Config) -> Result<Self> {
-        info!("Initializing stacks coordinator...");
-        let mut local_stacks_node = NodeClient::new(
-            config.stacks_node_rpc_url.clone(),
-            config.contract_name.clone(),
-            config.contract_address,
-        );
+    #[test]
+    fn previous_wallet_should_be_restored_after_a_restart() {
+        let dir = TempDir::new("").unwrap();
+        let mut coordinator = TestCoordinator::new(MockStacksNode::new(), MockBitcoinNode::new());
+        coordinator.data_directory = Some(dir.path().to_path_buf());
+        let xonly_pubkey = XOnlyPublicKey::from_str(PUBLIC_KEY_HEX).unwrap();
+        let aggregate_public_key = Point::lift_x(&Element::from(xonly_pubkey.serialize())).unwrap();
+        let mut previous = previous_wallet_until(110);
+        previous
+            .frost_coordinator
+            .set_aggregate_public_key(aggregate_public_key);
+        coordinator.previous_wallet = Some(previous);
+        coordinator.save_handoff_state().unwrap();
 
-        let stacks_wallet = StacksWallet::new(
-            config.contract_name.clone(),
-            config.contract_address,
-            config.stacks_private_key,
-            config.stacks_address,
-            config.stacks_version,
-            config.transaction_fee,
+        let saved = || {
+            read_handoff_state(dir.path())
+                .unwrap()
+                .previous_wallet
+                .unwrap()
+        };
+        assert!(matches!(
+            saved().restore(&[Scalar::from(2u32)], "http://127.0.0.1:0".to_string()),
+            Err(Error::ConfigError(_))
+        ));
+        let restored = saved()
+            .restore(
+                &[Scalar::from(2u32), Scalar::from(1u32)],
+                "http://127.0.0.1:0".to_string(),
+            )
+            .unwrap();
+        assert_eq!(restored.address, other_address());
+        assert_eq!(restored.grace_period_end, 110);
+        assert!(
+            restored
+                .frost_coordinator
+                .get_aggregate_public_key()
+                .unwrap()
+                == aggregate_public_key
+        );
+        assert_eq!(
+            restored.frost_coordinator.public_key().to_bytes(),
+            test_frost_coordinator().public_key().to_bytes()
         );
 
-        let mut frost_coordinator =
-            create_frost_coordinator(config, &mut local_stacks_node, &stacks_wallet)?;
+        // The end of the grace period is persisted as well
+        coordinator.previous_wallet = None;
+        coordinator.save_handoff_state().unwrap();
+        assert!(read_handoff_state(dir.path())
+            .unwrap()
+            .previous_wallet
+            .is_none());
+    }
 
-        // Load the public key from either the frost_coordinator or the sBTC contract
-        let xonly_pubkey = load_dkg_data(
-            config.data_directory.as_deref(),
-            &mut frost_coordinator,
-            &mut local_stacks_node,
-            &stacks_wallet,
-            &config.stacks_address,
-        )?;
-        let bitcoin_wallet = BitcoinWallet::new(xonly_pubkey, config.bitcoin_network);
+    #[test]
+    fn sweep_should_leave_fulfillment_utxos_of_queued_peg_outs_until_grace_period_end() {
+        let (op, fulfillment_utxo) = peg_out_request_op(1, 1000);
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node.expect_block_height().returning(|| Ok(10));
+        bitcoin_node
+            .expect_list_unspent()
+            .withf(|address| *address == other_address())
+            .returning(move |_| Ok(vec![fulfillment_utxo.clone()]));
+        bitcoin_node.expect_broadcast_transaction().never();
+        let mut coordinator = TestCoordinator::new(MockStacksNode::new(), bitcoin_node);
+        coordinator.previous_wallet = Some(previous_wallet_until(10));
+        coordinator.queue_ops(vec![], vec![op]);
 
-        // Load the bitcoin wallet
-        let local_bitcoin_node = LocalhostBitcoinNode::new(config.bitcoin_node_rpc_url.clone());
-        local_bitcoin_node.load_wallet(bitcoin_wallet.address())?;
+        coordinator.sweep_previous_wallet().unwrap();
 
-        // If a user has not specified a start block height, begin from the current burn block height by default
-        let start_block_height = config.start_block_height;
-        let current_block_height = local_stacks_node.burn_block_height()?;
-        let local_peg_queue = if let Some(path) = &config.data_directory {
-            let db_path = PathBuf::from(path).join("peg_queue.sqlite");
-            SqlitePegQueue::new(db_path, start_block_height, current_block_height)
-        } else {
-            SqlitePegQueue::in_memory(start_block_height, current_block_height)
-        }?;
+        assert!(coordinator.previous_wallet.is_some());
+    }
 
-        Ok(Self {
-            local_peg_queue,
-            local_stacks_node,
-            local_bitcoin_node,
-            frost_coordinator,
-            local_fee_wallet: WrapPegWallet {
-                bitcoin_wallet,
-                stacks_wallet,
-            },
-        })
+    #[test]
+    fn previous_wallet_should_be_dropped_after_grace_period_end() {
+        let mut bitcoin_node = bitcoin_node_with_utxos(vec![funding_utxo(100)]);
+        bitcoin_node.expect_block_height().returning(|| Ok(11));
+        bitcoin_node.expect_broadcast_transaction().never();
+        let mut coordinator = TestCoordinator::new(MockStacksNode::new(), bitcoin_node);
+        coordinator.previous_wallet = Some(previous_wallet_until(10));
+
+        // The dust left in the previous wallet does not cover the fee of a sweep
+        coordinator.sweep_previous_wallet().unwrap();
+
+        assert!(coordinator.previous_wallet.is_none());
     }
-}
 
-impl Coordinator for StacksCoordinator {
-    type PegQueue = SqlitePegQueue;
-    type FeeWallet = WrapPegWallet;
-    type StacksNode = NodeClient;
-    type BitcoinNode = LocalhostBitcoinNode;
+    /// A coordinator rotating signers through the stacking pool, at the given reward cycle
+    fn rotating_coordinator(
+        stacks_node: MockStacksNode,
+        reward_cycle: Option<u64>,
+    ) -> TestCoordinator {
+        let mut coordinator = TestCoordinator::new(stacks_node, MockBitcoinNode::new());
+        coordinator.signer_rotation = Some(SignerRotation {
+            stacking_pool: ContractName::from("sbtc-stacking-pool"),
+            network_private_key: Scalar::from(1u32),
+            http_relay_url: "http://127.0.0.1:0".to_string(),
+            reward_cycle,
+        });
+        coordinator
+    }
 
-    fn peg_queue(&self) -> &Self::PegQueue {
-        &self.local_peg_queue
+    fn reward_cycle(coordinator: &TestCoordinator) -> Option<u64> {
+        coordinator.signer_rotation.as_ref().unwrap().reward_cycle
     }
 
-    fn fee_wallet_mut(&mut self) -> &mut Self::FeeWallet {
-        &mut self.local_fee_wallet
+    #[test]
+    fn first_reward_cycle_should_be_the_current_signer_sets() {
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_current_reward_cycle()
+            .times(2)
+            .returning(|_, _| Ok(5));
+        stacks_node.expect_current_signer_set().never();
+        let mut coordinator = rotating_coordinator(stacks_node, None);
+
+        coordinator.rotate_signers().unwrap();
+        assert_eq!(reward_cycle(&coordinator), Some(5));
+        coordinator.rotate_signers().unwrap();
+        assert_eq!(reward_cycle(&coordinator), Some(5));
     }
 
-    fn fee_wallet(&self) -> &Self::FeeWallet {
-        &self.local_fee_wallet
+    #[test]
+    fn first_reward_cycle_should_be_saved() {
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_current_reward_cycle()
+            .returning(|_, _| Ok(5));
+        stacks_node.expect_current_signer_set().never();
+        let dir = TempDir::new("").unwrap();
+        let mut coordinator = rotating_coordinator(stacks_node, None);
+        coordinator.data_directory = Some(dir.path().to_path_buf());
+
+        coordinator.rotate_signers().unwrap();
+        assert_eq!(
+            read_handoff_state(dir.path()).unwrap().reward_cycle,
+            Some(5)
+        );
     }
 
-    fn frost_coordinator(&self) -> &FrostCoordinator {
-        &self.frost_coordinator
+    #[test]
+    fn signer_rotation_should_wait_for_the_previous_handoff() {
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_current_reward_cycle()
+            .returning(|_, _| Ok(6));
+        stacks_node.expect_current_signer_set().never();
+        let mut coordinator = rotating_coordinator(stacks_node, Some(5));
+        coordinator.previous_wallet = Some(previous_wallet_until(10));
+
+        coordinator.rotate_signers().unwrap();
+
+        assert_eq!(reward_cycle(&coordinator), Some(5));
     }
 
-    fn frost_coordinator_mut(&mut self) -> &mut FrostCoordinator {
-        &mut self.frost_coordinator
+    #[test]
+    fn empty_stacking_pool_should_keep_the_current_signers() {
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_current_reward_cycle()
+            .returning(|_, _| Ok(6));
+        stacks_node
+            .expect_current_signer_set()
+            .withf(|_, _, cycle| *cycle == 6)
+            .returning(|_, _, _| Ok(Some(SignerSet::new(vec![], 700))));
+        stacks_node.expect_coordinator_public_key().never();
+        let mut coordinator = rotating_coordinator(stacks_node, Some(5));
+
+        coordinator.rotate_signers().unwrap();
+
+        assert_eq!(reward_cycle(&coordinator), Some(6));
     }
 
-    fn stacks_node(&self) -> &Self::StacksNode {
-        &self.local_stacks_node
+    #[test]
+    fn signer_rotation_should_be_retried_until_it_succeeds() {
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_current_reward_cycle()
+            .returning(|_, _| Ok(6));
+        stacks_node
+            .expect_current_signer_set()
+            .withf(|_, _, cycle| *cycle == 6)
+            .returning(|_, _, _| {
+                Ok(Some(SignerSet::new(
+                    vec![ecdsa::PublicKey::new(&Scalar::from(2u32)).unwrap()],
+                    700,
+                )))
+            });
+        stacks_node
+            .expect_coordinator_public_key()
+            .returning(|_| Ok(None));
+        let mut coordinator = rotating_coordinator(stacks_node, Some(5));
+
+        assert!(matches!(
+            coordinator.rotate_signers(),
+            Err(Error::NoCoordinator)
+        ));
+        // The reward cycle only advances once its signers took over
+        assert_eq!(reward_cycle(&coordinator), Some(5));
     }
 
-    fn stacks_node_mut(&mut self) -> &mut Self::StacksNode {
-        &mut self.local_stacks_node
+    #[test]
+    fn run_state_should_follow_commands() {
+        assert_eq!(RunState::Running.apply(Command::Pause), RunState::Paused);
+        assert_eq!(RunState::Paused.apply(Command::Timeout), RunState::Paused);
+        assert_eq!(RunState::Paused.apply(Command::Resume), RunState::Running);
+        assert_eq!(RunState::Paused.apply(Command::Drain), RunState::Draining);
+        assert_eq!(
+            RunState::Draining.apply(Command::Resume),
+            RunState::Draining
+        );
+        assert_eq!(RunState::Draining.apply(Command::Stop), RunState::Stopped);
+        assert_eq!(RunState::Stopped.apply(Command::Resume), RunState::Stopped);
     }
 
-    fn bitcoin_node(&self) -> &Self::BitcoinNode {
-        &self.local_bitcoin_node
+    fn no_admin_requests(request: AdminRequest) -> AdminResponse {
+        panic!("Unexpected admin request {:?}", request);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::config::{Config, RawConfig};
-    use crate::coordinator::{CoordinatorHelpers, StacksCoordinator};
-    use crate::stacks_node::PegOutRequestOp;
-    use bitcoin::consensus::Encodable;
-    use blockstack_lib::burnchains::Txid;
-    use blockstack_lib::chainstate::stacks::address::{PoxAddress, PoxAddressType20};
-    use blockstack_lib::types::chainstate::BurnchainHeaderHash;
+    #[test]
+    fn receive_commands_should_apply_all_pending_commands() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        assert_eq!(
+            receive_commands(RunState::Running, &receiver, no_admin_requests).unwrap(),
+            RunState::Running
+        );
+
+        sender.send(Command::Pause).unwrap();
+        sender.send(Command::Resume).unwrap();
+        sender.send(Command::Drain).unwrap();
+        assert_eq!(
+            receive_commands(RunState::Running, &receiver, no_admin_requests).unwrap(),
+            RunState::Draining
+        );
+
+        drop(sender);
+        assert!(receive_commands(RunState::Draining, &receiver, no_admin_requests).is_err());
+    }
+
+    #[test]
+    fn admin_requests_should_be_served_while_waiting() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let (reply, response) = std::sync::mpsc::channel();
+        sender
+            .send(Command::Admin(AdminRequest::GetWallet, reply))
+            .unwrap();
+
+        let mut served = vec![];
+        let state = wait_for_command(
+            RunState::Paused,
+            &receiver,
+            Duration::from_millis(10),
+            |request| {
+                served.push(request);
+                Ok(json!({}))
+            },
+        )
+        .unwrap();
+
+        // Admin requests do not end the wait early
+        assert_eq!(state, RunState::Paused);
+        assert_eq!(served, vec![AdminRequest::GetWallet]);
+        assert_eq!(response.recv().unwrap(), Ok(json!({})));
+
+        sender.send(Command::Resume).unwrap();
+        assert_eq!(
+            wait_for_command(
+                RunState::Paused,
+                &receiver,
+                Duration::from_secs(60),
+                no_admin_requests
+            )
+            .unwrap(),
+            RunState::Running
+        );
+    }
+
+    #[test]
+    fn errors_should_be_classified() {
+        assert!(Error::StacksNodeError(StacksNodeError::Timeout).is_transient());
+        assert!(Error::PegQueueError(StacksNodeError::BehindChainTip.into()).is_transient());
+        assert!(Error::MaxNonceRetriesExceeded.is_transient());
+        assert!(!Error::ConfigError("Invalid config".to_string()).is_transient());
+        assert!(!Error::WalletNotEmpty("tb1p".to_string()).is_transient());
+        assert!(!Error::PegQueueError(PegQueueError::SqlitePegQueueError(
+            SqlitePegQueueError::InvalidStatusTransition("confirmed", "pending")
+        ))
+        .is_transient());
+    }
 
     #[ignore]
     #[test]
@@ -588,4 +3608,324 @@ mod tests {
         let verify_result = bitcoin::bitcoinconsensus::verify(&[], 100, &btc_tx_encoded, 0);
         assert!(verify_result.is_ok())
     }
+
+    #[test]
+    fn peg_out_batch_should_record_each_burn_despite_failed_broadcasts() {
+        let ops: Vec<_> = (1..=3).map(|id| peg_out_request_op(id, 10000)).collect();
+        let mut utxos: Vec<_> = ops.iter().map(|(_, utxo)| utxo.clone()).collect();
+        utxos.push(funding_utxo(100000));
+        let ops: Vec<_> = ops.into_iter().map(|(op, _)| op).collect();
+
+        // The burn of the second request is rejected
+        let mut stacks_node = MockStacksNode::new();
+        let mut broadcasts = 0;
+        stacks_node
+            .expect_broadcast_transaction()
+            .returning(move |_| {
+                broadcasts += 1;
+                if broadcasts == 2 {
+                    Err(StacksNodeError::BroadcastError(
+                        BroadcastError::NotEnoughFunds("".to_string()),
+                    ))
+                } else {
+                    Ok(())
+                }
+            });
+        let mut coordinator = TestCoordinator::new(stacks_node, bitcoin_node_with_utxos(utxos));
+        coordinator.queue_ops(vec![], ops.clone());
+
+        let results = coordinator.peg_out_batch(&ops).unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results[0].1.is_ok());
+        assert!(results[1].1.is_err());
+        assert!(results[2].1.is_ok());
+
+        assert_eq!(coordinator.status(&ops[0].txid), Status::StacksBroadcast);
+        // Left pending to be retried once its lease expires
+        assert_eq!(coordinator.status(&ops[1].txid), Status::Pending);
+        assert_eq!(coordinator.status(&ops[2].txid), Status::StacksBroadcast);
+    }
+
+    #[test]
+    fn peg_out_batch_should_broadcast_nothing_if_the_batch_cannot_be_fulfilled() {
+        let (op, utxo) = peg_out_request_op(1, 10000);
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node.expect_broadcast_transaction().never();
+        // The peg wallet cannot cover the requested amount
+        let mut coordinator = TestCoordinator::new(
+            stacks_node,
+            bitcoin_node_with_utxos(vec![utxo, funding_utxo(100)]),
+        );
+        coordinator.queue_ops(vec![], vec![op.clone()]);
+
+        assert!(matches!(
+            coordinator.peg_out_batch(&[op.clone()]),
+            Err(Error::PegWalletError(_))
+        ));
+        assert_eq!(coordinator.status(&op.txid), Status::Pending);
+    }
+
+    #[test]
+    fn peg_out_batch_should_not_sign_burns_before_broadcasting_them() {
+        let (op, utxo) = peg_out_request_op(1, 10000);
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node.expect_broadcast_transaction().never();
+        let mut coordinator = TestCoordinator::new(
+            stacks_node,
+            bitcoin_node_with_utxos(vec![utxo, funding_utxo(100)]),
+        );
+        use_unreachable_signer(&mut coordinator);
+        coordinator.queue_ops(vec![], vec![op.clone()]);
+
+        // The batch is checked without reaching the signer
+        assert!(matches!(
+            coordinator.peg_out_batch(&[op]),
+            Err(Error::PegWalletError(PegWalletError::BitcoinWalletError(_)))
+        ));
+    }
+
+    /// Sign the Stacks transactions of the coordinator with a remote signer which went offline
+    /// after it was connected to
+    fn use_unreachable_signer(coordinator: &mut TestCoordinator) {
+        let stacks_private_key = StacksPrivateKey::from_hex(PRIVATE_KEY_HEX).unwrap();
+        let public_key = StacksPublicKey::from_private(&stacks_private_key);
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let mock = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            MockRemoteSigner::new(PrivateKeySigner::new(stacks_private_key))
+                .serve(&mut stream)
+                .unwrap();
+        });
+        let remote_signer = RemoteSigner::connect(url, public_key).unwrap();
+        mock.join().unwrap();
+
+        coordinator.fee_wallet.stacks_wallet = StacksWallet::new(
+            ContractName::from("sbtc-alpha"),
+            StacksAddress::from_string("SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE").unwrap(),
+            Box::new(remote_signer),
+            *coordinator.fee_wallet.stacks_wallet.address(),
+            TransactionVersion::Testnet,
+            10,
+        );
+    }
+
+    #[test]
+    fn op_should_wait_for_an_unreachable_signer() {
+        let mut coordinator = TestCoordinator::new(MockStacksNode::new(), MockBitcoinNode::new());
+        use_unreachable_signer(&mut coordinator);
+        let op = peg_in_op(1);
+        coordinator.queue_ops(vec![op.clone()], vec![]);
+
+        let result = coordinator
+            .fee_wallet()
+            .stacks()
+            .build_transaction(&op, 0)
+            .map(|_| ())
+            .map_err(Error::from);
+        assert!(matches!(
+            result,
+            Err(Error::PegWalletError(PegWalletError::StacksWalletError(
+                StacksWalletError::StacksTxSignerError(StacksTxSignerError::Unreachable(_))
+            )))
+        ));
+        assert!(coordinator
+            .handle_op_result(&op.txid, &op.burn_header_hash, result)
+            .unwrap_err()
+            .is_transient());
+        assert_eq!(coordinator.status(&op.txid), Status::Pending);
+    }
+
+    /// A fulfillment paying 10000 sats
+    fn fulfillment_transaction() -> BitcoinTransaction {
+        BitcoinTransaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![TxIn::default()],
+            output: vec![TxOut {
+                value: 10000,
+                script_pubkey: Default::default(),
+            }],
+        }
+    }
+
+    /// Coordinator tracking the fulfillment `fulfillment_transaction()` of a single peg out request
+    fn tracking_coordinator(bitcoin_node: MockBitcoinNode) -> (TestCoordinator, PegOutRequestOp) {
+        let (op, _) = peg_out_request_op(1, 10000);
+        let coordinator = TestCoordinator::new(MockStacksNode::new(), bitcoin_node);
+        coordinator.queue_ops(vec![], vec![op.clone()]);
+        coordinator.record_fulfillment(&op, &fulfillment_transaction());
+        (coordinator, op)
+    }
+
+    #[test]
+    fn fulfillment_should_not_be_broadcast_unless_recorded() {
+        let ops: Vec<_> = (1..=2).map(|id| peg_out_request_op(id, 10000).0).collect();
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node.expect_block_height().returning(|| Ok(100));
+        bitcoin_node.expect_broadcast_transaction().never();
+        let mut coordinator = TestCoordinator::new(MockStacksNode::new(), bitcoin_node);
+        coordinator.queue_ops(vec![], ops.clone());
+
+        // The second request was not burned, so recording the fulfillment fails
+        coordinator
+            .peg_queue
+            .stacks_broadcast(&ops[0].txid, &ops[0].burn_header_hash, &Txid([0xee; 32]))
+            .unwrap();
+        assert!(coordinator
+            .broadcast_fulfillment(&ops, &fulfillment_transaction())
+            .is_err());
+        assert_eq!(coordinator.status(&ops[0].txid), Status::StacksBroadcast);
+        assert_eq!(coordinator.status(&ops[1].txid), Status::Pending);
+    }
+
+    #[test]
+    fn recorded_fulfillment_should_be_broadcast_until_the_node_knows_it() {
+        let (op, _) = peg_out_request_op(1, 10000);
+        let fulfillment = fulfillment_transaction();
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node.expect_block_height().returning(|| Ok(100));
+        let mut broadcasts = 0;
+        bitcoin_node
+            .expect_broadcast_transaction()
+            .withf(|tx| *tx == fulfillment_transaction())
+            .times(2)
+            .returning(move |tx| {
+                broadcasts += 1;
+                if broadcasts == 1 {
+                    Err(BitcoinNodeError::RPCError("Connection refused".to_string()))
+                } else {
+                    Ok(tx.txid())
+                }
+            });
+        bitcoin_node
+            .expect_get_transaction()
+            .returning(|txid| Err(BitcoinNodeError::UnknownTransaction(*txid)));
+        let mut coordinator = TestCoordinator::new(MockStacksNode::new(), bitcoin_node);
+        coordinator.queue_ops(vec![], vec![op.clone()]);
+        coordinator
+            .peg_queue
+            .stacks_broadcast(&op.txid, &op.burn_header_hash, &Txid([0xee; 32]))
+            .unwrap();
+
+        // The failed broadcast leaves the fulfillment recorded, so the burn is not fulfilled again
+        coordinator
+            .broadcast_fulfillment(&[op.clone()], &fulfillment)
+            .unwrap();
+        assert_eq!(coordinator.status(&op.txid), Status::BitcoinBroadcast);
+
+        // The node does not know the fulfillment, so the recorded transaction is broadcast again
+        coordinator.track_fulfillments().unwrap();
+        assert_eq!(coordinator.status(&op.txid), Status::BitcoinBroadcast);
+    }
+
+    #[test]
+    fn confirmed_fulfillment_should_be_acknowledged() {
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node.expect_block_height().returning(|| Ok(101));
+        bitcoin_node
+            .expect_get_transaction()
+            .returning(|_| Ok(wallet_transaction(10000, 1, vec![])));
+        let (mut coordinator, op) = tracking_coordinator(bitcoin_node);
+
+        coordinator.track_fulfillments().unwrap();
+        assert_eq!(coordinator.status(&op.txid), Status::Confirmed);
+    }
+
+    #[test]
+    fn dropped_fulfillment_should_be_rebroadcast_or_flagged() {
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node.expect_block_height().returning(|| Ok(101));
+        bitcoin_node
+            .expect_get_transaction()
+            .returning(|_| Ok(wallet_transaction(10000, 0, vec![])));
+        bitcoin_node.expect_is_in_mempool().returning(|_| Ok(false));
+        let mut broadcasts = 0;
+        bitcoin_node
+            .expect_broadcast_transaction()
+            .times(2)
+            .returning(move |tx| {
+                broadcasts += 1;
+                if broadcasts == 1 {
+                    Ok(tx.txid())
+                } else {
+                    Err(BitcoinNodeError::RPCError(
+                        "bad-txns-inputs-missingorspent".to_string(),
+                    ))
+                }
+            });
+        let (mut coordinator, op) = tracking_coordinator(bitcoin_node);
+
+        // Accepted again, so it is tracked as before
+        coordinator.track_fulfillments().unwrap();
+        assert_eq!(coordinator.status(&op.txid), Status::BitcoinBroadcast);
+
+        // Rejected on re-broadcast
+        coordinator.track_fulfillments().unwrap();
+        assert_eq!(coordinator.status(&op.txid), Status::Flagged);
+    }
+
+    #[test]
+    fn conflicted_fulfillment_should_follow_its_confirmed_version() {
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node.expect_block_height().returning(|| Ok(101));
+        bitcoin_node
+            .expect_get_transaction()
+            .withf(|txid| *txid == fulfillment_transaction().txid())
+            .returning(|_| Ok(wallet_transaction(10000, -1, vec![bitcoin_txid(2)])));
+        // An earlier version of the fulfillment, making the same payments
+        bitcoin_node
+            .expect_get_transaction()
+            .withf(|txid| *txid == bitcoin_txid(2))
+            .returning(|_| {
+                Ok(wallet_transaction(
+                    10000,
+                    1,
+                    vec![fulfillment_transaction().txid()],
+                ))
+            });
+        let (mut coordinator, op) = tracking_coordinator(bitcoin_node);
+
+        coordinator.track_fulfillments().unwrap();
+        let entry = &coordinator.peg_queue.entries_by_txid(&op.txid).unwrap()[0];
+        assert_eq!(entry.status, Status::BitcoinBroadcast);
+        assert_eq!(entry.bitcoin_txid, Some(bitcoin_txid(2)));
+    }
+
+    #[test]
+    fn fulfillment_conflicting_with_another_payment_should_be_flagged() {
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node.expect_block_height().returning(|| Ok(101));
+        bitcoin_node
+            .expect_get_transaction()
+            .withf(|txid| *txid == fulfillment_transaction().txid())
+            .returning(|_| Ok(wallet_transaction(10000, -1, vec![bitcoin_txid(2)])));
+        bitcoin_node
+            .expect_get_transaction()
+            .withf(|txid| *txid == bitcoin_txid(2))
+            .returning(|_| {
+                Ok(wallet_transaction(
+                    5000,
+                    1,
+                    vec![fulfillment_transaction().txid()],
+                ))
+            });
+        let (mut coordinator, op) = tracking_coordinator(bitcoin_node);
+
+        coordinator.track_fulfillments().unwrap();
+        assert_eq!(coordinator.status(&op.txid), Status::Flagged);
+    }
+
+    #[test]
+    fn unreachable_bitcoin_node_should_leave_fulfillment_tracked() {
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node.expect_block_height().returning(|| Ok(101));
+        bitcoin_node
+            .expect_get_transaction()
+            .returning(|_| Err(BitcoinNodeError::RPCError("Connection refused".to_string())));
+        let (mut coordinator, op) = tracking_coordinator(bitcoin_node);
+
+        coordinator.track_fulfillments().unwrap();
+        assert_eq!(coordinator.status(&op.txid), Status::BitcoinBroadcast);
+    }
 }
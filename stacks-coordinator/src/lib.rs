@@ -1,10 +1,16 @@
+pub mod admin;
 pub mod bitcoin_node;
 pub mod bitcoin_wallet;
 pub mod cli;
+pub mod coin_selector;
 pub mod config;
 pub mod coordinator;
+pub mod metrics;
+pub mod nonce_manager;
 pub mod peg_queue;
 pub mod peg_wallet;
+pub mod reconciliation;
 pub mod stacks_node;
+pub mod stacks_tx_signer;
 pub mod stacks_wallet;
 mod util;
@@ -1,8 +1,16 @@
 use clap::Parser;
-use frost_signer::logging;
+use frost_signer::{logging, metrics};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use stacks_coordinator::admin::{AdminRequest, AdminServer};
 use stacks_coordinator::cli::{Cli, Command};
 use stacks_coordinator::config::Config;
-use stacks_coordinator::coordinator::{Coordinator, StacksCoordinator};
+use stacks_coordinator::coordinator::{
+    Command as CoordinatorCommand, Coordinator, StacksCoordinator,
+};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
 use tracing::{error, info, warn};
 
 fn main() {
@@ -11,7 +19,7 @@ fn main() {
     logging::initiate_tracing_subscriber();
 
     //TODO: get configs from sBTC contract
-    match Config::from_path(&cli.config) {
+    match Config::load(&cli.config, cli.environment.as_deref()) {
         Ok(mut config) => {
             config.signer_config_path = Some(cli.signer_config);
             if cli.start_block_height == Some(0) {
@@ -21,12 +29,68 @@ fn main() {
             config.start_block_height = cli.start_block_height;
             match StacksCoordinator::try_from(&config) {
                 Ok(mut coordinator) => {
+                    coordinator.set_config_source(&cli.config, cli.environment.clone());
                     // Determine what action the caller wishes to perform
                     match cli.command {
                         Command::Run => {
                             info!("Running Coordinator");
+                            let (commands, receiver) = mpsc::channel();
+                            if let Some(address) = config.admin_api_address {
+                                let listener = match TcpListener::bind(address) {
+                                    Ok(listener) => listener,
+                                    Err(e) => {
+                                        error!("Failed to bind admin API to {}: {}", address, e);
+                                        return;
+                                    }
+                                };
+                                info!("Admin API listening on {}", address);
+                                let mut server = AdminServer::new(commands.clone());
+                                if let Some(token) = config.admin_api_token.clone() {
+                                    server.set_token(token);
+                                }
+                                thread::spawn(move || server.run(listener));
+                            }
+                            if let Some(address) = config.metrics_address {
+                                if let Err(e) = metrics::spawn_server(address) {
+                                    error!("Failed to serve metrics on {}: {}", address, e);
+                                    return;
+                                }
+                                info!("Serving metrics on {}", address);
+                            }
+                            // Stop cleanly on SIGINT and SIGTERM, finishing the current step first,
+                            // and reload the config file on SIGHUP
+                            let mut signals = match Signals::new([SIGINT, SIGTERM, SIGHUP]) {
+                                Ok(signals) => signals,
+                                Err(e) => {
+                                    error!("Failed to set signal handler: {}", e);
+                                    return;
+                                }
+                            };
+                            thread::spawn(move || {
+                                for signal in signals.forever() {
+                                    if signal != SIGHUP {
+                                        info!("Received termination signal. Stopping after the current step...");
+                                        let _ = commands.send(CoordinatorCommand::Stop);
+                                        continue;
+                                    }
+                                    info!("Received SIGHUP. Reloading the config file...");
+                                    let (reply, response) = mpsc::channel();
+                                    if commands
+                                        .send(CoordinatorCommand::Admin(
+                                            AdminRequest::ReloadConfig,
+                                            reply,
+                                        ))
+                                        .is_err()
+                                    {
+                                        break;
+                                    }
+                                    if let Ok(Err(e)) = response.recv() {
+                                        error!("Failed to reload the config file: {}", e);
+                                    }
+                                }
+                            });
                             //TODO: set up coordination with the stacks node
-                            if let Err(e) = coordinator.run(config.polling_interval) {
+                            if let Err(e) = coordinator.run(receiver) {
                                 error!("An error occurred running the coordinator: {}", e);
                             }
                         }
@@ -36,6 +100,17 @@ fn main() {
                                 error!("An error occurred during DKG round: {}", e);
                             }
                         }
+                        Command::Reconcile => {
+                            info!("Reconciling the peg wallet with the sBTC supply");
+                            match coordinator.reconcile_solvency() {
+                                Ok(report) => println!(
+                                    "{}",
+                                    serde_json::to_string_pretty(&report)
+                                        .expect("failed to serialize solvency report")
+                                ),
+                                Err(e) => error!("An error occurred during reconciliation: {}", e),
+                            }
+                        }
                         Command::DkgSign => {
                             info!("Running DKG Round");
                             if let Err(e) = coordinator.run_dkg_round() {
@@ -0,0 +1,64 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    register_int_counter_vec, register_int_gauge, register_int_gauge_vec, IntCounterVec, IntGauge,
+    IntGaugeVec,
+};
+
+lazy_static! {
+    /// Ops moved to each status, counting ops seen for the first time as moved to `new`
+    pub static ref OPS_PROCESSED: IntCounterVec = register_int_counter_vec!(
+        "stacks_coordinator_ops_processed_total",
+        "sBTC ops moved to each status of the queue",
+        &["status"]
+    )
+    .unwrap();
+
+    /// Ops in each unresolved status, refreshed every polling cycle
+    pub static ref QUEUE_DEPTH: IntGaugeVec = register_int_gauge_vec!(
+        "stacks_coordinator_queue_depth",
+        "sBTC ops in each unresolved status of the queue",
+        &["status"]
+    )
+    .unwrap();
+
+    pub static ref LAST_PROCESSED_BURN_HEIGHT: IntGauge = register_int_gauge!(
+        "stacks_coordinator_last_processed_burn_height",
+        "Height of the last burn block polled for sBTC ops"
+    )
+    .unwrap();
+
+    pub static ref BURN_HEIGHT_LAG: IntGauge = register_int_gauge!(
+        "stacks_coordinator_burn_height_lag",
+        "Burn blocks between the last processed one and the tip reported by the stacks node"
+    )
+    .unwrap();
+
+    /// Stacks broadcasts retried because the node refused the nonce or the fee
+    pub static ref BROADCAST_RETRIES: IntCounterVec = register_int_counter_vec!(
+        "stacks_coordinator_broadcast_retries_total",
+        "Stacks transaction broadcasts retried, by reason",
+        &["reason"]
+    )
+    .unwrap();
+
+    /// Stuck transactions replaced with a higher fee, on either chain
+    pub static ref FEE_BUMPS: IntCounterVec = register_int_counter_vec!(
+        "stacks_coordinator_fee_bumps_total",
+        "Stuck transactions replaced with a higher fee, by chain",
+        &["chain"]
+    )
+    .unwrap();
+
+    pub static ref PEG_WALLET_BALANCE: IntGauge = register_int_gauge!(
+        "stacks_coordinator_peg_wallet_balance_sats",
+        "Unspent balance of the peg wallet address"
+    )
+    .unwrap();
+
+    /// Peg wallet balance minus the sBTC supply and pending peg ins, as of the last reconciliation
+    pub static ref SOLVENCY_DISCREPANCY: IntGauge = register_int_gauge!(
+        "stacks_coordinator_solvency_discrepancy_sats",
+        "Peg wallet balance minus the sBTC supply and pending peg ins"
+    )
+    .unwrap();
+}
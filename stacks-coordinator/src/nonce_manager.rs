@@ -0,0 +1,467 @@
+use std::path::Path;
+
+use blockstack_lib::{
+    burnchains::Txid,
+    chainstate::stacks::StacksTransaction,
+    codec::{Error as CodecError, StacksMessageCodec},
+    types::chainstate::StacksAddress,
+    util::HexError,
+};
+use rusqlite::{Connection as RusqliteConnection, Error as RusqliteError};
+use tracing::debug;
+
+use crate::util::now;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Rusqlite Error: {0}")]
+    RusqliteError(#[from] RusqliteError),
+    #[error("Failed to (de)serialize transaction: {0}")]
+    CodecError(#[from] CodecError),
+    #[error("Account nonce of {0} was never reconciled with the stacks node")]
+    NotReconciled(String),
+    #[error("Hex codec error: {0}")]
+    HexError(#[from] HexError),
+}
+
+/// A transaction whose nonce the node has not yet accounted for
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutstandingTransaction {
+    pub transaction: StacksTransaction,
+    /// Unix time in seconds at which the transaction was broadcast
+    pub broadcast_at: u64,
+}
+
+/// Assigns Stacks transaction nonces locally so transactions can be broadcast without
+/// waiting for the node to account for the previous ones.
+///
+/// Nonces below the node's account nonce are settled. Nonces at or above it are outstanding
+/// and remembered with the transaction which used them, so it can be re-submitted if the
+/// mempool drops it. Released nonces leave a gap which the next transaction fills.
+pub trait NonceManager {
+    /// Records the node's account nonce for the address and forgets the nonces it settled.
+    /// Returns the outstanding transactions, lowest nonce first.
+    fn reconcile(
+        &self,
+        address: &StacksAddress,
+        account_nonce: u64,
+    ) -> Result<Vec<OutstandingTransaction>, Error>;
+
+    /// The lowest nonce not assigned to any outstanding transaction
+    fn next_nonce(&self, address: &StacksAddress) -> Result<u64, Error> {
+        Ok(self.next_nonces(address, 1)?[0])
+    }
+
+    /// The `count` lowest nonces not assigned to any outstanding transaction
+    fn next_nonces(&self, address: &StacksAddress, count: usize) -> Result<Vec<u64>, Error>;
+
+    /// Records the transaction broadcast with the given nonce, replacing any previous one.
+    /// A nonce used by a transaction the coordinator does not know is recorded without one,
+    /// until the next reconciliation.
+    fn record(
+        &self,
+        address: &StacksAddress,
+        nonce: u64,
+        tx: Option<&StacksTransaction>,
+    ) -> Result<(), Error>;
+
+    /// Frees the nonce so the next transaction fills it
+    fn release(&self, address: &StacksAddress, nonce: u64) -> Result<(), Error>;
+
+    /// Whether the transaction is outstanding
+    fn is_outstanding(&self, txid: &Txid) -> Result<bool, Error>;
+
+    /// Records that the transaction was replaced with one paying a higher fee
+    fn record_replacement(&self, txid: &Txid, replacement_txid: &Txid) -> Result<(), Error>;
+
+    /// The transactions the given one replaced, most recent first. Any of them may have been
+    /// mined instead of the replacement.
+    fn replaced_txids(&self, replacement_txid: &Txid) -> Result<Vec<Txid>, Error>;
+}
+
+pub struct SqliteNonceManager {
+    conn: RusqliteConnection,
+}
+
+impl SqliteNonceManager {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open(path)?)
+    }
+
+    pub fn in_memory() -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: RusqliteConnection) -> Result<Self, Error> {
+        conn.execute(Self::create_nonces_table(), rusqlite::params![])?;
+        conn.execute(Self::create_account_nonces_table(), rusqlite::params![])?;
+        conn.execute(Self::create_replacements_table(), rusqlite::params![])?;
+        Ok(Self { conn })
+    }
+
+    fn account_nonce(&self, address: &StacksAddress) -> Result<u64, Error> {
+        match self.conn.query_row(
+            Self::sql_select_account_nonce(),
+            rusqlite::params![address.to_string()],
+            |row| row.get::<_, i64>(0),
+        ) {
+            Ok(nonce) => Ok(nonce as u64),
+            Err(RusqliteError::QueryReturnedNoRows) => {
+                Err(Error::NotReconciled(address.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    const fn create_nonces_table() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS stacks_nonces (
+            address TEXT NOT NULL,
+            nonce INTEGER NOT NULL,
+            txid TEXT,
+            tx BLOB,
+            broadcast_at INTEGER NOT NULL DEFAULT 0,
+
+            PRIMARY KEY(address, nonce)
+        )
+        "#
+    }
+
+    const fn create_account_nonces_table() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS stacks_account_nonces (
+            address TEXT NOT NULL,
+            nonce INTEGER NOT NULL,
+
+            PRIMARY KEY(address)
+        )
+        "#
+    }
+
+    const fn create_replacements_table() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS stacks_replacements (
+            txid TEXT NOT NULL,
+            replacement_txid TEXT NOT NULL,
+
+            PRIMARY KEY(txid)
+        )
+        "#
+    }
+
+    const fn sql_insert_replacement() -> &'static str {
+        r#"
+        REPLACE INTO stacks_replacements (txid, replacement_txid) VALUES (?1, ?2)
+        "#
+    }
+
+    const fn sql_select_replaced() -> &'static str {
+        r#"
+        SELECT txid FROM stacks_replacements WHERE replacement_txid=?1
+        "#
+    }
+
+    const fn sql_replace_account_nonce() -> &'static str {
+        r#"
+        REPLACE INTO stacks_account_nonces (address, nonce) VALUES (?1, ?2)
+        "#
+    }
+
+    const fn sql_select_account_nonce() -> &'static str {
+        r#"
+        SELECT nonce FROM stacks_account_nonces WHERE address=?1
+        "#
+    }
+
+    const fn sql_delete_settled() -> &'static str {
+        r#"
+        DELETE FROM stacks_nonces WHERE address=?1 AND (nonce < ?2 OR tx IS NULL)
+        "#
+    }
+
+    const fn sql_select_outstanding() -> &'static str {
+        r#"
+        SELECT nonce, tx, broadcast_at FROM stacks_nonces WHERE address=?1 AND nonce >= ?2 ORDER BY nonce ASC
+        "#
+    }
+
+    const fn sql_replace_nonce() -> &'static str {
+        r#"
+        REPLACE INTO stacks_nonces (address, nonce, txid, tx, broadcast_at) VALUES (?1, ?2, ?3, ?4, ?5)
+        "#
+    }
+
+    const fn sql_delete_nonce() -> &'static str {
+        r#"
+        DELETE FROM stacks_nonces WHERE address=?1 AND nonce=?2
+        "#
+    }
+
+    const fn sql_count_txid() -> &'static str {
+        r#"
+        SELECT COUNT(*) FROM stacks_nonces WHERE txid=?1
+        "#
+    }
+}
+
+impl NonceManager for SqliteNonceManager {
+    fn reconcile(
+        &self,
+        address: &StacksAddress,
+        account_nonce: u64,
+    ) -> Result<Vec<OutstandingTransaction>, Error> {
+        self.conn.execute(
+            Self::sql_replace_account_nonce(),
+            rusqlite::params![address.to_string(), account_nonce as i64],
+        )?;
+        // Nonces used by unknown transactions are probed again by the next broadcast
+        let settled = self.conn.execute(
+            Self::sql_delete_settled(),
+            rusqlite::params![address.to_string(), account_nonce as i64],
+        )?;
+        debug!(
+            "Account nonce of {} is {}. Forgot {} nonces",
+            address, account_nonce, settled
+        );
+
+        let mut stmt = self.conn.prepare(Self::sql_select_outstanding())?;
+        let rows = stmt
+            .query_map(
+                rusqlite::params![address.to_string(), account_nonce as i64],
+                |row| Ok((row.get::<_, Vec<u8>>(1)?, row.get::<_, i64>(2)?)),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter()
+            .map(|(raw_tx, broadcast_at)| {
+                Ok(OutstandingTransaction {
+                    transaction: StacksTransaction::consensus_deserialize(&mut &raw_tx[..])?,
+                    broadcast_at: broadcast_at as u64,
+                })
+            })
+            .collect()
+    }
+
+    fn next_nonces(&self, address: &StacksAddress, count: usize) -> Result<Vec<u64>, Error> {
+        let mut next_nonce = self.account_nonce(address)?;
+        let mut stmt = self.conn.prepare(Self::sql_select_outstanding())?;
+        let nonces = stmt
+            .query_map(
+                rusqlite::params![address.to_string(), next_nonce as i64],
+                |row| row.get::<_, i64>(0),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut assigned = nonces.into_iter().map(|nonce| nonce as u64).peekable();
+        let mut next_nonces = Vec::with_capacity(count);
+        while next_nonces.len() < count {
+            if assigned.next_if_eq(&next_nonce).is_none() {
+                // Found a gap
+                next_nonces.push(next_nonce);
+            }
+            next_nonce += 1;
+        }
+        Ok(next_nonces)
+    }
+
+    fn record(
+        &self,
+        address: &StacksAddress,
+        nonce: u64,
+        tx: Option<&StacksTransaction>,
+    ) -> Result<(), Error> {
+        let txid = tx.map(|tx| tx.txid().to_hex());
+        let raw_tx = tx.map(|tx| tx.serialize_to_vec());
+        self.conn.execute(
+            Self::sql_replace_nonce(),
+            rusqlite::params![
+                address.to_string(),
+                nonce as i64,
+                txid,
+                raw_tx,
+                now() as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn release(&self, address: &StacksAddress, nonce: u64) -> Result<(), Error> {
+        self.conn.execute(
+            Self::sql_delete_nonce(),
+            rusqlite::params![address.to_string(), nonce as i64],
+        )?;
+        Ok(())
+    }
+
+    fn is_outstanding(&self, txid: &Txid) -> Result<bool, Error> {
+        Ok(self.conn.query_row(
+            Self::sql_count_txid(),
+            rusqlite::params![txid.to_hex()],
+            |row| row.get::<_, i64>(0),
+        )? > 0)
+    }
+
+    fn record_replacement(&self, txid: &Txid, replacement_txid: &Txid) -> Result<(), Error> {
+        self.conn.execute(
+            Self::sql_insert_replacement(),
+            rusqlite::params![txid.to_hex(), replacement_txid.to_hex()],
+        )?;
+        Ok(())
+    }
+
+    fn replaced_txids(&self, replacement_txid: &Txid) -> Result<Vec<Txid>, Error> {
+        let mut replaced_txids = vec![];
+        let mut stmt = self.conn.prepare(Self::sql_select_replaced())?;
+        let mut txid = *replacement_txid;
+        loop {
+            let replaced = match stmt.query_row(rusqlite::params![txid.to_hex()], |row| {
+                row.get::<_, String>(0)
+            }) {
+                Ok(replaced) => Txid::from_hex(&replaced)?,
+                Err(RusqliteError::QueryReturnedNoRows) => break,
+                Err(e) => return Err(e.into()),
+            };
+            replaced_txids.push(replaced);
+            txid = replaced;
+        }
+        Ok(replaced_txids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::XOnlyPublicKey;
+    use blockstack_lib::{
+        chainstate::stacks::TransactionVersion, types::chainstate::StacksPrivateKey,
+        vm::ContractName,
+    };
+
+    use crate::{
+        peg_wallet::StacksWallet as StacksWalletTrait,
+        stacks_tx_signer::PrivateKeySigner,
+        stacks_wallet::StacksWallet,
+        util::test::{PRIVATE_KEY_HEX, PUBLIC_KEY_HEX},
+    };
+
+    use super::*;
+
+    fn address() -> StacksAddress {
+        StacksAddress::from_string("SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE").unwrap()
+    }
+
+    fn transaction(nonce: u64) -> StacksTransaction {
+        let wallet = StacksWallet::new(
+            ContractName::from("sbtc-alpha"),
+            address(),
+            Box::new(PrivateKeySigner::new(
+                StacksPrivateKey::from_hex(PRIVATE_KEY_HEX).unwrap(),
+            )),
+            address(),
+            TransactionVersion::Testnet,
+            10,
+        );
+        let public_key = XOnlyPublicKey::from_str(PUBLIC_KEY_HEX).unwrap();
+        wallet
+            .build_set_bitcoin_wallet_public_key_transaction(&public_key, nonce)
+            .unwrap()
+    }
+
+    #[test]
+    fn next_nonce_should_require_reconciliation() {
+        let nonce_manager = SqliteNonceManager::in_memory().unwrap();
+        assert!(matches!(
+            nonce_manager.next_nonce(&address()),
+            Err(Error::NotReconciled(_))
+        ));
+    }
+
+    #[test]
+    fn nonces_should_be_assigned_locally() {
+        let nonce_manager = SqliteNonceManager::in_memory().unwrap();
+        let address = address();
+        assert!(nonce_manager.reconcile(&address, 5).unwrap().is_empty());
+
+        for nonce in 5..8 {
+            assert_eq!(nonce_manager.next_nonce(&address).unwrap(), nonce);
+            nonce_manager
+                .record(&address, nonce, Some(&transaction(nonce)))
+                .unwrap();
+        }
+        assert_eq!(nonce_manager.next_nonce(&address).unwrap(), 8);
+        assert!(nonce_manager
+            .is_outstanding(&transaction(6).txid())
+            .unwrap());
+    }
+
+    #[test]
+    fn reconcile_should_forget_settled_nonces() {
+        let nonce_manager = SqliteNonceManager::in_memory().unwrap();
+        let address = address();
+        nonce_manager.reconcile(&address, 5).unwrap();
+        for nonce in 5..8 {
+            nonce_manager
+                .record(&address, nonce, Some(&transaction(nonce)))
+                .unwrap();
+        }
+        // A nonce used by a transaction we did not broadcast
+        nonce_manager.record(&address, 8, None).unwrap();
+        assert_eq!(nonce_manager.next_nonce(&address).unwrap(), 9);
+
+        let outstanding: Vec<_> = nonce_manager
+            .reconcile(&address, 6)
+            .unwrap()
+            .into_iter()
+            .map(|outstanding| outstanding.transaction)
+            .collect();
+        assert_eq!(outstanding, vec![transaction(6), transaction(7)]);
+        assert!(!nonce_manager
+            .is_outstanding(&transaction(5).txid())
+            .unwrap());
+        assert_eq!(nonce_manager.next_nonce(&address).unwrap(), 8);
+    }
+
+    #[test]
+    fn replacements_should_be_traced_back() {
+        let nonce_manager = SqliteNonceManager::in_memory().unwrap();
+        nonce_manager
+            .record_replacement(&Txid([1; 32]), &Txid([2; 32]))
+            .unwrap();
+        nonce_manager
+            .record_replacement(&Txid([2; 32]), &Txid([3; 32]))
+            .unwrap();
+
+        assert_eq!(
+            nonce_manager.replaced_txids(&Txid([3; 32])).unwrap(),
+            vec![Txid([2; 32]), Txid([1; 32])]
+        );
+        assert!(nonce_manager
+            .replaced_txids(&Txid([1; 32]))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn released_nonces_should_be_filled_first() {
+        let nonce_manager = SqliteNonceManager::in_memory().unwrap();
+        let address = address();
+        nonce_manager.reconcile(&address, 0).unwrap();
+        for nonce in 0..3 {
+            nonce_manager
+                .record(&address, nonce, Some(&transaction(nonce)))
+                .unwrap();
+        }
+
+        nonce_manager.release(&address, 1).unwrap();
+        assert_eq!(nonce_manager.next_nonce(&address).unwrap(), 1);
+        nonce_manager
+            .record(&address, 1, Some(&transaction(1)))
+            .unwrap();
+        assert_eq!(nonce_manager.next_nonce(&address).unwrap(), 3);
+
+        nonce_manager.release(&address, 0).unwrap();
+        assert_eq!(
+            nonce_manager.next_nonces(&address, 3).unwrap(),
+            vec![0, 3, 4]
+        );
+    }
+}
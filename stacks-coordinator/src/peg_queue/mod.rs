@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use blockstack_lib::burnchains::Txid;
 use blockstack_lib::types::chainstate::BurnchainHeaderHash;
 
@@ -5,7 +7,7 @@ use crate::stacks_node;
 use crate::stacks_node::Error as StacksNodeError;
 mod sqlite_peg_queue;
 
-pub use sqlite_peg_queue::{Error as SqlitePegQueueError, SqlitePegQueue};
+pub use sqlite_peg_queue::{Error as SqlitePegQueueError, SqlitePegQueue, Status};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -15,12 +17,133 @@ pub enum Error {
     StacksNodeError(#[from] StacksNodeError),
 }
 
+impl Error {
+    /// Whether the error is expected to clear up by itself. Queue database errors are fatal.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::SqlitePegQueueError(_) => false,
+            Error::StacksNodeError(e) => e.is_transient(),
+        }
+    }
+}
+
+/// Lifecycle of an sBTC op in the queue:
+///
+/// `New -> Pending -> StacksBroadcast -> BitcoinBroadcast -> Confirmed`
+///
+/// Any non-terminal state may move to `Failed`. Peg ins whose deposit does not pay the
+/// peg wallet and peg outs overdrawing their requester's sBTC move from `Pending` to
+/// `Rejected`, as do ops whose Stacks transaction aborts from `StacksBroadcast`. `Pending` entries whose lease expired are moved back
+/// to `New` by `requeue_expired`. Operators may move `Failed`, `Flagged` and `Rejected`
+/// entries back to `New` through `requeue`, unless a transaction was broadcast for them.
+///
+/// When the burn block of an op is orphaned, the op becomes `Invalidated` if it was
+/// not acted upon yet, and `Flagged` for operator attention otherwise.
 pub trait PegQueue {
+    /// Returns the next `New` op and marks it `Pending`
     fn sbtc_op(&self) -> Result<Option<SbtcOp>, Error>;
+    /// Polls new burn blocks for ops, rolling back to the fork point first if a reorg is detected
     fn poll<N: stacks_node::StacksNode>(&self, stacks_node: &N) -> Result<(), Error>;
 
+    /// Records the Stacks transaction broadcast for the op
+    fn stacks_broadcast(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+        stacks_txid: &Txid,
+    ) -> Result<(), Error>;
+
+    /// Records the Bitcoin fulfillment transaction of the ops before it is broadcast, so it can be
+    /// broadcast again if the coordinator stops first. Either every op is updated or none is.
+    fn bitcoin_broadcast(
+        &self,
+        ops: &[(Txid, BurnchainHeaderHash)],
+        transaction: &bitcoin::Transaction,
+        broadcast_height: u64,
+    ) -> Result<(), Error>;
+
+    /// Returns the broadcast Bitcoin fulfillment transactions which are not yet confirmed
+    fn fulfillments(&self) -> Result<Vec<Fulfillment>, Error>;
+
+    /// Records that the fulfillment `bitcoin_txid` was replaced by `replacement_txid`.
+    /// Returns the number of ops fulfilled by the replacement.
+    fn replace_fulfillment(
+        &self,
+        bitcoin_txid: &bitcoin::Txid,
+        replacement_txid: &bitcoin::Txid,
+        broadcast_height: u64,
+    ) -> Result<usize, Error>;
+
+    /// Marks the op as confirmed
     fn acknowledge(&self, txid: &Txid, burn_header_hash: &BurnchainHeaderHash)
         -> Result<(), Error>;
+
+    /// Marks the op as rejected, either up front or by the Stacks chain, recording the reason
+    fn reject(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+        reason: &str,
+    ) -> Result<(), Error>;
+
+    /// Returns the ops whose Stacks transaction was broadcast but not yet resolved, with its txid
+    fn stacks_broadcasts(&self) -> Result<Vec<(SbtcOp, Txid)>, Error>;
+
+    /// Records that the Stacks transaction `stacks_txid` was replaced by `replacement_txid`.
+    /// Returns the number of ops whose transaction was replaced.
+    fn replace_stacks_transaction(
+        &self,
+        stacks_txid: &Txid,
+        replacement_txid: &Txid,
+    ) -> Result<usize, Error>;
+
+    /// Flags the op for operator attention. Flagged ops are never handed out again.
+    fn flag(&self, txid: &Txid, burn_header_hash: &BurnchainHeaderHash) -> Result<(), Error>;
+
+    /// Marks the op as failed. Failed ops are never handed out again.
+    fn fail(&self, txid: &Txid, burn_header_hash: &BurnchainHeaderHash) -> Result<(), Error>;
+
+    /// Moves `Pending` ops which have not progressed within `lease` back to `New`.
+    /// Returns the number of requeued ops.
+    fn requeue_expired(&self, lease: Duration) -> Result<usize, Error>;
+
+    /// Moves a `Pending`, `Failed`, `Flagged` or `Rejected` op back to `New` at an operator's request.
+    /// Ops with a broadcast Stacks or Bitcoin transaction are refused.
+    fn requeue(&self, txid: &Txid, burn_header_hash: &BurnchainHeaderHash) -> Result<(), Error>;
+
+    /// Returns the ops with the given status
+    fn entries(&self, status: Status) -> Result<Vec<Entry>, Error>;
+
+    /// Returns the ops with the given txid, one for each burn block the op was seen in
+    fn entries_by_txid(&self, txid: &Txid) -> Result<Vec<Entry>, Error>;
+}
+
+/// An op in the queue along with its progress
+#[derive(Debug)]
+pub struct Entry {
+    pub op: SbtcOp,
+    pub block_height: u64,
+    pub status: Status,
+    pub stacks_txid: Option<Txid>,
+    pub bitcoin_txid: Option<bitcoin::Txid>,
+    pub attempts: u32,
+    pub created_at: u64,
+    pub updated_at: u64,
+    /// Why the op was rejected
+    pub reason: Option<String>,
+}
+
+/// A Bitcoin transaction fulfilling one or more peg-out requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fulfillment {
+    pub bitcoin_txid: bitcoin::Txid,
+    /// The transaction as recorded before its broadcast. Fee bumps and replacements found on
+    /// chain are only recorded by txid.
+    pub transaction: Option<bitcoin::Transaction>,
+    /// Bitcoin block height at which the transaction was broadcast
+    pub broadcast_height: u64,
+    /// The `(txid, burn_header_hash)` of each fulfilled op
+    pub ops: Vec<(Txid, BurnchainHeaderHash)>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -30,6 +153,20 @@ pub enum SbtcOp {
 }
 
 impl SbtcOp {
+    pub fn txid(&self) -> &Txid {
+        match self {
+            Self::PegIn(op) => &op.txid,
+            Self::PegOutRequest(op) => &op.txid,
+        }
+    }
+
+    pub fn burn_header_hash(&self) -> &BurnchainHeaderHash {
+        match self {
+            Self::PegIn(op) => &op.burn_header_hash,
+            Self::PegOutRequest(op) => &op.burn_header_hash,
+        }
+    }
+
     pub fn as_peg_in(&self) -> Option<&stacks_node::PegInOp> {
         match self {
             Self::PegIn(op) => Some(op),
@@ -1,15 +1,22 @@
 use rusqlite::{Connection as RusqliteConnection, Error as RusqliteError, Row as SqliteRow};
 use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 
+use bitcoin::consensus::encode::{deserialize, serialize_hex};
+use bitcoin::hashes::hex::FromHex;
 use blockstack_lib::burnchains::Txid;
 use blockstack_lib::types::chainstate::BurnchainHeaderHash;
 use blockstack_lib::util::HexError;
 
-use crate::peg_queue::{Error as PegQueueError, PegQueue, SbtcOp};
+use crate::metrics::{BURN_HEIGHT_LAG, LAST_PROCESSED_BURN_HEIGHT, OPS_PROCESSED};
+use crate::peg_queue::{
+    Entry as QueueEntry, Error as PegQueueError, Fulfillment, PegQueue, SbtcOp,
+};
 use crate::stacks_node::{Error as StacksNodeError, PegInOp, PegOutRequestOp, StacksNode};
+use crate::util::now;
 
-use tracing::{debug, info};
+use tracing::{debug, error, info, warn};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -19,8 +26,16 @@ pub enum Error {
     JsonError(#[from] serde_json::Error),
     #[error("Hex codec error: {0}")]
     HexError(#[from] HexError),
+    #[error("Bitcoin hex codec error: {0}")]
+    BitcoinHexError(#[from] bitcoin::hashes::hex::Error),
+    #[error("Bitcoin transaction codec error: {0}")]
+    BitcoinEncodeError(#[from] bitcoin::consensus::encode::Error),
     #[error("Did not recognize status: {0}")]
     InvalidStatusError(String),
+    #[error("Invalid status transition from {0} to {1}")]
+    InvalidStatusTransition(&'static str, &'static str),
+    #[error("Op {0} was already broadcast and cannot be handed out again")]
+    AlreadyBroadcast(Txid),
 }
 
 // Workaround to allow non-perfect conversions in `Entry::from_row`
@@ -32,6 +47,8 @@ impl From<Error> for rusqlite::Error {
 
 pub struct SqlitePegQueue {
     conn: rusqlite::Connection,
+    /// The number of burn blocks, including its own, an op's block must have before the op is handed out
+    min_confirmations: u64,
 }
 
 impl SqlitePegQueue {
@@ -39,22 +56,26 @@ impl SqlitePegQueue {
         path: P,
         start_block_height: Option<u64>,
         current_block_height: u64,
+        min_confirmations: u64,
     ) -> Result<Self, Error> {
         Self::from_connection(
             RusqliteConnection::open(path)?,
             start_block_height,
             current_block_height,
+            min_confirmations,
         )
     }
 
     pub fn in_memory(
         start_block_height: Option<u64>,
         current_block_height: u64,
+        min_confirmations: u64,
     ) -> Result<Self, Error> {
         Self::from_connection(
             RusqliteConnection::open_in_memory()?,
             start_block_height,
             current_block_height,
+            min_confirmations,
         )
     }
 
@@ -62,23 +83,48 @@ impl SqlitePegQueue {
         conn: RusqliteConnection,
         start_block_height: Option<u64>,
         current_block_height: u64,
+        min_confirmations: u64,
     ) -> Result<Self, Error> {
-        let this = Self { conn };
+        let this = Self {
+            conn,
+            min_confirmations,
+        };
         this.conn
             .execute(Self::create_sbtc_ops_table(), rusqlite::params![])?;
         this.conn
             .execute(Self::create_metadata_table(), rusqlite::params![])?;
-
-        // Prevent overflow by calling saturating sub to ensure we don't go below 0
-        if let Some(start_block_height) = start_block_height {
-            this.insert_last_processed_block_height(start_block_height.saturating_sub(1))?;
-        } else if this.last_processed_block_height().is_err() {
-            // If we don't have a last processed block height, set it to the current block height
-            this.insert_last_processed_block_height(current_block_height.saturating_sub(1))?;
+        this.conn
+            .execute(Self::create_burn_blocks_table(), rusqlite::params![])?;
+        this.conn.execute(
+            Self::create_fulfillment_transactions_table(),
+            rusqlite::params![],
+        )?;
+        this.migrate_sbtc_ops_table()?;
+
+        // Resume from the last processed block of a previous run. Rewinding would poll ops which
+        // were already acted upon again.
+        if let Ok(last_processed_block_height) = this.last_processed_block_height() {
+            if start_block_height.is_some() {
+                info!(
+                    "Ignoring start_block_height and resuming after block height {}",
+                    last_processed_block_height
+                );
+            }
+        } else {
+            // Prevent overflow by calling saturating sub to ensure we don't go below 0
+            let last_processed_block_height = start_block_height
+                .unwrap_or(current_block_height)
+                .saturating_sub(1);
+            this.insert_last_processed_block_height(last_processed_block_height)?;
         }
         Ok(this)
     }
 
+    /// Sets the number of burn block confirmations before an op is handed out
+    pub fn set_min_confirmations(&mut self, min_confirmations: u64) {
+        self.min_confirmations = min_confirmations;
+    }
+
     fn poll_peg_in_ops<N: StacksNode>(
         &self,
         stacks_node: &N,
@@ -91,8 +137,7 @@ impl SqlitePegQueue {
             Err(e) => return Err(PegQueueError::from(e)),
             Ok(peg_in_ops) => {
                 for peg_in_op in peg_in_ops {
-                    let entry = Entry::from(peg_in_op);
-                    self.insert(&entry)?;
+                    self.insert_polled(Entry::from(peg_in_op))?;
                 }
             }
         }
@@ -111,13 +156,138 @@ impl SqlitePegQueue {
             Err(e) => return Err(PegQueueError::from(e)),
             Ok(peg_out_request_ops) => {
                 for peg_out_request_op in peg_out_request_ops {
-                    let entry = Entry::from(peg_out_request_op);
-                    self.insert(&entry)?;
+                    self.insert_polled(Entry::from(peg_out_request_op))?;
                 }
             }
         }
         Ok(())
     }
+
+    /// Inserts a freshly polled entry. If the same op was already acted upon under a burn block
+    /// which has since been orphaned, the entry is flagged instead of being handed out again.
+    /// Entries polled before keep their progress.
+    fn insert_polled(&self, mut entry: Entry) -> Result<(), Error> {
+        if self.is_txid_flagged(&entry.txid)? {
+            warn!(
+                "Op {} was already processed on an orphaned burn block. Flagging for operator attention.",
+                entry.txid
+            );
+            entry.status = Status::Flagged;
+        }
+        if !self.insert_new(&entry)? {
+            debug!("Op {} is already queued", entry.txid);
+            return Ok(());
+        }
+        OPS_PROCESSED
+            .with_label_values(&[entry.status.as_str()])
+            .inc();
+        Ok(())
+    }
+
+    fn is_txid_flagged(&self, txid: &Txid) -> Result<bool, Error> {
+        Ok(self.conn.query_row(
+            Self::sql_count_txid_status(),
+            rusqlite::params![txid.to_hex(), Status::Flagged.as_str()],
+            |row| row.get::<_, i64>(0),
+        )? > 0)
+    }
+
+    /// Walks back from `block_height` to the highest height whose recorded burn header hash still
+    /// matches the node's canonical chain. Heights without a recorded hash are assumed to match.
+    fn find_fork_point<N: StacksNode>(
+        &self,
+        stacks_node: &N,
+        mut block_height: u64,
+    ) -> Result<u64, PegQueueError> {
+        while block_height > 0 {
+            let Some(burn_header_hash) = self.burn_header_hash(block_height)? else {
+                break;
+            };
+            let Some(canonical_burn_header_hash) =
+                Self::canonical_burn_header_hash(stacks_node, block_height)?
+            else {
+                break;
+            };
+            if canonical_burn_header_hash == burn_header_hash {
+                break;
+            }
+            debug!("Burn block {} is no longer canonical", block_height);
+            block_height -= 1;
+        }
+        Ok(block_height)
+    }
+
+    /// The node's burn header hash at `block_height`, or `None` if the node cannot look it up.
+    /// Reorg detection is skipped for heights without a hash.
+    fn canonical_burn_header_hash<N: StacksNode>(
+        stacks_node: &N,
+        block_height: u64,
+    ) -> Result<Option<BurnchainHeaderHash>, PegQueueError> {
+        match stacks_node.burn_header_hash(block_height) {
+            Ok(burn_header_hash) => Ok(Some(burn_header_hash)),
+            Err(StacksNodeError::UnsupportedRoute(route)) => {
+                debug!(
+                    "Skipping reorg detection at block height {}: {} is not served",
+                    block_height, route
+                );
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Rolls the queue back to `fork_point`, invalidating entries from orphaned burn blocks
+    fn roll_back(&self, fork_point: u64) -> Result<(), Error> {
+        for entry in self.get_entries_above_block_height(fork_point)? {
+            match entry.status {
+                Status::New | Status::Pending => {
+                    info!("Invalidating op {} from orphaned burn block", entry.txid);
+                    self.transition(
+                        &entry.txid,
+                        &entry.burn_header_hash,
+                        Status::Invalidated,
+                        |_| {},
+                    )?;
+                }
+                Status::StacksBroadcast | Status::BitcoinBroadcast | Status::Confirmed => {
+                    error!(
+                        "Op {} was processed on an orphaned burn block (stacks txid: {:?}, bitcoin txid: {:?}). Flagging for operator attention.",
+                        entry.txid, entry.stacks_txid, entry.bitcoin_txid
+                    );
+                    self.transition(
+                        &entry.txid,
+                        &entry.burn_header_hash,
+                        Status::Flagged,
+                        |_| {},
+                    )?;
+                }
+                Status::Failed | Status::Invalidated | Status::Flagged | Status::Rejected => {}
+            }
+        }
+        self.delete_burn_blocks_above(fork_point)?;
+        self.insert_last_processed_block_height(fork_point)
+    }
+
+    /// Adds the lifecycle columns to queues created before they existed
+    fn migrate_sbtc_ops_table(&self) -> Result<(), Error> {
+        let columns = self
+            .conn
+            .prepare(Self::sql_sbtc_ops_table_info())?
+            .query_map(rusqlite::params![], |row| row.get::<_, String>(1))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (column, definition) in Self::sbtc_ops_lifecycle_columns() {
+            if !columns.iter().any(|existing| existing == column) {
+                debug!("Migrating sbtc_ops table: adding column {}", column);
+                self.conn.execute(
+                    &format!("ALTER TABLE sbtc_ops ADD COLUMN {column} {definition}"),
+                    rusqlite::params![],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
     fn insert(&self, entry: &Entry) -> Result<(), Error> {
         self.conn.execute(
             Self::sql_insert(),
@@ -127,21 +297,133 @@ impl SqlitePegQueue {
                 entry.block_height as i64, // Stacks will crash before the coordinator if this is invalid
                 serde_json::to_string(&entry.op)?,
                 entry.status.as_str(),
+                entry.stacks_txid.map(|txid| txid.to_hex()),
+                entry.bitcoin_txid.map(|txid| txid.to_string()),
+                entry.attempts as i64,
+                entry.created_at as i64,
+                entry.updated_at as i64,
+                entry.bitcoin_broadcast_height.map(|height| height as i64),
+                entry.reason,
             ],
         )?;
 
         Ok(())
     }
 
-    fn get_single_entry_with_status(&self, status: &Status) -> Result<Option<Entry>, Error> {
+    /// Inserts an entry which was not queued yet, returning whether it was. An `Invalidated` entry
+    /// whose burn block became canonical again is handed out anew, since it was never acted upon.
+    fn insert_new(&self, entry: &Entry) -> Result<bool, Error> {
+        let inserted = self.conn.execute(
+            Self::sql_insert_new(),
+            rusqlite::params![
+                entry.txid.to_hex(),
+                entry.burn_header_hash.to_hex(),
+                entry.block_height as i64,
+                serde_json::to_string(&entry.op)?,
+                entry.status.as_str(),
+                entry.created_at as i64,
+                entry.updated_at as i64,
+                Status::Invalidated.as_str(),
+            ],
+        )?;
+
+        Ok(inserted > 0)
+    }
+
+    /// Moves the entry to `status` if the transition is valid, applying `update` before persisting it
+    fn transition(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+        status: Status,
+        update: impl FnOnce(&mut Entry),
+    ) -> Result<(), Error> {
+        let mut entry = self.get_entry(txid, burn_header_hash)?;
+
+        if !entry.status.can_transition_to(&status) {
+            return Err(Error::InvalidStatusTransition(
+                entry.status.as_str(),
+                status.as_str(),
+            ));
+        }
+
+        debug!(
+            "Moving op {} from {} to {}",
+            txid,
+            entry.status.as_str(),
+            status.as_str()
+        );
+        entry.status = status;
+        entry.updated_at = now();
+        update(&mut entry);
+        self.insert(&entry)?;
+        OPS_PROCESSED.with_label_values(&[status.as_str()]).inc();
+        Ok(())
+    }
+
+    fn get_entries_above_block_height(&self, block_height: u64) -> Result<Vec<Entry>, Error> {
         Ok(self
             .conn
-            .prepare(Self::sql_select_status())?
+            .prepare(Self::sql_select_above_block_height())?
+            .query_map(rusqlite::params![block_height as i64], Entry::from_row)?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn get_entries_with_status(&self, status: &Status) -> Result<Vec<Entry>, Error> {
+        Ok(self
+            .conn
+            .prepare(Self::sql_select_all_status())?
             .query_map(rusqlite::params![status.as_str()], Entry::from_row)?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn get_entries_with_txid(&self, txid: &Txid) -> Result<Vec<Entry>, Error> {
+        Ok(self
+            .conn
+            .prepare(Self::sql_select_txid())?
+            .query_map(rusqlite::params![txid.to_hex()], Entry::from_row)?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn get_entries_with_status_updated_before(
+        &self,
+        status: &Status,
+        updated_before: u64,
+    ) -> Result<Vec<Entry>, Error> {
+        Ok(self
+            .conn
+            .prepare(Self::sql_select_status_updated_before())?
+            .query_map(
+                rusqlite::params![status.as_str(), updated_before as i64],
+                Entry::from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Returns the first entry with the given status whose burn block is at or below `max_block_height`
+    fn get_single_entry_with_status(
+        &self,
+        status: &Status,
+        max_block_height: u64,
+    ) -> Result<Option<Entry>, Error> {
+        Ok(self
+            .conn
+            .prepare(Self::sql_select_status())?
+            .query_map(
+                rusqlite::params![status.as_str(), max_block_height as i64],
+                Entry::from_row,
+            )?
             .next()
             .transpose()?)
     }
 
+    /// The highest burn block height whose ops have reached `min_confirmations`,
+    /// or `None` if no processed block has matured yet
+    fn matured_block_height(&self) -> Result<Option<u64>, Error> {
+        let last_processed_block_height = self.last_processed_block_height()?;
+        Ok((last_processed_block_height + 1).checked_sub(self.min_confirmations.max(1)))
+    }
+
     fn get_entry(
         &self,
         txid: &Txid,
@@ -173,6 +455,87 @@ impl SqlitePegQueue {
         Ok(())
     }
 
+    fn burn_header_hash(&self, height: u64) -> Result<Option<BurnchainHeaderHash>, Error> {
+        let burn_header_hash = self
+            .conn
+            .prepare(Self::sql_select_burn_header_hash())?
+            .query_map(rusqlite::params![height as i64], |row| {
+                row.get::<_, String>(0)
+            })?
+            .next()
+            .transpose()?;
+
+        Ok(burn_header_hash
+            .map(|hash| BurnchainHeaderHash::from_hex(&hash))
+            .transpose()?)
+    }
+
+    fn insert_burn_header_hash(
+        &self,
+        height: u64,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<(), Error> {
+        self.conn.execute(
+            Self::sql_insert_burn_header_hash(),
+            rusqlite::params![height as i64, burn_header_hash.to_hex()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Records the fulfillment `transaction` of the ops, all of them or none
+    fn insert_fulfillment(
+        &self,
+        ops: &[(Txid, BurnchainHeaderHash)],
+        transaction: &bitcoin::Transaction,
+        broadcast_height: u64,
+    ) -> Result<(), Error> {
+        let bitcoin_txid = transaction.txid();
+        let db_transaction = self.conn.unchecked_transaction()?;
+        self.conn.execute(
+            Self::sql_insert_fulfillment_transaction(),
+            rusqlite::params![bitcoin_txid.to_string(), serialize_hex(transaction)],
+        )?;
+        for (txid, burn_header_hash) in ops {
+            self.transition(txid, burn_header_hash, Status::BitcoinBroadcast, |entry| {
+                entry.bitcoin_txid = Some(bitcoin_txid);
+                entry.bitcoin_broadcast_height = Some(broadcast_height);
+            })?;
+        }
+        db_transaction.commit()?;
+        Ok(())
+    }
+
+    /// The fulfillment transaction `bitcoin_txid`, if it was recorded before its broadcast
+    fn fulfillment_transaction(
+        &self,
+        bitcoin_txid: &bitcoin::Txid,
+    ) -> Result<Option<bitcoin::Transaction>, Error> {
+        let transaction = self
+            .conn
+            .prepare(Self::sql_select_fulfillment_transaction())?
+            .query_map(rusqlite::params![bitcoin_txid.to_string()], |row| {
+                row.get::<_, String>(0)
+            })?
+            .next()
+            .transpose()?;
+
+        Ok(transaction
+            .map(|transaction| Vec::<u8>::from_hex(&transaction))
+            .transpose()?
+            .map(|bytes| deserialize(&bytes))
+            .transpose()?)
+    }
+
+    fn delete_burn_blocks_above(&self, height: u64) -> Result<(), Error> {
+        self.conn.execute(
+            Self::sql_delete_burn_blocks_above(),
+            rusqlite::params![height as i64],
+        )?;
+
+        Ok(())
+    }
+
     const fn create_sbtc_ops_table() -> &'static str {
         r#"
         CREATE TABLE IF NOT EXISTS sbtc_ops (
@@ -181,12 +544,59 @@ impl SqlitePegQueue {
             block_height INTEGER NOT NULL,
             op TEXT NOT NULL,
             status TEXT NOT NULL,
+            stacks_txid TEXT,
+            bitcoin_txid TEXT,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL DEFAULT 0,
+            updated_at INTEGER NOT NULL DEFAULT 0,
+            bitcoin_broadcast_height INTEGER,
+            reason TEXT,
 
             PRIMARY KEY(txid, burn_header_hash)
         )
         "#
     }
 
+    const fn create_burn_blocks_table() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS burn_blocks (
+            block_height INTEGER NOT NULL,
+            burn_header_hash TEXT NOT NULL,
+
+            PRIMARY KEY(block_height)
+        )
+        "#
+    }
+
+    const fn create_fulfillment_transactions_table() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS fulfillment_transactions (
+            bitcoin_txid TEXT NOT NULL,
+            raw_transaction TEXT NOT NULL,
+
+            PRIMARY KEY(bitcoin_txid)
+        )
+        "#
+    }
+
+    const fn sbtc_ops_lifecycle_columns() -> [(&'static str, &'static str); 7] {
+        [
+            ("stacks_txid", "TEXT"),
+            ("bitcoin_txid", "TEXT"),
+            ("attempts", "INTEGER NOT NULL DEFAULT 0"),
+            ("created_at", "INTEGER NOT NULL DEFAULT 0"),
+            ("updated_at", "INTEGER NOT NULL DEFAULT 0"),
+            ("bitcoin_broadcast_height", "INTEGER"),
+            ("reason", "TEXT"),
+        ]
+    }
+
+    const fn sql_sbtc_ops_table_info() -> &'static str {
+        r#"
+        PRAGMA table_info(sbtc_ops)
+        "#
+    }
+
     const fn create_metadata_table() -> &'static str {
         r#"
         CREATE TABLE IF NOT EXISTS peg_queue_metadata (
@@ -201,19 +611,68 @@ impl SqlitePegQueue {
 
     const fn sql_insert() -> &'static str {
         r#"
-        REPLACE INTO sbtc_ops (txid, burn_header_hash, block_height, op, status) VALUES (?1, ?2, ?3, ?4, ?5)
+        REPLACE INTO sbtc_ops (txid, burn_header_hash, block_height, op, status, stacks_txid, bitcoin_txid, attempts, created_at, updated_at, bitcoin_broadcast_height, reason) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        "#
+    }
+
+    const fn sql_insert_new() -> &'static str {
+        r#"
+        INSERT INTO sbtc_ops (txid, burn_header_hash, block_height, op, status, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        ON CONFLICT(txid, burn_header_hash) DO UPDATE SET status=excluded.status, updated_at=excluded.updated_at WHERE sbtc_ops.status=?8
         "#
     }
 
     const fn sql_select_status() -> &'static str {
         r#"
-        SELECT txid, burn_header_hash, block_height, op, status FROM sbtc_ops WHERE status=?1 ORDER BY block_height, op ASC
+        SELECT txid, burn_header_hash, block_height, op, status, stacks_txid, bitcoin_txid, attempts, created_at, updated_at, bitcoin_broadcast_height, reason FROM sbtc_ops WHERE status=?1 AND block_height<=?2 ORDER BY block_height, op ASC
+        "#
+    }
+
+    const fn sql_select_all_status() -> &'static str {
+        r#"
+        SELECT txid, burn_header_hash, block_height, op, status, stacks_txid, bitcoin_txid, attempts, created_at, updated_at, bitcoin_broadcast_height, reason FROM sbtc_ops WHERE status=?1 ORDER BY block_height, op ASC
+        "#
+    }
+
+    const fn sql_select_txid() -> &'static str {
+        r#"
+        SELECT txid, burn_header_hash, block_height, op, status, stacks_txid, bitcoin_txid, attempts, created_at, updated_at, bitcoin_broadcast_height, reason FROM sbtc_ops WHERE txid=?1 ORDER BY block_height ASC
+        "#
+    }
+
+    const fn sql_replace_stacks_txid() -> &'static str {
+        r#"
+        UPDATE sbtc_ops SET stacks_txid=?1, updated_at=?2 WHERE status=?3 AND stacks_txid=?4
+        "#
+    }
+
+    const fn sql_replace_bitcoin_txid() -> &'static str {
+        r#"
+        UPDATE sbtc_ops SET bitcoin_txid=?1, bitcoin_broadcast_height=?2, updated_at=?3 WHERE status=?4 AND bitcoin_txid=?5
+        "#
+    }
+
+    const fn sql_select_above_block_height() -> &'static str {
+        r#"
+        SELECT txid, burn_header_hash, block_height, op, status, stacks_txid, bitcoin_txid, attempts, created_at, updated_at, bitcoin_broadcast_height, reason FROM sbtc_ops WHERE block_height>?1 ORDER BY block_height, op ASC
+        "#
+    }
+
+    const fn sql_count_txid_status() -> &'static str {
+        r#"
+        SELECT COUNT(*) FROM sbtc_ops WHERE txid=?1 AND status=?2
+        "#
+    }
+
+    const fn sql_select_status_updated_before() -> &'static str {
+        r#"
+        SELECT txid, burn_header_hash, block_height, op, status, stacks_txid, bitcoin_txid, attempts, created_at, updated_at, bitcoin_broadcast_height, reason FROM sbtc_ops WHERE status=?1 AND updated_at<=?2 ORDER BY block_height, op ASC
         "#
     }
 
     const fn sql_select_pk() -> &'static str {
         r#"
-        SELECT txid, burn_header_hash, block_height, op, status FROM sbtc_ops WHERE txid=?1 AND burn_header_hash=?2
+        SELECT txid, burn_header_hash, block_height, op, status, stacks_txid, bitcoin_txid, attempts, created_at, updated_at, bitcoin_broadcast_height, reason FROM sbtc_ops WHERE txid=?1 AND burn_header_hash=?2
         "#
     }
 
@@ -228,27 +687,82 @@ impl SqlitePegQueue {
             REPLACE INTO peg_queue_metadata (id, last_processed_block_height) VALUES ('peg_queue', ?1)
         "#
     }
+
+    const fn sql_select_burn_header_hash() -> &'static str {
+        r#"
+            SELECT burn_header_hash FROM burn_blocks WHERE block_height=?1
+        "#
+    }
+
+    const fn sql_insert_burn_header_hash() -> &'static str {
+        r#"
+            REPLACE INTO burn_blocks (block_height, burn_header_hash) VALUES (?1, ?2)
+        "#
+    }
+
+    const fn sql_delete_burn_blocks_above() -> &'static str {
+        r#"
+            DELETE FROM burn_blocks WHERE block_height>?1
+        "#
+    }
+
+    const fn sql_select_fulfillment_transaction() -> &'static str {
+        r#"
+            SELECT raw_transaction FROM fulfillment_transactions WHERE bitcoin_txid=?1
+        "#
+    }
+
+    const fn sql_insert_fulfillment_transaction() -> &'static str {
+        r#"
+            REPLACE INTO fulfillment_transactions (bitcoin_txid, raw_transaction) VALUES (?1, ?2)
+        "#
+    }
 }
 
 impl PegQueue for SqlitePegQueue {
     fn sbtc_op(&self) -> Result<Option<SbtcOp>, PegQueueError> {
-        let maybe_entry = self.get_single_entry_with_status(&Status::New)?;
+        let Some(matured_block_height) = self.matured_block_height()? else {
+            return Ok(None);
+        };
+        let maybe_entry = self.get_single_entry_with_status(&Status::New, matured_block_height)?;
 
         let Some(mut entry) = maybe_entry else {
-            return Ok(None)
+            return Ok(None);
         };
 
         entry.status = Status::Pending;
+        entry.attempts += 1;
+        entry.updated_at = now();
         self.insert(&entry)?;
+        OPS_PROCESSED
+            .with_label_values(&[Status::Pending.as_str()])
+            .inc();
 
         Ok(Some(entry.op))
     }
 
     fn poll<N: StacksNode>(&self, stacks_node: &N) -> Result<(), PegQueueError> {
         let target_block_height = stacks_node.burn_block_height()?;
-        let start_block_height = self.last_processed_block_height().map(|count| count + 1)?;
+        let last_processed_block_height = self.last_processed_block_height()?;
+
+        // Heights above the node's tip cannot be checked yet; the node may simply be lagging behind
+        let check_block_height = last_processed_block_height.min(target_block_height);
+        let fork_point = self.find_fork_point(stacks_node, check_block_height)?;
+        let start_block_height = if fork_point < check_block_height {
+            warn!(
+                "Burnchain reorg detected. Rolling back from block height {} to {}",
+                last_processed_block_height, fork_point
+            );
+            self.roll_back(fork_point)?;
+            fork_point + 1
+        } else {
+            last_processed_block_height + 1
+        };
 
         if start_block_height > target_block_height {
+            LAST_PROCESSED_BURN_HEIGHT.set(last_processed_block_height as i64);
+            BURN_HEIGHT_LAG
+                .set(target_block_height.saturating_sub(last_processed_block_height) as i64);
             info!("No new blocks to process");
             return Ok(());
         }
@@ -259,25 +773,208 @@ impl PegQueue for SqlitePegQueue {
         );
 
         for block_height in start_block_height..=target_block_height {
+            let burn_header_hash = Self::canonical_burn_header_hash(stacks_node, block_height)?;
             self.poll_peg_in_ops(stacks_node, block_height)?;
             self.poll_peg_out_request_ops(stacks_node, block_height)?;
+            if let Some(burn_header_hash) = burn_header_hash {
+                self.insert_burn_header_hash(block_height, &burn_header_hash)?;
+            }
             self.insert_last_processed_block_height(block_height)?;
+            LAST_PROCESSED_BURN_HEIGHT.set(block_height as i64);
+            BURN_HEIGHT_LAG.set((target_block_height - block_height) as i64);
             info!("Processed block height {}", block_height);
         }
         Ok(())
     }
 
+    fn stacks_broadcast(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+        stacks_txid: &Txid,
+    ) -> Result<(), PegQueueError> {
+        Ok(
+            self.transition(txid, burn_header_hash, Status::StacksBroadcast, |entry| {
+                entry.stacks_txid = Some(*stacks_txid)
+            })?,
+        )
+    }
+
+    fn bitcoin_broadcast(
+        &self,
+        ops: &[(Txid, BurnchainHeaderHash)],
+        transaction: &bitcoin::Transaction,
+        broadcast_height: u64,
+    ) -> Result<(), PegQueueError> {
+        Ok(self.insert_fulfillment(ops, transaction, broadcast_height)?)
+    }
+
+    fn fulfillments(&self) -> Result<Vec<Fulfillment>, PegQueueError> {
+        let mut fulfillments: Vec<Fulfillment> = vec![];
+        for entry in self.get_entries_with_status(&Status::BitcoinBroadcast)? {
+            let (Some(bitcoin_txid), Some(broadcast_height)) =
+                (entry.bitcoin_txid, entry.bitcoin_broadcast_height)
+            else {
+                warn!(
+                    "Op {} was broadcast without recording its fulfillment",
+                    entry.txid
+                );
+                continue;
+            };
+            let op = (entry.txid, entry.burn_header_hash);
+            match fulfillments
+                .iter_mut()
+                .find(|fulfillment| fulfillment.bitcoin_txid == bitcoin_txid)
+            {
+                Some(fulfillment) => fulfillment.ops.push(op),
+                None => fulfillments.push(Fulfillment {
+                    bitcoin_txid,
+                    transaction: self.fulfillment_transaction(&bitcoin_txid)?,
+                    broadcast_height,
+                    ops: vec![op],
+                }),
+            }
+        }
+        Ok(fulfillments)
+    }
+
+    fn replace_stacks_transaction(
+        &self,
+        stacks_txid: &Txid,
+        replacement_txid: &Txid,
+    ) -> Result<usize, PegQueueError> {
+        Ok(self
+            .conn
+            .execute(
+                Self::sql_replace_stacks_txid(),
+                rusqlite::params![
+                    replacement_txid.to_hex(),
+                    now() as i64,
+                    Status::StacksBroadcast.as_str(),
+                    stacks_txid.to_hex(),
+                ],
+            )
+            .map_err(Error::from)?)
+    }
+
+    fn replace_fulfillment(
+        &self,
+        bitcoin_txid: &bitcoin::Txid,
+        replacement_txid: &bitcoin::Txid,
+        broadcast_height: u64,
+    ) -> Result<usize, PegQueueError> {
+        Ok(self
+            .conn
+            .execute(
+                Self::sql_replace_bitcoin_txid(),
+                rusqlite::params![
+                    replacement_txid.to_string(),
+                    broadcast_height as i64,
+                    now() as i64,
+                    Status::BitcoinBroadcast.as_str(),
+                    bitcoin_txid.to_string(),
+                ],
+            )
+            .map_err(Error::from)?)
+    }
+
     fn acknowledge(
         &self,
         txid: &Txid,
         burn_header_hash: &BurnchainHeaderHash,
     ) -> Result<(), PegQueueError> {
-        let mut entry = self.get_entry(txid, burn_header_hash)?;
+        Ok(self.transition(txid, burn_header_hash, Status::Confirmed, |_| {})?)
+    }
 
-        entry.status = Status::Acknowledged;
-        self.insert(&entry)?;
+    fn reject(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+        reason: &str,
+    ) -> Result<(), PegQueueError> {
+        Ok(
+            self.transition(txid, burn_header_hash, Status::Rejected, |entry| {
+                entry.reason = Some(reason.to_string())
+            })?,
+        )
+    }
 
-        Ok(())
+    fn stacks_broadcasts(&self) -> Result<Vec<(SbtcOp, Txid)>, PegQueueError> {
+        Ok(self
+            .get_entries_with_status(&Status::StacksBroadcast)?
+            .into_iter()
+            .filter_map(|entry| match entry.stacks_txid {
+                Some(stacks_txid) => Some((entry.op, stacks_txid)),
+                None => {
+                    warn!(
+                        "Op {} was broadcast without recording its Stacks transaction",
+                        entry.txid
+                    );
+                    None
+                }
+            })
+            .collect())
+    }
+
+    fn flag(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<(), PegQueueError> {
+        Ok(self.transition(txid, burn_header_hash, Status::Flagged, |_| {})?)
+    }
+
+    fn fail(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<(), PegQueueError> {
+        Ok(self.transition(txid, burn_header_hash, Status::Failed, |_| {})?)
+    }
+
+    fn requeue_expired(&self, lease: Duration) -> Result<usize, PegQueueError> {
+        let expired_before = now().saturating_sub(lease.as_secs());
+        let expired =
+            self.get_entries_with_status_updated_before(&Status::Pending, expired_before)?;
+
+        for entry in &expired {
+            warn!(
+                "Requeuing op {} after its lease expired (attempts: {})",
+                entry.txid, entry.attempts
+            );
+            self.transition(&entry.txid, &entry.burn_header_hash, Status::New, |_| {})?;
+        }
+        Ok(expired.len())
+    }
+
+    fn requeue(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<(), PegQueueError> {
+        // Handing out an op whose transaction was broadcast would mint or pay it out again
+        let entry = self.get_entry(txid, burn_header_hash)?;
+        if entry.stacks_txid.is_some() || entry.bitcoin_txid.is_some() {
+            return Err(Error::AlreadyBroadcast(*txid).into());
+        }
+        info!("Requeuing op {} at an operator's request", txid);
+        Ok(self.transition(txid, burn_header_hash, Status::New, |_| {})?)
+    }
+
+    fn entries(&self, status: Status) -> Result<Vec<QueueEntry>, PegQueueError> {
+        Ok(self
+            .get_entries_with_status(&status)?
+            .into_iter()
+            .map(QueueEntry::from)
+            .collect())
+    }
+
+    fn entries_by_txid(&self, txid: &Txid) -> Result<Vec<QueueEntry>, PegQueueError> {
+        Ok(self
+            .get_entries_with_txid(txid)?
+            .into_iter()
+            .map(QueueEntry::from)
+            .collect())
     }
 }
 
@@ -288,6 +985,14 @@ struct Entry {
     block_height: u64,
     op: SbtcOp,
     status: Status,
+    stacks_txid: Option<Txid>,
+    bitcoin_txid: Option<bitcoin::Txid>,
+    attempts: u32,
+    created_at: u64,
+    updated_at: u64,
+    bitcoin_broadcast_height: Option<u64>,
+    /// Why the op was rejected
+    reason: Option<String>,
 }
 
 impl Entry {
@@ -303,55 +1008,151 @@ impl Entry {
 
         let status: Status = row.get::<_, String>(4)?.parse()?;
 
+        let stacks_txid = row
+            .get::<_, Option<String>>(5)?
+            .map(|txid| Txid::from_hex(&txid))
+            .transpose()
+            .map_err(Error::from)?;
+
+        let bitcoin_txid = row
+            .get::<_, Option<String>>(6)?
+            .map(|txid| bitcoin::Txid::from_str(&txid))
+            .transpose()
+            .map_err(Error::from)?;
+
+        let attempts = row.get::<_, i64>(7)? as u32;
+        let created_at = row.get::<_, i64>(8)? as u64;
+        let updated_at = row.get::<_, i64>(9)? as u64;
+        let bitcoin_broadcast_height = row.get::<_, Option<i64>>(10)?.map(|height| height as u64);
+        let reason = row.get::<_, Option<String>>(11)?;
+
         Ok(Self {
             burn_header_hash,
             txid,
             block_height,
             op,
             status,
+            stacks_txid,
+            bitcoin_txid,
+            attempts,
+            created_at,
+            updated_at,
+            bitcoin_broadcast_height,
+            reason,
         })
     }
 }
 
+impl From<SbtcOp> for Entry {
+    fn from(op: SbtcOp) -> Self {
+        let now = now();
+        Self {
+            block_height: match &op {
+                SbtcOp::PegIn(op) => op.block_height,
+                SbtcOp::PegOutRequest(op) => op.block_height,
+            },
+            status: Status::New,
+            txid: *op.txid(),
+            burn_header_hash: *op.burn_header_hash(),
+            op,
+            stacks_txid: None,
+            bitcoin_txid: None,
+            attempts: 0,
+            created_at: now,
+            updated_at: now,
+            bitcoin_broadcast_height: None,
+            reason: None,
+        }
+    }
+}
+
+impl From<Entry> for QueueEntry {
+    fn from(entry: Entry) -> Self {
+        Self {
+            op: entry.op,
+            block_height: entry.block_height,
+            status: entry.status,
+            stacks_txid: entry.stacks_txid,
+            bitcoin_txid: entry.bitcoin_txid,
+            attempts: entry.attempts,
+            created_at: entry.created_at,
+            updated_at: entry.updated_at,
+            reason: entry.reason,
+        }
+    }
+}
+
 impl From<PegInOp> for Entry {
     fn from(op: PegInOp) -> Self {
-        Self {
-            block_height: op.block_height,
-            status: Status::New,
-            txid: op.txid,
-            burn_header_hash: op.burn_header_hash,
-            op: SbtcOp::PegIn(op),
-        }
+        Self::from(SbtcOp::PegIn(op))
     }
 }
 
 impl From<PegOutRequestOp> for Entry {
     fn from(op: PegOutRequestOp) -> Self {
-        Self {
-            block_height: op.block_height,
-            status: Status::New,
-            txid: op.txid,
-            burn_header_hash: op.burn_header_hash,
-            op: SbtcOp::PegOutRequest(op),
-        }
+        Self::from(SbtcOp::PegOutRequest(op))
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum Status {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
     New,
     Pending,
-    Acknowledged,
+    StacksBroadcast,
+    BitcoinBroadcast,
+    Confirmed,
+    Failed,
+    /// The op's burn block was orphaned before the op was acted upon
+    Invalidated,
+    /// The op was acted upon but its burn block was orphaned. Requires operator attention.
+    Flagged,
+    /// The op's Stacks transaction was not accepted by the chain
+    Rejected,
 }
 
 impl Status {
-    fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &'static str {
         match self {
             Self::New => "new",
             Self::Pending => "pending",
-            Self::Acknowledged => "acknowledged",
+            Self::StacksBroadcast => "stacks-broadcast",
+            Self::BitcoinBroadcast => "bitcoin-broadcast",
+            Self::Confirmed => "confirmed",
+            Self::Failed => "failed",
+            Self::Invalidated => "invalidated",
+            Self::Flagged => "flagged",
+            Self::Rejected => "rejected",
         }
     }
+
+    fn can_transition_to(&self, next: &Status) -> bool {
+        matches!(
+            (self, next),
+            (Self::New, Self::Pending)
+                | (Self::Pending, Self::New)
+                // Operators may hand out ops which did not go through again
+                | (Self::Failed | Self::Flagged | Self::Rejected, Self::New)
+                | (Self::Pending, Self::StacksBroadcast)
+                // A dropped Stacks transaction is broadcast again
+                | (Self::StacksBroadcast, Self::StacksBroadcast)
+                | (Self::StacksBroadcast, Self::BitcoinBroadcast)
+                // Requests are rejected up front, before broadcast, or when their transaction aborts
+                | (Self::Pending | Self::StacksBroadcast, Self::Rejected)
+                | (
+                    Self::Pending | Self::StacksBroadcast | Self::BitcoinBroadcast,
+                    Self::Confirmed
+                )
+                | (
+                    Self::New | Self::Pending | Self::StacksBroadcast | Self::BitcoinBroadcast,
+                    Self::Failed
+                )
+                | (Self::New | Self::Pending, Self::Invalidated)
+                | (
+                    Self::StacksBroadcast | Self::BitcoinBroadcast | Self::Confirmed,
+                    Self::Flagged
+                )
+        )
+    }
 }
 
 impl FromStr for Status {
@@ -360,7 +1161,14 @@ impl FromStr for Status {
         Ok(match s {
             "new" => Self::New,
             "pending" => Self::Pending,
-            "acknowledged" => Self::Acknowledged,
+            "stacks-broadcast" => Self::StacksBroadcast,
+            "bitcoin-broadcast" => Self::BitcoinBroadcast,
+            // Queues written before the lifecycle existed used "acknowledged" for completed ops
+            "confirmed" | "acknowledged" => Self::Confirmed,
+            "failed" => Self::Failed,
+            "invalidated" => Self::Invalidated,
+            "flagged" => Self::Flagged,
+            "rejected" => Self::Rejected,
             other => return Err(Error::InvalidStatusError(other.to_owned())),
         })
     }
@@ -383,7 +1191,7 @@ mod tests {
 
     #[test]
     fn calling_sbtc_op_should_return_new_peg_ops() {
-        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2).unwrap();
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
         let number_of_simulated_blocks: u64 = 3;
 
         let stacks_node_mock = default_stacks_node_mock(number_of_simulated_blocks);
@@ -407,7 +1215,7 @@ mod tests {
 
     #[test]
     fn calling_poll_should_not_query_new_ops_if_at_block_height() {
-        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2).unwrap();
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
         let number_of_simulated_blocks: u64 = 3;
 
         let stacks_node_mock = default_stacks_node_mock(number_of_simulated_blocks);
@@ -425,6 +1233,10 @@ mod tests {
             .expect_burn_block_height()
             .returning(move || Ok(number_of_simulated_blocks));
 
+        stacks_node_mock
+            .expect_burn_header_hash()
+            .returning(|height| Ok(burn_header_hash(height, 0)));
+
         stacks_node_mock.expect_get_peg_in_ops().never();
         stacks_node_mock.expect_get_peg_out_request_ops().never();
 
@@ -433,7 +1245,7 @@ mod tests {
 
     #[test]
     fn calling_poll_should_find_new_ops_if_at_new_block_height() {
-        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2).unwrap();
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
         let number_of_simulated_blocks: u64 = 3;
         let number_of_simulated_blocks_second_poll: u64 = 5;
 
@@ -461,8 +1273,8 @@ mod tests {
     }
 
     #[test]
-    fn acknowledged_entries_should_have_acknowledge_status() {
-        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2).unwrap();
+    fn acknowledged_entries_should_have_confirmed_status() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
         let number_of_simulated_blocks: u64 = 1;
 
         let stacks_node_mock = default_stacks_node_mock(number_of_simulated_blocks);
@@ -478,7 +1290,384 @@ mod tests {
             .get_entry(&peg_in_op.txid, &peg_in_op.burn_header_hash)
             .unwrap();
 
-        assert_eq!(entry.status, Status::Acknowledged);
+        assert_eq!(entry.status, Status::Confirmed);
+    }
+
+    #[test]
+    fn transitions_should_record_txids_and_attempts() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let stacks_node_mock = default_stacks_node_mock(1);
+        peg_queue.poll(&stacks_node_mock).unwrap();
+
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        let (txid, burn_header_hash) = (*next_op.txid(), *next_op.burn_header_hash());
+
+        let entry = peg_queue.get_entry(&txid, &burn_header_hash).unwrap();
+        assert_eq!(entry.status, Status::Pending);
+        assert_eq!(entry.attempts, 1);
+
+        let stacks_txid = Txid([7; 32]);
+        peg_queue
+            .stacks_broadcast(&txid, &burn_header_hash, &stacks_txid)
+            .unwrap();
+        let transaction = fulfillment_transaction();
+        let bitcoin_txid = transaction.txid();
+        peg_queue
+            .bitcoin_broadcast(&[(txid, burn_header_hash)], &transaction, 100)
+            .unwrap();
+
+        let entry = peg_queue.get_entry(&txid, &burn_header_hash).unwrap();
+        assert_eq!(entry.status, Status::BitcoinBroadcast);
+        assert_eq!(entry.stacks_txid, Some(stacks_txid));
+        assert_eq!(entry.bitcoin_txid, Some(bitcoin_txid));
+        assert_eq!(entry.bitcoin_broadcast_height, Some(100));
+        assert!(entry.updated_at >= entry.created_at);
+    }
+
+    #[test]
+    fn fulfillments_should_be_grouped_and_replaceable() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let stacks_node_mock = default_stacks_node_mock(1);
+        peg_queue.poll(&stacks_node_mock).unwrap();
+
+        let transaction = fulfillment_transaction();
+        let bitcoin_txid = transaction.txid();
+        let mut ops = vec![];
+        while let Some(op) = peg_queue.sbtc_op().unwrap() {
+            let (txid, burn_header_hash) = (*op.txid(), *op.burn_header_hash());
+            peg_queue
+                .stacks_broadcast(&txid, &burn_header_hash, &Txid([7; 32]))
+                .unwrap();
+            ops.push((txid, burn_header_hash));
+        }
+        peg_queue
+            .bitcoin_broadcast(&ops, &transaction, 100)
+            .unwrap();
+        assert_eq!(ops.len(), 2);
+
+        let fulfillments = peg_queue.fulfillments().unwrap();
+        assert_eq!(
+            fulfillments,
+            vec![Fulfillment {
+                bitcoin_txid,
+                transaction: Some(transaction),
+                broadcast_height: 100,
+                ops: ops.clone(),
+            }]
+        );
+
+        let replacement_txid = bitcoin::Txid::from_str(
+            "5d1ab6a1b4b9e4a3e6a8a08e8c4a1a9a0d4c2c7e9f6b3d2a1e0f9c8b7a6d5e4f",
+        )
+        .unwrap();
+        assert_eq!(
+            peg_queue
+                .replace_fulfillment(&bitcoin_txid, &replacement_txid, 106)
+                .unwrap(),
+            2
+        );
+        let fulfillments = peg_queue.fulfillments().unwrap();
+        assert_eq!(
+            fulfillments,
+            vec![Fulfillment {
+                bitcoin_txid: replacement_txid,
+                transaction: None,
+                broadcast_height: 106,
+                ops,
+            }]
+        );
+    }
+
+    #[test]
+    fn fulfillments_should_be_recorded_for_all_ops_or_none() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let stacks_node_mock = default_stacks_node_mock(1);
+        peg_queue.poll(&stacks_node_mock).unwrap();
+
+        // Only the first op was burned
+        let mut ops = vec![];
+        while let Some(op) = peg_queue.sbtc_op().unwrap() {
+            ops.push((*op.txid(), *op.burn_header_hash()));
+        }
+        peg_queue
+            .stacks_broadcast(&ops[0].0, &ops[0].1, &Txid([7; 32]))
+            .unwrap();
+
+        let transaction = fulfillment_transaction();
+        assert!(peg_queue
+            .bitcoin_broadcast(&ops, &transaction, 100)
+            .is_err());
+        let entry = peg_queue.get_entry(&ops[0].0, &ops[0].1).unwrap();
+        assert_eq!(entry.status, Status::StacksBroadcast);
+        assert_eq!(entry.bitcoin_txid, None);
+        assert!(peg_queue.fulfillments().unwrap().is_empty());
+        assert!(peg_queue
+            .fulfillment_transaction(&transaction.txid())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn settled_fulfillments_should_no_longer_be_tracked() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let stacks_node_mock = default_stacks_node_mock(1);
+        peg_queue.poll(&stacks_node_mock).unwrap();
+
+        let transaction = fulfillment_transaction();
+        let bitcoin_txid = transaction.txid();
+        let mut ops = vec![];
+        while let Some(op) = peg_queue.sbtc_op().unwrap() {
+            let (txid, burn_header_hash) = (*op.txid(), *op.burn_header_hash());
+            peg_queue
+                .stacks_broadcast(&txid, &burn_header_hash, &Txid([7; 32]))
+                .unwrap();
+            ops.push((txid, burn_header_hash));
+        }
+        peg_queue
+            .bitcoin_broadcast(&ops, &transaction, 100)
+            .unwrap();
+        assert_eq!(peg_queue.fulfillments().unwrap()[0].ops.len(), 2);
+
+        peg_queue.acknowledge(&ops[0].0, &ops[0].1).unwrap();
+        peg_queue.flag(&ops[1].0, &ops[1].1).unwrap();
+        assert!(peg_queue.fulfillments().unwrap().is_empty());
+
+        let entry = peg_queue.get_entry(&ops[1].0, &ops[1].1).unwrap();
+        assert_eq!(entry.status, Status::Flagged);
+    }
+
+    #[test]
+    fn rejected_entries_should_record_reason() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let stacks_node_mock = default_stacks_node_mock(1);
+        peg_queue.poll(&stacks_node_mock).unwrap();
+
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        let (txid, burn_header_hash) = (*next_op.txid(), *next_op.burn_header_hash());
+        peg_queue
+            .stacks_broadcast(&txid, &burn_header_hash, &Txid([7; 32]))
+            .unwrap();
+
+        let broadcasts = peg_queue.stacks_broadcasts().unwrap();
+        assert_eq!(broadcasts.len(), 1);
+        assert_eq!(broadcasts[0].0.txid(), &txid);
+        assert_eq!(broadcasts[0].1, Txid([7; 32]));
+
+        // A dropped transaction may be broadcast again
+        peg_queue
+            .stacks_broadcast(&txid, &burn_header_hash, &Txid([8; 32]))
+            .unwrap();
+        assert_eq!(peg_queue.stacks_broadcasts().unwrap()[0].1, Txid([8; 32]));
+
+        // A stuck transaction may be replaced with a higher fee
+        assert_eq!(
+            peg_queue
+                .replace_stacks_transaction(&Txid([8; 32]), &Txid([9; 32]))
+                .unwrap(),
+            1
+        );
+        assert_eq!(peg_queue.stacks_broadcasts().unwrap()[0].1, Txid([9; 32]));
+
+        peg_queue
+            .reject(&txid, &burn_header_hash, "abort_by_response (err u1)")
+            .unwrap();
+        assert!(peg_queue.stacks_broadcasts().unwrap().is_empty());
+
+        let entry = peg_queue.get_entry(&txid, &burn_header_hash).unwrap();
+        assert_eq!(entry.status, Status::Rejected);
+        assert_eq!(entry.reason.as_deref(), Some("abort_by_response (err u1)"));
+    }
+
+    #[test]
+    fn pending_entries_should_be_rejectable() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let stacks_node_mock = default_stacks_node_mock(1);
+        peg_queue.poll(&stacks_node_mock).unwrap();
+
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        let (txid, burn_header_hash) = (*next_op.txid(), *next_op.burn_header_hash());
+        peg_queue
+            .reject(&txid, &burn_header_hash, "insufficient sBTC balance")
+            .unwrap();
+
+        let entry = peg_queue.get_entry(&txid, &burn_header_hash).unwrap();
+        assert_eq!(entry.status, Status::Rejected);
+        assert_eq!(entry.reason.as_deref(), Some("insufficient sBTC balance"));
+        assert!(peg_queue.stacks_broadcasts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_transitions_should_be_rejected() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let stacks_node_mock = default_stacks_node_mock(1);
+        peg_queue.poll(&stacks_node_mock).unwrap();
+
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        let (txid, burn_header_hash) = (*next_op.txid(), *next_op.burn_header_hash());
+
+        peg_queue.fail(&txid, &burn_header_hash).unwrap();
+
+        let result = peg_queue.stacks_broadcast(&txid, &burn_header_hash, &Txid([7; 32]));
+        assert!(matches!(
+            result,
+            Err(PegQueueError::SqlitePegQueueError(
+                Error::InvalidStatusTransition(..)
+            ))
+        ));
+    }
+
+    #[test]
+    fn failed_entries_should_be_listable_and_requeueable() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let stacks_node_mock = default_stacks_node_mock(1);
+        peg_queue.poll(&stacks_node_mock).unwrap();
+
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        let (txid, burn_header_hash) = (*next_op.txid(), *next_op.burn_header_hash());
+        peg_queue.fail(&txid, &burn_header_hash).unwrap();
+
+        let failed = peg_queue.entries(Status::Failed).unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].op.txid(), &txid);
+
+        peg_queue.requeue(&txid, &burn_header_hash).unwrap();
+        assert!(peg_queue.entries(Status::Failed).unwrap().is_empty());
+
+        let entries = peg_queue.entries_by_txid(&txid).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].status, Status::New);
+        assert!(peg_queue
+            .entries_by_txid(&Txid([7; 32]))
+            .unwrap()
+            .is_empty());
+
+        // Only ops which did not go through may be requeued
+        assert!(peg_queue.requeue(&txid, &burn_header_hash).is_err());
+    }
+
+    #[test]
+    fn broadcast_entries_should_not_be_requeued() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let stacks_node_mock = default_stacks_node_mock(1);
+        peg_queue.poll(&stacks_node_mock).unwrap();
+
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        let (txid, burn_header_hash) = (*next_op.txid(), *next_op.burn_header_hash());
+        peg_queue
+            .stacks_broadcast(&txid, &burn_header_hash, &Txid([9; 32]))
+            .unwrap();
+        // An operator skipping the op does not make it safe to mint again
+        peg_queue.fail(&txid, &burn_header_hash).unwrap();
+
+        assert!(matches!(
+            peg_queue.requeue(&txid, &burn_header_hash),
+            Err(PegQueueError::SqlitePegQueueError(Error::AlreadyBroadcast(
+                _
+            )))
+        ));
+        let entries = peg_queue.entries_by_txid(&txid).unwrap();
+        assert_eq!(entries[0].status, Status::Failed);
+        assert_eq!(entries[0].stacks_txid, Some(Txid([9; 32])));
+    }
+
+    #[test]
+    fn expired_pending_entries_should_be_requeued() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let stacks_node_mock = default_stacks_node_mock(1);
+        peg_queue.poll(&stacks_node_mock).unwrap();
+
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        let (txid, burn_header_hash) = (*next_op.txid(), *next_op.burn_header_hash());
+
+        // The lease has not expired yet
+        assert_eq!(
+            peg_queue
+                .requeue_expired(Duration::from_secs(3600))
+                .unwrap(),
+            0
+        );
+
+        assert_eq!(peg_queue.requeue_expired(Duration::ZERO).unwrap(), 1);
+        let entry = peg_queue.get_entry(&txid, &burn_header_hash).unwrap();
+        assert_eq!(entry.status, Status::New);
+
+        // The requeued op is handed out again and its attempts are counted
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        assert_eq!(next_op.txid(), &txid);
+        let entry = peg_queue.get_entry(&txid, &burn_header_hash).unwrap();
+        assert_eq!(entry.attempts, 2);
+    }
+
+    #[test]
+    fn legacy_sbtc_ops_table_should_be_migrated() {
+        let conn = RusqliteConnection::open_in_memory().unwrap();
+        conn.execute(
+            r#"
+            CREATE TABLE sbtc_ops (
+                txid TEXT NOT NULL,
+                burn_header_hash TEXT NOT NULL,
+                block_height INTEGER NOT NULL,
+                op TEXT NOT NULL,
+                status TEXT NOT NULL,
+
+                PRIMARY KEY(txid, burn_header_hash)
+            )
+            "#,
+            rusqlite::params![],
+        )
+        .unwrap();
+        let op = peg_in_op(1);
+        conn.execute(
+            "INSERT INTO sbtc_ops (txid, burn_header_hash, block_height, op, status) VALUES (?1, ?2, ?3, ?4, 'acknowledged')",
+            rusqlite::params![
+                op.txid.to_hex(),
+                op.burn_header_hash.to_hex(),
+                1i64,
+                serde_json::to_string(&SbtcOp::PegIn(op.clone())).unwrap(),
+            ],
+        )
+        .unwrap();
+
+        let peg_queue = SqlitePegQueue::from_connection(conn, Some(1), 2, 1).unwrap();
+        let entry = peg_queue.get_entry(&op.txid, &op.burn_header_hash).unwrap();
+        assert_eq!(entry.status, Status::Confirmed);
+        assert_eq!(entry.attempts, 0);
+        assert_eq!(entry.stacks_txid, None);
+    }
+
+    #[test]
+    fn restart_should_not_repoll_or_reset_processed_ops() {
+        let dir = tempdir::TempDir::new("").unwrap();
+        let path = dir.path().join("peg_queue.sqlite");
+        let stacks_node_mock = default_stacks_node_mock(1);
+
+        let peg_queue = SqlitePegQueue::new(&path, Some(1), 2, 1).unwrap();
+        peg_queue.poll(&stacks_node_mock).unwrap();
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        let (txid, burn_header_hash) = (*next_op.txid(), *next_op.burn_header_hash());
+        let stacks_txid = Txid([7; 32]);
+        peg_queue
+            .stacks_broadcast(&txid, &burn_header_hash, &stacks_txid)
+            .unwrap();
+        peg_queue.acknowledge(&txid, &burn_header_hash).unwrap();
+        drop(peg_queue);
+
+        // A configured start_block_height does not rewind a queue which already processed blocks
+        let peg_queue = SqlitePegQueue::new(&path, Some(1), 2, 1).unwrap();
+        assert_eq!(peg_queue.last_processed_block_height().unwrap(), 1);
+
+        // Polling the block again keeps the op's progress
+        peg_queue.insert_last_processed_block_height(0).unwrap();
+        peg_queue.poll(&stacks_node_mock).unwrap();
+        let entry = peg_queue.get_entry(&txid, &burn_header_hash).unwrap();
+        assert_eq!(entry.status, Status::Confirmed);
+        assert_eq!(entry.stacks_txid, Some(stacks_txid));
+        assert_eq!(entry.attempts, 1);
+
+        // The confirmed op is not handed out again, only the op which was never taken
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        assert_ne!(next_op.txid(), &txid);
+        assert!(peg_queue.sbtc_op().unwrap().is_none());
     }
 
     #[test]
@@ -488,7 +1677,8 @@ mod tests {
         let second_poll_node_block_height: u64 = 40;
 
         let peg_queue =
-            SqlitePegQueue::in_memory(Some(start_block_height), initial_node_block_height).unwrap();
+            SqlitePegQueue::in_memory(Some(start_block_height), initial_node_block_height, 1)
+                .unwrap();
 
         let stacks_node_mock = default_stacks_node_mock(initial_node_block_height);
         peg_queue.poll(&stacks_node_mock).unwrap();
@@ -501,6 +1691,181 @@ mod tests {
         assert_eq!(peg_queue.last_processed_block_height().unwrap(), 40);
     }
 
+    #[test]
+    fn reorg_should_invalidate_orphaned_entries_and_repoll() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let number_of_simulated_blocks: u64 = 3;
+
+        peg_queue
+            .poll(&default_stacks_node_mock(number_of_simulated_blocks))
+            .unwrap();
+
+        // Process the ops of the first two blocks and start processing the peg in of the third
+        for _ in 1..number_of_simulated_blocks {
+            peg_queue.sbtc_op().unwrap().unwrap();
+            peg_queue.sbtc_op().unwrap().unwrap();
+        }
+        let processed_op = peg_queue.sbtc_op().unwrap().unwrap();
+        let processed_op = processed_op.as_peg_in().unwrap();
+        assert_eq!(processed_op.block_height, 3);
+        peg_queue
+            .stacks_broadcast(
+                &processed_op.txid,
+                &processed_op.burn_header_hash,
+                &Txid([7; 32]),
+            )
+            .unwrap();
+
+        // Block 3 is replaced by a competing block and block 4 is mined on top of it
+        let fork_point = 2;
+        peg_queue
+            .poll(&reorged_stacks_node_mock(4, fork_point))
+            .unwrap();
+        assert_eq!(peg_queue.last_processed_block_height().unwrap(), 4);
+
+        // The processed peg in is flagged and the unprocessed peg out is invalidated
+        let entry = peg_queue
+            .get_entry(&processed_op.txid, &processed_op.burn_header_hash)
+            .unwrap();
+        assert_eq!(entry.status, Status::Flagged);
+        let orphaned_peg_out = peg_out_request_op(3);
+        let entry = peg_queue
+            .get_entry(&orphaned_peg_out.txid, &orphaned_peg_out.burn_header_hash)
+            .unwrap();
+        assert_eq!(entry.status, Status::Invalidated);
+
+        // The peg in reappears in the new canonical block 3 and must not be processed twice
+        let entry = peg_queue
+            .get_entry(&processed_op.txid, &burn_header_hash(3, 1))
+            .unwrap();
+        assert_eq!(entry.status, Status::Flagged);
+
+        // Only the peg out of the new block 3 and the ops of block 4 are handed out
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        assert_eq!(next_op.as_peg_out_request().unwrap().block_height, 3);
+        assert_eq!(next_op.burn_header_hash(), &burn_header_hash(3, 1));
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        assert_eq!(next_op.as_peg_in().unwrap().block_height, 4);
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        assert_eq!(next_op.as_peg_out_request().unwrap().block_height, 4);
+        assert!(peg_queue.sbtc_op().unwrap().is_none());
+    }
+
+    #[test]
+    fn reorg_should_leave_settled_entries_alone() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        peg_queue.poll(&default_stacks_node_mock(3)).unwrap();
+
+        // Settle the ops of block 3 with every status a reorg leaves unchanged
+        for _ in 1..3 {
+            peg_queue.sbtc_op().unwrap().unwrap();
+            peg_queue.sbtc_op().unwrap().unwrap();
+        }
+        let rejected_op = peg_queue.sbtc_op().unwrap().unwrap();
+        peg_queue
+            .reject(
+                rejected_op.txid(),
+                rejected_op.burn_header_hash(),
+                "no deposit",
+            )
+            .unwrap();
+        let failed_op = peg_queue.sbtc_op().unwrap().unwrap();
+        peg_queue
+            .fail(failed_op.txid(), failed_op.burn_header_hash())
+            .unwrap();
+
+        peg_queue.poll(&reorged_stacks_node_mock(4, 2)).unwrap();
+        assert_eq!(peg_queue.last_processed_block_height().unwrap(), 4);
+
+        for (op, status) in [(rejected_op, Status::Rejected), (failed_op, Status::Failed)] {
+            let entry = peg_queue
+                .get_entry(op.txid(), op.burn_header_hash())
+                .unwrap();
+            assert_eq!(entry.status, status);
+        }
+    }
+
+    #[test]
+    fn sbtc_op_should_only_return_matured_ops() {
+        let min_confirmations = 3;
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, min_confirmations).unwrap();
+
+        // Only block 1 has 3 confirmations
+        peg_queue.poll(&default_stacks_node_mock(3)).unwrap();
+        for _ in 0..2 {
+            let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+            assert_eq!(next_op.burn_header_hash(), &burn_header_hash(1, 0));
+        }
+        assert!(peg_queue.sbtc_op().unwrap().is_none());
+
+        // Block 2 matures once block 4 is mined
+        peg_queue.poll(&default_stacks_node_mock(4)).unwrap();
+        for _ in 0..2 {
+            let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+            assert_eq!(next_op.burn_header_hash(), &burn_header_hash(2, 0));
+        }
+        assert!(peg_queue.sbtc_op().unwrap().is_none());
+    }
+
+    #[test]
+    fn sbtc_op_should_return_nothing_before_any_block_matured() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 6).unwrap();
+
+        peg_queue.poll(&default_stacks_node_mock(5)).unwrap();
+        assert!(peg_queue.sbtc_op().unwrap().is_none());
+
+        peg_queue.poll(&default_stacks_node_mock(6)).unwrap();
+        assert!(peg_queue.sbtc_op().unwrap().is_some());
+    }
+
+    #[test]
+    fn lagging_node_should_not_be_mistaken_for_reorg() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+
+        peg_queue.poll(&default_stacks_node_mock(5)).unwrap();
+
+        // The node reports a lower tip, but the blocks it knows about are unchanged
+        peg_queue.poll(&default_stacks_node_mock(3)).unwrap();
+        assert_eq!(peg_queue.last_processed_block_height().unwrap(), 5);
+
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        let entry = peg_queue
+            .get_entry(next_op.txid(), next_op.burn_header_hash())
+            .unwrap();
+        assert_eq!(entry.status, Status::Pending);
+    }
+
+    #[test]
+    fn node_without_sortitions_api_should_still_be_polled() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+
+        let mut stacks_node_mock = stacks_node::MockStacksNode::new();
+        stacks_node_mock
+            .expect_burn_block_height()
+            .returning(|| Ok(3));
+        stacks_node_mock.expect_burn_header_hash().returning(|_| {
+            Err(StacksNodeError::UnsupportedRoute(
+                "/v3/sortitions".to_string(),
+            ))
+        });
+        stacks_node_mock
+            .expect_get_peg_in_ops()
+            .returning(|height| Ok(vec![peg_in_op(height)]));
+        stacks_node_mock
+            .expect_get_peg_out_request_ops()
+            .returning(|height| Ok(vec![peg_out_request_op(height)]));
+
+        peg_queue.poll(&stacks_node_mock).unwrap();
+        peg_queue.poll(&stacks_node_mock).unwrap();
+        assert_eq!(peg_queue.last_processed_block_height().unwrap(), 3);
+        assert!(peg_queue.burn_header_hash(3).unwrap().is_none());
+        for height in 1..=3 {
+            let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+            assert_eq!(next_op.as_peg_in().unwrap().block_height, height);
+            peg_queue.sbtc_op().unwrap().unwrap();
+        }
+    }
+
     fn default_stacks_node_mock(block_height: u64) -> stacks_node::MockStacksNode {
         let mut stacks_node_mock = stacks_node::MockStacksNode::new();
 
@@ -508,6 +1873,10 @@ mod tests {
             .expect_burn_block_height()
             .returning(move || Ok(block_height));
 
+        stacks_node_mock
+            .expect_burn_header_hash()
+            .returning(|height| Ok(burn_header_hash(height, 0)));
+
         stacks_node_mock
             .expect_get_peg_in_ops()
             .returning(|height| Ok(vec![peg_in_op(height)]));
@@ -519,6 +1888,38 @@ mod tests {
         stacks_node_mock
     }
 
+    /// Mock of a node whose blocks above `fork_point` were replaced by a competing fork
+    fn reorged_stacks_node_mock(block_height: u64, fork_point: u64) -> stacks_node::MockStacksNode {
+        let mut stacks_node_mock = stacks_node::MockStacksNode::new();
+        let fork = move |height: u64| if height > fork_point { 1 } else { 0 };
+
+        stacks_node_mock
+            .expect_burn_block_height()
+            .returning(move || Ok(block_height));
+
+        stacks_node_mock
+            .expect_burn_header_hash()
+            .returning(move |height| Ok(burn_header_hash(height, fork(height))));
+
+        stacks_node_mock
+            .expect_get_peg_in_ops()
+            .returning(move |height| {
+                let mut op = peg_in_op(height);
+                op.burn_header_hash = burn_header_hash(height, fork(height));
+                Ok(vec![op])
+            });
+
+        stacks_node_mock
+            .expect_get_peg_out_request_ops()
+            .returning(move |height| {
+                let mut op = peg_out_request_op(height);
+                op.burn_header_hash = burn_header_hash(height, fork(height));
+                Ok(vec![op])
+            });
+
+        stacks_node_mock
+    }
+
     fn stacks_node_mock_with_no_sbtc_ops(block_height: u64) -> stacks_node::MockStacksNode {
         let mut stacks_node_mock = stacks_node::MockStacksNode::new();
 
@@ -526,6 +1927,10 @@ mod tests {
             .expect_burn_block_height()
             .returning(move || Ok(block_height));
 
+        stacks_node_mock
+            .expect_burn_header_hash()
+            .returning(|height| Ok(burn_header_hash(height, 0)));
+
         stacks_node_mock
             .expect_get_peg_in_ops()
             .returning(|_height| Ok(vec![]));
@@ -548,7 +1953,7 @@ mod tests {
             amount: 1337,
             memo: vec![1, 3, 3, 7],
             txid: Txid(hash_and_expand(block_height, 1)),
-            burn_header_hash: BurnchainHeaderHash(hash_and_expand(block_height, 0)),
+            burn_header_hash: burn_header_hash(block_height, 0),
             block_height,
             vtxindex: 0,
         }
@@ -567,12 +1972,27 @@ mod tests {
             signature: MessageSignature([0; 65]),
             memo: vec![1, 3, 3, 7],
             txid: Txid(hash_and_expand(block_height, 2)),
-            burn_header_hash: BurnchainHeaderHash(hash_and_expand(block_height, 0)),
+            burn_header_hash: burn_header_hash(block_height, 0),
             block_height,
             vtxindex: 0,
         }
     }
 
+    /// Burn header hash of the block at `block_height` on the given fork
+    fn fulfillment_transaction() -> bitcoin::Transaction {
+        bitcoin::Transaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![bitcoin::TxOut::default()],
+        }
+    }
+
+    fn burn_header_hash(block_height: u64, fork: u64) -> BurnchainHeaderHash {
+        // Salt the hash to keep it distinct from the txids derived from the same height
+        BurnchainHeaderHash(hash_and_expand(block_height, fork * 100))
+    }
+
     fn hash_and_expand(val: u64, nonce: u64) -> [u8; 32] {
         let mut hasher = DefaultHasher::new();
         hasher.write_u64(val);
@@ -9,6 +9,7 @@ use bitcoin::{Address as BitcoinAddress, TxOut};
 use blockstack_lib::{
     chainstate::stacks::StacksTransaction,
     types::chainstate::{StacksAddress, StacksPublicKey},
+    vm::types::PrincipalData,
 };
 use std::fmt::Debug;
 
@@ -27,6 +28,8 @@ pub trait StacksWallet {
         op: &T,
         nonce: u64,
     ) -> Result<StacksTransaction, Error>;
+    /// Recovers the principal whose sBTC a peg-out request burns
+    fn peg_out_requester(&self, op: &PegOutRequestOp) -> Result<PrincipalData, Error>;
     /// Builds a verified signed transaction for setting the sBTC wallet public key
     fn build_set_bitcoin_wallet_public_key_transaction(
         &self,
@@ -42,26 +45,65 @@ pub trait StacksWallet {
     ) -> Result<StacksTransaction, Error>;
     /// Returns the sBTC address for the wallet
     fn address(&self) -> &StacksAddress;
-    /// Returns the sBTC public key for the wallet
-    fn public_key(&self) -> &StacksPublicKey;
-    /// Sets the sBTC transaction fee
+    /// Returns the public keys authorizing the wallet's transactions, in order
+    fn public_keys(&self) -> &[StacksPublicKey];
+    /// Sets the sBTC transaction fee, paid when no estimate is available and the least paid otherwise
     fn set_fee(&mut self, fee: u64);
+    /// Returns the fee the transaction should pay
+    fn estimate_fee(&self, tx: &StacksTransaction) -> u64;
+    /// Builds a verified signed replacement of the transaction paying the given fee
+    fn bump_fee(&self, tx: &StacksTransaction, fee: u64) -> Result<StacksTransaction, Error>;
 }
 
 pub trait BitcoinWallet {
     type Error: Debug;
 
     // Builds a fulfilled unsigned transaction using the provided utxos to cover the spend amount
+    // and a miner fee of `fee_rate` sat/vB
     fn fulfill_peg_out(
         &self,
         op: &PegOutRequestOp,
         txouts: Vec<UTXO>,
+        fee_rate: u64,
+    ) -> Result<(bitcoin_node::BitcoinTransaction, Vec<TxOut>), Error> {
+        self.fulfill_peg_outs(std::slice::from_ref(op), txouts, fee_rate)
+    }
+
+    /// Builds a single unsigned transaction fulfilling all the given ops, with one output per
+    /// recipient, paying `fee_rate` sat/vB
+    fn fulfill_peg_outs(
+        &self,
+        ops: &[PegOutRequestOp],
+        txouts: Vec<UTXO>,
+        fee_rate: u64,
+    ) -> Result<(bitcoin_node::BitcoinTransaction, Vec<TxOut>), Error>;
+
+    /// Builds an unsigned replacement of the given transaction paying the given fee rate in sat/vB,
+    /// returning it with its prevouts. The additional fee is taken from the change, spending
+    /// confirmed utxos among `txouts` and adding a change output if it falls short.
+    fn bump_fee(
+        &self,
+        tx: &bitcoin_node::BitcoinTransaction,
+        prevouts: &[TxOut],
+        txouts: Vec<UTXO>,
+        fee_rate: u64,
+    ) -> Result<(bitcoin_node::BitcoinTransaction, Vec<TxOut>), Error>;
+
+    /// Builds an unsigned transaction paying all the given utxos to the wallet address, less a
+    /// miner fee of `fee_rate` sat/vB
+    fn sweep(
+        &self,
+        utxos: Vec<UTXO>,
+        fee_rate: u64,
     ) -> Result<(bitcoin_node::BitcoinTransaction, Vec<TxOut>), Error>;
 
     /// Returns the BTC address for the wallet
     fn address(&self) -> &BitcoinAddress;
 
     fn x_only_pub_key(&self) -> &XOnlyPublicKey;
+
+    /// Switches the wallet to the given aggregate public key and its address
+    fn set_x_only_pub_key(&mut self, public_key: XOnlyPublicKey);
 }
 
 pub trait PegWallet {
@@ -70,6 +112,7 @@ pub trait PegWallet {
     fn stacks(&self) -> &Self::StacksWallet;
     fn stacks_mut(&mut self) -> &mut Self::StacksWallet;
     fn bitcoin(&self) -> &Self::BitcoinWallet;
+    fn bitcoin_mut(&mut self) -> &mut Self::BitcoinWallet;
 }
 
 pub struct WrapPegWallet {
@@ -92,4 +135,8 @@ impl PegWallet for WrapPegWallet {
     fn bitcoin(&self) -> &Self::BitcoinWallet {
         &self.bitcoin_wallet
     }
+
+    fn bitcoin_mut(&mut self) -> &mut Self::BitcoinWallet {
+        &mut self.bitcoin_wallet
+    }
 }
@@ -0,0 +1,211 @@
+use serde::Serialize;
+
+use crate::bitcoin_node::UTXO;
+use crate::peg_queue::{Entry, SbtcOp, Status};
+
+/// Comparison of the BTC held by the peg wallet with the sBTC in circulation.
+///
+/// Every sBTC is backed by a sat in the peg wallet, except for ops the queue is still
+/// working on:
+///
+/// - peg ins whose deposit reached the wallet but whose sBTC is not minted yet
+///   (`New`, `Pending` or `StacksBroadcast`) add their amount to the expected balance.
+/// - peg outs whose burn was broadcast but not yet fulfilled (`StacksBroadcast`) may have
+///   their sBTC burned already while the wallet still holds the BTC. They allow a surplus
+///   of up to their amount.
+/// - unconfirmed utxos of no queued op, such as deposits still in the mempool, are not
+///   peg ins yet. They allow a surplus of up to their amount.
+///
+/// Fulfillments spend the wallet utxos as soon as they are broadcast, as unconfirmed
+/// utxos are counted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SolvencyReport {
+    pub wallet_address: String,
+    /// Sats held by the peg wallet address, including unconfirmed utxos
+    pub wallet_balance: u64,
+    pub sbtc_supply: u128,
+    /// Sats deposited by peg ins which are not minted yet
+    pub pending_peg_ins: u64,
+    /// Sats requested by peg outs whose burn may have been mined but which are not fulfilled yet
+    pub pending_peg_outs: u64,
+    /// Sats of unconfirmed utxos which no queued op accounts for
+    pub unconfirmed_deposits: u64,
+    /// Wallet balance minus the supply and pending peg ins. Negative if the wallet is short.
+    pub discrepancy: i128,
+}
+
+impl SolvencyReport {
+    /// Build the report from the wallet utxos, the sBTC supply and the queue entries
+    pub fn new(
+        wallet_address: String,
+        utxos: &[UTXO],
+        sbtc_supply: u128,
+        entries: &[Entry],
+    ) -> Self {
+        let wallet_balance: u64 = utxos.iter().map(|utxo| utxo.amount).sum();
+        let unconfirmed_deposits = utxos
+            .iter()
+            .filter(|utxo| {
+                utxo.confirmations == 0
+                    && !entries
+                        .iter()
+                        .any(|entry| entry.op.txid().to_string() == utxo.txid)
+            })
+            .map(|utxo| utxo.amount)
+            .sum();
+        let mut pending_peg_ins = 0;
+        let mut pending_peg_outs = 0;
+        for entry in entries {
+            match (&entry.op, entry.status) {
+                (SbtcOp::PegIn(op), Status::New | Status::Pending | Status::StacksBroadcast) => {
+                    pending_peg_ins += op.amount
+                }
+                (SbtcOp::PegOutRequest(op), Status::StacksBroadcast) => {
+                    pending_peg_outs += op.amount
+                }
+                _ => {}
+            }
+        }
+        let discrepancy =
+            i128::from(wallet_balance) - sbtc_supply as i128 - i128::from(pending_peg_ins);
+        Self {
+            wallet_address,
+            wallet_balance,
+            sbtc_supply,
+            pending_peg_ins,
+            pending_peg_outs,
+            unconfirmed_deposits,
+            discrepancy,
+        }
+    }
+
+    /// Whether the discrepancy is explained by pending peg outs and unconfirmed deposits, give
+    /// or take `tolerance` sats
+    pub fn is_balanced(&self, tolerance: u64) -> bool {
+        let tolerance = i128::from(tolerance);
+        let explained_surplus =
+            i128::from(self.pending_peg_outs) + i128::from(self.unconfirmed_deposits);
+        -tolerance <= self.discrepancy && self.discrepancy <= explained_surplus + tolerance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blockstack_lib::burnchains::Txid;
+    use blockstack_lib::chainstate::stacks::address::PoxAddress;
+    use blockstack_lib::types::chainstate::{BurnchainHeaderHash, StacksAddress};
+    use blockstack_lib::util::hash::Hash160;
+
+    use super::*;
+    use crate::stacks_node::PegInOp;
+    use crate::util::test::{build_peg_out_request_op, PRIVATE_KEY_HEX};
+
+    fn entry(op: SbtcOp, status: Status) -> Entry {
+        Entry {
+            op,
+            block_height: 1,
+            status,
+            stacks_txid: None,
+            bitcoin_txid: None,
+            attempts: 0,
+            created_at: 0,
+            updated_at: 0,
+            reason: None,
+        }
+    }
+
+    fn peg_in(amount: u64) -> SbtcOp {
+        SbtcOp::PegIn(PegInOp {
+            recipient: StacksAddress::new(26, Hash160([0; 20])).into(),
+            peg_wallet_address: PoxAddress::Standard(StacksAddress::new(0, Hash160([0; 20])), None),
+            amount,
+            memo: vec![],
+            txid: Txid([1; 32]),
+            burn_header_hash: BurnchainHeaderHash([2; 32]),
+            block_height: 1,
+            vtxindex: 0,
+        })
+    }
+
+    fn utxo(amount: u64) -> UTXO {
+        UTXO {
+            amount,
+            confirmations: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pending_ops_should_be_accounted_for() {
+        let entries = [
+            entry(peg_in(1000), Status::Pending),
+            // Minted already, so part of the supply
+            entry(peg_in(2000), Status::Confirmed),
+            entry(
+                SbtcOp::PegOutRequest(build_peg_out_request_op(PRIVATE_KEY_HEX, 500, 1, 1)),
+                Status::StacksBroadcast,
+            ),
+            entry(
+                SbtcOp::PegOutRequest(build_peg_out_request_op(PRIVATE_KEY_HEX, 700, 1, 1)),
+                Status::New,
+            ),
+        ];
+
+        let report = SolvencyReport::new(
+            "tb1p".to_string(),
+            &[utxo(6000), utxo(5000)],
+            10_000,
+            &entries,
+        );
+        assert_eq!(report.wallet_balance, 11_000);
+        assert_eq!(report.pending_peg_ins, 1000);
+        assert_eq!(report.pending_peg_outs, 500);
+        assert_eq!(report.discrepancy, 0);
+        assert!(report.is_balanced(0));
+
+        // The burn of the pending peg out was mined
+        let report = SolvencyReport::new("tb1p".to_string(), &[utxo(11_000)], 9_500, &entries);
+        assert_eq!(report.discrepancy, 500);
+        assert!(report.is_balanced(0));
+
+        // The wallet is short
+        let report = SolvencyReport::new("tb1p".to_string(), &[utxo(10_900)], 10_000, &entries);
+        assert_eq!(report.discrepancy, -100);
+        assert!(!report.is_balanced(0));
+        assert!(report.is_balanced(100));
+    }
+
+    #[test]
+    fn unconfirmed_deposits_should_explain_a_surplus() {
+        let entries = [entry(peg_in(1000), Status::Pending)];
+        let unconfirmed = |amount, txid: &str| UTXO {
+            txid: txid.to_string(),
+            amount,
+            confirmations: 0,
+            ..Default::default()
+        };
+        let confirmed = utxo(11_000);
+
+        // A deposit in the mempool which is not a peg in yet
+        let report = SolvencyReport::new(
+            "tb1p".to_string(),
+            &[confirmed.clone(), unconfirmed(300, "deposit")],
+            10_000,
+            &entries,
+        );
+        assert_eq!(report.unconfirmed_deposits, 300);
+        assert_eq!(report.discrepancy, 300);
+        assert!(report.is_balanced(0));
+
+        // The utxo of a queued peg in is accounted for already
+        let peg_in_txid = entries[0].op.txid().to_string();
+        let report = SolvencyReport::new(
+            "tb1p".to_string(),
+            &[confirmed, unconfirmed(300, &peg_in_txid)],
+            10_000,
+            &entries,
+        );
+        assert_eq!(report.unconfirmed_deposits, 0);
+        assert!(!report.is_balanced(0));
+    }
+}
@@ -1,12 +1,27 @@
-use std::time::{Duration, Instant};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
 
-use crate::stacks_node::{Error as StacksNodeError, PegInOp, PegOutRequestOp, StacksNode};
+use crate::stacks_node::{
+    Error as StacksNodeError, PegInOp, PegOutRequestOp, SignerSet, StacksNode, TransactionStatus,
+};
+use crate::stacks_wallet::StacksFeeEstimator;
 use bitcoin::XOnlyPublicKey;
 use blockstack_lib::{
-    chainstate::stacks::StacksTransaction,
+    burnchains::Txid,
+    chainstate::stacks::{StacksTransaction, TransactionPayload},
     codec::StacksMessageCodec,
-    types::chainstate::StacksAddress,
-    vm::{types::SequenceData, ClarityName, ContractName, Value as ClarityValue},
+    types::chainstate::{BurnchainHeaderHash, StacksAddress},
+    util::hash::to_hex,
+    vm::{
+        types::{PrincipalData, SequenceData},
+        ClarityName, ContractName, Value as ClarityValue,
+    },
 };
 use frost_signer::config::{PublicKeys, SignerKeyIds};
 use reqwest::{
@@ -14,10 +29,14 @@ use reqwest::{
     StatusCode,
 };
 use serde_json::{json, Value};
-use tracing::debug;
+use tracing::{debug, warn};
 use url::Url;
 use wsts::ecdsa::PublicKey;
 
+/// Number of burn blocks after which a transaction the Stacks API still does not know about is
+/// considered dropped
+const UNKNOWN_TRANSACTION_BLOCKS: u64 = 6;
+
 /// Kinds of stacks node broadcast errors that can occur
 #[derive(Debug, thiserror::Error)]
 pub enum BroadcastError {
@@ -61,10 +80,16 @@ impl From<&serde_json::Value> for BroadcastError {
 
 pub struct NodeClient {
     node_url: Url,
+    /// Url of the Stacks API indexing the node's transactions
+    api_url: Url,
     client: Client,
     contract_name: ContractName,
     contract_address: StacksAddress,
     next_nonce: Option<u64>,
+    /// Set once the node turned out not to serve the sortitions API, which predates Nakamoto
+    sortitions_unsupported: AtomicBool,
+    /// Burn block height at which each transaction was first found unknown to the Stacks API
+    unknown_transactions: Mutex<HashMap<Txid, u64>>,
 }
 
 impl NodeClient {
@@ -74,20 +99,32 @@ impl NodeClient {
         contract_address: StacksAddress,
     ) -> Self {
         Self {
+            api_url: node_url.clone(),
             node_url,
             client: Client::new(),
             contract_name,
             contract_address,
             next_nonce: None,
+            sortitions_unsupported: AtomicBool::new(false),
+            unknown_transactions: Mutex::new(HashMap::new()),
         }
     }
 
+    /// Sets the Stacks API queried for the status of transactions. Defaults to the node itself,
+    /// which only serves them if the API proxies it.
+    pub fn set_api_url(&mut self, api_url: Url) {
+        self.api_url = api_url;
+    }
+
     fn build_url(&self, route: &str) -> Result<Url, StacksNodeError> {
         Ok(self.node_url.join(route)?)
     }
 
     fn get_response(&self, route: &str) -> Result<Response, StacksNodeError> {
-        let url = self.build_url(route)?;
+        self.get_url_response(self.build_url(route)?)
+    }
+
+    fn get_url_response(&self, url: Url) -> Result<Response, StacksNodeError> {
         debug!("Sending Request to Stacks Node: {}", &url);
         let now = Instant::now();
         let notify = |_err, dur| {
@@ -203,18 +240,103 @@ impl NodeClient {
         ))
     }
 
+    /// Call a read-only function returning `(ok uint)`
+    fn call_read_ok_uint(
+        &self,
+        sender: &StacksAddress,
+        function_name: &str,
+        function_args: &[&str],
+    ) -> Result<u128, StacksNodeError> {
+        let value_hex = self.call_read(sender, function_name, function_args)?;
+        let value = ClarityValue::try_deserialize_hex_untyped(&value_hex)?;
+        if let ClarityValue::Response(response) = &value {
+            if let (true, ClarityValue::UInt(value)) = (response.committed, &*response.data) {
+                return Ok(*value);
+            }
+        }
+        Err(StacksNodeError::MalformedClarityValue(
+            function_name.to_string(),
+            value,
+        ))
+    }
+
+    /// The public key a stacker registered with the stacking pool for the given cycle
+    fn stacker_public_key(
+        &self,
+        sender: &StacksAddress,
+        stacking_pool: &ContractName,
+        stacker: &ClarityValue,
+        cycle: u64,
+    ) -> Result<PublicKey, StacksNodeError> {
+        let function_name = "get-signer-in-cycle";
+        let signer_hex = self.call_read_contract(
+            stacking_pool,
+            sender,
+            function_name,
+            &[
+                &stacker.serialize_to_hex(),
+                &ClarityValue::UInt(cycle.into()).serialize_to_hex(),
+            ],
+        )?;
+        let signer = ClarityValue::try_deserialize_hex_untyped(&signer_hex)?;
+        if let ClarityValue::Tuple(tuple_data) = &signer {
+            if let Some(ClarityValue::Sequence(SequenceData::Buffer(public_key))) =
+                tuple_data.data_map.get(&ClarityName::from("public-key"))
+            {
+                if let Ok(public_key) = PublicKey::try_from(public_key.data.as_slice()) {
+                    return Ok(public_key);
+                }
+            }
+        }
+        Err(StacksNodeError::MalformedClarityValue(
+            function_name.to_string(),
+            signer,
+        ))
+    }
+
+    /// Read a data var of the given contract
+    fn data_var(
+        &self,
+        contract_name: &ContractName,
+        var_name: &str,
+    ) -> Result<ClarityValue, StacksNodeError> {
+        let json = self
+            .get_response(&format!(
+                "/v2/data_var/{}/{}/{var_name}?proof=0",
+                self.contract_address,
+                contract_name.as_str()
+            ))?
+            .json::<Value>()?;
+        let data = json
+            .get("data")
+            .and_then(Value::as_str)
+            .ok_or_else(|| StacksNodeError::InvalidJsonEntry("data".to_string()))?;
+        Ok(ClarityValue::try_deserialize_hex_untyped(data)?)
+    }
+
     fn call_read(
         &self,
         sender: &StacksAddress,
         function_name: &str,
         function_args: &[&str],
+    ) -> Result<String, StacksNodeError> {
+        self.call_read_contract(&self.contract_name, sender, function_name, function_args)
+    }
+
+    /// Call a read-only function of another contract deployed alongside the sBTC contract
+    fn call_read_contract(
+        &self,
+        contract_name: &ContractName,
+        sender: &StacksAddress,
+        function_name: &str,
+        function_args: &[&str],
     ) -> Result<String, StacksNodeError> {
         debug!("Calling read-only function {}...", function_name);
         let body = json!({"sender": sender.to_string(), "arguments": function_args}).to_string();
         let url = self.build_url(&format!(
             "/v2/contracts/call-read/{}/{}/{function_name}",
             self.contract_address,
-            self.contract_name.as_str()
+            contract_name.as_str()
         ))?;
         let response = self
             .client
@@ -247,6 +369,37 @@ impl NodeClient {
     }
 }
 
+impl StacksFeeEstimator for NodeClient {
+    fn estimate_fee(
+        &self,
+        payload: &TransactionPayload,
+        estimated_len: u64,
+    ) -> Result<u64, StacksNodeError> {
+        debug!("Estimating transaction fee...");
+        let body = json!({
+            "transaction_payload": to_hex(&payload.serialize_to_vec()),
+            "estimated_len": estimated_len,
+        });
+        let url = self.build_url("/v2/fees/transaction")?;
+        let response = self
+            .client
+            .post(url)
+            .header("content-type", "application/json")
+            .body(body.to_string())
+            .send()?
+            .json::<Value>()?;
+        // The node returns low, middle and high estimates. Pay the middle one.
+        let entry = "estimations";
+        response
+            .get(entry)
+            .and_then(|estimations| estimations.as_array())
+            .and_then(|estimations| estimations.get(estimations.len() / 2))
+            .and_then(|estimation| estimation.get("fee"))
+            .and_then(|fee| fee.as_u64())
+            .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))
+    }
+}
+
 impl StacksNode for NodeClient {
     fn get_peg_in_ops(&self, block_height: u64) -> Result<Vec<PegInOp>, StacksNodeError> {
         debug!("Retrieving peg-in ops...");
@@ -270,6 +423,37 @@ impl StacksNode for NodeClient {
             .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))
     }
 
+    fn burn_header_hash(&self, block_height: u64) -> Result<BurnchainHeaderHash, StacksNodeError> {
+        debug!("Retrieving burn header hash...");
+        let route = format!("/v3/sortitions/burn_height/{block_height}");
+        if self.sortitions_unsupported.load(Ordering::Relaxed) {
+            return Err(StacksNodeError::UnsupportedRoute(route));
+        }
+        let response = self.get_response(&route)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            // Nakamoto nodes answer unknown heights with a 404 as well, so probe the sortitions
+            // API itself before concluding the node does not serve it
+            if self.get_response("/v3/sortitions")?.status() != StatusCode::NOT_FOUND {
+                return Err(StacksNodeError::UnknownBlockHeight(block_height));
+            }
+            if !self.sortitions_unsupported.swap(true, Ordering::Relaxed) {
+                warn!("Stacks node does not serve the sortitions API. Burnchain reorgs will not be detected");
+            }
+            return Err(StacksNodeError::UnsupportedRoute(route));
+        }
+        let json = response
+            .json::<Value>()
+            .map_err(|_| StacksNodeError::UnknownBlockHeight(block_height))?;
+        let entry = "burn_block_hash";
+        let burn_header_hash = json
+            .get(0)
+            .and_then(|sortition| sortition.get(entry))
+            .and_then(|hash| hash.as_str())
+            .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))?;
+        BurnchainHeaderHash::from_hex(burn_header_hash.trim_start_matches("0x"))
+            .map_err(|_| StacksNodeError::InvalidJsonEntry(entry.to_string()))
+    }
+
     fn next_nonce(&mut self, address: &StacksAddress) -> Result<u64, StacksNodeError> {
         debug!("Retrieving next nonce...");
         if let Some(nonce) = self.next_nonce {
@@ -277,6 +461,13 @@ impl StacksNode for NodeClient {
             self.next_nonce = Some(next_nonce);
             return Ok(next_nonce);
         }
+        let nonce = self.account_nonce(address)?;
+        self.next_nonce = Some(nonce);
+        Ok(nonce)
+    }
+
+    fn account_nonce(&self, address: &StacksAddress) -> Result<u64, StacksNodeError> {
+        debug!("Retrieving account nonce...");
         let address = address.to_string();
         let entry = "nonce";
         let route = format!("/v2/accounts/{}", address);
@@ -287,12 +478,9 @@ impl StacksNode for NodeClient {
         let json = response
             .json::<Value>()
             .map_err(|_| StacksNodeError::BehindChainTip)?;
-        let nonce = json
-            .get(entry)
+        json.get(entry)
             .and_then(|nonce| nonce.as_u64())
-            .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))?;
-        self.next_nonce = Some(nonce);
-        Ok(nonce)
+            .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))
     }
 
     fn broadcast_transaction(&self, tx: &StacksTransaction) -> Result<(), StacksNodeError> {
@@ -317,6 +505,76 @@ impl StacksNode for NodeClient {
         Ok(())
     }
 
+    fn get_transaction_status(&self, txid: &Txid) -> Result<TransactionStatus, StacksNodeError> {
+        debug!("Retrieving transaction status...");
+        // Transaction results are indexed by the Stacks API rather than the node
+        let url = self.api_url.join(&format!("/extended/v1/tx/0x{}", txid))?;
+        let response = self.get_url_response(url)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            // Not indexed yet, or never reached the mempool
+            let block_height = self.burn_block_height()?;
+            let mut unknown_transactions = self
+                .unknown_transactions
+                .lock()
+                .expect("Unknown transactions lock poisoned");
+            let unknown_since = *unknown_transactions.entry(*txid).or_insert(block_height);
+            let unknown_blocks = block_height.saturating_sub(unknown_since);
+            if unknown_blocks > UNKNOWN_TRANSACTION_BLOCKS {
+                unknown_transactions.remove(txid);
+                warn!(
+                    "Transaction {} is unknown to the Stacks API after {} burn blocks",
+                    txid, unknown_blocks
+                );
+                return Ok(TransactionStatus::Dropped(format!(
+                    "unknown for {unknown_blocks} burn blocks"
+                )));
+            }
+            return Ok(TransactionStatus::Pending);
+        }
+        self.unknown_transactions
+            .lock()
+            .expect("Unknown transactions lock poisoned")
+            .remove(txid);
+        let json = response.json::<Value>()?;
+        let entry = "tx_status";
+        let status = json
+            .get(entry)
+            .and_then(|status| status.as_str())
+            .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))?;
+        Ok(match status {
+            "pending" => TransactionStatus::Pending,
+            "success" => TransactionStatus::Success,
+            aborted if aborted.starts_with("abort") => {
+                let result = json
+                    .get("tx_result")
+                    .and_then(|result| result.get("repr"))
+                    .and_then(|repr| repr.as_str())
+                    .unwrap_or_default();
+                TransactionStatus::Aborted(format!("{aborted} {result}").trim().to_string())
+            }
+            dropped if dropped.starts_with("dropped") => {
+                TransactionStatus::Dropped(dropped.to_string())
+            }
+            _ => return Err(StacksNodeError::InvalidJsonEntry(entry.to_string())),
+        })
+    }
+
+    fn sbtc_balance(
+        &self,
+        sender: &StacksAddress,
+        principal: &PrincipalData,
+    ) -> Result<u128, StacksNodeError> {
+        self.call_read_ok_uint(
+            sender,
+            "get-balance",
+            &[&ClarityValue::Principal(principal.clone()).serialize_to_hex()],
+        )
+    }
+
+    fn sbtc_total_supply(&self, sender: &StacksAddress) -> Result<u128, StacksNodeError> {
+        self.call_read_ok_uint(sender, "get-total-supply", &[])
+    }
+
     fn keys_threshold(&self, sender: &StacksAddress) -> Result<u128, StacksNodeError> {
         let function_name = "get-threshold";
         let threshold_hex = self.call_read(sender, function_name, &[])?;
@@ -425,6 +683,67 @@ impl StacksNode for NodeClient {
             bitcoin_wallet_public_key,
         ))
     }
+
+    fn current_reward_cycle(
+        &self,
+        sender: &StacksAddress,
+        stacking_pool: &ContractName,
+    ) -> Result<u64, StacksNodeError> {
+        let function_name = "current-pox-reward-cycle";
+        let cycle_hex = self.call_read_contract(stacking_pool, sender, function_name, &[])?;
+        let cycle = ClarityValue::try_deserialize_hex_untyped(&cycle_hex)?;
+        if let ClarityValue::UInt(reward_cycle) = cycle {
+            if let Ok(reward_cycle) = u64::try_from(reward_cycle) {
+                return Ok(reward_cycle);
+            }
+        }
+        Err(StacksNodeError::MalformedClarityValue(
+            function_name.to_string(),
+            cycle,
+        ))
+    }
+
+    fn current_signer_set(
+        &self,
+        sender: &StacksAddress,
+        stacking_pool: &ContractName,
+        cycle: u64,
+    ) -> Result<Option<SignerSet>, StacksNodeError> {
+        let function_name = "get-current-cycle-pool";
+        let pool_hex = self.call_read_contract(stacking_pool, sender, function_name, &[])?;
+        let pool = ClarityValue::try_deserialize_hex_untyped(&pool_hex)?;
+        let malformed =
+            || StacksNodeError::MalformedClarityValue(function_name.to_string(), pool.clone());
+        let ClarityValue::Optional(optional_data) = &pool else {
+            return Err(malformed());
+        };
+        let Some(pool_data) = optional_data.data.as_deref() else {
+            return Ok(None);
+        };
+        let stackers = match pool_data {
+            ClarityValue::Tuple(tuple_data) => {
+                match tuple_data.data_map.get(&ClarityName::from("stackers")) {
+                    Some(ClarityValue::Sequence(SequenceData::List(stackers))) => &stackers.data,
+                    _ => return Err(malformed()),
+                }
+            }
+            _ => return Err(malformed()),
+        };
+        let public_keys = stackers
+            .iter()
+            .map(|stacker| self.stacker_public_key(sender, stacking_pool, stacker, cycle))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let var_name = "threshold-consensus";
+        let threshold = self.data_var(stacking_pool, var_name)?;
+        let ClarityValue::UInt(threshold_per_mille) = threshold else {
+            return Err(StacksNodeError::MalformedClarityValue(
+                var_name.to_string(),
+                threshold,
+            ));
+        };
+        Ok(Some(SignerSet::new(public_keys, threshold_per_mille)))
+    }
 }
 
 #[cfg(test)]
@@ -445,6 +764,7 @@ mod tests {
         },
         types::chainstate::{StacksPrivateKey, StacksPublicKey},
         util::{hash::Hash160, secp256k1::MessageSignature},
+        vm::types::StacksAddressExtensions,
     };
 
     use crate::util::test::PRIVATE_KEY_HEX;
@@ -579,6 +899,89 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn sbtc_balance_test() {
+        let config = TestConfig::new();
+        let principal = config.sender.to_account_principal();
+
+        let h = spawn(move || config.client.sbtc_balance(&config.sender, &principal));
+        let request_bytes = write_response(
+            config.mock_server,
+            b"HTTP/1.1 200 OK\n\n{\"okay\":true,\"result\":\"0x070100000000000000000000000000001388\"}",
+        );
+        let result = h.join().unwrap().unwrap();
+        assert_eq!(result, 5000);
+        assert!(String::from_utf8_lossy(&request_bytes).contains("/get-balance"));
+    }
+
+    #[test]
+    fn sbtc_balance_err_test() {
+        let config = TestConfig::new();
+        let principal = config.sender.to_account_principal();
+
+        let h = spawn(move || config.client.sbtc_balance(&config.sender, &principal));
+        write_response(
+            config.mock_server,
+            b"HTTP/1.1 200 OK\n\n{\"okay\":true,\"result\":\"0x080100000000000000000000000000000001\"}",
+        );
+        let result = h.join().unwrap();
+        assert!(matches!(
+            result,
+            Err(StacksNodeError::MalformedClarityValue(..))
+        ));
+    }
+
+    #[test]
+    fn sbtc_total_supply_test() {
+        let config = TestConfig::new();
+
+        let h = spawn(move || config.client.sbtc_total_supply(&config.sender));
+        let request_bytes = write_response(
+            config.mock_server,
+            b"HTTP/1.1 200 OK\n\n{\"okay\":true,\"result\":\"0x070100000000000000000000000000002710\"}",
+        );
+        let result = h.join().unwrap().unwrap();
+        assert_eq!(result, 10000);
+        assert!(String::from_utf8_lossy(&request_bytes).contains("/get-total-supply"));
+    }
+
+    #[test]
+    fn current_reward_cycle_test() {
+        let config = TestConfig::new();
+
+        let h = spawn(move || {
+            config
+                .client
+                .current_reward_cycle(&config.sender, &ContractName::from("sbtc-stacking-pool"))
+        });
+        let request_bytes = write_response(
+            config.mock_server,
+            b"HTTP/1.1 200 OK\n\n{\"okay\":true,\"result\":\"0x0100000000000000000000000000000005\"}",
+        );
+        let result = h.join().unwrap().unwrap();
+        assert_eq!(result, 5);
+        assert!(String::from_utf8_lossy(&request_bytes)
+            .contains("/sbtc-stacking-pool/current-pox-reward-cycle"));
+    }
+
+    #[test]
+    fn current_signer_set_none_test() {
+        let config = TestConfig::new();
+
+        let h = spawn(move || {
+            config.client.current_signer_set(
+                &config.sender,
+                &ContractName::from("sbtc-stacking-pool"),
+                5,
+            )
+        });
+        write_response(
+            config.mock_server,
+            b"HTTP/1.1 200 OK\n\n{\"okay\":true,\"result\":\"0x09\"}",
+        );
+        assert!(h.join().unwrap().unwrap().is_none());
+    }
+
     #[test]
     fn num_signers_test() {
         let config = TestConfig::new();
@@ -663,6 +1066,160 @@ mod tests {
         assert!(matches!(result, Err(StacksNodeError::InvalidJsonEntry(_))));
     }
 
+    #[test]
+    fn burn_header_hash_success_test() {
+        let config = TestConfig::new();
+
+        let h = spawn(move || config.client.burn_header_hash(2430220));
+        write_response(
+            config.mock_server,
+            b"HTTP/1.1 200 OK\n\n[{\"burn_block_hash\":\"0x0000000000000000000000000000000000000000000000000000000000000a0c\",\"burn_block_height\":2430220}]",
+        );
+        let result = h.join().unwrap().unwrap();
+        let mut expected = [0u8; 32];
+        expected[30] = 0x0a;
+        expected[31] = 0x0c;
+        assert_eq!(result, BurnchainHeaderHash(expected));
+    }
+
+    #[test]
+    fn burn_header_hash_failure_test() {
+        let config = TestConfig::new();
+
+        let h = spawn(move || config.client.burn_header_hash(2430220));
+        write_response(
+            config.mock_server,
+            b"HTTP/1.1 200 OK\n\n[{\"burn_block_height\":2430220}]",
+        );
+        let result = h.join().unwrap();
+        assert!(matches!(result, Err(StacksNodeError::InvalidJsonEntry(_))));
+    }
+
+    #[test]
+    fn burn_header_hash_unknown_height_test() {
+        let config = TestConfig::new();
+        let mock_server = config.mock_server.try_clone().unwrap();
+
+        let h = spawn(move || config.client.burn_header_hash(2430220));
+        write_response(
+            mock_server.try_clone().unwrap(),
+            b"HTTP/1.1 404 Not Found\n\n",
+        );
+        write_response(
+            mock_server,
+            b"HTTP/1.1 200 OK\n\n[{\"burn_block_hash\":\"0x0000000000000000000000000000000000000000000000000000000000000a0c\",\"burn_block_height\":2430219}]",
+        );
+        let result = h.join().unwrap();
+        assert!(matches!(
+            result,
+            Err(StacksNodeError::UnknownBlockHeight(2430220))
+        ));
+    }
+
+    #[test]
+    fn burn_header_hash_unsupported_test() {
+        let config = TestConfig::new();
+        let mock_server = config.mock_server.try_clone().unwrap();
+
+        let h = spawn(move || {
+            let first = config.client.burn_header_hash(2430220);
+            // The node is not asked again once it turned out not to serve the route
+            let second = config.client.burn_header_hash(2430221);
+            (first, second)
+        });
+        write_response(
+            mock_server.try_clone().unwrap(),
+            b"HTTP/1.1 404 Not Found\n\n",
+        );
+        write_response(mock_server, b"HTTP/1.1 404 Not Found\n\n");
+        let (first, second) = h.join().unwrap();
+        assert!(matches!(first, Err(StacksNodeError::UnsupportedRoute(_))));
+        assert!(matches!(second, Err(StacksNodeError::UnsupportedRoute(_))));
+    }
+
+    #[test]
+    fn estimate_fee_test() {
+        let config = TestConfig::new();
+        let payload = TransactionPayload::Coinbase(CoinbasePayload([0u8; 32]), None);
+
+        let h = spawn(move || config.client.estimate_fee(&payload, 180));
+        let request_bytes = write_response(
+            config.mock_server,
+            b"HTTP/1.1 200 OK\n\n{\"estimated_cost_scalar\":14,\"estimations\":[{\"fee_rate\":1.0,\"fee\":180},{\"fee_rate\":2.5,\"fee\":450},{\"fee_rate\":4.0,\"fee\":720}]}",
+        );
+        assert_eq!(h.join().unwrap().unwrap(), 450);
+        let request = String::from_utf8_lossy(&request_bytes);
+        assert!(request.starts_with("POST /v2/fees/transaction"));
+        assert!(request.contains("\"estimated_len\":180"));
+    }
+
+    #[test]
+    fn get_transaction_status_test() {
+        let responses: [(&[u8], TransactionStatus); 3] = [
+            (
+                b"HTTP/1.1 200 OK\n\n{\"tx_status\":\"success\",\"tx_result\":{\"repr\":\"(ok true)\"}}",
+                TransactionStatus::Success,
+            ),
+            (
+                b"HTTP/1.1 200 OK\n\n{\"tx_status\":\"abort_by_response\",\"tx_result\":{\"repr\":\"(err u1)\"}}",
+                TransactionStatus::Aborted("abort_by_response (err u1)".to_string()),
+            ),
+            (
+                b"HTTP/1.1 200 OK\n\n{\"tx_status\":\"dropped_replace_by_fee\"}",
+                TransactionStatus::Dropped("dropped_replace_by_fee".to_string()),
+            ),
+        ];
+        for (response, expected) in responses {
+            let config = TestConfig::new();
+            let h = spawn(move || config.client.get_transaction_status(&Txid([1; 32])));
+            let request = write_response(config.mock_server, response);
+            let request = String::from_utf8_lossy(&request);
+            assert!(request.starts_with(&format!("GET /extended/v1/tx/0x{}", Txid([1; 32]))));
+            assert_eq!(h.join().unwrap().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn get_transaction_status_unknown_test() {
+        let config = TestConfig::new();
+        let mut client = config.client;
+        let mut api_server_addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        let api_server = TcpListener::bind(api_server_addr).unwrap();
+        api_server_addr.set_port(api_server.local_addr().unwrap().port());
+        client.set_api_url(Url::parse(&format!("http://{}", api_server_addr)).unwrap());
+
+        let (statuses, receive_status) = std::sync::mpsc::channel();
+        let h = spawn(move || {
+            for _ in 0..3 {
+                statuses
+                    .send(client.get_transaction_status(&Txid([1; 32])).unwrap())
+                    .unwrap();
+            }
+        });
+        // The transaction stays unknown to the API while the node mines burn blocks
+        for (block_height, expected) in [
+            (100, TransactionStatus::Pending),
+            (106, TransactionStatus::Pending),
+            (
+                107,
+                TransactionStatus::Dropped("unknown for 7 burn blocks".to_string()),
+            ),
+        ] {
+            let request = write_response(
+                api_server.try_clone().unwrap(),
+                b"HTTP/1.1 404 Not Found\n\n",
+            );
+            let request = String::from_utf8_lossy(&request);
+            assert!(request.starts_with(&format!("GET /extended/v1/tx/0x{}", Txid([1; 32]))));
+            write_response(
+                config.mock_server.try_clone().unwrap(),
+                format!("HTTP/1.1 200 OK\n\n{{\"burn_block_height\":{block_height}}}").as_bytes(),
+            );
+            assert_eq!(receive_status.recv().unwrap(), expected);
+        }
+        h.join().unwrap();
+    }
+
     #[test]
     fn should_send_tx_bytes_to_node() {
         let config = TestConfig::new();
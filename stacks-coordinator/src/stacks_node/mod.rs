@@ -2,10 +2,14 @@ pub mod client;
 
 use bitcoin::XOnlyPublicKey;
 use blockstack_lib::{
+    burnchains::Txid,
     chainstate::{burn::operations as burn_ops, stacks::StacksTransaction},
     codec::Error as CodecError,
-    types::chainstate::StacksAddress,
-    vm::{types::serialization::SerializationError, Value as ClarityValue},
+    types::chainstate::{BurnchainHeaderHash, StacksAddress},
+    vm::{
+        types::{serialization::SerializationError, PrincipalData},
+        ContractName, Value as ClarityValue,
+    },
 };
 use frost_signer::config::{PublicKeys, SignerKeyIds};
 use wsts::ecdsa::PublicKey;
@@ -47,6 +51,34 @@ pub enum Error {
     Utf8Error(#[from] std::str::Utf8Error),
     #[error("URL Parse Error: {0}")]
     UrlParseError(#[from] url::ParseError),
+    #[error("Stacks node does not serve {0}")]
+    UnsupportedRoute(String),
+}
+
+impl Error {
+    /// Whether the error is caused by the stacks node being unreachable, behind or rejecting a
+    /// request for now, so the request may be retried
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Timeout
+            | Error::ReqwestError(_)
+            | Error::BehindChainTip
+            | Error::UnknownBlockHeight(_)
+            | Error::BroadcastError(_)
+            | Error::ReadOnlyFailure(_) => true,
+            Error::InvalidJsonEntry(_)
+            | Error::UnknownAddress(_)
+            | Error::JsonError(_)
+            | Error::CodecError(_)
+            | Error::SerializationError(_)
+            | Error::NoCoordinatorData
+            | Error::NoSignerData(_)
+            | Error::MalformedClarityValue(_, _)
+            | Error::Utf8Error(_)
+            | Error::UrlParseError(_)
+            | Error::UnsupportedRoute(_) => false,
+        }
+    }
 }
 
 #[cfg_attr(test, mockall::automock)]
@@ -54,8 +86,23 @@ pub trait StacksNode {
     fn get_peg_in_ops(&self, block_height: u64) -> Result<Vec<PegInOp>, Error>;
     fn get_peg_out_request_ops(&self, block_height: u64) -> Result<Vec<PegOutRequestOp>, Error>;
     fn burn_block_height(&self) -> Result<u64, Error>;
+    /// The burn header hash of the canonical burn block at the given height. Fails with
+    /// `Error::UnsupportedRoute` if the node cannot look it up
+    fn burn_header_hash(&self, block_height: u64) -> Result<BurnchainHeaderHash, Error>;
     fn next_nonce(&mut self, addr: &StacksAddress) -> Result<u64, Error>;
+    /// The nonce of the next transaction the node would accept from the account, ignoring its mempool
+    fn account_nonce(&self, addr: &StacksAddress) -> Result<u64, Error>;
     fn broadcast_transaction(&self, tx: &StacksTransaction) -> Result<(), Error>;
+    /// The status of a broadcast transaction
+    fn get_transaction_status(&self, txid: &Txid) -> Result<TransactionStatus, Error>;
+    /// The sBTC balance of the given principal
+    fn sbtc_balance(
+        &self,
+        sender: &StacksAddress,
+        principal: &PrincipalData,
+    ) -> Result<u128, Error>;
+    /// The total supply of sBTC
+    fn sbtc_total_supply(&self, sender: &StacksAddress) -> Result<u128, Error>;
     fn keys_threshold(&self, sender: &StacksAddress) -> Result<u128, Error>;
     fn public_keys(&self, sender: &StacksAddress) -> Result<PublicKeys, Error>;
     fn signer_key_ids(&self, sender: &StacksAddress) -> Result<SignerKeyIds, Error>;
@@ -64,7 +111,91 @@ pub trait StacksNode {
         &self,
         sender: &StacksAddress,
     ) -> Result<Option<XOnlyPublicKey>, Error>;
+    /// The current PoX reward cycle according to the stacking pool contract
+    fn current_reward_cycle(
+        &self,
+        sender: &StacksAddress,
+        stacking_pool: &ContractName,
+    ) -> Result<u64, Error>;
+    /// The signers registered in the stacking pool of the current reward cycle `cycle`, if any
+    fn current_signer_set(
+        &self,
+        sender: &StacksAddress,
+        stacking_pool: &ContractName,
+        cycle: u64,
+    ) -> Result<Option<SignerSet>, Error>;
+}
+
+/// A signer set as read from the stacking pool contract
+#[derive(Debug, Clone)]
+pub struct SignerSet {
+    pub keys_threshold: u32,
+    pub public_keys: PublicKeys,
+    pub signer_key_ids: SignerKeyIds,
+}
+
+impl SignerSet {
+    /// Build the signer set of the given stackers' public keys, in pool order. Each stacker runs
+    /// a single signer holding a single key, both numbered from 1. The pool's consensus threshold
+    /// is in thousandths of the signers, rounded up to the next key.
+    pub fn new(stacker_public_keys: Vec<PublicKey>, threshold_per_mille: u128) -> Self {
+        let mut public_keys = PublicKeys::default();
+        let mut signer_key_ids = SignerKeyIds::default();
+        for (id, public_key) in (1u32..).zip(stacker_public_keys) {
+            public_keys.signers.insert(id, public_key);
+            public_keys.key_ids.insert(id, public_key);
+            signer_key_ids.insert(id, vec![id]);
+        }
+        let total_keys = public_keys.key_ids.len() as u128;
+        let keys_threshold = ((total_keys * threshold_per_mille + 999) / 1000).max(1);
+        Self {
+            keys_threshold: keys_threshold.try_into().unwrap_or(u32::MAX),
+            public_keys,
+            signer_key_ids,
+        }
+    }
+}
+
+/// Status of a broadcast Stacks transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionStatus {
+    /// Waiting in the mempool, or not yet known to the node
+    Pending,
+    /// Anchored with an `ok` result
+    Success,
+    /// Anchored but aborted, with the reason
+    Aborted(String),
+    /// Evicted from the mempool without being anchored, with the reason
+    Dropped(String),
 }
 
 pub type PegInOp = burn_ops::PegInOp;
 pub type PegOutRequestOp = burn_ops::PegOutRequestOp;
+
+#[cfg(test)]
+mod tests {
+    use wsts::Scalar;
+
+    use super::*;
+
+    #[test]
+    fn signer_set_should_number_stackers_from_one() {
+        let public_keys: Vec<PublicKey> = (1..=4u32)
+            .map(|i| PublicKey::new(&Scalar::from(i)).unwrap())
+            .collect();
+
+        let signer_set = SignerSet::new(public_keys.clone(), 700);
+        assert_eq!(signer_set.keys_threshold, 3);
+        assert_eq!(
+            signer_set.public_keys.signers[&1].to_bytes(),
+            public_keys[0].to_bytes()
+        );
+        assert_eq!(
+            signer_set.public_keys.key_ids[&4].to_bytes(),
+            public_keys[3].to_bytes()
+        );
+        assert_eq!(signer_set.signer_key_ids[&2], vec![2]);
+
+        assert_eq!(SignerSet::new(public_keys, 0).keys_threshold, 1);
+    }
+}
@@ -0,0 +1,690 @@
+use std::{io::Error as IoError, net::TcpListener, path::Path, time::Duration};
+
+use blockstack_lib::{
+    burnchains::Txid,
+    chainstate::stacks::{
+        MultisigHashMode, StacksTransaction, TransactionAuth, TransactionAuthField,
+        TransactionAuthFlags, TransactionPublicKeyEncoding, TransactionSpendingCondition,
+    },
+    codec::StacksMessageCodec,
+    types::chainstate::{StacksPrivateKey, StacksPublicKey},
+    util::{
+        hash::{hex_bytes, to_hex},
+        secp256k1::MessageSignature,
+    },
+};
+use frost_signer::http_server;
+use keystore::{KeyKind, PASSWORD_ENV};
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+use tracing::warn;
+use url::Url;
+use yarpc::http::{IoStream, Method, Request};
+
+/// How long to wait for a remote signer to respond
+const REMOTE_SIGNER_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    ///Error occured while signing a transaction
+    #[error("{0}")]
+    SigningError(String),
+    ///Error occurred unlocking the keystore
+    #[error("Keystore error: {0}")]
+    KeystoreError(String),
+    ///The remote signer could not be reached
+    #[error("Remote signer unreachable: {0}")]
+    Unreachable(String),
+    ///The remote signer refused to sign
+    #[error("Remote signer rejected the request: {0}")]
+    Rejected(String),
+    ///The remote signer answered with something other than what was asked for
+    #[error("Invalid remote signer response: {0}")]
+    InvalidResponse(String),
+    ///The signer holds another key than the configured one
+    #[error("Signer holds the key of {0}, not the configured public key")]
+    PublicKeyMismatch(String),
+    ///A multisig origin needs more signatures than it has signers, or none
+    #[error("Invalid multisig: {0}")]
+    InvalidMultisig(String),
+    ///Too few signers of a multisig origin signed
+    #[error("Collected {0} of {1} signatures: {2}")]
+    InsufficientSignatures(u16, u16, String),
+}
+
+impl Error {
+    /// Whether signing may succeed if retried
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Error::Unreachable(_))
+    }
+}
+
+/// Signs the Stacks transactions of the fee wallet, wherever its private key lives
+pub trait StacksTxSigner: Send {
+    /// The public key of the transactions' origin
+    fn public_key(&self) -> StacksPublicKey;
+
+    /// Sign the origin of a transaction. The signature of a multisig origin is appended to those
+    /// already collected.
+    fn sign(&self, unsigned_tx: &StacksTransaction) -> Result<StacksTransaction, Error>;
+}
+
+/// Signs with a private key held in memory
+pub struct PrivateKeySigner {
+    private_key: StacksPrivateKey,
+}
+
+impl PrivateKeySigner {
+    pub fn new(private_key: StacksPrivateKey) -> Self {
+        Self { private_key }
+    }
+}
+
+impl StacksTxSigner for PrivateKeySigner {
+    fn public_key(&self) -> StacksPublicKey {
+        StacksPublicKey::from_private(&self.private_key)
+    }
+
+    fn sign(&self, unsigned_tx: &StacksTransaction) -> Result<StacksTransaction, Error> {
+        let mut signed_tx = unsigned_tx.clone();
+        signed_tx
+            .sign_next_origin(&next_origin_sighash(unsigned_tx)?, &self.private_key)
+            .map_err(|e| Error::SigningError(e.to_string()))?;
+        Ok(signed_tx)
+    }
+}
+
+/// Signs with a private key kept encrypted in a keystore. The key is decrypted once when the
+/// keystore is unlocked, and the password is not kept.
+pub struct KeystoreSigner {
+    signer: PrivateKeySigner,
+}
+
+impl KeystoreSigner {
+    /// Open the keystore at `path` with the password in KEYSTORE_PASSWORD_FILE or KEYSTORE_PASSWORD
+    pub fn unlock(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let password = keystore::password_from_env(PASSWORD_ENV)
+            .map_err(keystore_error)?
+            .ok_or_else(|| {
+                keystore_error(keystore::Error::MissingPassword(path.display().to_string()))
+            })?;
+        Self::unlock_with(path, &password)
+    }
+
+    /// Open the keystore at `path` with `password`
+    pub fn unlock_with(path: impl AsRef<Path>, password: &str) -> Result<Self, Error> {
+        let private_key =
+            keystore::unlock_with(path, KeyKind::Stacks, password).map_err(keystore_error)?;
+        let private_key = StacksPrivateKey::from_hex(&private_key)
+            .map_err(|e| Error::KeystoreError(e.to_string()))?;
+        Ok(Self {
+            signer: PrivateKeySigner::new(private_key),
+        })
+    }
+}
+
+fn keystore_error(e: keystore::Error) -> Error {
+    Error::KeystoreError(e.to_string())
+}
+
+impl StacksTxSigner for KeystoreSigner {
+    fn public_key(&self) -> StacksPublicKey {
+        self.signer.public_key()
+    }
+
+    fn sign(&self, unsigned_tx: &StacksTransaction) -> Result<StacksTransaction, Error> {
+        self.signer.sign(unsigned_tx)
+    }
+}
+
+/// Signs with a remote service holding the private key, e.g. in front of an HSM. It speaks JSON
+/// over HTTP:
+///
+/// - `GET /v1/public_key` answers `{"public_key": "<hex>"}`
+/// - `POST /v1/sign` with `{"transaction": "<hex>"}`, an unsigned transaction, answers
+///   `{"transaction": "<hex>"}` with its origin signed. A multisig origin may already hold the
+///   signatures of other signers, and the new one is appended to them.
+///
+/// Errors are answered with a non-200 status and `{"error": "<reason>"}`.
+pub struct RemoteSigner {
+    url: Url,
+    client: Client,
+    public_key: StacksPublicKey,
+}
+
+impl RemoteSigner {
+    /// Connect to the remote signer at `url`, checking that it holds the key of `public_key`
+    pub fn connect(url: Url, public_key: StacksPublicKey) -> Result<Self, Error> {
+        let client = Client::builder()
+            .timeout(REMOTE_SIGNER_TIMEOUT)
+            .build()
+            .map_err(|e| Error::Unreachable(e.to_string()))?;
+        let signer = Self {
+            url,
+            client,
+            public_key,
+        };
+        let remote_public_key = signer.get("v1/public_key")?;
+        let remote_public_key = remote_public_key
+            .get("public_key")
+            .and_then(Value::as_str)
+            .ok_or_else(|| Error::InvalidResponse("Missing public_key".to_string()))?;
+        if remote_public_key != public_key.to_hex() {
+            return Err(Error::PublicKeyMismatch(remote_public_key.to_string()));
+        }
+        Ok(signer)
+    }
+
+    fn endpoint(&self, path: &str) -> Result<Url, Error> {
+        self.url
+            .join(path)
+            .map_err(|e| Error::InvalidResponse(e.to_string()))
+    }
+
+    fn get(&self, path: &str) -> Result<Value, Error> {
+        let response = self.client.get(self.endpoint(path)?).send();
+        Self::read(response)
+    }
+
+    fn post(&self, path: &str, body: &Value) -> Result<Value, Error> {
+        let response = self.client.post(self.endpoint(path)?).json(body).send();
+        Self::read(response)
+    }
+
+    fn read(response: reqwest::Result<reqwest::blocking::Response>) -> Result<Value, Error> {
+        let response = response.map_err(|e| Error::Unreachable(e.to_string()))?;
+        let status = response.status();
+        let body: Value = response
+            .json()
+            .map_err(|e| Error::InvalidResponse(e.to_string()))?;
+        if !status.is_success() {
+            let reason = body.get("error").and_then(Value::as_str).unwrap_or("");
+            return Err(Error::Rejected(format!("{} {}", status, reason)));
+        }
+        Ok(body)
+    }
+}
+
+impl StacksTxSigner for RemoteSigner {
+    fn public_key(&self) -> StacksPublicKey {
+        self.public_key
+    }
+
+    fn sign(&self, unsigned_tx: &StacksTransaction) -> Result<StacksTransaction, Error> {
+        let response = self.post(
+            "v1/sign",
+            &json!({ "transaction": to_hex(&unsigned_tx.serialize_to_vec()) }),
+        )?;
+        let signed_tx = response
+            .get("transaction")
+            .and_then(Value::as_str)
+            .ok_or_else(|| Error::InvalidResponse("Missing transaction".to_string()))?;
+        let signed_tx = decode_transaction(signed_tx).map_err(Error::InvalidResponse)?;
+
+        // Only the next signature may be added, and it must be the configured key's
+        check_next_signature(unsigned_tx, &signed_tx, &self.public_key)
+            .map_err(Error::InvalidResponse)?;
+        Ok(signed_tx)
+    }
+}
+
+fn decode_transaction(hex: &str) -> Result<StacksTransaction, String> {
+    let bytes = hex_bytes(hex).map_err(|e| format!("{:?}", e))?;
+    StacksTransaction::consensus_deserialize(&mut &bytes[..]).map_err(|e| e.to_string())
+}
+
+/// The sighash the next signature of a transaction's origin signs. For a multisig origin it
+/// follows from the signatures already collected.
+fn next_origin_sighash(tx: &StacksTransaction) -> Result<Txid, Error> {
+    let mut sighash = tx.sign_begin();
+    if let TransactionAuth::Standard(TransactionSpendingCondition::Multisig(condition)) = &tx.auth {
+        for field in &condition.fields {
+            if let TransactionAuthField::Signature(key_encoding, signature) = field {
+                (_, sighash) = recover_signer(
+                    &sighash,
+                    condition.tx_fee,
+                    condition.nonce,
+                    key_encoding,
+                    signature,
+                )?;
+            }
+        }
+    }
+    Ok(sighash)
+}
+
+/// The public key which made a signature of the origin, and the sighash of the next signature
+fn recover_signer(
+    sighash: &Txid,
+    tx_fee: u64,
+    nonce: u64,
+    key_encoding: &TransactionPublicKeyEncoding,
+    signature: &MessageSignature,
+) -> Result<(StacksPublicKey, Txid), Error> {
+    TransactionSpendingCondition::next_verification(
+        sighash,
+        &TransactionAuthFlags::AuthStandard,
+        tx_fee,
+        nonce,
+        key_encoding,
+        signature,
+    )
+    .map_err(|e| Error::SigningError(e.to_string()))
+}
+
+/// Check that `signed_tx` only adds the next signature of the origin to `unsigned_tx`, and that
+/// it was made with the key of `public_key`
+fn check_next_signature(
+    unsigned_tx: &StacksTransaction,
+    signed_tx: &StacksTransaction,
+    public_key: &StacksPublicKey,
+) -> Result<(), String> {
+    let sighash = next_origin_sighash(unsigned_tx).map_err(|e| e.to_string())?;
+    let mut tx = signed_tx.clone();
+    let (signer, _) = match &mut tx.auth {
+        TransactionAuth::Standard(TransactionSpendingCondition::Singlesig(condition)) => {
+            let signature = std::mem::replace(&mut condition.signature, MessageSignature::empty());
+            recover_signer(
+                &sighash,
+                condition.tx_fee,
+                condition.nonce,
+                &condition.key_encoding,
+                &signature,
+            )
+        }
+        TransactionAuth::Standard(TransactionSpendingCondition::Multisig(condition)) => {
+            match condition.fields.pop() {
+                Some(TransactionAuthField::Signature(key_encoding, signature)) => recover_signer(
+                    &sighash,
+                    condition.tx_fee,
+                    condition.nonce,
+                    &key_encoding,
+                    &signature,
+                ),
+                _ => return Err("No signature was added".to_string()),
+            }
+        }
+        TransactionAuth::Sponsored(..) => {
+            return Err("Sponsored transactions are not signed".to_string())
+        }
+    }
+    .map_err(|e| format!("Invalid signature: {}", e))?;
+    if tx != *unsigned_tx {
+        return Err("The signed transaction differs from the unsigned one".to_string());
+    }
+    if signer != *public_key {
+        return Err(format!(
+            "Signed with the key of {} instead of the configured one",
+            signer.to_hex()
+        ));
+    }
+    Ok(())
+}
+
+/// Collects the signatures of a multisig origin from several signers, e.g. local keys and
+/// signing services, so that no single key authorizes the fee wallet's transactions
+pub struct MultisigSigner {
+    hash_mode: MultisigHashMode,
+    signatures_required: u16,
+    signers: Vec<Box<dyn StacksTxSigner>>,
+}
+
+impl MultisigSigner {
+    /// The signers are ordered like the public keys of the multisig address
+    pub fn new(
+        hash_mode: MultisigHashMode,
+        signatures_required: u16,
+        signers: Vec<Box<dyn StacksTxSigner>>,
+    ) -> Result<Self, Error> {
+        if signatures_required == 0 || usize::from(signatures_required) > signers.len() {
+            return Err(Error::InvalidMultisig(format!(
+                "{} of {} signatures required",
+                signatures_required,
+                signers.len()
+            )));
+        }
+        Ok(Self {
+            hash_mode,
+            signatures_required,
+            signers,
+        })
+    }
+
+    /// The public keys of the signers, in order
+    pub fn public_keys(&self) -> Vec<StacksPublicKey> {
+        self.signers
+            .iter()
+            .map(|signer| signer.public_key())
+            .collect()
+    }
+
+    /// The unsigned spending condition of the multisig origin
+    pub fn spending_condition(&self) -> Option<TransactionSpendingCondition> {
+        match self.hash_mode {
+            MultisigHashMode::P2SH => TransactionSpendingCondition::new_multisig_p2sh(
+                self.signatures_required,
+                self.public_keys(),
+            ),
+            MultisigHashMode::P2WSH => TransactionSpendingCondition::new_multisig_p2wsh(
+                self.signatures_required,
+                self.public_keys(),
+            ),
+        }
+    }
+
+    /// A copy of `unsigned_tx` whose origin holds placeholder signatures of the first signers
+    /// required and the public keys of the others, as long as the transaction once signed
+    pub fn with_placeholder_signatures(
+        &self,
+        unsigned_tx: &StacksTransaction,
+    ) -> StacksTransaction {
+        let mut tx = unsigned_tx.clone();
+        if let TransactionAuth::Standard(TransactionSpendingCondition::Multisig(condition)) =
+            &mut tx.auth
+        {
+            condition.fields = self
+                .signers
+                .iter()
+                .enumerate()
+                .map(|(i, signer)| {
+                    if i < usize::from(self.signatures_required) {
+                        TransactionAuthField::Signature(
+                            TransactionPublicKeyEncoding::Compressed,
+                            MessageSignature::empty(),
+                        )
+                    } else {
+                        TransactionAuthField::PublicKey(signer.public_key())
+                    }
+                })
+                .collect();
+        }
+        tx
+    }
+
+    /// Collect signatures from the signers in order until enough were, adding the public keys of
+    /// the signers which failed or were not asked instead.
+    ///
+    /// Each signature signs over those before it, so the signers are asked one after another. An
+    /// unreachable remote signer delays signing by up to `REMOTE_SIGNER_TIMEOUT` before the next
+    /// one is asked, so signers that are often down are best listed last.
+    pub fn sign(&self, unsigned_tx: &StacksTransaction) -> Result<StacksTransaction, Error> {
+        let mut tx = unsigned_tx.clone();
+        let mut collected = 0;
+        let mut errors = Vec::new();
+        for signer in &self.signers {
+            if collected < self.signatures_required {
+                match signer.sign(&tx) {
+                    Ok(signed_tx) => {
+                        tx = signed_tx;
+                        collected += 1;
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("{} failed to sign: {}", signer.public_key().to_hex(), e);
+                        errors.push(e);
+                    }
+                }
+            }
+            tx.append_next_origin(&signer.public_key())
+                .map_err(|e| Error::SigningError(e.to_string()))?;
+        }
+        if collected < self.signatures_required {
+            // Signing may succeed once an unreachable signer is back
+            if let Some(i) = errors.iter().position(Error::is_transient) {
+                return Err(errors.swap_remove(i));
+            }
+            let reasons = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+            return Err(Error::InsufficientSignatures(
+                collected,
+                self.signatures_required,
+                reasons.join("; "),
+            ));
+        }
+        Ok(tx)
+    }
+}
+
+/// Serves the remote signer protocol with a local signer, standing in for a signing service in
+/// tests and development setups
+pub struct MockRemoteSigner<S: StacksTxSigner> {
+    signer: S,
+}
+
+impl<S: StacksTxSigner> MockRemoteSigner<S> {
+    pub fn new(signer: S) -> Self {
+        Self { signer }
+    }
+
+    /// Serve the remote signer protocol on `listener` until it fails
+    pub fn run(&self, listener: TcpListener) {
+        http_server::run(listener, "remote signer", "application/json", |request| {
+            self.respond(request)
+        })
+    }
+
+    /// Answer a single request read from the stream
+    pub fn serve(&self, io: &mut impl IoStream) -> Result<(), IoError> {
+        http_server::serve(io, "remote signer", "application/json", |request| {
+            self.respond(request)
+        })
+    }
+
+    fn respond(&self, request: &Request) -> (u16, &'static str, Vec<u8>) {
+        let (code, phrase, content) = match self.call(request) {
+            Ok(value) => (200, "OK", value),
+            Err((code, phrase, reason)) => (code, phrase, json!({ "error": reason })),
+        };
+        (code, phrase, content.to_string().into_bytes())
+    }
+
+    fn call(&self, request: &Request) -> Result<Value, (u16, &'static str, String)> {
+        let bad_request = |reason: String| (400, "Bad Request", reason);
+        match (request.method, request.url.trim_end_matches('/')) {
+            (Method::GET, "/v1/public_key") => {
+                Ok(json!({ "public_key": self.signer.public_key().to_hex() }))
+            }
+            (Method::POST, "/v1/sign") => {
+                let body: Value = serde_json::from_slice(&request.content)
+                    .map_err(|e| bad_request(e.to_string()))?;
+                let unsigned_tx = body
+                    .get("transaction")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| bad_request("Missing transaction".to_string()))?;
+                let unsigned_tx = decode_transaction(unsigned_tx).map_err(bad_request)?;
+                let signed_tx = self
+                    .signer
+                    .sign(&unsigned_tx)
+                    .map_err(|e| (500, "Internal Server Error", e.to_string()))?;
+                Ok(json!({ "transaction": to_hex(&signed_tx.serialize_to_vec()) }))
+            }
+            _ => Err((404, "Not Found", "Unknown endpoint".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, thread};
+
+    use blockstack_lib::{
+        chainstate::stacks::{TransactionContractCall, TransactionPayload, TransactionVersion},
+        types::chainstate::StacksAddress,
+        vm::{ClarityName, ContractName},
+    };
+
+    use crate::util::test::PRIVATE_KEY_HEX;
+
+    use super::*;
+
+    fn private_key_signer() -> PrivateKeySigner {
+        PrivateKeySigner::new(StacksPrivateKey::from_hex(PRIVATE_KEY_HEX).unwrap())
+    }
+
+    fn unsigned_tx(signer: &impl StacksTxSigner) -> StacksTransaction {
+        let spending_condition =
+            TransactionSpendingCondition::new_singlesig_p2pkh(signer.public_key()).unwrap();
+        unsigned_tx_with(spending_condition)
+    }
+
+    fn unsigned_tx_with(mut spending_condition: TransactionSpendingCondition) -> StacksTransaction {
+        spending_condition.set_nonce(3);
+        spending_condition.set_tx_fee(10);
+        let payload = TransactionPayload::ContractCall(TransactionContractCall {
+            address: StacksAddress::from_string("SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE")
+                .unwrap(),
+            contract_name: ContractName::from("sbtc-alpha"),
+            function_name: ClarityName::from("mint!"),
+            function_args: vec![],
+        });
+        StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::Standard(spending_condition),
+            payload,
+        )
+    }
+
+    fn spawn_mock(signer: PrivateKeySigner) -> Url {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        thread::spawn(move || MockRemoteSigner::new(signer).run(listener));
+        url
+    }
+
+    #[test]
+    fn private_key_signer_test() {
+        let signer = private_key_signer();
+        let signed_tx = signer.sign(&unsigned_tx(&signer)).unwrap();
+        signed_tx.verify().unwrap();
+        assert_eq!(signed_tx.get_origin_nonce(), 3);
+    }
+
+    #[test]
+    fn remote_signer_test() {
+        let signer = private_key_signer();
+        let public_key = signer.public_key();
+        let unsigned_tx = unsigned_tx(&signer);
+        let url = spawn_mock(signer);
+
+        let remote_signer = RemoteSigner::connect(url.clone(), public_key).unwrap();
+        assert_eq!(remote_signer.public_key(), public_key);
+        let signed_tx = remote_signer.sign(&unsigned_tx).unwrap();
+        signed_tx.verify().unwrap();
+        assert_eq!(
+            check_next_signature(&unsigned_tx, &signed_tx, &public_key),
+            Ok(())
+        );
+
+        // The remote signer must hold the configured key
+        let other_key = StacksPublicKey::from_private(&StacksPrivateKey::new());
+        assert!(matches!(
+            RemoteSigner::connect(url, other_key),
+            Err(Error::PublicKeyMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn keystore_signer_test() {
+        let dir = tempdir::TempDir::new("").unwrap();
+        let path = dir.path().join("stacks_private_key.json");
+        let scrypt = keystore::ScryptParams {
+            log_n: 4,
+            r: 8,
+            p: 1,
+        };
+        keystore::Keystore::encrypt_with(KeyKind::Stacks, PRIVATE_KEY_HEX, "password", scrypt)
+            .unwrap()
+            .create(&path)
+            .unwrap();
+        assert!(KeystoreSigner::unlock_with(&path, "wrong password").is_err());
+        let signer = KeystoreSigner::unlock_with(&path, "password").unwrap();
+        assert_eq!(signer.public_key(), private_key_signer().public_key());
+        signer
+            .sign(&unsigned_tx(&signer))
+            .unwrap()
+            .verify()
+            .unwrap();
+    }
+
+    #[test]
+    fn multisig_signer_test() {
+        let local_signer = private_key_signer();
+        let remote_key = StacksPrivateKey::new();
+        let remote_public_key = StacksPublicKey::from_private(&remote_key);
+        let url = spawn_mock(PrivateKeySigner::new(remote_key));
+        let offline_key = StacksPublicKey::from_private(&StacksPrivateKey::new());
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let offline_url =
+            Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let offline_signer = || RemoteSigner {
+            url: offline_url.clone(),
+            client: Client::new(),
+            public_key: offline_key,
+        };
+        drop(listener);
+
+        // Two of three signatures suffice, so the offline signer is skipped
+        for hash_mode in [MultisigHashMode::P2SH, MultisigHashMode::P2WSH] {
+            let signer = MultisigSigner::new(
+                hash_mode,
+                2,
+                vec![
+                    Box::new(offline_signer()),
+                    Box::new(private_key_signer()),
+                    Box::new(RemoteSigner::connect(url.clone(), remote_public_key).unwrap()),
+                ],
+            )
+            .unwrap();
+            let unsigned_tx = unsigned_tx_with(signer.spending_condition().unwrap());
+            let signed_tx = signer.sign(&unsigned_tx).unwrap();
+            signed_tx.verify().unwrap();
+            assert_eq!(
+                signer.public_keys(),
+                vec![offline_key, local_signer.public_key(), remote_public_key]
+            );
+        }
+
+        // Without enough reachable signers, signing is retried later
+        let signer = MultisigSigner::new(
+            MultisigHashMode::P2SH,
+            3,
+            vec![
+                Box::new(offline_signer()),
+                Box::new(private_key_signer()),
+                Box::new(RemoteSigner::connect(url, remote_public_key).unwrap()),
+            ],
+        )
+        .unwrap();
+        let unsigned_tx = unsigned_tx_with(signer.spending_condition().unwrap());
+        assert!(signer.sign(&unsigned_tx).unwrap_err().is_transient());
+
+        // A multisig needs at least one and at most all of its signers' signatures
+        assert!(matches!(
+            MultisigSigner::new(
+                MultisigHashMode::P2SH,
+                2,
+                vec![Box::new(private_key_signer())]
+            ),
+            Err(Error::InvalidMultisig(_))
+        ));
+        assert!(matches!(
+            MultisigSigner::new(
+                MultisigHashMode::P2SH,
+                0,
+                vec![Box::new(private_key_signer())]
+            ),
+            Err(Error::InvalidMultisig(_))
+        ));
+    }
+
+    #[test]
+    fn unreachable_remote_signer_test() {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        drop(listener);
+        let error = RemoteSigner::connect(url, private_key_signer().public_key())
+            .err()
+            .unwrap();
+        assert!(error.is_transient());
+    }
+}
@@ -1,23 +1,28 @@
 use crate::{
     peg_wallet::{Error as PegWalletError, StacksWallet as StacksWalletTrait},
-    stacks_node::{PegInOp, PegOutRequestOp},
+    stacks_node::{Error as StacksNodeError, PegInOp, PegOutRequestOp},
+    stacks_tx_signer::{Error as StacksTxSignerError, MultisigSigner, StacksTxSigner},
     util::address_version,
 };
 use bitcoin::XOnlyPublicKey;
 use blockstack_lib::{
     chainstate::stacks::{
-        StacksTransaction, StacksTransactionSigner, TransactionAnchorMode, TransactionAuth,
-        TransactionContractCall, TransactionPayload, TransactionPostConditionMode,
-        TransactionSpendingCondition, TransactionVersion,
+        StacksTransaction, TransactionAnchorMode, TransactionAuth, TransactionContractCall,
+        TransactionPayload, TransactionPostConditionMode, TransactionSpendingCondition,
+        TransactionVersion,
     },
+    codec::StacksMessageCodec,
     core::{CHAIN_ID_MAINNET, CHAIN_ID_TESTNET},
-    types::chainstate::{StacksAddress, StacksPrivateKey, StacksPublicKey},
+    types::chainstate::{StacksAddress, StacksPublicKey},
     vm::{
         errors::RuntimeErrorType,
-        types::{ASCIIData, BuffData, SequenceData, StacksAddressExtensions, TupleData},
+        types::{
+            ASCIIData, BuffData, PrincipalData, SequenceData, StacksAddressExtensions, TupleData,
+        },
         ClarityName, ContractName, Value,
     },
 };
+use tracing::warn;
 
 #[derive(thiserror::Error, Debug, PartialEq)]
 pub enum Error {
@@ -27,6 +32,9 @@ pub enum Error {
     ///Error occured while signing a transaction
     #[error("Failed to sign transaction: {0}")]
     SigningError(String),
+    ///Error occured in the signing backend
+    #[error("Failed to sign transaction: {0}")]
+    StacksTxSignerError(#[from] StacksTxSignerError),
     ///Error occurred due to a malformed op
     #[error("{0}")]
     MalformedOp(String),
@@ -38,37 +46,137 @@ pub enum Error {
     BlockstackError(#[from] blockstack_lib::vm::errors::Error),
 }
 
+/// Fee bounds and bump timing for Stacks transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StacksFeePolicy {
+    /// Lowest fee in microSTX the coordinator will pay
+    pub min_fee: u64,
+    /// Highest fee in microSTX the coordinator will pay
+    pub max_fee: u64,
+    /// Seconds a transaction may remain in the mempool before its fee is bumped
+    pub bump_timeout: u64,
+}
+
+impl StacksFeePolicy {
+    /// Clamp the given fee to the configured floor and ceiling
+    pub fn clamp(&self, fee: u64) -> u64 {
+        fee.clamp(self.min_fee, self.max_fee)
+    }
+
+    /// Fee for a replacement transaction given the current and estimated fees.
+    /// Returns None if the ceiling prevents any increase.
+    pub fn bumped_fee(&self, current_fee: u64, estimated_fee: u64) -> Option<u64> {
+        // The mempool only replaces a transaction paying a higher fee
+        let min_increase = current_fee + (current_fee / 4).max(1);
+        let fee = self.clamp(estimated_fee.max(min_increase));
+        (fee > current_fee).then_some(fee)
+    }
+}
+
+/// Estimates the fee a Stacks transaction should pay to be mined
+pub trait StacksFeeEstimator: Send {
+    /// Estimates the fee in microSTX of a transaction with the given payload and serialized length
+    fn estimate_fee(
+        &self,
+        payload: &TransactionPayload,
+        estimated_len: u64,
+    ) -> Result<u64, StacksNodeError>;
+}
+
+/// Signs the origin of the wallet's transactions
+enum WalletSigner {
+    Singlesig(Box<dyn StacksTxSigner>),
+    Multisig(MultisigSigner),
+}
+
 pub struct StacksWallet {
     contract_address: StacksAddress,
     contract_name: ContractName,
-    sender_key: StacksPrivateKey,
-    public_key: StacksPublicKey,
+    signer: WalletSigner,
+    public_keys: Vec<StacksPublicKey>,
     address: StacksAddress,
     version: TransactionVersion,
+    /// Fee paid when no estimate is available, and the least paid otherwise
     fee: u64,
+    fee_policy: Option<StacksFeePolicy>,
+    fee_estimator: Option<Box<dyn StacksFeeEstimator>>,
 }
 
 impl StacksWallet {
     pub fn new(
         contract_name: ContractName,
         contract_address: StacksAddress,
-        sender_key: StacksPrivateKey,
+        signer: Box<dyn StacksTxSigner>,
+        address: StacksAddress,
+        version: TransactionVersion,
+        fee: u64,
+    ) -> Self {
+        let public_keys = vec![signer.public_key()];
+        Self {
+            contract_address,
+            contract_name,
+            signer: WalletSigner::Singlesig(signer),
+            public_keys,
+            address,
+            version,
+            fee,
+            fee_policy: None,
+            fee_estimator: None,
+        }
+    }
+
+    /// A wallet whose transactions are signed by several signers, at the multisig address of
+    /// their public keys
+    pub fn new_multisig(
+        contract_name: ContractName,
+        contract_address: StacksAddress,
+        signer: MultisigSigner,
         address: StacksAddress,
         version: TransactionVersion,
         fee: u64,
     ) -> Self {
-        let public_key = StacksPublicKey::from_private(&sender_key);
+        let public_keys = signer.public_keys();
         Self {
             contract_address,
             contract_name,
-            sender_key,
-            public_key,
+            signer: WalletSigner::Multisig(signer),
+            public_keys,
             address,
             version,
             fee,
+            fee_policy: None,
+            fee_estimator: None,
         }
     }
 
+    /// Estimate the fee of every transaction before signing it, within the policy's bounds
+    pub fn set_fee_estimator(
+        &mut self,
+        fee_estimator: Box<dyn StacksFeeEstimator>,
+        fee_policy: StacksFeePolicy,
+    ) {
+        self.fee_estimator = Some(fee_estimator);
+        self.fee_policy = Some(fee_policy);
+    }
+
+    /// Sets the bounds of estimated fees. Has no effect if fees are not estimated.
+    pub fn set_fee_policy(&mut self, fee_policy: StacksFeePolicy) {
+        if self.fee_estimator.is_some() {
+            self.fee_policy = Some(fee_policy);
+        }
+    }
+
+    fn sign(&self, tx: &StacksTransaction) -> Result<StacksTransaction, Error> {
+        // Signatures over a previous fee are dropped before signing again
+        let mut unsigned_tx = tx.clone();
+        unsigned_tx.auth = unsigned_tx.auth.into_initial_sighash_auth();
+        let signed_tx = match &self.signer {
+            WalletSigner::Singlesig(signer) => signer.sign(&unsigned_tx)?,
+            WalletSigner::Multisig(signer) => signer.sign(&unsigned_tx)?,
+        };
+        Ok(signed_tx)
+    }
+
     fn build_transaction_signed(
         &self,
         function_name: impl Into<String>,
@@ -76,19 +184,14 @@ impl StacksWallet {
         nonce: u64,
     ) -> Result<StacksTransaction, Error> {
         // First build an unsigned transaction
-        let unsigned_tx = self.build_transaction_unsigned(function_name, function_args, nonce)?;
+        let mut unsigned_tx =
+            self.build_transaction_unsigned(function_name, function_args, nonce)?;
+
+        // The fee is covered by the signature, so estimate it first
+        unsigned_tx.set_tx_fee(self.estimate_fee(&unsigned_tx));
 
         // Do the signing
-        let mut tx_signer = StacksTransactionSigner::new(&unsigned_tx);
-        tx_signer
-            .sign_origin(&self.sender_key)
-            .map_err(|e| Error::SigningError(e.to_string()))?;
-
-        // Retrieve the signed transaction from the signer
-        let signed_tx = tx_signer.get_tx().ok_or(Error::SigningError(
-            "Unable to retrieve signed transaction from the signer.".to_string(),
-        ))?;
-        Ok(signed_tx)
+        self.sign(&unsigned_tx)
     }
 
     fn build_transaction_unsigned(
@@ -100,15 +203,19 @@ impl StacksWallet {
         // First build the payload from the provided function and its arguments
         let payload = self.build_transaction_payload(function_name, function_args)?;
 
-        // Next build the authorization from the provided sender key
-        let public_key = self.public_key();
-        let mut spending_condition = TransactionSpendingCondition::new_singlesig_p2pkh(*public_key)
-            .ok_or_else(|| {
-                Error::SigningError(
-                    "Failed to create transaction spending condition from provided sender_key."
-                        .to_string(),
-                )
-            })?;
+        // Next build the authorization from the signers' public keys
+        let spending_condition = match &self.signer {
+            WalletSigner::Singlesig(signer) => {
+                TransactionSpendingCondition::new_singlesig_p2pkh(signer.public_key())
+            }
+            WalletSigner::Multisig(signer) => signer.spending_condition(),
+        };
+        let mut spending_condition = spending_condition.ok_or_else(|| {
+            Error::SigningError(
+                "Failed to create transaction spending condition from the signers' public keys."
+                    .to_string(),
+            )
+        })?;
         spending_condition.set_nonce(nonce);
         spending_condition.set_tx_fee(self.fee);
         let auth = TransactionAuth::Standard(spending_condition);
@@ -183,15 +290,7 @@ impl BuildStacksTransaction for PegOutRequestOp {
         // Build the function arguments
         let amount = Value::UInt(self.amount.into());
         // Retrieve the stacks address to burn from
-        let address = self
-            .stx_address(address_version(&wallet.version))
-            .map_err(|_| {
-                Error::MalformedOp(
-                    "Failed to recover stx address from peg-out request op.".to_string(),
-                )
-            })?;
-        let principal_data = address.to_account_principal();
-        let principal = Value::Principal(principal_data);
+        let principal = Value::Principal(wallet.peg_out_requester(self)?);
         //Note that this tx_id is only used to print info inside the contract call.
         let tx_id = Value::from(ASCIIData {
             data: self.txid.to_string().as_bytes().to_vec(),
@@ -211,6 +310,17 @@ impl StacksWalletTrait for StacksWallet {
     ) -> Result<StacksTransaction, PegWalletError> {
         op.build_transaction(self, nonce)
     }
+    fn peg_out_requester(&self, op: &PegOutRequestOp) -> Result<PrincipalData, PegWalletError> {
+        let address = op
+            .stx_address(address_version(&self.version))
+            .map_err(|_| {
+                Error::MalformedOp(
+                    "Failed to recover stx address from peg-out request op.".to_string(),
+                )
+            })?;
+        Ok(address.to_account_principal())
+    }
+
     fn build_set_bitcoin_wallet_public_key_transaction(
         &self,
         public_key: &XOnlyPublicKey,
@@ -251,22 +361,59 @@ impl StacksWalletTrait for StacksWallet {
         &self.address
     }
 
-    fn public_key(&self) -> &StacksPublicKey {
-        &self.public_key
+    fn public_keys(&self) -> &[StacksPublicKey] {
+        &self.public_keys
     }
 
     fn set_fee(&mut self, fee: u64) {
         self.fee = fee;
     }
+
+    fn estimate_fee(&self, tx: &StacksTransaction) -> u64 {
+        let (Some(fee_estimator), Some(fee_policy)) = (&self.fee_estimator, &self.fee_policy)
+        else {
+            return self.fee;
+        };
+        // The fields of a multisig origin are only added when it is signed
+        let estimated_len = match &self.signer {
+            WalletSigner::Singlesig(_) => tx.serialize_to_vec().len(),
+            WalletSigner::Multisig(signer) => signer
+                .with_placeholder_signatures(tx)
+                .serialize_to_vec()
+                .len(),
+        } as u64;
+        match fee_estimator.estimate_fee(&tx.payload, estimated_len) {
+            Ok(fee) => fee_policy.clamp(fee.max(self.fee)),
+            Err(e) => {
+                warn!(
+                    "Failed to estimate Stacks transaction fee: {}. Paying {}",
+                    e, self.fee
+                );
+                self.fee
+            }
+        }
+    }
+
+    fn bump_fee(
+        &self,
+        tx: &StacksTransaction,
+        fee: u64,
+    ) -> Result<StacksTransaction, PegWalletError> {
+        let mut unsigned_tx = tx.clone();
+        unsigned_tx.set_tx_fee(fee);
+        Ok(self.sign(&unsigned_tx)?)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
         peg_wallet::StacksWallet as StacksWalletTrait,
-        stacks_wallet::StacksWallet,
+        stacks_node::Error as StacksNodeError,
+        stacks_tx_signer::{MultisigSigner, PrivateKeySigner, StacksTxSigner},
+        stacks_wallet::{StacksFeeEstimator, StacksFeePolicy, StacksWallet, WalletSigner},
         util::{
-            address_version,
+            address_version, multisig_address_version,
             test::{build_peg_out_request_op, PRIVATE_KEY_HEX, PUBLIC_KEY_HEX},
         },
     };
@@ -276,8 +423,11 @@ mod tests {
         burnchains::{Address, Txid},
         chainstate::{
             burn::operations::{PegInOp, PegOutRequestOp},
-            stacks::{address::PoxAddress, TransactionVersion},
+            stacks::{
+                address::PoxAddress, MultisigHashMode, TransactionPayload, TransactionVersion,
+            },
         },
+        codec::StacksMessageCodec,
         types::chainstate::{
             BurnchainHeaderHash, StacksAddress, StacksPrivateKey, StacksPublicKey,
         },
@@ -321,13 +471,133 @@ mod tests {
         StacksWallet::new(
             contract_name,
             contract_address,
-            sender_key,
+            Box::new(PrivateKeySigner::new(sender_key)),
             address,
             TransactionVersion::Testnet,
             10,
         )
     }
 
+    struct FixedFeeEstimator(Option<u64>);
+
+    impl StacksFeeEstimator for FixedFeeEstimator {
+        fn estimate_fee(
+            &self,
+            _payload: &TransactionPayload,
+            _estimated_len: u64,
+        ) -> Result<u64, StacksNodeError> {
+            self.0
+                .ok_or_else(|| StacksNodeError::InvalidJsonEntry("estimations".to_string()))
+        }
+    }
+
+    fn fee_policy() -> StacksFeePolicy {
+        StacksFeePolicy {
+            min_fee: 100,
+            max_fee: 1_000,
+            bump_timeout: 600,
+        }
+    }
+
+    #[test]
+    fn fee_policy_test() {
+        let policy = fee_policy();
+        assert_eq!(policy.clamp(5), 100);
+        assert_eq!(policy.clamp(5_000), 1_000);
+        // The replacement pays at least a quarter more than the original
+        assert_eq!(policy.bumped_fee(400, 300), Some(500));
+        assert_eq!(policy.bumped_fee(400, 800), Some(800));
+        assert_eq!(policy.bumped_fee(900, 300), Some(1_000));
+        assert_eq!(policy.bumped_fee(1_000, 300), None);
+    }
+
+    #[test]
+    fn estimated_fee_should_be_signed() {
+        let public_key = XOnlyPublicKey::from_str(PUBLIC_KEY_HEX).unwrap();
+        let mut wallet = stacks_wallet();
+        wallet.set_fee_estimator(Box::new(FixedFeeEstimator(Some(5_000))), fee_policy());
+        let tx = wallet
+            .build_set_bitcoin_wallet_public_key_transaction(&public_key, 0)
+            .unwrap();
+        assert_eq!(tx.get_tx_fee(), 1_000);
+        tx.verify().unwrap();
+
+        // Without an estimate the configured fee is paid
+        wallet.set_fee_estimator(Box::new(FixedFeeEstimator(None)), fee_policy());
+        let tx = wallet
+            .build_set_bitcoin_wallet_public_key_transaction(&public_key, 0)
+            .unwrap();
+        assert_eq!(tx.get_tx_fee(), 10);
+    }
+
+    #[test]
+    fn bump_fee_test() {
+        let public_key = XOnlyPublicKey::from_str(PUBLIC_KEY_HEX).unwrap();
+        let wallet = stacks_wallet();
+        let tx = wallet
+            .build_set_bitcoin_wallet_public_key_transaction(&public_key, 7)
+            .unwrap();
+        let replacement = wallet.bump_fee(&tx, 20).unwrap();
+        assert_eq!(replacement.get_tx_fee(), 20);
+        assert_eq!(replacement.get_origin_nonce(), 7);
+        assert_ne!(replacement.txid(), tx.txid());
+        replacement.verify().unwrap();
+    }
+
+    #[test]
+    fn multisig_wallet_test() {
+        let signers: Vec<Box<dyn StacksTxSigner>> = vec![
+            Box::new(PrivateKeySigner::new(StacksPrivateKey::new())),
+            Box::new(PrivateKeySigner::new(
+                StacksPrivateKey::from_hex(PRIVATE_KEY_HEX).unwrap(),
+            )),
+            Box::new(PrivateKeySigner::new(StacksPrivateKey::new())),
+        ];
+        let public_keys: Vec<_> = signers.iter().map(|signer| signer.public_key()).collect();
+        let address = StacksAddress::from_public_keys(
+            multisig_address_version(&TransactionVersion::Testnet),
+            &AddressHashMode::SerializeP2SH,
+            2,
+            &public_keys,
+        )
+        .unwrap();
+        let wallet = StacksWallet::new_multisig(
+            ContractName::from("sbtc-alpha"),
+            StacksAddress::from_string("SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE").unwrap(),
+            MultisigSigner::new(MultisigHashMode::P2SH, 2, signers).unwrap(),
+            address,
+            TransactionVersion::Testnet,
+            10,
+        );
+        assert_eq!(wallet.public_keys(), &public_keys[..]);
+
+        let public_key = XOnlyPublicKey::from_str(PUBLIC_KEY_HEX).unwrap();
+        let tx = wallet
+            .build_set_bitcoin_wallet_public_key_transaction(&public_key, 7)
+            .unwrap();
+        tx.verify().unwrap();
+        assert_eq!(tx.origin_address(), address);
+
+        // A replacement collects new signatures instead of appending to the old ones
+        let replacement = wallet.bump_fee(&tx, 20).unwrap();
+        assert_eq!(replacement.get_tx_fee(), 20);
+        replacement.verify().unwrap();
+
+        // Fees are estimated on a transaction as long as the signed one
+        let WalletSigner::Multisig(signer) = &wallet.signer else {
+            panic!("Expected a multisig signer");
+        };
+        let mut unsigned_tx = tx.clone();
+        unsigned_tx.auth = unsigned_tx.auth.into_initial_sighash_auth();
+        assert_eq!(
+            signer
+                .with_placeholder_signatures(&unsigned_tx)
+                .serialize_to_vec()
+                .len(),
+            tx.serialize_to_vec().len()
+        );
+    }
+
     #[test]
     fn build_mint_transaction_test() {
         let p = PegInOp {
@@ -1,7 +1,11 @@
 use blockstack_lib::{
-    address::{C32_ADDRESS_VERSION_MAINNET_SINGLESIG, C32_ADDRESS_VERSION_TESTNET_SINGLESIG},
+    address::{
+        C32_ADDRESS_VERSION_MAINNET_MULTISIG, C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
+        C32_ADDRESS_VERSION_TESTNET_MULTISIG, C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+    },
     chainstate::stacks::TransactionVersion,
 };
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub fn address_version(version: &TransactionVersion) -> u8 {
     match version {
@@ -10,6 +14,21 @@ pub fn address_version(version: &TransactionVersion) -> u8 {
     }
 }
 
+pub fn multisig_address_version(version: &TransactionVersion) -> u8 {
+    match version {
+        TransactionVersion::Mainnet => C32_ADDRESS_VERSION_MAINNET_MULTISIG,
+        TransactionVersion::Testnet => C32_ADDRESS_VERSION_TESTNET_MULTISIG,
+    }
+}
+
+/// Seconds since the unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
 #[cfg(test)]
 pub mod test {
     use blockstack_lib::{
@@ -0,0 +1,15 @@
+-- A secret key unlocked from a keystore is not stored, only the path of its keystore
+ALTER TABLE config RENAME TO config_old;
+
+CREATE TABLE config (
+    id INTEGER NOT NULL PRIMARY KEY,
+    secret_key TEXT,
+    secret_key_keystore TEXT,
+    auto_approve_max_amount INTEGER NOT NULL,
+    delegate_public_key TEXT NOT NULL
+);
+
+INSERT INTO config (id, secret_key, auto_approve_max_amount, delegate_public_key)
+    SELECT id, secret_key, auto_approve_max_amount, delegate_public_key FROM config_old;
+
+DROP TABLE config_old;
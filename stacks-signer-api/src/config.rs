@@ -1,5 +1,7 @@
+use keystore::KeyKind;
 use secp256k1::{PublicKey, Secp256k1, SecretKey};
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use utoipa::{ToResponse, ToSchema};
 
 const DEFAULT_MAX_AMOUNT: u64 = 100_000;
@@ -19,13 +21,21 @@ pub enum Error {
     /// Config error due to toml deserialization error
     #[error("Toml Error: {0}")]
     TomlError(#[from] toml::de::Error),
+    /// Config error due to a keystore which could not be unlocked
+    #[error("Keystore Error: {0}")]
+    KeystoreError(#[from] keystore::Error),
+    /// Config error due to a missing or doubly specified secret key
+    #[error("Exactly one of secret_key and secret_key_keystore must be specified")]
+    InvalidSecretKey,
 }
 
 #[derive(serde::Deserialize)]
 /// A raw signer configuration that can be deserialized from a TOML file.
 pub struct RawConfig {
     /// The signer's secret key.
-    pub secret_key: SecretKey,
+    pub secret_key: Option<SecretKey>,
+    /// The keystore holding the signer's secret key, unlocked with the password in KEYSTORE_PASSWORD.
+    pub secret_key_keystore: Option<String>,
     /// The maximum dollar amount of a transaction that will be auto approved
     pub delegate_public_key: Option<PublicKey>,
     /// The public keys of signers that this signer has agreed to sign on behalf of
@@ -50,6 +60,9 @@ pub struct Config {
     /// The signer's secret key.
     #[schema(value_type = String)]
     pub secret_key: SecretKey,
+    /// The keystore the secret key was unlocked from, which is stored instead of the secret key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_key_keystore: Option<String>,
     /// The maximum dollar amount of a transaction that will be auto approved
     pub auto_approve_max_amount: u64,
     /// The public key of the signer being delegated to
@@ -69,6 +82,7 @@ impl Config {
         let public_key = PublicKey::from_secret_key(&secp, &secret_key);
         Self {
             secret_key,
+            secret_key_keystore: None,
             delegate_public_key: public_key,
             auto_approve_max_amount: DEFAULT_MAX_AMOUNT,
             delegator_public_keys: vec![],
@@ -93,10 +107,18 @@ impl Config {
 impl TryFrom<RawConfig> for Config {
     type Error = Error;
     fn try_from(raw_config: RawConfig) -> Result<Config, Error> {
+        let secret_key = match (&raw_config.secret_key, &raw_config.secret_key_keystore) {
+            (Some(secret_key), None) => *secret_key,
+            (None, Some(path)) => {
+                SecretKey::from_str(&keystore::unlock(path, KeyKind::Secp256k1)?)?
+            }
+            _ => return Err(Error::InvalidSecretKey),
+        };
         let secp = Secp256k1::new();
-        let public_key = PublicKey::from_secret_key(&secp, &raw_config.secret_key);
+        let public_key = PublicKey::from_secret_key(&secp, &secret_key);
         Ok(Config {
-            secret_key: raw_config.secret_key,
+            secret_key,
+            secret_key_keystore: raw_config.secret_key_keystore,
             delegate_public_key: raw_config.delegate_public_key.unwrap_or(public_key),
             auto_approve_max_amount: raw_config
                 .auto_approve_max_amount
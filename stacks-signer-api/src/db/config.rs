@@ -1,15 +1,40 @@
 use crate::{config::Config, db::Error};
 
+use keystore::{KeyKind, PASSWORD_ENV};
 use secp256k1::{PublicKey, SecretKey};
 use sqlx::SqlitePool;
 use std::str::FromStr;
 
-/// Helper function for retriving a signer from the database given a signer ID.
+/// Helper function for retriving a signer from the database given a signer ID. A secret key
+/// stored as a keystore is unlocked with the password in KEYSTORE_PASSWORD_FILE or KEYSTORE_PASSWORD.
 pub async fn get_config(pool: &SqlitePool) -> Result<Config, Error> {
-    let row = sqlx::query!("SELECT * FROM config").fetch_one(pool).await?;
-    let secret_bytes = hex::decode(row.secret_key)?;
+    get_config_with(pool, |name| std::env::var(name).ok()).await
+}
+
+/// Helper function for retriving a signer from the database, looking up the keystore password
+/// variables with `lookup`.
+async fn get_config_with(
+    pool: &SqlitePool,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Config, Error> {
+    let row = sqlx::query!(
+        "SELECT secret_key, secret_key_keystore, auto_approve_max_amount, delegate_public_key FROM config"
+    )
+    .fetch_one(pool)
+    .await?;
+    let secret_key = match (row.secret_key, &row.secret_key_keystore) {
+        (_, Some(path)) => {
+            let password = keystore::password_from_vars(PASSWORD_ENV, lookup)?
+                .ok_or_else(|| keystore::Error::MissingPassword(path.clone()))?;
+            let secret_key = keystore::unlock_with(path, KeyKind::Secp256k1, &password)?;
+            SecretKey::from_str(&secret_key)?
+        }
+        (Some(secret_key), None) => SecretKey::from_slice(&hex::decode(secret_key)?)?,
+        (None, None) => return Err(Error::MissingSecretKey),
+    };
     Ok(Config {
-        secret_key: SecretKey::from_slice(&secret_bytes)?,
+        secret_key,
+        secret_key_keystore: row.secret_key_keystore,
         auto_approve_max_amount: row.auto_approve_max_amount as u64,
         delegate_public_key: PublicKey::from_str(row.delegate_public_key.as_str())?,
         delegator_public_keys: get_delegator_public_keys(pool).await?,
@@ -17,15 +42,20 @@ pub async fn get_config(pool: &SqlitePool) -> Result<Config, Error> {
     })
 }
 
-/// Helper function for adding a signer to the database.
+/// Helper function for adding a signer to the database. The secret key of a config unlocked from a
+/// keystore is not stored, only the path of its keystore.
 pub async fn update_config(pool: &SqlitePool, config: &Config) -> Result<(), Error> {
-    let secret_key = hex::encode(config.secret_key.secret_bytes()).to_string();
+    let secret_key = match config.secret_key_keystore {
+        Some(_) => None,
+        None => Some(hex::encode(config.secret_key.secret_bytes())),
+    };
     let auto_approve_max_amount = config.auto_approve_max_amount as i64;
     let delegate_public_key = config.delegate_public_key.to_string();
     sqlx::query!(
-        "REPLACE INTO config (id, secret_key, delegate_public_key, auto_approve_max_amount) VALUES (?1, ?2, ?3, ?4)",
+        "REPLACE INTO config (id, secret_key, secret_key_keystore, delegate_public_key, auto_approve_max_amount) VALUES (?1, ?2, ?3, ?4, ?5)",
         1,
         secret_key,
+        config.secret_key_keystore,
         delegate_public_key,
         auto_approve_max_amount,
     )
@@ -76,6 +106,7 @@ async fn get_delegator_public_keys(pool: &SqlitePool) -> Result<Vec<PublicKey>,
 mod tests {
     use super::*;
     use crate::{config::Config, db::init_pool};
+    use tempdir::TempDir;
 
     //const TEST_PUBLIC_KEY_1: &str =
     //    "025972a1f2532b44348501075075b31eb21c02eef276b91db99d30703f2081b773";
@@ -194,4 +225,50 @@ mod tests {
             .expect("failed to get auto deny addresses");
         assert_eq!(addresses, config.auto_deny_addresses);
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ntest::timeout(1000)]
+    async fn test_secret_key_keystore() {
+        let pool = init_db().await;
+        let dir = TempDir::new("").expect("Failed to create temp dir.");
+        let path = dir.path().join("secret_key.json");
+        // Cheap parameters to keep the test fast
+        let scrypt = keystore::ScryptParams {
+            log_n: 4,
+            r: 8,
+            p: 1,
+        };
+        keystore::Keystore::encrypt_with(
+            KeyKind::Secp256k1,
+            &TEST_SECRET_KEY_1.to_lowercase(),
+            "password",
+            scrypt,
+        )
+        .and_then(|keystore| keystore.create(&path))
+        .expect("Failed to create keystore.");
+        let mut expected_config =
+            Config::from_secret_key(TEST_SECRET_KEY_1).expect("Failed to create config.");
+        expected_config.secret_key_keystore = Some(path.display().to_string());
+
+        update_config(&pool, &expected_config)
+            .await
+            .expect("failed to add config");
+        // Only the path of the keystore is stored
+        let secret_key: Option<String> = sqlx::query_scalar("SELECT secret_key FROM config")
+            .fetch_one(&pool)
+            .await
+            .expect("Failed to select secret key");
+        assert!(secret_key.is_none());
+
+        // The keystore is unlocked when the config is retrieved
+        let lookup = |name: &str| (name == PASSWORD_ENV).then(|| "password".to_string());
+        let config = get_config_with(&pool, lookup)
+            .await
+            .expect("Failed to get config");
+        assert_eq!(config, expected_config);
+        assert!(matches!(
+            get_config_with(&pool, |_| None).await,
+            Err(Error::KeystoreError(keystore::Error::MissingPassword(_)))
+        ));
+    }
 }
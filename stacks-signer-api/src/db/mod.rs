@@ -27,6 +27,12 @@ pub enum Error {
     /// Secret key parsing related error
     #[error("Invalid Secret Key: {0}")]
     InvalidSecretKey(#[from] hex::FromHexError),
+    /// Keystore related error
+    #[error("Keystore Error: {0}")]
+    KeystoreError(#[from] keystore::Error),
+    /// A config stored with neither a secret key nor a keystore
+    #[error("No secret key or keystore stored in the config")]
+    MissingSecretKey,
 }
 
 impl warp::reject::Reject for Error {}